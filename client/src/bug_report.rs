@@ -0,0 +1,111 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use bomberhans_lib::game_state::GameState;
+use bomberhans_lib::settings::Settings;
+use bomberhans_lib::simulation::format_script;
+use bomberhans_lib::simulation::GameSpec;
+use bomberhans_lib::simulation::ScriptedAction;
+
+/// Write everything needed to understand a bug into a new folder in `parent`
+///
+/// Returns the folder, which the user can attach to a bug report.
+/// A `recording` of the local player's actions is written as a script that
+/// replays the game with `bomberhans-simulate`.
+pub fn export(
+    parent: &Path,
+    settings: &Settings,
+    game_state: Option<&GameState>,
+    recording: Option<&[ScriptedAction]>,
+    packets: &[String],
+) -> io::Result<PathBuf> {
+    let seconds = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let dir = parent.join(format!("bomberhans-bug-report-{seconds}"));
+    fs::create_dir_all(&dir)?;
+
+    fs::write(dir.join("version.txt"), bomberhans_lib::VERSION)?;
+    fs::write(
+        dir.join("settings.txt"),
+        format!("{}\n\n{settings:#?}\n", settings.to_shareable_string()),
+    )?;
+    if let Some(game_state) = game_state {
+        fs::write(
+            dir.join("game_state.txt"),
+            format!("{}\n{game_state:#?}\n", game_state.field.string_grid()),
+        )?;
+        fs::write(
+            dir.join("spec.txt"),
+            format!(
+                "{}\n\nReplay with: bomberhans-simulate <script> --spec <the line above>\n",
+                GameSpec::new(&game_state.game).to_shareable_string()
+            ),
+        )?;
+    }
+    if let Some(recording) = recording {
+        fs::write(dir.join("recording.txt"), format_script(recording))?;
+    }
+    fs::write(dir.join("packets.txt"), packets.join("\n"))?;
+
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::Game;
+
+    #[test]
+    fn test_export_running_game() {
+        let parent = std::env::temp_dir().join(format!("bomberhans-test-{}", std::process::id()));
+        let settings = Settings {
+            map_seed: Some(42),
+            ..Settings::default()
+        };
+        let mut game = Game::new_local_game(settings.clone(), false);
+        let packets = vec!["sent: Hello".to_owned()];
+
+        let state = game.local_state().clone();
+        let dir = export(
+            &parent,
+            &settings,
+            Some(&state),
+            game.recording(),
+            &packets,
+        )
+        .unwrap();
+
+        let mut files: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                "game_state.txt",
+                "packets.txt",
+                "recording.txt",
+                "settings.txt",
+                "spec.txt",
+                "version.txt"
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("packets.txt")).unwrap(),
+            "sent: Hello"
+        );
+        let settings_file = fs::read_to_string(dir.join("settings.txt")).unwrap();
+        let shared = settings_file.lines().next().unwrap();
+        assert_eq!(Settings::from_shareable_string(shared), Ok(settings));
+        let spec_file = fs::read_to_string(dir.join("spec.txt")).unwrap();
+        let spec = GameSpec::from_shareable_string(spec_file.lines().next().unwrap()).unwrap();
+        assert_eq!(spec.game_state().field, game.local_state().field);
+
+        fs::remove_dir_all(parent).unwrap();
+    }
+}
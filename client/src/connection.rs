@@ -1,17 +1,45 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::net::IpAddr;
 use std::net::Ipv6Addr;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::LazyLock;
 
+use bomberhans_lib::game_state::Action;
+use bomberhans_lib::game_state::GameStatic;
+use bomberhans_lib::game_state::Player;
+use bomberhans_lib::game_state::Results;
 use bomberhans_lib::network::*;
+use bomberhans_lib::settings::Settings;
+use bomberhans_lib::utils::PlayerId;
+use bomberhans_lib::utils::RingBuffer;
+use bomberhans_lib::utils::TimeStamp;
 use tokio::net::UdpSocket;
 use tokio::sync::mpsc::Receiver;
 use tokio::sync::mpsc::Sender;
-use tokio::time::sleep;
+use tokio::time::sleep_until;
 use tokio::time::Duration;
 use tokio::time::Instant;
 
+/// How often to tell the server that we are still there
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long to wait for the server to acknowledge a reliable message before sending it again
+const RETRANSMIT_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long to keep sending `Bye` when the server does not acknowledge it
+const BYE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long to wait before asking a full server again, doubled with every rejection
+const SERVER_FULL_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The longest wait before asking a full server again
+const SERVER_FULL_RETRY_MAX: Duration = Duration::from_secs(30);
+
+/// How many sent and received packets to keep for debugging
+const PACKET_HISTORY: usize = 64;
+
 /// The shared Runtime for all Communication
 static RUNTIME: LazyLock<tokio::runtime::Runtime> =
     LazyLock::new(|| tokio::runtime::Runtime::new().unwrap());
@@ -27,13 +55,47 @@ type Lobbies = Vec<(GameId, String)>;
 #[derive(Debug, Clone)]
 enum State {
     Pinging,
+    /// The server rejected us because it is full, `handle_timeout` asks again with a growing
+    /// backoff
+    ServerFull {
+        /// Rejections since the first one
+        retries: u32,
+    },
     Alive {
         lobbies: Lobbies,
         server_info: ServerInfo,
     },
     OpeningNewLobby,
-    Lobby,
-    Game,
+    JoiningLobby {
+        game_id: GameId,
+        lobbies: Lobbies,
+        server_info: ServerInfo,
+    },
+    /// The server did not let us join, otherwise like `Alive`
+    JoinRejected {
+        lobbies: Lobbies,
+        server_info: ServerInfo,
+        reason: DisconnectReason,
+    },
+    Lobby {
+        client_player_id: PlayerId,
+        game: GameStatic,
+        players_ready: BTreeSet<PlayerId>,
+        /// Why the server did not start the game or refused the settings, until the next lobby
+        /// update
+        start_rejected: Option<DisconnectReason>,
+    },
+    Game {
+        start: ServerGameStart,
+        /// When `start` arrived, to align the game clock with the server
+        received: Instant,
+        /// The final standings, once the server ended the game
+        outcome: Option<Results>,
+        /// The wins so far, if the game is a round of a tournament
+        tournament: Option<TournamentStatus>,
+        /// Server updates that the game did not take yet, with when they arrived
+        updates: Vec<(ServerUpdate, Instant)>,
+    },
 
     Failed(String),
     Disconnected,
@@ -44,29 +106,58 @@ struct CommunicationBackend {
     /// The state of the connection with the server
     state: Arc<std::sync::Mutex<State>>,
 
+    /// Last measured round trip time, kept through all states
+    ping: Arc<std::sync::Mutex<Option<Duration>>>,
+
     /// The server this connection is for
     server: SocketAddr,
 
     /// Channel to receive commands from gui thread on
     rx: Receiver<GuiToCommCommands>,
 
+    /// Fires when the `Connection` is dropped
+    shutdown: tokio::sync::oneshot::Receiver<()>,
+
     /// Socket to send to server with
     socket: UdpSocket,
 
     /// When did we last hear from server
     last_server_message: Instant,
 
+    /// When did we last handle a timeout
+    last_timeout: Instant,
+
     /// Name of the player
     player_name: String,
 
     /// Id that the server identifies us with
     client_id: Option<ClientId>,
 
-    /// List of all sent packets for debugging
-    sent_packets: Vec<(Instant, ClientMessage)>,
+    /// Recently sent packets, for debugging and to compute the ping
+    sent_packets: RingBuffer<(Instant, ClientMessage)>,
+
+    /// Whether we already asked for the lobby again because our field did not match the server's
+    resynced_lobby: bool,
+
+    /// Number of received datagrams that could not be decoded
+    undecodable_packets: u32,
+
+    /// Recently received packets for debugging
+    received_packets: RingBuffer<ServerMessage>,
+
+    /// Messages that are sent again until the server acknowledges them
+    reliable: ReliableQueue<ClientMessage>,
+
+    /// Time of the newest server update of our game, acknowledged with every `ClientUpdate`
+    last_server_update: TimeStamp,
+
+    /// The `GetGame` request the server did not acknowledge yet. Updates until then were
+    /// sent before it forgot our acknowledgement and must not raise `last_server_update`.
+    resync: Option<PacketNumber>,
 
-    /// List of all received packets for debugging
-    received_packets: Vec<ServerMessage>,
+    /// The local player's current action and when it started, repeated with every
+    /// `ClientUpdate` so a lost one does not lose the action
+    action: (TimeStamp, Action),
 }
 
 impl CommunicationBackend {
@@ -75,8 +166,10 @@ impl CommunicationBackend {
     /// TODO: having `new`  as an async that never returns is strange
     async fn new(
         state: Arc<std::sync::Mutex<State>>,
+        ping: Arc<std::sync::Mutex<Option<Duration>>>,
         server: SocketAddr,
         rx: Receiver<GuiToCommCommands>,
+        shutdown: tokio::sync::oneshot::Receiver<()>,
         player_name: String,
     ) {
         let addr = SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0);
@@ -89,14 +182,23 @@ impl CommunicationBackend {
             .expect("can set socket's remote address");
         CommunicationBackend {
             state,
+            ping,
             server,
             rx,
+            shutdown,
             socket,
             player_name,
-            last_server_message: Instant::now(), // value immediately overwritten
-            sent_packets: Vec::new(),
-            received_packets: Vec::new(),
+            last_server_message: Instant::now(),
+            last_timeout: Instant::now(),
+            sent_packets: RingBuffer::new(PACKET_HISTORY),
+            received_packets: RingBuffer::new(PACKET_HISTORY),
+            undecodable_packets: 0,
+            resynced_lobby: false,
+            reliable: ReliableQueue::new(RETRANSMIT_INTERVAL),
             client_id: None,
+            last_server_update: TimeStamp::default(),
+            resync: None,
+            action: (TimeStamp::default(), Action::idle()),
         }
         .receive_commands_and_messages()
         .await
@@ -105,41 +207,34 @@ impl CommunicationBackend {
     async fn receive_commands_and_messages(&mut self) {
         self.send_hello().await;
         let mut buf = [0; 1024];
+        let mut keep_alive = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+        let mut retransmit = tokio::time::interval(RETRANSMIT_INTERVAL / 4);
         loop {
-            //            TODO: I dont want the timeout signal every single loop.
-            //            once it happened, back of for another interval if the state can deal with
-            //            that, otherwise 🤷
-            //            let timeout_in_current_state: i32 = {
-            //                match *self.state.lock().unwrap() {
-            //                    State::Pinging | State::Alive { .. } => 100,
-            //                    State::OpeningNewLobby => 100,
-            //                    State::Lobby => 5000,
-            //                    State::Game => 1000,
-            //                    State::Failed(_) | State::Disconnected => 86400000,
-            //                }
-            //            };
-            //            let sleep_time:i32 = timeout_in_current_state
-            //                - self
-            //                    .last_server_message
-            //                    .elapsed()
-            //                    .as_millis()
-            //                    .try_into()
-            //                    .unwrap();
-            //            if sleep_time < 0 {self.handle_timeout()
-
+            let timeout = self.next_timeout();
             tokio::select! {
-                // _ = sleep(sleep_tim) => { self.handle_timeout().await }
+                () = sleep_until(timeout) => { self.handle_timeout().await; }
+                _ = keep_alive.tick() => { self.send_keep_alive().await; }
+                _ = retransmit.tick() => { self.retransmit().await; }
                 cmd = self.rx.recv() => {
                     match cmd {
                         Some(cmd) => self.handle_command(cmd) .await,
 
-                        None  => { self.disconnect().await;  }
+                        // the `Connection` was dropped, say bye and end the task
+                        None  => { self.disconnect().await; return; }
 
                     }
                 }
+                _ = &mut self.shutdown => {
+                    // the receiver must not be polled again once it fired
+                    self.disconnect().await;
+                    return;
+                }
                 result = self.socket.recv(&mut buf) => {
                     match result {
                         Ok(len)=> {self.handle_message(&buf[0..len]).await;}
+                        Err(err) if is_transient(&err) => {
+                            log::warn!("ignoring receive error {err:?}");
+                        }
                         Err(err) => {
                             *self.state.lock().unwrap() = State::Failed(format!("can not receive {err:?}"));
                         }
@@ -163,26 +258,140 @@ impl CommunicationBackend {
         };
         match cmd {
             GuiToCommCommands::OpenLobby => match state {
-                State::Alive { .. } => {
+                State::Alive { .. } | State::JoinRejected { .. } => {
                     *self.state.lock().unwrap() = State::OpeningNewLobby;
                     self.send_open_lobby().await;
                 }
                 _ => panic!("unexpected command {cmd:#?}  in state {state:#?}"),
             },
-            GuiToCommCommands::JoinLobby(_) => todo!(),
+            GuiToCommCommands::JoinLobby(game_id) => match state {
+                State::Alive {
+                    lobbies,
+                    server_info,
+                }
+                | State::JoinRejected {
+                    lobbies,
+                    server_info,
+                    ..
+                } => {
+                    *self.state.lock().unwrap() = State::JoiningLobby {
+                        game_id,
+                        lobbies,
+                        server_info,
+                    };
+                    self.send_join_lobby(game_id).await;
+                }
+                _ => panic!("unexpected command {cmd:#?}  in state {state:#?}"),
+            },
+            GuiToCommCommands::SetReady(ready) => match state {
+                State::Lobby { .. } => {
+                    self.send_reliable(ClientMessage::SetReady(self.client_id.unwrap(), ready))
+                        .await;
+                }
+                // the gui may not have noticed the start yet
+                State::Game { .. } => log::info!("not setting ready, the game started"),
+                _ => panic!("unexpected command {cmd:#?}  in state {state:#?}"),
+            },
+            GuiToCommCommands::ForceStart => match state {
+                State::Lobby { .. } => {
+                    self.send_reliable(ClientMessage::ForceStart(self.client_id.unwrap()))
+                        .await;
+                }
+                // the gui may not have noticed the start yet
+                State::Game { .. } => log::info!("not starting, the game started already"),
+                _ => panic!("unexpected command {cmd:#?}  in state {state:#?}"),
+            },
+            GuiToCommCommands::SetSettings(settings) => match state {
+                State::Lobby { .. } => {
+                    self.send_reliable(ClientMessage::SetSettings(
+                        self.client_id.unwrap(),
+                        Box::new(settings),
+                    ))
+                    .await;
+                }
+                // the gui may not have noticed the start yet
+                State::Game { .. } => log::info!("not changing settings, the game started"),
+                _ => panic!("unexpected command SetSettings in state {state:#?}"),
+            },
+            GuiToCommCommands::Retry => match state {
+                State::ServerFull { .. } => self.send_hello().await,
+                _ => log::debug!("not retrying in state {state:?}"),
+            },
+            GuiToCommCommands::SetUpdateRate(rate) => match self.client_id {
+                Some(client_id) => {
+                    self.send_reliable(ClientMessage::SetUpdateRate(client_id, rate))
+                        .await;
+                }
+                None => log::debug!("not setting update rate before hello"),
+            },
+            GuiToCommCommands::ResyncGame => match state {
+                State::Game { .. } => {
+                    self.last_server_update = TimeStamp::default();
+                    let packet_number = self
+                        .send_reliable(ClientMessage::GetGame(self.client_id.unwrap()))
+                        .await;
+                    self.resync = Some(packet_number);
+                }
+                _ => log::debug!("not resyncing in state {state:?}"),
+            },
+            GuiToCommCommands::Actions(actions) => match state {
+                State::Game { .. } => {
+                    for action in actions {
+                        self.action = action;
+                        self.send_update().await;
+                    }
+                }
+                _ => log::debug!("not sending actions in state {state:?}"),
+            },
+            GuiToCommCommands::LogPacketHistory => {
+                for line in self.packet_history() {
+                    log::info!("{line}");
+                }
+            }
+            GuiToCommCommands::GetPacketHistory(tx) => {
+                // the gui might have stopped waiting
+                let _ = tx.send(self.packet_history());
+            }
         }
     }
 
+    /// The recently sent and received packets, one line each
+    fn packet_history(&self) -> Vec<String> {
+        self.sent_packets
+            .iter()
+            .map(|(time, msg)| format!("sent {:?} ago: {msg:?}", time.elapsed()))
+            .chain(
+                self.received_packets
+                    .iter()
+                    .map(|msg| format!("received: {msg:?}")),
+            )
+            .collect()
+    }
+
     async fn handle_message(&mut self, data: &[u8]) {
         let Some(msg) = decode(data) else {
-            log::warn!("unparseable data: {data:?}");
+            self.undecodable_packets += 1;
+            log::warn!(
+                "unparseable data ({} so far): {data:?}",
+                self.undecodable_packets
+            );
             return;
         };
         log::debug!("received: {msg:#?}");
+        self.last_server_message = Instant::now();
         match &msg {
             ServerMessage::Hello(msg) => self.handle_server_hello(msg),
-            ServerMessage::Update(msg) => self.handle_server_update(msg),
-            ServerMessage::LobbyUpdate(msg) => self.handle_server_lobby_update(msg),
+            ServerMessage::Update(msg) => self.handle_server_update(msg).await,
+            ServerMessage::LobbyUpdate(msg) => self.handle_server_lobby_update(msg).await,
+            ServerMessage::GameStart(msg) => self.handle_server_game_start(msg),
+            ServerMessage::Ack(packet_number) => self.handle_server_ack(*packet_number),
+            ServerMessage::ByeAck => log::warn!("unexpected ByeAck"),
+            ServerMessage::Bye(reason) => self.handle_server_bye(*reason),
+            ServerMessage::GameOver(outcome) => self.handle_server_game_over(outcome).await,
+            ServerMessage::TournamentStatus(status) => self.handle_server_tournament_status(status),
+            ServerMessage::AdminGames(_)
+            | ServerMessage::AdminGameInfo(_)
+            | ServerMessage::AdminRejected => log::warn!("unexpected admin response"),
         }
         self.received_packets.push(msg);
     }
@@ -190,19 +399,22 @@ impl CommunicationBackend {
     fn handle_server_hello(&mut self, msg: &ServerHello) {
         let state: &mut State = &mut *self.state.lock().unwrap();
         match { &state } {
-            State::Pinging | State::Alive { .. } => {
-                let (packet_time, _) = self
-                    .sent_packets
-                    .iter()
-                    .rfind(|(_, p)| {
-                        if let ClientMessage::Hello(hello) = p {
-                            hello.nonce == msg.clients_nonce
-                        } else {
-                            false
-                        }
-                    })
-                    .expect("the server responded to our hello, not something else");
+            State::Pinging
+            | State::ServerFull { .. }
+            | State::Alive { .. }
+            | State::JoinRejected { .. } => {
+                let Some((packet_time, _)) = self.sent_packets.iter().rfind(|(_, p)| {
+                    if let ClientMessage::Hello(hello) = p {
+                        hello.nonce == msg.clients_nonce
+                    } else {
+                        false
+                    }
+                }) else {
+                    log::warn!("ignoring response to a hello that is no longer in the history");
+                    return;
+                };
                 let ping = packet_time.elapsed();
+                *self.ping.lock().unwrap() = Some(ping);
                 let lobbies = msg.lobbies.clone();
 
                 let server_info = ServerInfo {
@@ -223,38 +435,266 @@ impl CommunicationBackend {
 
                 self.client_id = Some(msg.client_id);
             }
-            _ => todo!(),
+            // a late response to a hello sent before we asked for a lobby
+            _ => log::info!("ignoring server hello in state {state:?}"),
         };
     }
 
-    fn handle_server_update(&self, msg: &ServerUpdate) {
-        todo!()
+    /// Keep the update for the game and acknowledge it with our current action
+    fn handle_server_ack(&mut self, packet_number: PacketNumber) {
+        self.reliable.acknowledge(packet_number);
+        if self.resync == Some(packet_number) {
+            self.resync = None;
+        }
+    }
+
+    async fn handle_server_update(&mut self, msg: &ServerUpdate) {
+        {
+            let state: &mut State = &mut *self.state.lock().unwrap();
+            let State::Game { updates, .. } = state else {
+                log::debug!("ignoring update in {state:?}");
+                return;
+            };
+            updates.push((msg.clone(), Instant::now()));
+        }
+        if self.resync.is_none() && msg.time > self.last_server_update {
+            self.last_server_update = msg.time;
+        }
+        self.send_update().await;
+    }
+
+    async fn handle_server_lobby_update(&mut self, msg: &ServerLobbyUpdate) {
+        if !msg.field_matches() {
+            if self.resynced_lobby {
+                *self.state.lock().unwrap() = State::Failed(
+                    "The server generates a different field, update Bomberhans".to_owned(),
+                );
+            } else {
+                // Maybe the update was stale, ask once more before giving up
+                log::warn!("field of lobby update does not match, resyncing");
+                self.resynced_lobby = true;
+                self.send_reliable(ClientMessage::GetLobby(self.client_id.unwrap()))
+                    .await;
+            }
+            return;
+        }
+        self.resynced_lobby = false;
+        *self.state.lock().unwrap() = State::Lobby {
+            client_player_id: msg.client_player_id,
+            game: msg.game.clone(),
+            players_ready: msg.players_ready.clone(),
+            start_rejected: None,
+        };
+    }
+
+    fn handle_server_game_start(&mut self, msg: &ServerGameStart) {
+        let state: &mut State = &mut *self.state.lock().unwrap();
+        let tournament = match state {
+            State::Lobby { .. } => {
+                log::info!("Game started at {:?}", msg.time);
+                None
+            }
+            State::Game {
+                outcome: Some(_),
+                tournament,
+                ..
+            } => {
+                log::info!("Next tournament round started at {:?}", msg.time);
+                tournament.take()
+            }
+            State::Game { .. } => {
+                log::debug!("ignoring repeated GameStart");
+                return;
+            }
+            _ => {
+                log::warn!("unexpected GameStart in {state:?}");
+                return;
+            }
+        };
+        *state = State::Game {
+            start: msg.clone(),
+            received: Instant::now(),
+            outcome: None,
+            tournament,
+            updates: Vec::new(),
+        };
+        self.last_server_update = TimeStamp::default();
+        self.resync = None;
+        self.action = (TimeStamp::default(), Action::idle());
+    }
+
+    fn handle_server_bye(&self, reason: DisconnectReason) {
+        let state: &mut State = &mut *self.state.lock().unwrap();
+        if reason == DisconnectReason::ServerFull {
+            let retries = match state {
+                State::Pinging => 0,
+                State::ServerFull { retries } => *retries + 1,
+                _ => {
+                    log::warn!("unexpected Bye({reason}) in state {state:?}");
+                    return;
+                }
+            };
+            log::info!(
+                "Server is full, asking again in {:?}",
+                server_full_backoff(retries)
+            );
+            *state = State::ServerFull { retries };
+        } else if let State::JoiningLobby {
+            lobbies,
+            server_info,
+            ..
+        } = state
+        {
+            log::info!("Server rejected join: {reason}");
+            *state = State::JoinRejected {
+                lobbies: std::mem::take(lobbies),
+                server_info: server_info.clone(),
+                reason,
+            };
+        } else if let State::Lobby { start_rejected, .. } = state {
+            if reason.ends_game() {
+                log::info!("Server ended our lobby: {reason}");
+                *state = State::Failed(reason.to_string());
+            } else {
+                log::info!("Server refused our request: {reason}");
+                *start_rejected = Some(reason);
+            }
+        } else if let State::Game { .. } = state {
+            if reason.ends_game() {
+                log::info!("Server ended our game: {reason}");
+                *state = State::Failed(reason.to_string());
+            } else {
+                // e.g. settings that were sent just before the game started
+                log::info!("Server refused our request: {reason}");
+            }
+        } else {
+            log::warn!("unexpected Bye({reason}) in state {state:?}");
+        }
+    }
+
+    async fn handle_server_game_over(&mut self, msg: &Results) {
+        {
+            let state: &mut State = &mut *self.state.lock().unwrap();
+            let State::Game { outcome, .. } = state else {
+                log::warn!("unexpected GameOver in {state:?}");
+                return;
+            };
+            log::info!("Game over, winner: {:?}", msg.winner);
+            *outcome = Some(msg.clone());
+        }
+        // GameOver is repeated until acknowledged
+        self.send(ClientMessage::GameOverAck(self.client_id.unwrap()))
+            .await;
+    }
+
+    fn handle_server_tournament_status(&self, msg: &TournamentStatus) {
+        let state: &mut State = &mut *self.state.lock().unwrap();
+        if let State::Game { tournament, .. } = state {
+            log::info!("Tournament wins: {:?}, winner: {:?}", msg.wins, msg.winner);
+            *tournament = Some(msg.clone());
+        } else {
+            log::warn!("unexpected TournamentStatus in {state:?}");
+        }
+    }
+
+    /// How long the server may stay silent in the current state before `handle_timeout`
+    fn timeout_in_current_state(&self) -> Duration {
+        match *self.state.lock().unwrap() {
+            State::Pinging | State::OpeningNewLobby | State::JoiningLobby { .. } => {
+                Duration::from_millis(500)
+            }
+            State::Alive { .. } | State::JoinRejected { .. } | State::Game { .. } => {
+                Duration::from_secs(1)
+            }
+            State::ServerFull { retries } => server_full_backoff(retries),
+            State::Lobby { .. } => Duration::from_secs(5),
+            State::Failed(_) | State::Disconnected => Duration::from_secs(86400),
+        }
     }
 
-    fn handle_server_lobby_update(&self, msg: &ServerLobbyUpdate) {
-        todo!()
+    /// When to handle a timeout if the server stays silent
+    ///
+    /// After a timeout, back off for another interval instead of signaling it every loop
+    fn next_timeout(&self) -> Instant {
+        Instant::max(self.last_server_message, self.last_timeout) + self.timeout_in_current_state()
     }
 
     async fn handle_timeout(&mut self) {
+        self.last_timeout = Instant::now();
         let state = self.state.lock().unwrap().clone();
         match state {
-            State::Pinging | State::Alive { .. } => {
-                std::mem::drop(state); // TODO: when is state dropped?
-                self.send_hello().await
+            State::Pinging
+            | State::ServerFull { .. }
+            | State::Alive { .. }
+            | State::JoinRejected { .. } => {
+                // also refreshes the list of lobbies
+                self.send_hello().await;
+            }
+            State::OpeningNewLobby => {
+                // the server answers with the lobby it already opened for us if only the
+                // response got lost
+                log::info!("no response to OpenNewLobby, sending again");
+                self.send_open_lobby().await;
+            }
+            State::JoiningLobby { game_id, .. } => {
+                // joining twice is harmless, the server answers with the lobby again
+                log::info!("no response to JoinLobby, sending again");
+                self.send_join_lobby(game_id).await;
+            }
+            State::Lobby { .. } => {
+                log::warn!(
+                    "no lobby update for {:?}",
+                    self.last_server_message.elapsed()
+                );
             }
-            _ => todo!(),
+            State::Game { .. } => {
+                *self.state.lock().unwrap() = State::Failed(format!(
+                    "Server stopped sending updates {:?} ago",
+                    self.last_server_message.elapsed()
+                ));
+            }
+            State::Failed(_) | State::Disconnected => {}
         }
     }
 
     async fn disconnect(&mut self) {
         if let Some(client_id) = self.client_id {
-            self.send(ClientMessage::Bye(client_id)).await;
-            sleep(Duration::from_millis(10)).await;
-            self.send(ClientMessage::Bye(client_id)).await;
+            let deadline = Instant::now() + BYE_TIMEOUT;
+            while Instant::now() < deadline {
+                self.send(ClientMessage::Bye(client_id)).await;
+                let retransmit = deadline.min(Instant::now() + RETRANSMIT_INTERVAL);
+                if tokio::time::timeout_at(retransmit, self.receive_bye_ack())
+                    .await
+                    .is_ok()
+                {
+                    log::info!("Server acknowledged Bye");
+                    break;
+                }
+            }
         }
         *self.state.lock().unwrap() = State::Disconnected;
     }
 
+    /// Wait for the server to acknowledge our `Bye`, or to say `Bye` itself
+    ///
+    /// Everything else the server sends is for a connection that is ending anyway.
+    async fn receive_bye_ack(&mut self) {
+        let mut buf = [0; 1024];
+        loop {
+            match self.socket.recv(&mut buf).await {
+                Ok(len) => match decode(&buf[0..len]) {
+                    Some(ServerMessage::ByeAck | ServerMessage::Bye(_)) => return,
+                    msg => log::debug!("ignoring while saying bye: {msg:?}"),
+                },
+                Err(err) => {
+                    log::warn!("can not receive ByeAck: {err:?}");
+                    // returning would count as acknowledged, let the timeout end this
+                    std::future::pending::<()>().await;
+                }
+            }
+        }
+    }
+
     async fn send(&mut self, msg: ClientMessage) {
         log::debug!("Sending {msg:#?}");
         let now = Instant::now();
@@ -268,6 +708,29 @@ impl CommunicationBackend {
         self.sent_packets.push((now, msg));
     }
 
+    /// Send a message and keep sending it until the server acknowledges it
+    async fn send_reliable(&mut self, msg: ClientMessage) -> PacketNumber {
+        let packet_number = self.reliable.push(msg.clone(), Instant::now().into_std());
+        self.send(ClientMessage::Reliable(packet_number, Box::new(msg)))
+            .await;
+        packet_number
+    }
+
+    /// Send a message reliably again, instead of the copy that is still unacknowledged
+    async fn resend_reliable(&mut self, msg: ClientMessage) {
+        let packet_number = self.reliable.resend(msg.clone(), Instant::now().into_std());
+        self.send(ClientMessage::Reliable(packet_number, Box::new(msg)))
+            .await;
+    }
+
+    async fn retransmit(&mut self) {
+        for (packet_number, msg) in self.reliable.due(Instant::now().into_std()) {
+            log::debug!("retransmitting {packet_number:?}");
+            self.send(ClientMessage::Reliable(packet_number, Box::new(msg)))
+                .await;
+        }
+    }
+
     async fn send_hello(&mut self) {
         self.send(ClientMessage::Hello(ClientHello {
             magic: BOMBERHANS_MAGIC_NO_V1,
@@ -277,26 +740,94 @@ impl CommunicationBackend {
         .await;
     }
 
+    /// Acknowledge the newest server update and repeat our current action
+    async fn send_update(&mut self) {
+        let Some(client_id) = self.client_id else {
+            return;
+        };
+        let (current_action_start_time, current_player_action) = self.action;
+        self.send(ClientMessage::Update(ClientUpdate {
+            client_id,
+            last_server_update: self.last_server_update,
+            current_player_action,
+            current_action_start_time,
+        }))
+        .await;
+    }
+
+    async fn send_keep_alive(&mut self) {
+        if let Some(client_id) = self.client_id {
+            self.send(ClientMessage::KeepAlive(client_id)).await;
+        }
+    }
+
     async fn send_open_lobby(&mut self) {
-        self.send(ClientMessage::OpenNewLobby(self.client_id.unwrap()))
+        self.resend_reliable(ClientMessage::OpenNewLobby(self.client_id.unwrap()))
             .await;
     }
+
+    async fn send_join_lobby(&mut self, lobby: GameId) {
+        self.resend_reliable(ClientMessage::JoinLobby(ClientJoinLobby {
+            client_id: self.client_id.unwrap(),
+            lobby,
+        }))
+        .await;
+    }
+}
+
+/// How long to wait before asking a full server again after `retries` more rejections
+fn server_full_backoff(retries: u32) -> Duration {
+    SERVER_FULL_RETRY_INTERVAL
+        .saturating_mul(2_u32.saturating_pow(retries))
+        .min(SERVER_FULL_RETRY_MAX)
+}
+
+/// Receive errors that do not end the connection
+///
+/// `ConnectionRefused`/`ConnectionReset` are ICMP errors for an earlier datagram, the server might
+/// only be restarting. If it stays away, the timeouts notice.
+fn is_transient(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::WouldBlock
+            | std::io::ErrorKind::Interrupted
+            | std::io::ErrorKind::ConnectionRefused
+            | std::io::ErrorKind::ConnectionReset
+    )
 }
 
 #[derive(Debug)]
 enum GuiToCommCommands {
     OpenLobby,
     JoinLobby(GameId),
+    SetReady(bool),
+    ForceStart,
+    SetSettings(Settings),
+    Retry,
+    ResyncGame,
+    /// Receive at most this many updates per second, 0 for every tick
+    SetUpdateRate(u32),
+    /// Changes of the local player's action, oldest first
+    Actions(Vec<(TimeStamp, Action)>),
+    LogPacketHistory,
+    GetPacketHistory(tokio::sync::oneshot::Sender<Vec<String>>),
 }
 
 /// Communication with one server
+///
+/// Dropping it tells the backend to say `Bye` to the server and end.
 #[derive(Debug)]
 pub struct Connection {
     /// Send commands from gui to comm via this channel
     tx: Sender<GuiToCommCommands>,
 
+    /// Stop the backend, taken when dropped
+    shutdown: Option<tokio::sync::oneshot::Sender<()>>,
+
     state: Arc<std::sync::Mutex<State>>,
 
+    ping: Arc<std::sync::Mutex<Option<Duration>>>,
+
     pub server: SocketAddr,
 }
 
@@ -307,8 +838,18 @@ impl Connection {
             State::Alive {
                 lobbies,
                 server_info,
+            }
+            | State::JoiningLobby {
+                lobbies,
+                server_info,
+                ..
+            }
+            | State::JoinRejected {
+                lobbies,
+                server_info,
+                ..
             } => Some(Ok((lobbies.clone(), server_info.clone()))),
-            State::Pinging => None,
+            State::Pinging | State::ServerFull { .. } => None,
             State::Disconnected => return Some(Err("Disconnected".to_owned())),
             State::Failed(err) => return Some(Err(err.clone())),
 
@@ -316,21 +857,857 @@ impl Connection {
         }
     }
 
+    /// Whether the server rejected us because it is full, we keep asking it again
+    pub fn server_full(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), State::ServerFull { .. })
+    }
+
+    /// Round trip time to the server, once it answered
+    pub fn get_ping(&self) -> Option<Duration> {
+        *self.ping.lock().unwrap()
+    }
+
+    /// Our game and when it started, while it runs
+    pub fn game_start(&self) -> Option<(ServerGameStart, std::time::Instant)> {
+        match &*self.state.lock().unwrap() {
+            State::Game {
+                start,
+                received,
+                outcome: None,
+                ..
+            } => Some((start.clone(), received.into_std())),
+            _ => None,
+        }
+    }
+
+    /// The updates of our game that arrived since the last call, with when they arrived
+    pub fn take_server_updates(&self) -> Vec<(ServerUpdate, std::time::Instant)> {
+        match &mut *self.state.lock().unwrap() {
+            State::Game { updates, .. } => std::mem::take(updates)
+                .into_iter()
+                .map(|(update, received)| (update, received.into_std()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The final standings the server sent when our game ended
+    pub fn game_outcome(&self) -> Option<Results> {
+        match &*self.state.lock().unwrap() {
+            State::Game { outcome, .. } => outcome.clone(),
+            _ => None,
+        }
+    }
+
+    /// The wins so far of the tournament our game is a round of
+    pub fn tournament_status(&self) -> Option<TournamentStatus> {
+        match &*self.state.lock().unwrap() {
+            State::Game { tournament, .. } => tournament.clone(),
+            _ => None,
+        }
+    }
+
+    /// Whether we are in the lobby we wanted to join, `None` while still waiting
+    pub fn join_status(&self) -> Option<Result<(), DisconnectReason>> {
+        match &*self.state.lock().unwrap() {
+            State::Lobby { .. } => Some(Ok(())),
+            State::JoinRejected { reason, .. } => Some(Err(*reason)),
+            _ => None,
+        }
+    }
+
+    /// The settings of the lobby we are in, if any
+    pub fn lobby_settings(&self) -> Option<Settings> {
+        match &*self.state.lock().unwrap() {
+            State::Lobby { game, .. } => Some(game.settings.clone()),
+            _ => None,
+        }
+    }
+
+    /// The settings of our game once it started, they are locked until it is a lobby again
+    pub fn locked_settings(&self) -> Option<Settings> {
+        match &*self.state.lock().unwrap() {
+            State::Game { start, .. } => Some(start.game.settings.clone()),
+            _ => None,
+        }
+    }
+
+    /// Why the server did not start the game of our lobby or refused its settings
+    pub fn start_rejected(&self) -> Option<DisconnectReason> {
+        match &*self.state.lock().unwrap() {
+            State::Lobby { start_rejected, .. } => *start_rejected,
+            _ => None,
+        }
+    }
+
+    /// Names of the players in our lobby and whether they are ready
+    pub fn lobby_players(&self) -> Option<Vec<(String, bool)>> {
+        match &*self.state.lock().unwrap() {
+            State::Lobby {
+                game,
+                players_ready,
+                ..
+            } => Some(lobby_player_list(&game.players, players_ready)),
+            _ => None,
+        }
+    }
+
     pub fn open_new_lobby(&self) {
         self.tx.blocking_send(GuiToCommCommands::OpenLobby).unwrap();
     }
+
+    pub fn join_lobby(&self, game_id: GameId) {
+        self.tx
+            .blocking_send(GuiToCommCommands::JoinLobby(game_id))
+            .unwrap();
+    }
+
+    /// Tell the server whether we are ready to start the game
+    pub fn set_ready(&self, ready: bool) {
+        self.tx
+            .blocking_send(GuiToCommCommands::SetReady(ready))
+            .unwrap();
+    }
+
+    /// Start the game we host with the players that are ready
+    pub fn force_start(&self) {
+        self.tx
+            .blocking_send(GuiToCommCommands::ForceStart)
+            .unwrap();
+    }
+
+    /// Change the settings of the lobby we host
+    pub fn set_settings(&self, settings: Settings) {
+        self.tx
+            .blocking_send(GuiToCommCommands::SetSettings(settings))
+            .unwrap();
+    }
+
+    /// Ask a full server again now, instead of waiting for the next retry
+    pub fn retry(&self) {
+        self.tx.blocking_send(GuiToCommCommands::Retry).unwrap();
+    }
+
+    /// Ask the server for all updates of our game again, to replay it after a desync
+    pub fn resync_game(&self) {
+        self.tx
+            .blocking_send(GuiToCommCommands::ResyncGame)
+            .unwrap();
+    }
+
+    /// Ask the server for at most `rate` updates per second, 0 for every tick
+    ///
+    /// The local simulation fills the ticks between updates, only the other players' actions
+    /// arrive later.
+    pub fn set_update_rate(&self, rate: u32) {
+        self.tx
+            .blocking_send(GuiToCommCommands::SetUpdateRate(rate))
+            .unwrap();
+    }
+
+    /// Tell the server how the local player's action changed, oldest first
+    pub fn send_actions(&self, actions: Vec<(TimeStamp, Action)>) {
+        self.tx
+            .blocking_send(GuiToCommCommands::Actions(actions))
+            .unwrap();
+    }
+
+    /// Log the recently sent and received packets
+    pub fn log_packet_history(&self) {
+        self.tx
+            .blocking_send(GuiToCommCommands::LogPacketHistory)
+            .unwrap();
+    }
+
+    /// The recently sent and received packets, empty if the connection is closed
+    pub fn packet_history(&self) -> Vec<String> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        if self
+            .tx
+            .blocking_send(GuiToCommCommands::GetPacketHistory(tx))
+            .is_err()
+        {
+            return Vec::new();
+        }
+        rx.blocking_recv().unwrap_or_default()
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // don't rely on the closing command channel alone, a clone of `tx` might keep it open
+        if let Some(shutdown) = self.shutdown.take() {
+            // the backend may have ended already
+            let _ = shutdown.send(());
+        }
+    }
 }
 
 pub fn connect(server: SocketAddr, player_name: String) -> Connection {
     let (tx, rx) = tokio::sync::mpsc::channel::<GuiToCommCommands>(32);
+    let (shutdown, shutdown_rx) = tokio::sync::oneshot::channel();
     let state = State::Pinging;
     let state = std::sync::Mutex::new(state);
     let state = Arc::new(state);
+    let ping = Arc::new(std::sync::Mutex::new(None));
 
     {
         let state = Arc::clone(&state);
-        let foo = RUNTIME.spawn(CommunicationBackend::new(state, server, rx, player_name));
+        let ping = Arc::clone(&ping);
+        let foo = RUNTIME.spawn(CommunicationBackend::new(
+            state,
+            ping,
+            server,
+            rx,
+            shutdown_rx,
+            player_name,
+        ));
+    }
+
+    Connection {
+        tx,
+        shutdown: Some(shutdown),
+        state,
+        ping,
+        server,
+    }
+}
+
+/// Names of the players and whether they are ready, ordered by `PlayerId`
+///
+/// The gui relies on the order to show the same list to everyone, so it is sorted here
+/// explicitly instead of depending on how the server stores its players.
+fn lobby_player_list(
+    players: &BTreeMap<PlayerId, Player>,
+    players_ready: &BTreeSet<PlayerId>,
+) -> Vec<(String, bool)> {
+    let mut players: Vec<_> = players.iter().collect();
+    players.sort_by_key(|(id, _)| **id);
+    players
+        .into_iter()
+        .map(|(id, player)| (player.name.clone(), players_ready.contains(id)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_garbage_does_not_end_connection() {
+        RUNTIME.block_on(async {
+            let server = UdpSocket::bind("[::1]:0").await.unwrap();
+            let connection = connect(server.local_addr().unwrap(), "Hans".to_owned());
+
+            let mut buf = [0; 1024];
+            let (len, client) = server.recv_from(&mut buf).await.unwrap();
+            let Some(ClientMessage::Hello(hello)) = decode(&buf[..len]) else {
+                panic!("client starts with hello");
+            };
+
+            for garbage in [&[][..], &[0xff; 3], &[1, 2, 3, 4, 5], &[0xff; 1000]] {
+                server.send_to(garbage, client).await.unwrap();
+            }
+            let response = ServerMessage::Hello(ServerHello {
+                clients_nonce: hello.nonce,
+                client_id: ClientId::new(1),
+                server_name: "Test".to_owned(),
+                lobbies: Vec::new(),
+            });
+            server.send_to(&encode(&response), client).await.unwrap();
+
+            for _ in 0..100 {
+                if let Some(info) = connection.get_server_info() {
+                    let (_, server_info) = info.expect("connection survives garbage");
+                    assert_eq!(server_info.server_name, "Test");
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!("no server info");
+        });
+    }
+
+    #[test]
+    fn test_full_server_is_retried() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        let connection = connect(server.local_addr().unwrap(), "Hans".to_owned());
+        let receive_hello = || {
+            let mut buf = [0; 1024];
+            let (len, client) = server.recv_from(&mut buf).unwrap();
+            let Some(ClientMessage::Hello(hello)) = decode(&buf[..len]) else {
+                panic!("client says hello");
+            };
+            (hello, client)
+        };
+
+        let (_, client) = receive_hello();
+        server
+            .send_to(
+                &encode(&ServerMessage::Bye(DisconnectReason::ServerFull)),
+                client,
+            )
+            .unwrap();
+        while !connection.server_full() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(
+            connection.get_server_info().is_none(),
+            "still connecting, not failed"
+        );
+
+        connection.retry();
+        let (hello, client) = receive_hello();
+        let response = ServerMessage::Hello(ServerHello {
+            clients_nonce: hello.nonce,
+            client_id: ClientId::new(1),
+            server_name: "Test".to_owned(),
+            lobbies: Vec::new(),
+        });
+        server.send_to(&encode(&response), client).unwrap();
+        while connection.server_full() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(matches!(connection.get_server_info(), Some(Ok(_))));
+    }
+
+    #[test]
+    fn test_server_full_backoff_grows_to_the_limit() {
+        assert_eq!(server_full_backoff(0), SERVER_FULL_RETRY_INTERVAL);
+        assert_eq!(server_full_backoff(1), SERVER_FULL_RETRY_INTERVAL * 2);
+        assert_eq!(server_full_backoff(100), SERVER_FULL_RETRY_MAX);
+    }
+
+    /// Receive the next message from the client, skipping periodic `KeepAlive`s and `Hello`s
+    fn receive(server: &std::net::UdpSocket) -> (ClientMessage, SocketAddr) {
+        let mut buf = [0; 1024];
+        loop {
+            let (len, client) = server.recv_from(&mut buf).unwrap();
+            match decode(&buf[..len]).expect("client sends valid messages") {
+                ClientMessage::KeepAlive(_) | ClientMessage::Hello(_) => {}
+                msg => return (msg, client),
+            }
+        }
     }
 
-    Connection { tx, state, server }
+    /// Connect to a fake server and answer the client's hello
+    fn connect_to_fake_server(
+        server: &std::net::UdpSocket,
+        lobbies: Lobbies,
+    ) -> (Connection, ClientId, SocketAddr) {
+        server
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        let connection = connect(server.local_addr().unwrap(), "Hans".to_owned());
+        let client_id = ClientId::new(1);
+
+        let mut buf = [0; 1024];
+        let (len, client) = server.recv_from(&mut buf).unwrap();
+        let Some(ClientMessage::Hello(hello)) = decode(&buf[..len]) else {
+            panic!("client starts with hello");
+        };
+        let response = ServerMessage::Hello(ServerHello {
+            clients_nonce: hello.nonce,
+            client_id,
+            server_name: "Test".to_owned(),
+            lobbies,
+        });
+        server.send_to(&encode(&response), client).unwrap();
+        while connection.get_server_info().is_none() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        (connection, client_id, client)
+    }
+
+    #[test]
+    fn test_dropping_the_connection_ends_the_backend() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, client_id, client) = connect_to_fake_server(&server, Vec::new());
+        let state = Arc::downgrade(&connection.state);
+
+        drop(connection);
+        let (bye, _) = receive(&server);
+        assert_eq!(bye, ClientMessage::Bye(client_id));
+        server
+            .send_to(&encode(&ServerMessage::ByeAck), client)
+            .unwrap();
+
+        // the backend holds the state until it returns
+        for _ in 0..100 {
+            if state.upgrade().is_none() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("backend still running");
+    }
+
+    #[test]
+    fn test_dropping_the_connection_leaves_while_commands_can_be_sent() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, client_id, client) = connect_to_fake_server(&server, Vec::new());
+        let state = Arc::downgrade(&connection.state);
+        let tx = connection.tx.clone();
+
+        drop(connection);
+        let (bye, _) = receive(&server);
+        assert_eq!(bye, ClientMessage::Bye(client_id));
+        server
+            .send_to(&encode(&ServerMessage::ByeAck), client)
+            .unwrap();
+
+        for _ in 0..100 {
+            // the open channel alone would keep the backend running
+            if tx.is_closed() {
+                assert!(state.upgrade().is_none());
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("backend still running");
+    }
+
+    #[test]
+    fn test_unacknowledged_bye_gives_up_in_time() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, client_id, client) = connect_to_fake_server(&server, Vec::new());
+        let state = Arc::downgrade(&connection.state);
+
+        server
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let start = std::time::Instant::now();
+        drop(connection);
+        let mut byes = 0;
+        while state.upgrade().is_some() {
+            // other messages for the leaving client do not keep it waiting
+            server
+                .send_to(&encode(&ServerMessage::Ack(PacketNumber::new(7))), client)
+                .unwrap();
+            let mut buf = [0; 1024];
+            if let Ok((len, _)) = server.recv_from(&mut buf) {
+                if decode(&buf[..len]) == Some(ClientMessage::Bye(client_id)) {
+                    byes += 1;
+                }
+            }
+        }
+        assert!(start.elapsed() < BYE_TIMEOUT + RETRANSMIT_INTERVAL);
+        assert!(byes > 1, "bye is repeated until the timeout");
+    }
+
+    #[test]
+    fn test_server_bye_ends_the_disconnect() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, client_id, client) = connect_to_fake_server(&server, Vec::new());
+        let state = Arc::downgrade(&connection.state);
+
+        drop(connection);
+        let (bye, _) = receive(&server);
+        assert_eq!(bye, ClientMessage::Bye(client_id));
+        server
+            .send_to(
+                &encode(&ServerMessage::Bye(DisconnectReason::GameClosed)),
+                client,
+            )
+            .unwrap();
+
+        for _ in 0..10 {
+            if state.upgrade().is_none() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("backend still waiting for a ByeAck");
+    }
+
+    #[test]
+    fn test_field_mismatch_requests_resync() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, client_id, client) = connect_to_fake_server(&server, Vec::new());
+
+        connection.open_new_lobby();
+        let (ClientMessage::Reliable(packet_number, open), _) = receive(&server) else {
+            panic!("client opens lobby");
+        };
+        assert_eq!(*open, ClientMessage::OpenNewLobby(client_id));
+        server
+            .send_to(&encode(&ServerMessage::Ack(packet_number)), client)
+            .unwrap();
+
+        let game = GameStatic {
+            players: std::collections::BTreeMap::new(),
+            settings: Settings::default(),
+            local_player: PlayerId(0),
+        };
+        let mut update = ServerLobbyUpdate::new(PlayerId(0), game, BTreeSet::new());
+        update.field_checksum += 1;
+        server
+            .send_to(&encode(&ServerMessage::LobbyUpdate(update)), client)
+            .unwrap();
+
+        let (ClientMessage::Reliable(_, resync), _) = receive(&server) else {
+            panic!("client asks for the lobby again");
+        };
+        assert_eq!(*resync, ClientMessage::GetLobby(client_id));
+        assert_eq!(
+            connection.lobby_settings(),
+            None,
+            "mismatching lobby not entered"
+        );
+    }
+
+    #[test]
+    fn test_join_full_game_returns_to_lobby_list() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let game_id = GameId::new(42);
+        let lobbies = vec![(game_id, "Full Game".to_owned())];
+        let (connection, client_id, client) = connect_to_fake_server(&server, lobbies.clone());
+
+        connection.join_lobby(game_id);
+        let (ClientMessage::Reliable(packet_number, join), _) = receive(&server) else {
+            panic!("client joins lobby");
+        };
+        assert_eq!(
+            *join,
+            ClientMessage::JoinLobby(ClientJoinLobby {
+                client_id,
+                lobby: game_id
+            })
+        );
+        assert_eq!(connection.join_status(), None);
+
+        for msg in [
+            ServerMessage::Ack(packet_number),
+            ServerMessage::Bye(DisconnectReason::GameFull),
+        ] {
+            server.send_to(&encode(&msg), client).unwrap();
+        }
+        while connection.join_status().is_none() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(
+            connection.join_status(),
+            Some(Err(DisconnectReason::GameFull))
+        );
+        let Some(Ok((still_listed, _))) = connection.get_server_info() else {
+            panic!("back to the list of lobbies");
+        };
+        assert_eq!(still_listed, lobbies);
+    }
+
+    /// Connect to a fake server, open a lobby and start its game
+    fn start_game_on_fake_server(
+        server: &std::net::UdpSocket,
+    ) -> (Connection, ClientId, SocketAddr) {
+        let (connection, client_id, client) = connect_to_fake_server(server, Vec::new());
+        connection.open_new_lobby();
+        let (ClientMessage::Reliable(packet_number, _), _) = receive(server) else {
+            panic!("client opens lobby");
+        };
+        let game = GameStatic {
+            players: std::collections::BTreeMap::new(),
+            settings: Settings::default(),
+            local_player: PlayerId(0),
+        };
+        for msg in [
+            ServerMessage::Ack(packet_number),
+            ServerMessage::LobbyUpdate(ServerLobbyUpdate::new(
+                PlayerId(0),
+                game.clone(),
+                BTreeSet::new(),
+            )),
+            ServerMessage::GameStart(ServerGameStart {
+                game,
+                time: TimeStamp::default(),
+            }),
+        ] {
+            server.send_to(&encode(&msg), client).unwrap();
+        }
+        while connection.game_start().is_none() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        (connection, client_id, client)
+    }
+
+    #[test]
+    fn test_server_updates_are_passed_on_and_acknowledged() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, client_id, client) = start_game_on_fake_server(&server);
+        assert!(connection.take_server_updates().is_empty());
+
+        let update = ServerUpdate {
+            time: TimeStamp::default() + bomberhans_lib::utils::Duration::from_ticks(5),
+            checksum: 42,
+            updates: Vec::new(),
+        };
+        server
+            .send_to(&encode(&ServerMessage::Update(update.clone())), client)
+            .unwrap();
+
+        let (ack, _) = receive(&server);
+        assert_eq!(
+            ack,
+            ClientMessage::Update(ClientUpdate {
+                client_id,
+                last_server_update: update.time,
+                current_player_action: Action::idle(),
+                current_action_start_time: TimeStamp::default(),
+            })
+        );
+        let updates = connection.take_server_updates();
+        assert_eq!(
+            updates.into_iter().map(|(u, _)| u).collect::<Vec<_>>(),
+            [update]
+        );
+        assert!(connection.take_server_updates().is_empty(), "taken once");
+    }
+
+    #[test]
+    fn test_actions_are_sent_and_repeated_with_acknowledgements() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, client_id, client) = start_game_on_fake_server(&server);
+        let walking = Action {
+            walking: Some(bomberhans_lib::utils::Direction::North),
+            ..Action::idle()
+        };
+        let tick =
+            |ticks| TimeStamp::default() + bomberhans_lib::utils::Duration::from_ticks(ticks);
+
+        connection.send_actions(vec![(tick(1), Action::idle()), (tick(2), walking)]);
+        let sent = || {
+            let (ClientMessage::Update(update), _) = receive(&server) else {
+                panic!("client sends updates");
+            };
+            assert_eq!(update.client_id, client_id);
+            (
+                update.current_action_start_time,
+                update.current_player_action,
+            )
+        };
+        assert_eq!(sent(), (tick(1), Action::idle()));
+        assert_eq!(sent(), (tick(2), walking));
+
+        let update = ServerUpdate {
+            time: tick(3),
+            checksum: 42,
+            updates: Vec::new(),
+        };
+        server
+            .send_to(&encode(&ServerMessage::Update(update)), client)
+            .unwrap();
+        assert_eq!(sent(), (tick(2), walking), "the latest action is repeated");
+    }
+
+    #[test]
+    fn test_game_over_is_acknowledged() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, client_id, client) = start_game_on_fake_server(&server);
+        let outcome = Results {
+            standings: Vec::new(),
+            winner: None,
+            winning_team: None,
+        };
+        for _ in 0..2 {
+            server
+                .send_to(&encode(&ServerMessage::GameOver(outcome.clone())), client)
+                .unwrap();
+            let (ack, _) = receive(&server);
+            assert_eq!(ack, ClientMessage::GameOverAck(client_id), "every time");
+        }
+        assert_eq!(connection.game_outcome(), Some(outcome));
+    }
+
+    #[test]
+    fn test_finished_game_returns_to_the_lobby() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, _, client) = start_game_on_fake_server(&server);
+        let game = connection.game_start().unwrap().0.game;
+        let outcome = Results {
+            standings: Vec::new(),
+            winner: None,
+            winning_team: None,
+        };
+        server
+            .send_to(&encode(&ServerMessage::GameOver(outcome)), client)
+            .unwrap();
+        receive(&server);
+        assert!(connection.lobby_settings().is_none(), "results are shown");
+
+        // the server turns the game back into its lobby once `return_to_lobby_after_s` passed
+        let update = ServerLobbyUpdate::new(PlayerId(0), game, BTreeSet::new());
+        server
+            .send_to(&encode(&ServerMessage::LobbyUpdate(update)), client)
+            .unwrap();
+        for _ in 0..100 {
+            if connection.lobby_settings().is_some() {
+                assert!(connection.locked_settings().is_none(), "settings unlocked");
+                assert!(connection.game_outcome().is_none());
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("client stays in the finished game");
+    }
+
+    #[test]
+    fn test_unanswered_open_lobby_is_replaced() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, client_id, _) = connect_to_fake_server(&server, Vec::new());
+        let open_lobby = || {
+            let (ClientMessage::Reliable(packet_number, msg), _) = receive(&server) else {
+                panic!("client opens the lobby reliably");
+            };
+            assert_eq!(*msg, ClientMessage::OpenNewLobby(client_id));
+            packet_number
+        };
+
+        connection.open_new_lobby();
+        let first = open_lobby();
+        while open_lobby() == first {}
+        let start = std::time::Instant::now();
+        while start.elapsed() < RETRANSMIT_INTERVAL * 3 {
+            assert_ne!(open_lobby(), first, "the retry replaces the first request");
+        }
+    }
+
+    #[test]
+    fn test_late_hello_while_opening_a_lobby_is_ignored() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, client_id, client) = connect_to_fake_server(&server, Vec::new());
+
+        connection.open_new_lobby();
+        let (ClientMessage::Reliable(packet_number, _), _) = receive(&server) else {
+            panic!("client opens lobby");
+        };
+        let game = GameStatic {
+            players: std::collections::BTreeMap::new(),
+            settings: Settings::default(),
+            local_player: PlayerId(0),
+        };
+        for msg in [
+            ServerMessage::Hello(ServerHello {
+                clients_nonce: 0,
+                client_id,
+                server_name: "Test".to_owned(),
+                lobbies: Vec::new(),
+            }),
+            ServerMessage::Ack(packet_number),
+            ServerMessage::LobbyUpdate(ServerLobbyUpdate::new(PlayerId(0), game, BTreeSet::new())),
+        ] {
+            server.send_to(&encode(&msg), client).unwrap();
+        }
+        while connection.lobby_settings().is_none() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_lobby_commands_after_the_start_are_ignored() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, client_id, _) = start_game_on_fake_server(&server);
+
+        // clicked before the gui noticed the start
+        connection.set_ready(true);
+        connection.force_start();
+
+        connection.set_update_rate(4);
+        let (ClientMessage::Reliable(_, msg), _) = receive(&server) else {
+            panic!("client is still connected");
+        };
+        assert_eq!(*msg, ClientMessage::SetUpdateRate(client_id, 4));
+        assert!(connection.game_start().is_some());
+    }
+
+    #[test]
+    fn test_update_rate_is_sent_reliably() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, client_id, _) = connect_to_fake_server(&server, Vec::new());
+
+        connection.set_update_rate(4);
+        let (ClientMessage::Reliable(_, msg), _) = receive(&server) else {
+            panic!("client sends the rate reliably");
+        };
+        assert_eq!(*msg, ClientMessage::SetUpdateRate(client_id, 4));
+    }
+
+    #[test]
+    fn test_resync_acknowledges_updates_only_after_the_request() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, client_id, client) = start_game_on_fake_server(&server);
+        let update = |ticks| {
+            ServerMessage::Update(ServerUpdate {
+                time: TimeStamp::default() + bomberhans_lib::utils::Duration::from_ticks(ticks),
+                checksum: 42,
+                updates: Vec::new(),
+            })
+        };
+        let acknowledged = || {
+            let (ClientMessage::Update(update), _) = receive(&server) else {
+                panic!("client acknowledges updates");
+            };
+            update.last_server_update.ticks_from_start()
+        };
+        server.send_to(&encode(&update(20)), client).unwrap();
+        assert_eq!(acknowledged(), 20);
+
+        connection.resync_game();
+        let (ClientMessage::Reliable(packet_number, msg), _) = receive(&server) else {
+            panic!("client requests the game");
+        };
+        assert_eq!(*msg, ClientMessage::GetGame(client_id));
+        server.send_to(&encode(&update(24)), client).unwrap();
+        assert_eq!(acknowledged(), 0, "sent before the server got the request");
+
+        server
+            .send_to(&encode(&ServerMessage::Ack(packet_number)), client)
+            .unwrap();
+        server.send_to(&encode(&update(8)), client).unwrap();
+        assert_eq!(acknowledged(), 8, "the first chunk of the replay");
+    }
+
+    #[test]
+    fn test_locked_settings_do_not_end_the_game() {
+        let server = std::net::UdpSocket::bind("[::1]:0").unwrap();
+        let (connection, _, client) = connect_to_fake_server(&server, Vec::new());
+
+        connection.open_new_lobby();
+        let (ClientMessage::Reliable(packet_number, _), _) = receive(&server) else {
+            panic!("client opens lobby");
+        };
+        let game = GameStatic {
+            players: std::collections::BTreeMap::new(),
+            settings: Settings::default(),
+            local_player: PlayerId(0),
+        };
+        for msg in [
+            ServerMessage::Ack(packet_number),
+            ServerMessage::LobbyUpdate(ServerLobbyUpdate::new(
+                PlayerId(0),
+                game.clone(),
+                BTreeSet::new(),
+            )),
+            ServerMessage::GameStart(ServerGameStart {
+                game,
+                time: TimeStamp::default(),
+            }),
+            // the answer to settings sent just before the game started
+            ServerMessage::Bye(DisconnectReason::SettingsLocked),
+        ] {
+            server.send_to(&encode(&msg), client).unwrap();
+        }
+        while connection.locked_settings().is_none() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+
+        assert_eq!(
+            connection.locked_settings(),
+            Some(Settings::default()),
+            "still in the game"
+        );
+    }
 }
@@ -1,37 +1,208 @@
 use bomberhans_lib::field::Field;
 use bomberhans_lib::game_state::{Action, GameState, GameStatic, Player};
+use bomberhans_lib::network::{JitterBuffer, ServerUpdate};
 use bomberhans_lib::settings::Settings;
-use bomberhans_lib::utils::{PlayerId, Position, TimeStamp, TIME_PER_TICK};
+use bomberhans_lib::simulation::{Heatmap, ScriptedAction};
+use bomberhans_lib::utils::{Duration, PlayerId, Position, TimeStamp, TIME_PER_TICK};
 use std::collections::{BTreeMap, VecDeque};
 use std::rc::Rc;
 use std::time;
 
+/// How many ticks server updates are held back, to apply them evenly even if they arrive
+/// unevenly
+const JITTER_BUFFER_TICKS: u32 = 3;
+
 #[derive(Debug)]
 pub struct MultiPlayerGame {
     game_static: Rc<GameStatic>,
+
+    /// The server's state, replayed from its updates to check them against its checksum
     server_state: GameState,
+
+    /// The replayed server state did not match the server's checksum, until `resync`
+    desynced: bool,
+
+    /// Take over the replayed server state once it matches again after a `resync`
+    resyncing: bool,
+
+    /// Changes of the local player's action to send to the server, at most one per tick
     local_actions: VecDeque<(TimeStamp, Action)>,
     local_state: GameState,
-    last_local_update: std::time::Instant,
+
+    /// When the server was at tick 0, as far as we can tell
+    clock_start: std::time::Instant,
+
+    /// Received server updates that are not applied yet
+    server_updates: JitterBuffer<ServerUpdate>,
+
+    /// Where the local player was and placed bombs, as predicted locally
+    heatmap: Heatmap,
 }
 
 impl MultiPlayerGame {
-    /// proceed game time according to real time since last update
-    fn update_local_simulation_realtime(&mut self) {
-        let now = time::Instant::now();
-        while now >= self.last_local_update + TIME_PER_TICK {
-            self.last_local_update += TIME_PER_TICK;
+    /// A game that the server started at `start_time`, which we `received` at
+    pub fn new(
+        game_static: Rc<GameStatic>,
+        start_time: TimeStamp,
+        received: time::Instant,
+    ) -> Self {
+        let server_state = GameState::new(Rc::clone(&game_static));
+        let local_state = server_state.clone();
+        let heatmap = Heatmap::new(&local_state.field);
+        let mut game = Self {
+            game_static,
+            server_state,
+            desynced: false,
+            resyncing: false,
+            local_actions: VecDeque::new(),
+            local_state,
+            clock_start: received,
+            server_updates: JitterBuffer::new(JITTER_BUFFER_TICKS),
+            heatmap,
+        };
+        game.align_clock(start_time, received);
+        game
+    }
+
+    /// Adjust the clock to a server message sent at `time` that we `received`
+    ///
+    /// Messages can only be delayed, so the earliest estimate of the server's start is the
+    /// best one. A client that received `GameStart` late catches up with the next update.
+    pub fn align_clock(&mut self, time: TimeStamp, received: time::Instant) {
+        if let Some(start) = received.checked_sub(TIME_PER_TICK * time.ticks_from_start()) {
+            self.clock_start = self.clock_start.min(start);
+        }
+    }
+
+    /// The tick the server is at, according to our clock
+    fn server_ticks_at(&self, now: time::Instant) -> u32 {
+        let elapsed = now.saturating_duration_since(self.clock_start);
+        (elapsed.as_micros() / TIME_PER_TICK.as_micros()) as u32
+    }
+
+    /// Keep an update from the server until the jitter buffer releases it
+    pub fn receive_server_update(&mut self, update: ServerUpdate, received: time::Instant) {
+        self.align_clock(update.time, received);
+        self.server_updates.push(update.time, update, received);
+    }
+
+    /// Take over the other players' actions from server updates
+    fn apply_server_update(&mut self, update: &ServerUpdate) {
+        for u in &update.updates {
+            if u.player != self.game_static.local_player {
+                self.local_state.set_player_action(u.player, u.action);
+            }
+        }
+        self.replay_server_state(update);
+    }
+
+    /// Fast-forward the server's state to the update and compare it with the server's checksum
+    ///
+    /// Actions are applied after the tick they are due in, like the server does. Updates
+    /// repeat what the client did not acknowledge yet, the ones already replayed are skipped.
+    fn replay_server_state(&mut self, update: &ServerUpdate) {
+        if self.desynced || update.time < self.server_state.time {
+            return;
+        }
+        while self.server_state.time < update.time {
+            self.server_state.simulate_1_update();
+            let time = self.server_state.time;
+            for u in update.updates.iter().filter(|u| u.time == time) {
+                self.server_state.set_player_action(u.player, u.action);
+            }
+        }
+        let checksum = self.server_state.checksum();
+        if checksum != update.checksum && self.resyncing {
+            // sent before the server got our request, start over with the replayed updates
+            log::debug!("skipping update at {:?} while resyncing", update.time);
+            self.server_state = GameState::new(Rc::clone(&self.game_static));
+        } else if checksum != update.checksum {
+            log::warn!(
+                "desynced at {:?}: server checksum {:08x}, ours {checksum:08x}",
+                update.time,
+                update.checksum
+            );
+            self.desynced = true;
+        } else if self.resyncing {
+            self.local_state = self.server_state.clone();
+            self.resyncing = false;
+        }
+    }
+
+    /// Our simulation of the server's state diverged from the server's
+    pub fn is_desynced(&self) -> bool {
+        self.desynced
+    }
+
+    /// Replay the game from the start, the server has to send all its updates again
+    pub fn resync(&mut self) {
+        self.server_state = GameState::new(Rc::clone(&self.game_static));
+        self.desynced = false;
+        self.resyncing = true;
+    }
+
+    /// proceed game time until it matches the server's clock at `now`
+    fn update_local_simulation(&mut self, now: time::Instant) {
+        for update in self.server_updates.release(now) {
+            self.apply_server_update(&update);
+        }
+        let ticks = self.server_ticks_at(now);
+        while self.local_state.time.ticks_from_start() < ticks {
             self.local_state.simulate_1_update();
+            self.heatmap
+                .observe(&self.local_state, self.game_static.local_player);
         }
     }
 
+    /// proceed game time according to real time
+    fn update_local_simulation_realtime(&mut self) {
+        self.update_local_simulation(time::Instant::now());
+    }
+
+    /// Ticks that inputs take to reach the server, so the local simulation runs this far ahead
+    pub fn prediction_lead_ticks(ping: time::Duration) -> u32 {
+        let one_way = (ping / 2).as_micros();
+        let tick = TIME_PER_TICK.as_micros();
+        ((one_way + tick - 1) / tick) as u32
+    }
+
+    /// Take over the local player's action now and queue it for the server
+    ///
+    /// A player mashing keys changes the action several times per tick, only the last change
+    /// in each tick is sent.
     pub fn set_local_player_action(&mut self, action: Action) {
-        self.local_state
-            .set_player_action(self.game_static.local_player, action);
-        self.local_actions
-            .push_back((self.local_state.time, action));
-        // TODO: send to server
+        let time = self.local_state.time;
+        let local_player = self.game_static.local_player;
+        // Only start placing with a bomb left, the server suspects clients that ask for more
+        let already_placing = self.local_state.player_states[local_player].action.placing;
+        let action = Action {
+            placing: action.placing
+                && (already_placing || self.local_state.can_place_bomb(local_player)),
+            ..action
+        };
+        if !self.local_state.set_player_action(local_player, action) {
+            return;
+        }
+        match self.local_actions.back_mut() {
+            Some((queued_time, queued)) if *queued_time == time => *queued = action,
+            _ => self.local_actions.push_back((time, action)),
+        }
     }
+
+    /// The queued changes of the local player's action, to send them to the server
+    pub fn take_local_actions(&mut self) -> Vec<(TimeStamp, Action)> {
+        self.local_actions.drain(..).collect()
+    }
+}
+
+/// Debugging controls of a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Stop or resume the simulation
+    TogglePause,
+
+    /// Advance a paused game by exactly one update
+    StepTick,
 }
 
 #[derive(Debug)]
@@ -39,21 +210,73 @@ pub struct SinglePlayerGame {
     game_static: Rc<GameStatic>,
     game_state: GameState,
     last_update: std::time::Instant,
+
+    /// Time only proceeds by `Command::StepTick`
+    paused: bool,
+
+    /// The player on the second set of keys, when two players share the keyboard
+    second_player: Option<PlayerId>,
+
+    /// The local player's changes of action, a script to review or replay the game with
+    recording: Vec<ScriptedAction>,
+
+    /// Where the local player was and placed bombs
+    heatmap: Heatmap,
 }
 
 impl SinglePlayerGame {
     /// proceed game time according to real time since last update
     fn update_simulation_realtime(&mut self) {
+        if self.paused {
+            return;
+        }
         let now = time::Instant::now();
         while now >= self.last_update + TIME_PER_TICK {
             self.last_update += TIME_PER_TICK;
-            self.game_state.simulate_1_update();
+            self.simulate_1_update();
         }
     }
 
+    fn simulate_1_update(&mut self) {
+        self.game_state.simulate_1_update();
+        self.heatmap
+            .observe(&self.game_state, self.game_static.local_player);
+    }
+
     pub fn set_local_player_action(&mut self, action: Action) {
-        self.game_state
-            .set_player_action(self.game_static.local_player, action);
+        self.set_player_action(self.game_static.local_player, action);
+    }
+
+    /// Set the action of the player on the second set of keys, if there is one
+    pub fn set_second_player_action(&mut self, action: Action) {
+        if let Some(player) = self.second_player {
+            self.set_player_action(player, action);
+        }
+    }
+
+    fn set_player_action(&mut self, player: PlayerId, action: Action) {
+        if self.game_state.set_player_action(player, action) {
+            self.recording.push(ScriptedAction {
+                time: self.game_state.time,
+                player,
+                action,
+            });
+        }
+    }
+
+    fn command(&mut self, command: Command) {
+        match command {
+            Command::TogglePause => {
+                self.update_simulation_realtime();
+                self.paused = !self.paused;
+                self.last_update = time::Instant::now();
+            }
+            Command::StepTick => {
+                if self.paused {
+                    self.simulate_1_update();
+                }
+            }
+        }
     }
 }
 
@@ -64,13 +287,15 @@ pub enum Game {
 }
 
 impl Game {
-    pub fn new_local_game(settings: Settings) -> Self {
-        let field = Field::new(settings.width, settings.height);
+    /// A game without a server, with `split_keyboard` a second player shares the keyboard
+    pub fn new_local_game(settings: Settings, split_keyboard: bool) -> Self {
+        let field = Field::new_from_rules(&settings);
         let start_positions = field.start_positions();
 
         assert!(start_positions.len() >= settings.players as _);
 
         let local_player = PlayerId(0);
+        let second_player = (split_keyboard && settings.players > 1).then_some(PlayerId(1));
 
         let players: BTreeMap<PlayerId, Player> = (0..(settings.players as usize))
             .map(|id| {
@@ -78,7 +303,7 @@ impl Game {
                     PlayerId(id),
                     Player {
                         name: {
-                            if id == local_player.0 {
+                            if id == local_player.0 || Some(PlayerId(id)) == second_player {
                                 format!("Player {id}")
                             } else {
                                 "Local Player".into()
@@ -86,6 +311,7 @@ impl Game {
                         },
                         id: PlayerId(id as _),
                         start_position: Position::from_cell_position(start_positions[id]),
+                        team: settings.team_of(id),
                     },
                 )
             })
@@ -98,34 +324,115 @@ impl Game {
         };
         let game_static = Rc::new(game_static);
         let game_state = GameState::new(Rc::clone(&game_static));
+        let heatmap = Heatmap::new(&game_state.field);
 
         Game::SinglePlayer(SinglePlayerGame {
             game_state,
             game_static,
             last_update: time::Instant::now(),
+            paused: false,
+            second_player,
+            recording: Vec::new(),
+            heatmap,
         })
     }
 
     pub fn new_multiplayer_game(
-        settings: Settings,
-        socket: (),
-        local_player: PlayerId,
-        players: BTreeMap<PlayerId, Player>,
+        game_static: GameStatic,
+        start_time: TimeStamp,
+        received: time::Instant,
     ) -> Self {
-        let game_static = GameStatic {
-            players,
-            settings,
-            local_player,
-        };
-        let state = GameState::new(Rc::new(game_static));
+        Game::MultiPlayer(MultiPlayerGame::new(
+            Rc::new(game_static),
+            start_time,
+            received,
+        ))
+    }
+
+    /// A multiplayer game whose simulation diverged from the server's
+    pub fn is_desynced(&self) -> bool {
+        match self {
+            Game::SinglePlayer(_) => false,
+            Game::MultiPlayer(mpg) => mpg.is_desynced(),
+        }
+    }
 
-        todo!()
+    /// Pass an update from the server to a multiplayer game, `received` at
+    pub fn receive_server_update(&mut self, update: ServerUpdate, received: time::Instant) {
+        match self {
+            Game::SinglePlayer(_) => log::warn!("ignoring server update in a single player game"),
+            Game::MultiPlayer(mpg) => mpg.receive_server_update(update, received),
+        }
+    }
+
+    /// Replay a multiplayer game from the start, once the server resends all updates
+    pub fn resync(&mut self) {
+        if let Game::MultiPlayer(mpg) = self {
+            mpg.resync();
+        }
     }
 
     pub fn set_local_player_action(&mut self, action: Action) {
         match self {
             Game::SinglePlayer(spg) => spg.set_local_player_action(action),
-            Game::MultiPlayer(mpg) => todo!(),
+            Game::MultiPlayer(mpg) => mpg.set_local_player_action(action),
+        }
+    }
+
+    /// The local player's changes of action that the server does not know yet, only
+    /// multiplayer games send them
+    pub fn take_local_actions(&mut self) -> Vec<(TimeStamp, Action)> {
+        match self {
+            Game::SinglePlayer(_) => Vec::new(),
+            Game::MultiPlayer(mpg) => mpg.take_local_actions(),
+        }
+    }
+
+    /// Set the action of the second player at this keyboard, only local games can have one
+    pub fn set_second_player_action(&mut self, action: Action) {
+        match self {
+            Game::SinglePlayer(spg) => spg.set_second_player_action(action),
+            Game::MultiPlayer(_) => {}
+        }
+    }
+
+    /// Whether two players share the keyboard
+    pub fn is_split_keyboard(&self) -> bool {
+        match self {
+            Game::SinglePlayer(spg) => spg.second_player.is_some(),
+            Game::MultiPlayer(_) => false,
+        }
+    }
+
+    pub fn command(&mut self, command: Command) {
+        match self {
+            Game::SinglePlayer(spg) => spg.command(command),
+            Game::MultiPlayer(_) => {
+                log::warn!("{command:?} is only possible in single player games");
+            }
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        match self {
+            Game::SinglePlayer(spg) => spg.paused,
+            Game::MultiPlayer(_) => false,
+        }
+    }
+
+    /// The local player's recorded actions, only single player games are recorded
+    pub fn recording(&self) -> Option<&[ScriptedAction]> {
+        match self {
+            Game::SinglePlayer(spg) => Some(&spg.recording),
+            Game::MultiPlayer(_) => None,
+        }
+    }
+
+    /// Where the local player spent their time so far
+    pub fn heatmap(&self) -> &Heatmap {
+        match self {
+            Game::SinglePlayer(spg) => &spg.heatmap,
+            Game::MultiPlayer(mpg) => &mpg.heatmap,
         }
     }
 
@@ -151,7 +458,291 @@ impl Game {
                                                   // for this
                 &spg.game_state
             }
-            Game::MultiPlayer(mpg) => &mpg.local_state,
+            Game::MultiPlayer(mpg) => {
+                mpg.update_local_simulation_realtime();
+                &mpg.local_state
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bomberhans_lib::network::Update;
+    use bomberhans_lib::utils::{CellPosition, Direction};
+
+    #[test]
+    fn test_late_game_start_converges_after_server_update() {
+        let game_static = Rc::new(GameStatic {
+            players: BTreeMap::new(),
+            settings: Settings::default(),
+            local_player: PlayerId(0),
+        });
+        let start = TimeStamp::default();
+        let t0 = time::Instant::now();
+        let mut punctual = MultiPlayerGame::new(Rc::clone(&game_static), start, t0);
+        let mut late = MultiPlayerGame::new(game_static, start, t0 + TIME_PER_TICK * 3);
+
+        let now = t0 + TIME_PER_TICK * 10;
+        assert_eq!(punctual.server_ticks_at(now), 10);
+        assert_eq!(late.server_ticks_at(now), 7);
+
+        let server_time = start + Duration::from_ticks(10);
+        punctual.align_clock(server_time, now);
+        late.align_clock(server_time, now);
+
+        let later = now + TIME_PER_TICK * 5;
+        assert_eq!(punctual.server_ticks_at(later), 15);
+        assert_eq!(late.server_ticks_at(later), 15);
+
+        punctual.update_local_simulation(later);
+        late.update_local_simulation(later);
+        assert_eq!(punctual.local_state.time, late.local_state.time);
+    }
+
+    #[test]
+    fn test_action_changes_within_one_tick_are_sent_once() {
+        let position = Position::from_cell_position(CellPosition::new(0, 0));
+        let game_static = Rc::new(GameStatic {
+            players: BTreeMap::from([(
+                PlayerId(0),
+                Player::new("Hans".to_owned(), PlayerId(0), position),
+            )]),
+            settings: Settings::default(),
+            local_player: PlayerId(0),
+        });
+        let t0 = time::Instant::now();
+        let mut game = MultiPlayerGame::new(game_static, TimeStamp::default(), t0);
+        let walk = |direction| Action {
+            walking: Some(direction),
+            ..Action::idle()
+        };
+
+        game.set_local_player_action(walk(Direction::North));
+        game.set_local_player_action(walk(Direction::West));
+        game.set_local_player_action(walk(Direction::South));
+        game.set_local_player_action(walk(Direction::South));
+        game.update_local_simulation(t0 + TIME_PER_TICK);
+        game.set_local_player_action(walk(Direction::East));
+
+        let start = TimeStamp::default();
+        assert_eq!(
+            game.take_local_actions(),
+            vec![
+                (start, walk(Direction::South)),
+                (start + Duration::from_ticks(1), walk(Direction::East))
+            ]
+        );
+        assert!(game.take_local_actions().is_empty());
+        assert_eq!(
+            game.local_state.player_states[PlayerId(0)].action,
+            walk(Direction::East)
+        );
+    }
+
+    #[test]
+    fn test_corrupted_server_update_is_detected() {
+        let position = Position::from_cell_position(CellPosition::new(0, 0));
+        let game_static = Rc::new(GameStatic {
+            players: BTreeMap::from([(
+                PlayerId(0),
+                Player::new("Hans".to_owned(), PlayerId(0), position),
+            )]),
+            settings: Settings {
+                countdown_s: 0,
+                ..Settings::default()
+            },
+            local_player: PlayerId(0),
+        });
+        let mut game = MultiPlayerGame::new(
+            Rc::clone(&game_static),
+            TimeStamp::default(),
+            time::Instant::now(),
+        );
+        let walk = Update {
+            player: PlayerId(0),
+            action: Action {
+                walking: Some(Direction::East),
+                ..Action::idle()
+            },
+            time: TimeStamp::default() + Duration::from_ticks(2),
+        };
+        // the server applies actions after the tick they are due in
+        let mut server = GameState::new(game_static);
+        let mut server_update = |ticks, updates: Vec<Update>| {
+            while server.time.ticks_from_start() < ticks {
+                server.simulate_1_update();
+                let time = server.time;
+                for u in updates.iter().filter(|u| u.time == time) {
+                    server.set_player_action(u.player, u.action);
+                }
+            }
+            ServerUpdate {
+                time: server.time,
+                checksum: server.checksum(),
+                updates,
+            }
+        };
+
+        game.apply_server_update(&server_update(4, vec![walk.clone()]));
+        game.apply_server_update(&server_update(8, Vec::new()));
+        assert!(!game.is_desynced());
+
+        let mut corrupted = server_update(12, Vec::new());
+        let checksum = corrupted.checksum;
+        corrupted.checksum ^= 1;
+        game.apply_server_update(&corrupted);
+        assert!(game.is_desynced());
+
+        game.resync();
+        game.apply_server_update(&ServerUpdate {
+            time: corrupted.time,
+            checksum,
+            updates: vec![walk.clone()],
+        });
+        assert!(!game.is_desynced(), "replaying all updates matches");
+        assert_eq!(game.local_state.checksum(), checksum);
+
+        game.resync();
+        let stale = server_update(16, Vec::new());
+        game.apply_server_update(&stale);
+        assert!(
+            !game.is_desynced(),
+            "updates sent before the server got the request are skipped"
+        );
+        game.apply_server_update(&ServerUpdate {
+            time: stale.time,
+            checksum: stale.checksum,
+            updates: vec![walk],
+        });
+        assert!(!game.is_desynced());
+        assert_eq!(game.local_state.checksum(), stale.checksum);
+    }
+
+    #[test]
+    fn test_placing_only_starts_with_a_bomb_left() {
+        let position = Position::from_cell_position(CellPosition::new(0, 0));
+        let game_static = Rc::new(GameStatic {
+            players: BTreeMap::from([(
+                PlayerId(0),
+                Player::new("Hans".to_owned(), PlayerId(0), position),
+            )]),
+            settings: Settings::default(),
+            local_player: PlayerId(0),
+        });
+        let mut game =
+            MultiPlayerGame::new(game_static, TimeStamp::default(), time::Instant::now());
+        let placing = Action {
+            placing: true,
+            ..Action::idle()
+        };
+        let player = game.local_state.player_states.get_mut(PlayerId(0)).unwrap();
+        player.current_bombs_placed = player.bombs;
+
+        game.set_local_player_action(placing);
+        assert!(game.take_local_actions().is_empty(), "no bomb left");
+
+        game.local_state
+            .player_states
+            .get_mut(PlayerId(0))
+            .unwrap()
+            .current_bombs_placed = 0;
+        game.set_local_player_action(placing);
+        assert_eq!(
+            game.take_local_actions(),
+            vec![(TimeStamp::default(), placing)]
+        );
+    }
+
+    #[test]
+    fn test_recording_matches_the_actions_fed_in() {
+        let settings = Settings {
+            countdown_s: 0,
+            ..Settings::default()
+        };
+        let mut game = Game::new_local_game(settings, false);
+        game.command(Command::TogglePause);
+
+        let script = bomberhans_lib::simulation::parse_script(
+            "0 0 east\n30 0 place\n31 0 west\n40 0 idle\n",
+        )
+        .unwrap();
+        for scripted in &script {
+            while game.local_state().time < scripted.time {
+                game.command(Command::StepTick);
+            }
+            game.set_local_player_action(scripted.action);
+            // repeating the current action is not a change
+            game.set_local_player_action(scripted.action);
+        }
+        for _ in 0..10 {
+            game.command(Command::StepTick);
         }
+        assert_eq!(game.recording(), Some(&script[..]));
+
+        // replaying the recording gives the same game
+        let mut replayed = bomberhans_lib::simulation::GameSpec::new(game.stat()).game_state();
+        let ticks = game.local_state().time.ticks_from_start();
+        bomberhans_lib::simulation::run(&mut replayed, &script, ticks, |_| {}).unwrap();
+        assert_eq!(replayed.checksum(), game.local_state().checksum());
+        let heatmap = game.heatmap();
+        assert_eq!(heatmap.visits.iter().sum::<u32>(), ticks);
+        assert_eq!(heatmap.bombs.iter().sum::<u32>(), 1);
+    }
+
+    #[test]
+    fn test_split_keyboard_players_act_separately() {
+        let mut game = Game::new_local_game(Settings::default(), true);
+        assert!(game.is_split_keyboard());
+        let walk = |direction| Action {
+            walking: Some(direction),
+            ..Action::idle()
+        };
+
+        game.set_local_player_action(walk(Direction::East));
+        game.set_second_player_action(walk(Direction::West));
+
+        let state = game.local_state();
+        assert_eq!(
+            state.player_states[PlayerId(0)].action,
+            walk(Direction::East)
+        );
+        assert_eq!(
+            state.player_states[PlayerId(1)].action,
+            walk(Direction::West)
+        );
+        let recorded: Vec<PlayerId> = game.recording().unwrap().iter().map(|a| a.player).collect();
+        assert_eq!(recorded, [PlayerId(0), PlayerId(1)]);
+
+        let mut alone = Game::new_local_game(Settings::default(), false);
+        alone.set_second_player_action(walk(Direction::West));
+        assert_eq!(
+            alone.local_state().player_states[PlayerId(1)].action,
+            Action::idle()
+        );
+    }
+
+    #[test]
+    fn test_step_advances_a_paused_game_by_one_tick() {
+        let mut game = Game::new_local_game(Settings::default(), false);
+        game.command(Command::TogglePause);
+        assert!(game.is_paused());
+        let paused_at = game.local_state().time;
+
+        std::thread::sleep(TIME_PER_TICK * 3);
+        assert_eq!(game.local_state().time, paused_at, "time stands still");
+
+        game.command(Command::StepTick);
+        assert_eq!(game.local_state().time, paused_at + Duration::from_ticks(1));
+
+        game.command(Command::TogglePause);
+        assert!(!game.is_paused());
+        game.command(Command::StepTick);
+        assert_eq!(
+            game.local_state().time,
+            paused_at + Duration::from_ticks(1),
+            "stepping only works while paused"
+        );
     }
 }
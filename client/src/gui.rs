@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::Path;
 use std::rc::Rc;
 
 use eframe::egui;
@@ -13,20 +14,34 @@ use egui::TextureId;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::bug_report;
 use crate::connection::connect;
 use crate::connection::Connection;
+use crate::game::Command;
 use crate::game::Game;
+use crate::game::MultiPlayerGame;
 use bomberhans_lib::field::Cell;
+use bomberhans_lib::field::Field;
+use bomberhans_lib::field::Upgrade;
 use bomberhans_lib::game_state::Action;
+use bomberhans_lib::game_state::GameStatic;
 use bomberhans_lib::game_state::PlayerState;
+use bomberhans_lib::game_state::PlayerStates;
+use bomberhans_lib::game_state::Results;
+use bomberhans_lib::network::TournamentStatus;
+use bomberhans_lib::settings::describe_errors;
+use bomberhans_lib::settings::DeathPenalty;
 use bomberhans_lib::settings::Settings;
+use bomberhans_lib::simulation::Heatmap;
 use bomberhans_lib::utils::CellPosition;
 use bomberhans_lib::utils::Direction;
+use bomberhans_lib::utils::PlayerId;
 use bomberhans_lib::utils::Position;
 use bomberhans_lib::utils::TimeStamp;
 use bomberhans_lib::utils::TICKS_PER_SECOND;
 
-const PIXEL_PER_CELL: f32 = 42.0;
+/// Smallest size of a cell, below that the board overflows the window instead of shrinking
+const MIN_PIXEL_PER_CELL: f32 = 12.0;
 
 enum State {
     Initial,
@@ -36,11 +51,34 @@ enum State {
     MultiPlayerServerGuest,
     MultiPlayerServerHost,
     Game(Game),
+    /// The game diverged from the server's, it stands still until the user resyncs
+    Desynced(Game),
     GameOver(String),
+    /// Standings of a finished game, `settings` to play again
+    Results {
+        outcome: Results,
+        settings: Settings,
+        /// When a multiplayer game turns back into its lobby
+        back_to_lobby: Option<std::time::Instant>,
+    },
     MpOpeningLobby,
+    MpJoiningLobby,
 }
 
 impl State {
+    /// Show the outcome of `game`, multiplayer games go back to their lobby after a while
+    fn results(game: &Game, outcome: Results) -> Self {
+        let settings = game.settings().clone();
+        let after = settings.return_to_lobby_after_s;
+        let back_to_lobby = (matches!(game, Game::MultiPlayer(_)) && after != 0)
+            .then(|| std::time::Instant::now() + std::time::Duration::from_secs(after.into()));
+        State::Results {
+            outcome,
+            settings,
+            back_to_lobby,
+        }
+    }
+
     fn game(&mut self) -> &mut Game {
         if let State::Game(game) = self {
             game
@@ -48,19 +86,92 @@ impl State {
             panic!("no game running");
         }
     }
+
+    /// Stop a game that diverged from the server's
+    fn check_desync(&mut self) {
+        if matches!(self, State::Game(game) if game.is_desynced()) {
+            if let State::Game(game) = std::mem::replace(self, State::Initial) {
+                *self = State::Desynced(game);
+            }
+        }
+    }
+}
+
+/// Size of a cell so that a field of `width` x `height` and its border fit into `available`
+///
+/// Cells stay square, so the board only fills `available` in one direction.
+fn pixel_per_cell(available: egui::Vec2, width: u32, height: u32) -> f32 {
+    let x = available.x / (width + 2) as f32;
+    let y = available.y / (height + 2) as f32;
+    x.min(y).max(MIN_PIXEL_PER_CELL)
+}
+
+/// Closest and farthest zoom of the game view, 1 fits the board to the window
+const MIN_ZOOM: f32 = 1.0;
+const MAX_ZOOM: f32 = 4.0;
+
+/// Zoom and pan of the game view, on top of fitting the board to the window
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Camera {
+    zoom: f32,
+
+    /// Offset of the board's center from the center of the view
+    pan: egui::Vec2,
+
+    /// Player the view stays centered on, `None` for a free camera, e.g. to spectate after
+    /// running out of lives
+    follow: Option<PlayerId>,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            zoom: MIN_ZOOM,
+            pan: egui::Vec2::ZERO,
+            follow: None,
+        }
+    }
+}
+
+impl Camera {
+    fn zoom_by(&mut self, factor: f32) {
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.pan *= zoom / self.zoom;
+        self.zoom = zoom;
+    }
+
+    /// Move the board by `delta`, the center of the view always stays on a board of `board_size`
+    ///
+    /// This frees the camera from the player it followed.
+    fn pan_by(&mut self, delta: egui::Vec2, board_size: egui::Vec2) {
+        self.follow = None;
+        let limit = board_size / 2.0;
+        self.pan = (self.pan + delta).clamp(-limit, limit);
+    }
+
+    /// Center the view on `target`, an offset from the center of a board of `board_size`
+    fn center_on(&mut self, target: egui::Vec2, board_size: egui::Vec2) {
+        let limit = board_size / 2.0;
+        self.pan = (-target).clamp(-limit, limit);
+    }
 }
 
-fn cell_rect(pos: CellPosition, offset: Pos2) -> egui::Rect {
-    let x = (pos.x + 1) as f32 * PIXEL_PER_CELL + offset.x;
-    let y = (pos.y + 1) as f32 * PIXEL_PER_CELL + offset.y;
+/// Offset of a player at `pos` from the center of a board of `cells` drawn with `scale`
+fn offset_from_board_center(pos: Position, cells: egui::Vec2, scale: f32) -> egui::Vec2 {
+    player_rect(pos, Pos2::ZERO, scale).center().to_vec2() - cells * scale / 2.0
+}
+
+fn cell_rect(pos: CellPosition, offset: Pos2, scale: f32) -> egui::Rect {
+    let x = (pos.x + 1) as f32 * scale + offset.x;
+    let y = (pos.y + 1) as f32 * scale + offset.y;
 
-    Rect::from_min_max(pos2(x, y), pos2(x + PIXEL_PER_CELL, y + PIXEL_PER_CELL))
+    Rect::from_min_max(pos2(x, y), pos2(x + scale, y + scale))
 }
 
-fn player_rect(pos: Position, offset: Pos2) -> egui::Rect {
-    let x = (pos.x as f32 / Position::ACCURACY as f32 + 1.0) * PIXEL_PER_CELL + offset.x;
-    let y = (pos.y as f32 / Position::ACCURACY as f32 - 0.2 + 1.0) * PIXEL_PER_CELL + offset.y;
-    let p = PIXEL_PER_CELL / 2.0;
+fn player_rect(pos: Position, offset: Pos2, scale: f32) -> egui::Rect {
+    let x = (pos.x as f32 / Position::ACCURACY as f32 + 1.0) * scale + offset.x;
+    let y = (pos.y as f32 / Position::ACCURACY as f32 - 0.2 + 1.0) * scale + offset.y;
+    let p = scale / 2.0;
 
     Rect::from_min_max(pos2(x - p, y - p), pos2(x + p, y + p))
 }
@@ -79,7 +190,14 @@ pub fn gui() {
                 app_settings: AppSettings::load(),
                 textures: None,
                 walking_directions: DirectionStack::new(),
+                second_walking_directions: DirectionStack::new(),
                 connection: None,
+                shared_settings: String::new(),
+                toast: None,
+                static_layer: None,
+                camera: Camera::default(),
+                debug_grid: false,
+                show_heatmap: false,
             })
         }),
     );
@@ -87,9 +205,44 @@ pub fn gui() {
 
 struct TextureManager {
     textures: HashMap<&'static str, TextureHandle>,
+
+    /// Pixels of the tiles, to composite the static layer
+    images: HashMap<&'static str, egui::ColorImage>,
+
+    /// All tiles in one texture, to draw the game without switching textures
+    atlas: Atlas,
+
+    /// Draw cells from the colorblind friendly tile set
+    colorblind: bool,
 }
 
 impl TextureManager {
+    fn new(ctx: &egui::Context, colorblind: bool) -> Self {
+        let (textures, images) = load_tiles(ctx);
+        let (atlas_image, uvs) = pack_atlas(&images);
+        let atlas = Atlas {
+            texture: ctx.load_texture("atlas", atlas_image, egui::TextureOptions::NEAREST),
+            uvs,
+        };
+        Self {
+            textures,
+            images,
+            atlas,
+            colorblind,
+        }
+    }
+
+    /// The atlas and where `texture` is in it
+    fn get_atlas_tile(&self, texture: &str) -> (TextureId, Rect) {
+        let uv = self
+            .atlas
+            .uvs
+            .get(texture)
+            .ok_or_else(|| format!("Expected {texture} to exist"))
+            .unwrap();
+        (self.atlas.texture.id(), *uv)
+    }
+
     fn get_texture(self: &Rc<Self>, texture: &str) -> TextureId {
         self.textures
             .get(texture)
@@ -98,11 +251,39 @@ impl TextureManager {
             .into()
     }
 
-    fn get_cell(self: &Rc<Self>, cell: &Cell) -> TextureId {
-        self.get_texture(&format!("cell_{}", cell.name()))
+    fn get_cell(self: &Rc<Self>, cell: &Cell) -> (TextureId, Rect) {
+        self.get_atlas_tile(&cell_texture_key(cell, self.colorblind))
+    }
+
+    /// Draw all static cells of `field` into one image, `STATIC_LAYER_PIXELS_PER_CELL` each
+    fn composite_static_layer(&self, field: &Field) -> egui::ColorImage {
+        let p = STATIC_LAYER_PIXELS_PER_CELL;
+        let mut image = egui::ColorImage::new(
+            [
+                (field.width as usize + 2) * p,
+                (field.height as usize + 2) * p,
+            ],
+            Color32::TRANSPARENT,
+        );
+        let image_width = image.size[0];
+        for (pos, cell) in field.iter_with_border() {
+            let cell = if is_static(cell) { cell } else { &Cell::Empty };
+            let key = cell_texture_key(cell, self.colorblind);
+            let tile = &self.images[key.as_str()];
+            let [tile_width, tile_height] = tile.size;
+            let left = (pos.x + 1) as usize * p;
+            let top = (pos.y + 1) as usize * p;
+            for y in 0..p {
+                for x in 0..p {
+                    image.pixels[(top + y) * image_width + left + x] =
+                        tile.pixels[y * tile_height / p * tile_width + x * tile_width / p];
+                }
+            }
+        }
+        image
     }
 
-    fn get_player(self: &Rc<Self>, player: &PlayerState, time: TimeStamp) -> TextureId {
+    fn get_player(self: &Rc<Self>, player: &PlayerState, time: TimeStamp) -> (TextureId, Rect) {
         let odd = if time.ticks_from_start() / 15 % 2 == 0 {
             "2"
         } else {
@@ -117,8 +298,304 @@ impl TextureManager {
             None if player.action.placing => "placing",
             _ => "standing",
         };
-        self.get_texture(&format!("hans_{s}{odd}"))
+        self.get_atlas_tile(&format!("hans_{s}{odd}"))
+    }
+}
+
+/// All tiles packed into one texture
+struct Atlas {
+    texture: TextureHandle,
+
+    /// Where each tile is in `texture`
+    uvs: HashMap<&'static str, Rect>,
+}
+
+/// Pack `images` into one image, in rows of equally sized slots
+///
+/// Returns the image and where each tile is in it, in UV coordinates
+fn pack_atlas(
+    images: &HashMap<&'static str, egui::ColorImage>,
+) -> (egui::ColorImage, HashMap<&'static str, Rect>) {
+    let mut names: Vec<&'static str> = images.keys().copied().collect();
+    names.sort_unstable();
+
+    // 1 pixel gap, so tiles do not bleed into each other
+    let slot_width = images.values().map(|i| i.size[0]).max().unwrap_or(0) + 1;
+    let slot_height = images.values().map(|i| i.size[1]).max().unwrap_or(0) + 1;
+    let columns = ((names.len() as f64).sqrt().ceil() as usize).max(1);
+    let rows = (names.len() + columns - 1) / columns;
+
+    let mut atlas = egui::ColorImage::new(
+        [columns * slot_width, rows * slot_height],
+        Color32::TRANSPARENT,
+    );
+    let [atlas_width, atlas_height] = atlas.size;
+    let mut uvs = HashMap::new();
+    for (i, name) in names.into_iter().enumerate() {
+        let tile = &images[name];
+        let [tile_width, tile_height] = tile.size;
+        let left = i % columns * slot_width;
+        let top = i / columns * slot_height;
+        for y in 0..tile_height {
+            for x in 0..tile_width {
+                atlas.pixels[(top + y) * atlas_width + left + x] = tile.pixels[y * tile_width + x];
+            }
+        }
+        uvs.insert(
+            name,
+            Rect::from_min_size(
+                pos2(
+                    left as f32 / atlas_width as f32,
+                    top as f32 / atlas_height as f32,
+                ),
+                egui::vec2(
+                    tile_width as f32 / atlas_width as f32,
+                    tile_height as f32 / atlas_height as f32,
+                ),
+            ),
+        );
+    }
+    (atlas, uvs)
+}
+
+/// Pixels per cell in the composited static layer, the size of most tiles
+const STATIC_LAYER_PIXELS_PER_CELL: usize = 25;
+
+/// Cells that rarely change, they are drawn once into the static layer
+fn is_static(cell: &Cell) -> bool {
+    matches!(
+        cell,
+        Cell::Empty | Cell::Wall | Cell::Wood | Cell::StartPoint
+    )
+}
+
+/// The name of each cell in the static layer, dynamic cells have the empty floor
+fn static_layer_cells(field: &Field) -> impl Iterator<Item = &'static str> + '_ {
+    field.iter_with_border().map(|(_, cell)| {
+        if is_static(cell) {
+            cell.name()
+        } else {
+            Cell::Empty.name()
+        }
+    })
+}
+
+/// All static cells of a field composited into one texture
+struct StaticLayer {
+    /// Cells and tile set the layer was drawn from, redraw when they change
+    cells: Vec<&'static str>,
+    colorblind: bool,
+    texture: TextureHandle,
+}
+
+/// The static layer over the whole field, and a shape for each dynamic cell on top
+fn field_shapes(
+    textures: &Rc<TextureManager>,
+    field: &Field,
+    static_layer: TextureId,
+    offset: Pos2,
+    scale: f32,
+) -> Vec<Shape> {
+    let field_rect = Rect::from_min_max(
+        cell_rect(CellPosition::new(-1, -1), offset, scale).min,
+        cell_rect(
+            CellPosition::new(field.width as i32, field.height as i32),
+            offset,
+            scale,
+        )
+        .max,
+    );
+    let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+    std::iter::once(Shape::image(static_layer, field_rect, uv, Color32::WHITE))
+        .chain(
+            field
+                .iter()
+                .filter(|(_, cell)| !is_static(cell))
+                .map(|(pos, cell)| {
+                    let (texture, uv) = textures.get_cell(cell);
+                    Shape::image(texture, cell_rect(pos, offset, scale), uv, Color32::WHITE)
+                }),
+        )
+        .collect()
+}
+
+/// Draw order of the game, later layers are drawn on top of earlier ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Layer {
+    /// Static layer and dynamic cells
+    Field,
+
+    /// Where the local player has been and placed bombs
+    Heatmap,
+
+    /// Player sprites
+    Players,
+
+    /// Rings around explosions, over the players caught in them
+    Shockwaves,
+
+    /// Player names, never hidden by another player's sprite
+    Names,
+
+    /// Grid lines and cell coordinates, for debugging
+    Grid,
+
+    /// Frame and FPS counter
+    Hud,
+}
+
+/// All shapes ordered by their layer, keeping the order within a layer
+fn layered(mut shapes: Vec<(Layer, Shape)>) -> Vec<Shape> {
+    shapes.sort_by_key(|(layer, _)| *layer);
+    shapes.into_iter().map(|(_, shape)| shape).collect()
+}
+
+/// Players from top to bottom, so a player overlaps the ones standing behind them
+fn player_draw_order(players: &PlayerStates) -> Vec<(PlayerId, &PlayerState)> {
+    let mut players: Vec<_> = players.iter().collect();
+    players.sort_by_key(|(id, player)| (player.position.y, *id));
+    players
+}
+
+/// Translucent color of a cell with `heat` from 0 to 1, blue for cold to red for hot
+fn heat_color(heat: f32) -> Color32 {
+    let heat = heat.clamp(0.0, 1.0);
+    let (cold, hot) = (Color32::from_rgb(0, 64, 255), Color32::from_rgb(255, 32, 0));
+    let mix = |cold: u8, hot: u8| (f32::from(cold) * (1.0 - heat) + f32::from(hot) * heat) as u8;
+    Color32::from_rgba_unmultiplied(
+        mix(cold.r(), hot.r()),
+        mix(cold.g(), hot.g()),
+        mix(cold.b(), hot.b()),
+        mix(64, 192),
+    )
+}
+
+/// A square on each visited cell colored by the time the player spent there, and a dot on
+/// each cell where they placed bombs
+fn heatmap_shapes(heatmap: &Heatmap, offset: Pos2, scale: f32) -> Vec<Shape> {
+    let max_visits = heatmap.max_visits().max(1) as f32;
+    let mut shapes = Vec::new();
+    for y in 0..heatmap.height as i32 {
+        for x in 0..heatmap.width as i32 {
+            let pos = CellPosition::new(x, y);
+            let rect = cell_rect(pos, offset, scale);
+            let visits = heatmap.visits(pos);
+            if visits > 0 {
+                shapes.push(Shape::rect_filled(
+                    rect,
+                    egui::Rounding::none(),
+                    heat_color(visits as f32 / max_visits),
+                ));
+            }
+            if heatmap.bombs(pos) > 0 {
+                shapes.push(Shape::circle_filled(
+                    rect.center(),
+                    scale / 8.0,
+                    Color32::BLACK,
+                ));
+            }
+        }
     }
+    shapes
+}
+
+/// Radius and line width in cells of the ring around an explosion of `power`, with `progress`
+/// from 0 when it starts to 1 when its fire goes out
+///
+/// The ring grows from the exploding cell to the reach of its fire, stronger explosions have
+/// bigger and thicker rings.
+fn shockwave_size(power: u32, progress: f32) -> (f32, f32) {
+    let power = power as f32;
+    let radius = 0.5 + power * progress.clamp(0.0, 1.0);
+    let width = (0.05 * power).min(0.4);
+    (radius, width)
+}
+
+/// A fading ring around each cell where something exploded
+fn shockwave_shapes(
+    field: &Field,
+    time: TimeStamp,
+    burn_ticks: u32,
+    offset: Pos2,
+    scale: f32,
+) -> Vec<Shape> {
+    field
+        .iter()
+        .filter_map(|(pos, cell)| match *cell {
+            Cell::Fire { expire, power, .. } if power > 0 => {
+                let ticks_left = expire
+                    .ticks_from_start()
+                    .saturating_sub(time.ticks_from_start());
+                let progress = 1.0 - ticks_left as f32 / burn_ticks.max(1) as f32;
+                let (radius, width) = shockwave_size(power, progress);
+                Some(Shape::circle_stroke(
+                    cell_rect(pos, offset, scale).center(),
+                    radius * scale,
+                    egui::Stroke {
+                        width: width * scale,
+                        color: Color32::from_rgba_unmultiplied(
+                            255,
+                            200,
+                            64,
+                            ((1.0 - progress) * 192.0) as u8,
+                        ),
+                    },
+                ))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Label of a cell in the debug grid, the same as in the logs
+fn cell_label(pos: CellPosition) -> String {
+    format!("{pos:?}")
+}
+
+/// Lines between all cells of a `width` x `height` field, and each cell's coordinates
+fn grid_shapes(
+    fonts: &egui::epaint::text::Fonts,
+    width: u32,
+    height: u32,
+    offset: Pos2,
+    scale: f32,
+) -> Vec<Shape> {
+    let (width, height) = (width as i32, height as i32);
+    let stroke = egui::Stroke {
+        width: 1.0,
+        color: Color32::from_white_alpha(64),
+    };
+    let top_left = cell_rect(CellPosition::new(0, 0), offset, scale).min;
+    let bottom_right = cell_rect(CellPosition::new(width - 1, height - 1), offset, scale).max;
+    let mut shapes = Vec::new();
+    for x in 0..=width {
+        let x = cell_rect(CellPosition::new(x, 0), offset, scale).min.x;
+        shapes.push(Shape::line_segment(
+            [pos2(x, top_left.y), pos2(x, bottom_right.y)],
+            stroke,
+        ));
+    }
+    for y in 0..=height {
+        let y = cell_rect(CellPosition::new(0, y), offset, scale).min.y;
+        shapes.push(Shape::line_segment(
+            [pos2(top_left.x, y), pos2(bottom_right.x, y)],
+            stroke,
+        ));
+    }
+    for y in 0..height {
+        for x in 0..width {
+            let pos = CellPosition::new(x, y);
+            shapes.push(Shape::text(
+                fonts,
+                cell_rect(pos, offset, scale).center(),
+                egui::Align2::CENTER_CENTER,
+                cell_label(pos),
+                egui::FontId::monospace(scale / 5.0),
+                Color32::WHITE,
+            ));
+        }
+    }
+    shapes
 }
 
 struct DirectionStack {
@@ -149,6 +626,136 @@ impl DirectionStack {
     pub fn get(&self) -> Option<Direction> {
         self.elements.last().copied()
     }
+
+    /// The most recent direction perpendicular to `get`, to walk diagonally
+    pub fn get_diagonal(&self) -> Option<Direction> {
+        let walking = self.get()?;
+        self.elements
+            .iter()
+            .rev()
+            .find(|d| **d == walking.left() || **d == walking.right())
+            .copied()
+    }
+
+    /// The held directions without pairs of opposite directions
+    fn without_opposites(&self) -> DirectionStack {
+        Self {
+            elements: self
+                .elements
+                .iter()
+                .filter(|d| !self.elements.contains(&d.opposite()))
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Resolve the held directions into `(walking, diagonal)` according to `policy`
+    pub fn resolve(&self, policy: MovementPolicy) -> (Option<Direction>, Option<Direction>) {
+        match policy {
+            MovementPolicy::LastPressed => (self.get(), self.get_diagonal()),
+            MovementPolicy::CancelOpposites => (self.without_opposites().get(), None),
+            MovementPolicy::Combine => {
+                let remaining = self.without_opposites();
+                (remaining.get(), remaining.get_diagonal())
+            }
+        }
+    }
+}
+
+/// The keys one player at the keyboard plays with
+struct KeyBindings {
+    north: egui::Key,
+    south: egui::Key,
+    west: egui::Key,
+    east: egui::Key,
+    place: egui::Key,
+    detonate: egui::Key,
+}
+
+impl KeyBindings {
+    /// The first player, also when playing alone
+    const WASD: KeyBindings = KeyBindings {
+        north: egui::Key::W,
+        south: egui::Key::S,
+        west: egui::Key::A,
+        east: egui::Key::D,
+        place: egui::Key::Space,
+        detonate: egui::Key::E,
+    };
+
+    /// The second player, when two players share the keyboard
+    const ARROWS: KeyBindings = KeyBindings {
+        north: egui::Key::ArrowUp,
+        south: egui::Key::ArrowDown,
+        west: egui::Key::ArrowLeft,
+        east: egui::Key::ArrowRight,
+        place: egui::Key::Enter,
+        detonate: egui::Key::Backspace,
+    };
+
+    /// Track the direction keys in `held` and turn them into the player's action
+    fn read_action(
+        &self,
+        ctx: &egui::Context,
+        held: &mut DirectionStack,
+        policy: MovementPolicy,
+        diagonal_walking: bool,
+    ) -> Action {
+        for (key, direction) in [
+            (self.north, Direction::North),
+            (self.south, Direction::South),
+            (self.west, Direction::West),
+            (self.east, Direction::East),
+        ] {
+            if ctx.input_mut().key_pressed(key) {
+                held.push(direction);
+            }
+            if ctx.input_mut().key_released(key) {
+                held.remove(direction);
+            }
+        }
+
+        let placing = ctx.input_mut().key_down(self.place);
+        let detonate = ctx.input_mut().key_down(self.detonate);
+        let (walking, diagonal) = held.resolve(policy);
+        let diagonal = diagonal.filter(|_| diagonal_walking);
+        Action {
+            walking,
+            diagonal,
+            placing,
+            detonate,
+        }
+    }
+}
+
+/// How simultaneously held direction keys are turned into movement
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum MovementPolicy {
+    /// The most recently pressed key wins, a perpendicular key walks diagonally
+    #[default]
+    LastPressed,
+
+    /// Opposite keys cancel each other, the most recent remaining key wins
+    CancelOpposites,
+
+    /// Opposite keys cancel each other, perpendicular keys walk diagonally
+    Combine,
+}
+
+impl MovementPolicy {
+    const ALL: [MovementPolicy; 3] = [
+        MovementPolicy::LastPressed,
+        MovementPolicy::CancelOpposites,
+        MovementPolicy::Combine,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            MovementPolicy::LastPressed => "Last Key Wins",
+            MovementPolicy::CancelOpposites => "Opposite Keys Cancel",
+            MovementPolicy::Combine => "Combine Keys",
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -157,10 +764,44 @@ struct AppSettings {
     // thing !
     player_name: String,
     server: String,
+    #[serde(default)]
+    movement_policy: MovementPolicy,
+    #[serde(default)]
+    colorblind_tiles: bool,
+    #[serde(default = "AppSettings::shockwaves_default")]
+    shockwaves: bool,
+    /// Two players share the keyboard in local games
+    #[serde(default)]
+    split_keyboard: bool,
+    /// Servers we connected to, the most recent first
+    #[serde(default)]
+    recent_servers: Vec<String>,
+    /// Server updates per second in multiplayer games, 0 for every tick
+    #[serde(default)]
+    update_rate: u32,
     game_settings: Settings,
 }
 
 impl AppSettings {
+    /// How many servers are kept in `recent_servers`
+    const RECENT_SERVERS: usize = 5;
+
+    fn shockwaves_default() -> bool {
+        true
+    }
+
+    /// Put `server` first in `recent_servers`, forgetting the oldest ones
+    fn remember_server(&mut self, server: String) {
+        self.recent_servers.retain(|s| *s != server);
+        self.recent_servers.insert(0, server);
+        self.recent_servers.truncate(Self::RECENT_SERVERS);
+    }
+
+    /// A local game with the single player settings
+    fn local_game(&self) -> Game {
+        Game::new_local_game(self.game_settings.clone(), self.split_keyboard)
+    }
+
     fn save(&self) {
         match confy::store("bomberhans2", Some("client"), self) {
             Ok(()) => log::info!("Settings stored"),
@@ -188,6 +829,12 @@ impl Default for AppSettings {
             game_settings: Settings::default(),
             player_name: String::from("Hans"),
             server: String::from("[::1]:4267"),
+            movement_policy: MovementPolicy::default(),
+            colorblind_tiles: false,
+            shockwaves: Self::shockwaves_default(),
+            split_keyboard: false,
+            recent_servers: Vec::new(),
+            update_rate: 0,
         }
     }
 }
@@ -195,225 +842,119 @@ impl Default for AppSettings {
 struct MyApp {
     state: State,
     walking_directions: DirectionStack,
+
+    /// The held direction keys of the second player, when two players share the keyboard
+    second_walking_directions: DirectionStack,
+
     textures: Option<Rc<TextureManager>>,
 
     app_settings: AppSettings,
 
+    /// Settings string pasted by the user
+    shared_settings: String,
+
+    /// Message for the user, e.g. why joining a game failed
+    toast: Option<String>,
+
+    /// Cells that rarely change, so they are not drawn one by one each frame
+    static_layer: Option<StaticLayer>,
+
+    /// Zoom and pan of the game view
+    camera: Camera,
+
+    /// Draw grid lines and cell coordinates over the game
+    debug_grid: bool,
+
+    /// Draw where the local player has been over the game
+    show_heatmap: bool,
+
     // TODO: The following values should live in step
     connection: Option<Connection>,
 }
 
 impl MyApp {
     fn textures(&mut self, ctx: &egui::Context) -> Rc<TextureManager> {
-        Rc::clone(self.textures.get_or_insert_with(|| {
-            Rc::new(TextureManager {
-                textures: load_tiles(ctx),
-            })
-        }))
+        let colorblind = self.app_settings.colorblind_tiles;
+        Rc::clone(
+            self.textures
+                .get_or_insert_with(|| Rc::new(TextureManager::new(ctx, colorblind))),
+        )
     }
 
-    #[allow(clippy::too_many_lines)] // GUI code has to be long and ugly
     fn update_singleplayer_settings(&mut self, ui: &mut egui::Ui) {
         let textures = self.textures(ui.ctx());
 
-        let settings = &mut self.app_settings.game_settings;
-
-        ui.style_mut().spacing.slider_width = 300.0;
-
         if let State::GameOver(s) = &self.state {
             ui.label(format!("GameOver: {s}"));
         }
-        ui.add(egui::TextEdit::singleline(&mut settings.game_name))
-            .on_hover_text("Name of the Game");
+
+        if let Some(settings) = update_settings(
+            ui,
+            &textures,
+            &self.app_settings.game_settings,
+            ReadOnly::ReadWrite,
+        ) {
+            self.app_settings.game_settings = settings;
+        }
 
         ui.horizontal(|ui| {
-            ui.vertical(|ui| {
-                ui.heading("Game Options");
-                ui.add(
-                    egui::Slider::new(&mut settings.width, Settings::WIDTH_RANGE)
-                        .text("Width")
-                        .clamp_to_range(true),
-                )
-                .on_hover_text("Width of the game field [cells]");
-                ui.add(
-                    egui::Slider::new(&mut settings.height, Settings::HEIGHT_RANGE)
-                        .text("Height")
-                        .clamp_to_range(true),
-                )
-                .on_hover_text("Height of the game field [cells]");
-                ui.add(
-                    egui::Slider::new(&mut settings.players, Settings::PLAYERS_RANGE)
-                        .text("Players")
-                        .clamp_to_range(true),
-                )
-                .on_hover_text("Number of players that can join this game");
-                ui.add(
-                    egui::Slider::new(
-                        &mut settings.bomb_explode_time_ms,
-                        Settings::BOMB_TIME_RANGE,
-                    )
-                    .text("Bomb Time")
-                    .clamp_to_range(true),
-                )
-                .on_hover_text("Time between placing a bomb and its explosion [ms]");
-                ui.add(
-                    egui::Slider::new(&mut settings.speed_base, Settings::SPEED_BASE_RANGE)
-                        .text("Base Speed")
-                        .clamp_to_range(false),
-                )
-                .on_hover_text("Speed of the Player without any upgrades [Cells/s/100]");
-                ui.add(
-                    egui::Slider::new(
-                        &mut settings.speed_multiplyer,
-                        Settings::SPEED_MULTIPLYER_RANGE,
-                    )
-                    .text("Speed Increase")
-                    .clamp_to_range(false),
-                )
-                .on_hover_text("Player speed increase per speed powerup [Cells/s/100]");
-                ui.add(
-                    egui::Slider::new(
-                        &mut settings.bomb_walking_chance,
-                        Settings::BOMB_WALKING_CHANCE_RANGE,
-                    )
-                    .text("Bomb Walking")
-                    .clamp_to_range(true),
-                )
-                .on_hover_text("Chance that a player can walk over a bomb in an update [%]");
-                ui.add(
-                    egui::Slider::new(
-                        &mut settings.tombstone_walking_chance,
-                        Settings::TOMBSTONE_WALKING_CHANCE_RANGE,
-                    )
-                    .text("Tombstone Walking")
-                    .clamp_to_range(true),
-                )
-                .on_hover_text("Chance that a player can walk over a tombstone in an update [%]");
-                ui.add(
-                    egui::Slider::new(
-                        &mut settings.upgrade_explosion_power,
-                        Settings::UPGRADE_EXPLOSION_POWER_RANGE,
-                    )
-                    .text("Upgrade Explosion")
-                    .clamp_to_range(false),
-                )
-                .on_hover_text("Explosion Range of ignited Powerups [cells]");
-                ui.add(
-                    egui::Slider::new(
-                        &mut settings.wood_burn_time_ms,
-                        Settings::WOOD_BURN_TIME_RANGE,
-                    )
-                    .text("Wood Burn Time")
-                    .clamp_to_range(false),
-                )
-                .on_hover_text("Time that wood burns after igniting [ms]");
-                ui.add(
-                    egui::Slider::new(
-                        &mut settings.fire_burn_time_ms,
-                        Settings::FIRE_BURN_TIME_RANGE,
-                    )
-                    .text("Fire Burn Time")
-                    .clamp_to_range(false),
-                )
-                .on_hover_text("Time that fire burns [ms]");
-                ui.add(
-                    egui::Slider::new(&mut settings.bomb_offset, Settings::BOMB_OFFSET_RANGE)
-                        .text("Bomb Placement Offset")
-                        .clamp_to_range(false),
-                )
-                .on_hover_text("While running, how far behind hans a bomb is placed [cells/100]");
-            });
-            ui.vertical(|ui| {
-                const RATIO_RANGE: std::ops::RangeInclusive<u32> = 0..=50;
-                ui.heading("Ratios of cells that burned wood will turn into");
-                ui.horizontal(|ui| {
-                    ui.add(
-                        egui::Slider::new(&mut settings.ratios.power, RATIO_RANGE).text("Power Upgrade"),
-                    );
-                })
-                .response
-                .on_hover_text("Consuming this will upgrade the player's bomb's explosion range");
-                ui.horizontal(|ui| {
-                    ui.add(
-                        egui::Slider::new(&mut settings.ratios.speed, RATIO_RANGE).text("Speed Upgrade"),
-                    );
-                })
-                .response
-                .on_hover_text("Consuming this will upgrade the player's walking speed");
-                ui.horizontal(|ui| {
-                    ui.add(egui::Slider::new(&mut settings.ratios.bombs, RATIO_RANGE).text("Bomb Upgrade"));
-                })
-                .response
-                .on_hover_text(
-                    "Consuming this will increase how many bombs the player can place simultaneously",
-                );
-                ui.horizontal(|ui| { ui.add(egui::Slider::new(&mut settings.ratios.teleport, RATIO_RANGE).text("Teleport")); }). response.on_hover_text("Teleport\nWalking into a teleport will move you to another TB and consume both.\nIgniting a Teleport will ignite another TP as well");
-                ui.horizontal(|ui| {
-                    ui.add(egui::Slider::new(&mut settings.ratios.wall, RATIO_RANGE).text("Wall"));
-                })
-                .response
-                .on_hover_text("Wall\nIf this happens too often, you will be stuck.");
-                ui.horizontal(|ui| {
-                    ui.add(egui::Slider::new(&mut settings.ratios.wood, RATIO_RANGE).text("Wood"));
-                })
-                .response
-                .on_hover_text("Wood\nYou can try and explode again");
-                ui.horizontal(|ui| {
-                    ui.add(egui::Slider::new(&mut settings.ratios.clear, RATIO_RANGE).text("Empty Cell"));
-                })
-                .response
-                .on_hover_text("Just a boring empty Cell");
-            });
-            ui.vertical(|ui| {
-                ui.heading("effective Ratios");
-                let image_dims = egui::Vec2 { x: 16.0, y: 16.0 };
-                let percentages = settings.ratios.normalize();
-                ui.horizontal(|ui| {
-                    ui.image(textures.get_texture("cell_upgrade_power"), image_dims);
-                    ui.label(format!("{}%", percentages.power));
-                })
-                .response
-                .on_hover_text("Consuming this will upgrade the player's bomb's explosion range");
-                ui.horizontal(|ui| {
-                    ui.image(textures.get_texture("cell_upgrade_speed"), image_dims);
-                    ui.label(format!("{}%", percentages.speed));
-                })
-                .response
-                .on_hover_text("Consuming this will upgrade the player's walking speed");
-                ui.horizontal(|ui| {
-                    ui.image(textures.get_texture("cell_upgrade_bomb"), image_dims);
-                    ui.label(format!("{}%", percentages.bombs));
-                })
-                .response
-                .on_hover_text(
-                    "Consuming this will increase how many bombs the player can place simultaneously",
-                );
-                ui.horizontal(|ui| { ui.image(textures.get_texture("cell_teleport"), image_dims); ui.label(format!("{}%", percentages.teleport)); }). response.on_hover_text("Teleport\nWalking into a teleport will move you to another TB and consume both.\nIgniting a Teleport will ignite another TP as well");
-                ui.horizontal(|ui| {
-                    ui.image(textures.get_texture("cell_wall"), image_dims);
-                    ui.label(format!("{}%", percentages.wall));
-                })
-                .response
-                .on_hover_text("Wall\nIf this happens too often, you will be stuck.");
-                ui.horizontal(|ui| {
-                    ui.image(textures.get_texture("cell_wood"), image_dims);
-                    ui.label(format!("{}%", percentages.wood));
-                })
-                .response
-                .on_hover_text("Wood\nYou can try and explode again");
-                ui.horizontal(|ui| {
-                    ui.image(textures.get_texture("cell_empty"), image_dims);
-                    ui.label(format!("{}%", percentages.clear));
-                })
-                .response
-                .on_hover_text("Just a boring empty Cell");
-            });
+            copy_settings_button(ui, &self.app_settings.game_settings);
+            ui.add(
+                egui::TextEdit::singleline(&mut self.shared_settings)
+                    .hint_text("Paste shared settings or a map seed here"),
+            );
+            match (
+                Settings::from_shareable_string(&self.shared_settings),
+                Settings::parse_map_seed(&self.shared_settings),
+            ) {
+                (Ok(settings), _) => {
+                    if ui
+                        .button("Apply")
+                        .on_hover_text("Use the pasted settings")
+                        .clicked()
+                    {
+                        self.app_settings.game_settings = settings;
+                        self.shared_settings.clear();
+                    }
+                }
+                (_, Ok(seed)) => {
+                    if ui
+                        .button("Apply Seed")
+                        .on_hover_text("Play on the map of the pasted seed")
+                        .clicked()
+                    {
+                        self.app_settings.game_settings.map_seed = Some(seed);
+                        self.shared_settings.clear();
+                    }
+                }
+                (Err(err), Err(_)) => {
+                    if !self.shared_settings.is_empty() {
+                        ui.label(format!("Invalid settings: {err}"));
+                    }
+                }
+            }
         });
+
         ui.horizontal(|ui| {
             if ui.button("Restore Default Settings").clicked() {
                 self.app_settings.game_settings = Settings::default();
             }
 
+            let death_penalty = &mut self.app_settings.game_settings.death_penalty;
+            let mut keep_upgrades = *death_penalty == DeathPenalty::KeepAll;
+            if ui
+                .checkbox(&mut keep_upgrades, "Keep upgrades on death (practice)")
+                .on_hover_text("Dying costs no upgrades, to try out big bombs")
+                .changed()
+            {
+                *death_penalty = practice_death_penalty(keep_upgrades);
+            }
+
+            ui.checkbox(&mut self.app_settings.split_keyboard, "Split Keyboard")
+                .on_hover_text(
+                    "A second player plays with the arrow keys, Enter and Backspace, the first with WASD, Space and E",
+                );
+
             let start_button = ui.button("Start").on_hover_text("Start local game");
             {
                 let mut memory = ui.memory();
@@ -423,9 +964,8 @@ impl MyApp {
             }
 
             if start_button.clicked() {
-                todo!("update settings, save");
-                let game = Game::new_local_game(self.app_settings.game_settings.clone());
-                self.state = State::Game(game);
+                self.app_settings.save();
+                self.state = State::Game(self.app_settings.local_game());
                 return;
             }
 
@@ -436,111 +976,501 @@ impl MyApp {
     }
 
     fn update_game(&mut self, ui: &mut egui::Ui) {
+        self.receive_server_updates();
+        self.state.check_desync();
+        if let State::Desynced(_) = self.state {
+            self.update_desynced(ui);
+            return;
+        }
         self.update_game_inputs(ui);
         self.update_game_draw(ui);
     }
 
-    fn update_game_inputs(&mut self, ui: &mut egui::Ui) {
-        let game = self.state.game();
+    /// Pass the updates that the server sent since the last frame to a multiplayer game
+    fn receive_server_updates(&mut self) {
+        let (State::Game(game), Some(connection)) = (&mut self.state, &self.connection) else {
+            return;
+        };
+        if !matches!(game, Game::MultiPlayer(_)) {
+            return;
+        }
+        for (update, received) in connection.take_server_updates() {
+            game.receive_server_update(update, received);
+        }
+    }
 
-        for (key, direction) in [
-            (egui::Key::W, Direction::North),
-            (egui::Key::S, Direction::South),
-            (egui::Key::A, Direction::West),
-            (egui::Key::D, Direction::East),
-        ] {
-            if ui.ctx().input_mut().key_pressed(key) {
-                self.walking_directions.push(direction);
+    /// Start simulating the game of our lobby once the server started it
+    ///
+    /// Returns whether it started
+    fn start_multiplayer_game(&mut self) -> bool {
+        let Some((start, received)) = self.connection.as_ref().and_then(Connection::game_start)
+        else {
+            return false;
+        };
+        self.state = State::Game(Game::new_multiplayer_game(start.game, start.time, received));
+        true
+    }
+
+    /// Offer to replay the game from the server's updates after a desync
+    fn update_desynced(&mut self, ui: &mut egui::Ui) {
+        ui.label("Out of sync with the server, your game no longer matches the other players'");
+        if ui
+            .button("Resync")
+            .on_hover_text("Replay the game from the server's updates")
+            .clicked()
+        {
+            if let State::Desynced(mut game) = std::mem::replace(&mut self.state, State::Initial) {
+                game.resync();
+                if let Some(connection) = &self.connection {
+                    connection.resync_game();
+                }
+                self.state = State::Game(game);
             }
-            if ui.ctx().input_mut().key_released(key) {
-                self.walking_directions.remove(direction);
+        }
+    }
+
+    fn update_game_inputs(&mut self, ui: &mut egui::Ui) {
+        let game = self.state.game();
+        let policy = self.app_settings.movement_policy;
+        let diagonal_walking = game.settings().diagonal_walking;
+
+        let action = KeyBindings::WASD.read_action(
+            ui.ctx(),
+            &mut self.walking_directions,
+            policy,
+            diagonal_walking,
+        );
+        game.set_local_player_action(action);
+        let actions = game.take_local_actions();
+        if !actions.is_empty() {
+            if let Some(connection) = &self.connection {
+                connection.send_actions(actions);
             }
         }
+        if game.is_split_keyboard() {
+            let action = KeyBindings::ARROWS.read_action(
+                ui.ctx(),
+                &mut self.second_walking_directions,
+                policy,
+                diagonal_walking,
+            );
+            game.set_second_player_action(action);
+        }
+
+        if ui.ctx().input_mut().key_pressed(egui::Key::P) {
+            game.command(Command::TogglePause);
+        }
+        if ui.ctx().input_mut().key_pressed(egui::Key::N) {
+            game.command(Command::StepTick);
+        }
+    }
 
-        let placing = ui.ctx().input_mut().key_down(egui::Key::Space);
-        let walking = self.walking_directions.get();
-        game.set_local_player_action(Action { walking, placing });
+    /// Write a bug report to the current directory and tell the user where
+    fn export_bug_report(&mut self) {
+        let packets = self
+            .connection
+            .as_ref()
+            .map(Connection::packet_history)
+            .unwrap_or_default();
+        let (settings, game_state, recording) = match &mut self.state {
+            State::Game(game) => (
+                game.settings().clone(),
+                Some(game.local_state().clone()),
+                game.recording().map(<[_]>::to_vec),
+            ),
+            _ => (self.app_settings.game_settings.clone(), None, None),
+        };
+        self.toast = Some(
+            match bug_report::export(
+                Path::new("."),
+                &settings,
+                game_state.as_ref(),
+                recording.as_deref(),
+                &packets,
+            ) {
+                Ok(dir) => format!("Bug report written to {}", dir.display()),
+                Err(err) => format!("Can not write bug report: {err}"),
+            },
+        );
     }
 
     fn update_game_draw(&mut self, ui: &mut egui::Ui) {
         let textures = self.textures(ui.ctx());
 
-        let game_over = ui
+        let (game_over, export_bug_report) = ui
             .horizontal(|ui| {
                 ui.label(&self.state.game().settings().game_name);
                 let button = ui.button("Stop Game");
+                let export_bug_report = ui
+                    .button("Export Bug Report")
+                    .on_hover_text("Write settings, game state and network log to a folder")
+                    .clicked();
+                ui.checkbox(&mut self.debug_grid, "Grid")
+                    .on_hover_text("Show grid lines and the coordinates of each cell");
+                ui.checkbox(&mut self.show_heatmap, "Heatmap")
+                    .on_hover_text("Show where you spent your time and placed bombs");
                 if button.clicked() {
-                    self.state = State::GameOver("You pressed Stop".to_owned());
-                    true
+                    let game = self.state.game();
+                    let outcome = game.local_state().results();
+                    self.state = State::results(game, outcome);
+                    (true, false)
                 } else {
-                    false
+                    (false, export_bug_report)
                 }
             })
             .inner;
         if game_over {
             return;
         };
+        let server_outcome = self
+            .connection
+            .as_ref()
+            .and_then(Connection::game_outcome)
+            .filter(|_| matches!(self.state.game(), Game::MultiPlayer(_)));
+        // The server's outcome wins over a local simulation that diverged
+        let outcome = server_outcome.or_else(|| {
+            let local_state = self.state.game().local_state();
+            local_state.is_decided().then(|| local_state.results())
+        });
+        if let Some(outcome) = outcome {
+            self.state = State::results(self.state.game(), outcome);
+            return;
+        }
+        if export_bug_report {
+            self.export_bug_report();
+        }
 
         let step = &mut self.state;
         let game = step.game();
+        let game_static = game.stat().clone();
+
+        // leave room for the camera and the scoreboard: speed, heading and one row per player
+        let scoreboard_height =
+            (game_static.players.len() + 3) as f32 * ui.spacing().interact_size.y;
+        let available = ui.available_size() - egui::vec2(0.0, scoreboard_height);
+        let (width, height) = (game.settings().width, game.settings().height);
+        let fit_scale = pixel_per_cell(available, width, height);
+        let cells = egui::vec2((width + 2) as f32, (height + 2) as f32);
+
+        // center the board in all available space, letterboxing the rest
+        let (space, response) =
+            ui.allocate_exact_size(available.max(cells * fit_scale), egui::Sense::drag());
+        if response.double_clicked() {
+            self.camera = Camera::default();
+        }
+        if response.hovered() {
+            let scroll = ui.input().scroll_delta.y;
+            self.camera.zoom_by((scroll / 200.0).exp());
+        }
+        if response.dragged_by(egui::PointerButton::Middle) {
+            self.camera
+                .pan_by(response.drag_delta(), cells * fit_scale * self.camera.zoom);
+        }
+        let scale = fit_scale * self.camera.zoom;
+        if let Some(player) = self
+            .camera
+            .follow
+            .and_then(|id| game.local_state().player_states.get(id))
+        {
+            let target = offset_from_board_center(player.position, cells, scale);
+            self.camera.center_on(target, cells * scale);
+        }
+        let board = Rect::from_center_size(space.center() + self.camera.pan, cells * scale);
+
+        let painter = ui.painter_at(space);
+
+        let paused = game.is_paused();
+        let ping = self
+            .connection
+            .as_ref()
+            .and_then(Connection::get_ping)
+            .filter(|_| matches!(game, Game::MultiPlayer(_)));
+        // the heatmap can not be borrowed alongside the local state
+        let heatmap = if self.show_heatmap {
+            heatmap_shapes(game.heatmap(), board.min, scale)
+        } else {
+            Vec::new()
+        };
+        let state = game.local_state();
+        let field = &state.field;
+        if self.static_layer.as_ref().map_or(true, |layer| {
+            layer.colorblind != textures.colorblind
+                || !layer.cells.iter().copied().eq(static_layer_cells(field))
+        }) {
+            let texture = ui.ctx().load_texture(
+                "static_layer",
+                textures.composite_static_layer(field),
+                egui::TextureOptions::default(),
+            );
+            self.static_layer = Some(StaticLayer {
+                cells: static_layer_cells(field).collect(),
+                colorblind: textures.colorblind,
+                texture,
+            });
+        }
+        let static_layer = self
+            .static_layer
+            .as_ref()
+            .expect("was just drawn")
+            .texture
+            .id();
+        let mut shapes: Vec<(Layer, Shape)> =
+            field_shapes(&textures, field, static_layer, board.min, scale)
+                .into_iter()
+                .map(|shape| (Layer::Field, shape))
+                .collect();
+
+        let fps = 1.0 / ui.ctx().input().stable_dt;
+        {
+            let fonts = painter.fonts();
+            for (id, player) in player_draw_order(&state.player_states) {
+                if !player.is_alive(&game_static.settings) {
+                    continue;
+                }
+                let (texture, uv) = textures.get_player(player, state.time);
+                let rect = player_rect(player.position, board.min, scale);
+                shapes.push((
+                    Layer::Players,
+                    Shape::image(texture, rect, uv, Color32::WHITE),
+                ));
+                shapes.push((
+                    Layer::Names,
+                    Shape::text(
+                        &fonts,
+                        rect.center_top(),
+                        egui::Align2::CENTER_BOTTOM,
+                        &game_static.players[&id].name,
+                        egui::FontId::proportional(12.0),
+                        Color32::WHITE,
+                    ),
+                ));
+            }
 
-        let width = (game.settings().width + 2) as f32 * PIXEL_PER_CELL;
-        let height = (game.settings().height + 2) as f32 * PIXEL_PER_CELL;
-
-        let game_field = ui.image(
-            textures.get_texture("background"),
-            egui::Vec2 {
-                x: width,
-                y: height,
-            },
-        );
+            if self.app_settings.shockwaves {
+                shapes.extend(
+                    shockwave_shapes(
+                        field,
+                        state.time,
+                        game_static.settings.fire_burn_time().ticks(),
+                        board.min,
+                        scale,
+                    )
+                    .into_iter()
+                    .map(|shape| (Layer::Shockwaves, shape)),
+                );
+            }
 
-        let painter = ui.painter_at(game_field.rect);
+            shapes.extend(heatmap.into_iter().map(|shape| (Layer::Heatmap, shape)));
 
-        painter.rect_stroke(
-            game_field.rect,
-            egui::Rounding::none(),
-            egui::Stroke {
-                width: 2.0,
-                color: egui::Color32::GOLD,
-            },
-        );
+            if self.debug_grid {
+                shapes.extend(
+                    grid_shapes(&fonts, width, height, board.min, scale)
+                        .into_iter()
+                        .map(|shape| (Layer::Grid, shape)),
+                );
+            }
 
-        painter.extend(
-            game.local_state()
-                .field
-                .iter_with_border()
-                .map(|(pos, cell)| {
-                    Shape::image(
-                        textures.get_cell(cell),
-                        cell_rect(pos, game_field.rect.min),
-                        Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
-                        Color32::WHITE,
-                    )
-                }),
-        );
+            shapes.push((
+                Layer::Hud,
+                Shape::rect_stroke(
+                    board,
+                    egui::Rounding::none(),
+                    egui::Stroke {
+                        width: 2.0,
+                        color: egui::Color32::GOLD,
+                    },
+                ),
+            ));
+            shapes.push((
+                Layer::Hud,
+                Shape::text(
+                    &fonts,
+                    space.right_top() + egui::vec2(-4.0, 4.0),
+                    egui::Align2::RIGHT_TOP,
+                    format!("{fps:.0} FPS"),
+                    egui::FontId::monospace(12.0),
+                    Color32::GOLD,
+                ),
+            ));
+            if let Some(ping) = ping {
+                shapes.push((
+                    Layer::Hud,
+                    Shape::text(
+                        &fonts,
+                        space.right_top() + egui::vec2(-4.0, 20.0),
+                        egui::Align2::RIGHT_TOP,
+                        input_delay_text(ping),
+                        egui::FontId::monospace(12.0),
+                        Color32::GOLD,
+                    ),
+                ));
+            }
+            if paused {
+                shapes.push((
+                    Layer::Hud,
+                    Shape::text(
+                        &fonts,
+                        space.left_top() + egui::vec2(4.0, 4.0),
+                        egui::Align2::LEFT_TOP,
+                        format!(
+                            "Paused at tick {}, N: step, P: resume",
+                            state.time.ticks_from_start()
+                        ),
+                        egui::FontId::monospace(12.0),
+                        Color32::GOLD,
+                    ),
+                ));
+            }
+        }
+        if let Some(text) = countdown_text(state.time.ticks_from_start(), state.start_delay_ticks())
+        {
+            shapes.push((
+                Layer::Hud,
+                Shape::text(
+                    &painter.fonts(),
+                    board.center(),
+                    egui::Align2::CENTER_CENTER,
+                    text,
+                    egui::FontId::proportional(scale * 2.0),
+                    Color32::GOLD,
+                ),
+            ));
+        }
+        painter.extend(layered(shapes));
 
-        let time = game.local_state().time;
+        update_camera_target(ui, &mut self.camera, &game_static);
+        update_scoreboard(ui, game);
 
-        painter.extend(game.local_state().player_states.values().map(|player| {
-            Shape::image(
-                textures.get_player(player, time),
-                player_rect(player.position, game_field.rect.min),
-                Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
-                Color32::WHITE,
-            )
-        }));
         ui.ctx()
             .request_repaint_after(std::time::Duration::from_secs_f32(
                 1.0 / TICKS_PER_SECOND as f32,
             ));
     }
 
+    fn update_results(&mut self, ui: &mut egui::Ui) {
+        let State::Results {
+            outcome,
+            settings,
+            back_to_lobby,
+        } = &self.state
+        else {
+            panic!("no results");
+        };
+        let (outcome, settings, back_to_lobby) =
+            (outcome.clone(), settings.clone(), *back_to_lobby);
+        match outcome.winner {
+            Some(winner) => {
+                let name = &outcome
+                    .standings
+                    .iter()
+                    .find(|s| s.player == winner)
+                    .expect("winner has a standing")
+                    .name;
+                ui.heading(format!("{name} wins!"));
+            }
+            None => {
+                ui.heading("Draw");
+            }
+        }
+        if let Some(status) = self
+            .connection
+            .as_ref()
+            .and_then(Connection::tournament_status)
+        {
+            ui.label(tournament_summary(&status, &outcome));
+        }
+        egui::Grid::new("results").striped(true).show(ui, |ui| {
+            for heading in [
+                "", "Player", "Kills", "Deaths", "Streak", "Bombs", "Walked", "Upgrades", "Points",
+            ] {
+                ui.strong(heading);
+            }
+            ui.end_row();
+            for (place, standing) in outcome.standings.iter().enumerate() {
+                ui.label(format!("{}.", place + 1));
+                ui.label(&standing.name);
+                ui.label(standing.kills.to_string());
+                ui.label(standing.deaths.to_string());
+                ui.label(standing.longest_kill_streak.to_string())
+                    .on_hover_text("Most kills without dying");
+                ui.label(standing.bombs_placed.to_string());
+                ui.label(format!("{} cells", standing.distance_walked));
+                ui.label(standing.upgrades_collected.to_string());
+                ui.label(standing.upgrade_points.to_string())
+                    .on_hover_text("Upgrades eaten at their cap");
+                ui.end_row();
+            }
+        });
+        if let Some(back_to_lobby) = back_to_lobby {
+            let remaining = back_to_lobby.saturating_duration_since(std::time::Instant::now());
+            let in_lobby = self
+                .connection
+                .as_ref()
+                .and_then(Connection::lobby_settings)
+                .is_some();
+            if remaining.is_zero() && in_lobby {
+                self.state = State::MultiPlayerServerGuest;
+                return;
+            }
+            // the next round of a tournament starts without the lobby
+            if self.start_multiplayer_game() {
+                return;
+            }
+            if remaining.is_zero() {
+                ui.label("Waiting for the lobby");
+            } else {
+                ui.label(format!(
+                    "Back to the lobby in {} s",
+                    remaining.as_secs_f32().ceil()
+                ));
+            }
+            // count down without input
+            ui.ctx()
+                .request_repaint_after(std::time::Duration::from_millis(100));
+        }
+        ui.horizontal(|ui| {
+            if ui
+                .button("Play Again")
+                .on_hover_text("Start a new game with the same settings")
+                .clicked()
+            {
+                self.state = State::Game(Game::new_local_game(
+                    settings,
+                    self.app_settings.split_keyboard,
+                ));
+            } else if ui.button("Menu").clicked() {
+                self.state = State::Initial;
+            }
+        });
+    }
+
     fn update_initial(&mut self, ui: &mut egui::Ui) {
         ui.add(egui::TextEdit::singleline(
             &mut self.app_settings.player_name,
         ))
         .on_hover_text("Player Name");
+        egui::ComboBox::from_label("Movement Keys")
+            .selected_text(self.app_settings.movement_policy.name())
+            .show_ui(ui, |ui| {
+                for policy in MovementPolicy::ALL {
+                    ui.selectable_value(
+                        &mut self.app_settings.movement_policy,
+                        policy,
+                        policy.name(),
+                    );
+                }
+            })
+            .response
+            .on_hover_text("How holding several direction keys at once is resolved");
+        if ui
+            .checkbox(&mut self.app_settings.colorblind_tiles, "Colorblind Tiles")
+            .on_hover_text("Mark fire, upgrades and teleports with patterns and letters")
+            .changed()
+        {
+            self.textures = None;
+        }
+        ui.checkbox(&mut self.app_settings.shockwaves, "Shockwaves")
+            .on_hover_text("Draw a ring around explosions, bigger for more powerful bombs");
         ui.horizontal(|ui| {
             let local_button = ui
                 .button("Single Player")
@@ -574,9 +1504,7 @@ impl MyApp {
                 if connect_button.clicked() {
                     self.app_settings.save(); // TODO: should only save server
 
-                    self.connection = Some(connect(server, self.app_settings.player_name.clone()));
-                    self.state = State::MultiPlayerConnectingToServer; // TODO: connection should
-                                                                     // live in step
+                    self.connect(server);
                 }
                 }
             }
@@ -584,128 +1512,984 @@ impl MyApp {
 
 
         });
+        if !self.app_settings.recent_servers.is_empty() {
+            ui.horizontal(|ui| {
+                ui.label("Recent Servers:");
+                let mut clicked = None;
+                for server in &self.app_settings.recent_servers {
+                    if ui
+                        .button(server)
+                        .on_hover_text("Connect to this server")
+                        .clicked()
+                    {
+                        clicked = Some(server.clone());
+                    }
+                }
+                if let Some(server) = clicked {
+                    match server.parse() {
+                        Ok(address) => {
+                            self.app_settings.server = server;
+                            self.connect(address);
+                        }
+                        Err(err) => {
+                            self.toast = Some(format!("Can not connect to {server}: {err}"));
+                        }
+                    }
+                }
+            });
+        }
+    }
+
+    fn connect(&mut self, server: SocketAddr) {
+        self.connection = Some(connect(server, self.app_settings.player_name.clone()));
+        self.state = State::MultiPlayerConnectingToServer; // TODO: connection should live in step
     }
 
     fn update_multiplayer_view(&mut self, ui: &mut egui::Ui) {
         let connection = self.connection.as_ref().unwrap();
         if let Some(Ok((lobbies, server_info))) = connection.get_server_info() {
-            ui.heading(&format!(
-                "Multiplayer Games on {} ({}), Ping {:.1}",
-                server_info.server_name,
-                connection.server,
-                server_info.ping.as_secs_f32() / 1000.0
-            ));
+            ui.horizontal(|ui| {
+                ui.heading(&format!(
+                    "Multiplayer Games on {} ({}), Ping {:.1}",
+                    server_info.server_name,
+                    connection.server,
+                    server_info.ping.as_secs_f32() / 1000.0
+                ));
+                if ui
+                    .button("Copy Address")
+                    .on_hover_text("Copy the server's address to the clipboard to share it")
+                    .clicked()
+                {
+                    ui.output().copied_text = connection.server.to_string();
+                }
+            });
+            ui.add(
+                egui::Slider::new(&mut self.app_settings.update_rate, 0..=TICKS_PER_SECOND)
+                    .text("Updates per second")
+                    .clamp_to_range(true),
+            )
+            .on_hover_text(
+                "Fewer updates save bandwidth, other players react later. 0 for every tick",
+            );
+            let update_rate = self.app_settings.update_rate;
             for (game_id, game_name) in lobbies {
                 ui.horizontal(|ui| {
                     if ui.button("Join").clicked() {
-                        todo!("join {game_id:?}");
+                        connection.set_update_rate(update_rate);
+                        connection.join_lobby(game_id);
+                        self.state = State::MpJoiningLobby;
                     }
                     ui.label(game_name);
                 });
             }
             if ui.button("Host new Game").clicked() {
+                connection.set_update_rate(update_rate);
                 connection.open_new_lobby();
                 self.state = State::MpOpeningLobby;
             }
+            if cfg!(debug_assertions) && ui.button("Log Packets").clicked() {
+                connection.log_packet_history();
+            }
         };
     }
 
-    fn update_multiplayer_guest(&self, ui: &mut egui::Ui) {
-        todo!()
-    }
-
-    fn update_multiplayer_host(&self, ui: &mut egui::Ui) {
-        todo!()
+    fn update_multiplayer_guest(&mut self, ui: &mut egui::Ui) {
+        if self.start_multiplayer_game() {
+            return;
+        }
+        let textures = self.textures(ui.ctx());
+        let connection = self.connection.as_ref().unwrap();
+        if lobby_players(ui, connection)
+            && ui
+                .button("Ready")
+                .on_hover_text("Start as soon as everyone is ready")
+                .clicked()
+        {
+            connection.set_ready(true);
+        }
+        match connection.lobby_settings() {
+            Some(settings) => {
+                ui.heading("Settings of this Game");
+                update_settings(ui, &textures, &settings, ReadOnly::ReadOnly);
+                copy_settings_button(ui, &settings);
+            }
+            None => {
+                if !show_locked_settings(ui, &textures, connection) {
+                    ui.label("Waiting for Lobby");
+                }
+            }
+        }
     }
-}
 
-impl eframe::App for MyApp {
-    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Bomberhans");
-            match self.state {
-                State::Initial => self.update_initial(ui),
-                State::GameOver(_) | State::SinglePlayerSettings => {
-                    self.update_singleplayer_settings(ui)
-                }
-                State::Game(_) => self.update_game(ui),
-                State::MultiPlayerConnectingToServer => {
-                    let connection = self.connection.as_ref().unwrap();
-                    match connection.get_server_info() {
-                        Some(Ok(server_info)) => {
-                            self.state = State::MultiPlayerServerView;
-                            self.update_multiplayer_view(ui);
-                        }
-                        Some(Err(err)) => {
-                            let server = connection.server;
-                            self.update_initial(ui);
-                            ui.label(&format!("Error connecting to {}: {}", server, err));
-                        }
-                        None => {
-                            ui.label(&format!(
-                                "connecting to {}",
-                                self.connection.as_ref().unwrap().server
-                            ));
-                            if ui.button("Cancel ").clicked() {
-                                self.state = State::Initial;
-                            }
-                        }
-                    }
+    fn update_multiplayer_host(&mut self, ui: &mut egui::Ui) {
+        if self.start_multiplayer_game() {
+            return;
+        }
+        let textures = self.textures(ui.ctx());
+        let connection = self.connection.as_ref().unwrap();
+        if lobby_players(ui, connection) {
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Ready")
+                    .on_hover_text("Start as soon as everyone is ready")
+                    .clicked()
+                {
+                    connection.set_ready(true);
                 }
-                State::MultiPlayerServerView => self.update_multiplayer_view(ui),
-                State::MpOpeningLobby => {
-                    ui.label(&format!("Waiting for new Lobby to open",));
-                    if ui.button("Cancel ").clicked() {
-                        self.state = State::Initial;
-                    }
+                if ui
+                    .button("Start")
+                    .on_hover_text("Start now, players that are not ready are dropped")
+                    .clicked()
+                {
+                    connection.force_start();
                 }
-                State::MultiPlayerServerGuest => self.update_multiplayer_guest(ui),
-                State::MultiPlayerServerHost => self.update_multiplayer_host(ui),
+            });
+            if let Some(reason) = connection.start_rejected() {
+                ui.colored_label(Color32::RED, reason.to_string());
             }
-        });
-        if !frame.is_web() {
-            egui::gui_zoom::zoom_with_keyboard_shortcuts(ctx, frame.info().native_pixels_per_point);
         }
-    }
-}
-
-/// Create an image from byte slice
-///
-/// `image_data` the image bytes (e.g. a Bitmap)
-/// `transparent` turn all pixels with the same color as the top left corenr transparent
-fn load_image_from_memory(image_data: &[u8], transparent: bool) -> egui::ColorImage {
-    let image = image::load_from_memory(image_data).expect("resources can be loaded");
-    let size = [image.width() as _, image.height() as _];
-    let mut image_buffer = image.to_rgba8();
-    let top_left = image_buffer[(0, 0)];
-    if transparent {
-        for pixel in image_buffer.pixels_mut() {
-            if *pixel == top_left {
-                pixel[3] = 0;
+        let Some(lobby_settings) = connection.lobby_settings() else {
+            if !show_locked_settings(ui, &textures, connection) {
+                ui.label("Waiting for Lobby");
+            }
+            return;
+        };
+        ui.heading("Settings of this Game");
+        let edited = &mut self.app_settings.game_settings;
+        ui.horizontal(|ui| {
+            let apply = ui
+                .add_enabled(
+                    *edited != lobby_settings,
+                    egui::Button::new("Apply Settings"),
+                )
+                .on_hover_text(if lobby_settings.unready_on_settings_change {
+                    "Change the settings of the game, everyone has to get ready again"
+                } else {
+                    "Change the settings of the game"
+                });
+            if apply.clicked() {
+                connection.set_settings(edited.clone());
+            }
+            if ui
+                .button("Revert")
+                .on_hover_text("Go back to the settings of the game")
+                .clicked()
+            {
+                *edited = lobby_settings.clone();
             }
+            copy_settings_button(ui, &lobby_settings);
+        });
+        if let Some(settings) = update_settings(ui, &textures, edited, ReadOnly::ReadWrite) {
+            *edited = settings;
         }
     }
-    let pixels = image_buffer.as_flat_samples();
-    egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice())
 }
 
-fn load_tiles(ctx: &egui::Context) -> HashMap<&'static str, TextureHandle> {
+/// Show the settings of the started game as read only, `false` if it did not start
+fn show_locked_settings(
+    ui: &mut egui::Ui,
+    textures: &Rc<TextureManager>,
+    connection: &Connection,
+) -> bool {
+    let Some(settings) = connection.locked_settings() else {
+        return false;
+    };
+    ui.heading("Settings of this Game");
+    ui.label("🔒 The game started, the settings are locked until it is back in the lobby");
+    update_settings(ui, textures, &settings, ReadOnly::ReadOnly);
+    copy_settings_button(ui, &settings);
+    true
+}
+
+/// The wins of the players in a tournament, by name
+fn tournament_summary(status: &TournamentStatus, outcome: &Results) -> String {
+    let name = |player: PlayerId| {
+        outcome
+            .standings
+            .iter()
+            .find(|s| s.player == player)
+            .map_or("?", |s| s.name.as_str())
+    };
+    let wins: Vec<String> = status
+        .wins
+        .iter()
+        .map(|(&player, wins)| format!("{} {wins}", name(player)))
+        .collect();
+    let summary = format!("Best of {}: {}", status.best_of, wins.join(", "));
+    match status.winner {
+        Some(winner) => format!("{summary}, {} wins the tournament!", name(winner)),
+        None => summary,
+    }
+}
+
+/// List the players of the lobby and whether they are ready, `false` if not in a lobby yet
+fn lobby_players(ui: &mut egui::Ui, connection: &Connection) -> bool {
+    let Some(players) = connection.lobby_players() else {
+        return false;
+    };
+    ui.heading("Players");
+    for (name, ready) in &players {
+        ui.label(format!("{} {name}", if *ready { "✔" } else { "⏳" }));
+    }
+    match players_not_ready(&players) {
+        0 => ui.label("Everyone is ready"),
+        1 => ui.label("Waiting for 1 player"),
+        n => ui.label(format!("Waiting for {n} players")),
+    };
+    if let Some(settings) = connection.lobby_settings() {
+        if players.len() < settings.min_players_to_start as usize {
+            ui.label(format!(
+                "At least {} players are needed to start",
+                settings.min_players_to_start
+            ));
+        }
+    }
+    true
+}
+
+/// Whether settings can be changed in `update_settings`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadOnly {
+    ReadOnly,
+    ReadWrite,
+}
+
+/// Button that copies `settings` to the clipboard, to paste them into another game
+fn copy_settings_button(ui: &mut egui::Ui, settings: &Settings) {
+    if ui
+        .button("Copy Settings")
+        .on_hover_text("Copy the settings to the clipboard to share them")
+        .clicked()
+    {
+        ui.output().copied_text = settings.to_shareable_string();
+    }
+}
+
+/// Draw `add_contents` with a highlighted text color if `highlighted`
+fn highlight(ui: &mut egui::Ui, highlighted: bool, add_contents: impl FnOnce(&mut egui::Ui)) {
+    ui.scope(|ui| {
+        if highlighted {
+            ui.visuals_mut().override_text_color = Some(Color32::GOLD);
+        }
+        add_contents(ui);
+    });
+}
+
+/// Draw the settings of a game, settings that differ from their default are highlighted
+///
+/// Returns the new settings if the user changed them, which never happens if `read_only`
+#[allow(clippy::too_many_lines)] // GUI code has to be long and ugly
+fn update_settings(
+    ui: &mut egui::Ui,
+    textures: &Rc<TextureManager>,
+    old_settings: &Settings,
+    read_only: ReadOnly,
+) -> Option<Settings> {
+    let enabled = read_only == ReadOnly::ReadWrite;
+    let changed = old_settings.differences(&Settings::default());
+    let changed = |name: &str| changed.iter().any(|c| *c == name);
+
+    let mut settings = old_settings.clone();
+
+    ui.style_mut().spacing.slider_width = 300.0;
+
+    highlight(ui, changed("game_name"), |ui| {
+        ui.add_enabled(enabled, egui::TextEdit::singleline(&mut settings.game_name))
+            .on_hover_text("Name of the Game");
+    });
+
+    ui.horizontal(|ui| {
+        ui.vertical(|ui| {
+            ui.heading("Game Options");
+            highlight(ui, changed("width"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.width, Settings::WIDTH_RANGE)
+                        .text("Width")
+                        .clamp_to_range(true),
+                )
+                .on_hover_text("Width of the game field [cells]");
+            });
+            highlight(ui, changed("height"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.height, Settings::HEIGHT_RANGE)
+                        .text("Height")
+                        .clamp_to_range(true),
+                )
+                .on_hover_text("Height of the game field [cells]");
+            });
+            highlight(ui, changed("players"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.players, Settings::PLAYERS_RANGE)
+                        .text("Players")
+                        .clamp_to_range(true),
+                )
+                .on_hover_text("Number of players that can join this game");
+            });
+            highlight(ui, changed("min_players_to_start"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.min_players_to_start, 1..=settings.players)
+                        .text("Players to Start")
+                        .clamp_to_range(true),
+                )
+                .on_hover_text("The game starts when this many players are ready
+The host can start earlier");
+            });
+            highlight(ui, changed("map_seed"), |ui| {
+                ui.horizontal(|ui| {
+                    let mut seeded = settings.map_seed.is_some();
+                    if ui
+                        .add_enabled(enabled, egui::Checkbox::new(&mut seeded, "Map Seed"))
+                        .on_hover_text("Generate the layout of the field from a seed, otherwise play the classic field")
+                        .changed()
+                    {
+                        settings.map_seed = seeded.then(rand::random);
+                    }
+                    if let Some(seed) = &mut settings.map_seed {
+                        ui.add_enabled(enabled, egui::DragValue::new(seed))
+                            .on_hover_text("The same seed always generates the same field");
+                    }
+                    if ui.add_enabled(enabled, egui::Button::new("Random")).clicked() {
+                        settings.map_seed = Some(rand::random());
+                    }
+                    if let Some(seed) = settings.map_seed {
+                        if ui
+                            .button("Copy Seed")
+                            .on_hover_text("Copy the seed to the clipboard to play this map again")
+                            .clicked()
+                        {
+                            ui.output().copied_text = seed.to_string();
+                        }
+                    }
+                });
+            });
+            highlight(ui, changed("burnt_wood_percent"), |ui| {
+                ui.add_enabled(
+                    enabled && settings.custom_map.is_none(),
+                    egui::Slider::new(&mut settings.burnt_wood_percent, Settings::BURNT_WOOD_RANGE)
+                        .text("Burnt Wood [%]")
+                        .clamp_to_range(true),
+                )
+                .on_hover_text("This much of the wood starts burnt down, so there are upgrades from the start");
+            });
+            highlight(ui, changed("custom_map"), |ui| {
+                let mut custom = settings.custom_map.is_some();
+                ui.add_enabled(enabled, egui::Checkbox::new(&mut custom, "Custom Map"))
+                    .on_hover_text("Play on a hand-designed field instead of a generated one");
+                if !custom {
+                    settings.custom_map = None;
+                } else if settings.custom_map.is_none() {
+                    // start from the generated field
+                    settings.custom_map = Some(Field::new_from_rules(&settings).string_grid());
+                }
+                if let Some(map) = &mut settings.custom_map {
+                    ui.add_enabled(enabled, egui::TextEdit::multiline(map).code_editor())
+                        .on_hover_text("One line per row: _ empty, # wall, + wood, O start point, T teleport, s p b upgrades");
+                }
+                if let (Some(_), Err(errors)) = (&settings.custom_map, settings.validate()) {
+                    ui.colored_label(Color32::RED, describe_errors(&errors));
+                }
+            });
+            highlight(ui, changed("kills_to_win"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.kills_to_win, Settings::KILLS_TO_WIN_RANGE)
+                        .text("Kills to Win")
+                        .clamp_to_range(true),
+                )
+                .on_hover_text("The game ends when a player has this many kills, 0 to play until stopped");
+            });
+            highlight(ui, changed("lives"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.lives, Settings::LIVES_RANGE)
+                        .text("Lives")
+                        .clamp_to_range(true),
+                )
+                .on_hover_text("Players are out after dying this often, the last one left wins. 0 to respawn forever");
+            });
+            highlight(ui, changed("teams"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.teams, Settings::TEAMS_RANGE)
+                        .text("Teams")
+                        .clamp_to_range(true),
+                )
+                .on_hover_text("Split the players into this many teams that win together. 0 or 1 for everyone on their own");
+            });
+            highlight(ui, changed("friendly_fire"), |ui| {
+                ui.add_enabled(
+                    enabled && settings.teams > 1,
+                    egui::Checkbox::new(&mut settings.friendly_fire, "Friendly Fire"),
+                )
+                .on_hover_text("Fire of teammates hurts, but killing a teammate never scores");
+            });
+            highlight(ui, changed("return_to_lobby_after_s"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(
+                        &mut settings.return_to_lobby_after_s,
+                        Settings::RETURN_TO_LOBBY_AFTER_RANGE,
+                    )
+                    .text("Back to Lobby after [s]")
+                    .clamp_to_range(true),
+                )
+                .on_hover_text("Return from the results to the lobby after this many seconds, 0 to stay");
+            });
+            highlight(ui, changed("best_of"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.best_of, Settings::BEST_OF_RANGE)
+                        .text("Best of")
+                        .clamp_to_range(true),
+                )
+                .on_hover_text("Play a tournament of this many games, the next one starts right after the results. 0 or 1 for single games");
+            });
+            highlight(ui, changed("unready_on_settings_change"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Checkbox::new(
+                        &mut settings.unready_on_settings_change,
+                        "Unready on Settings Change",
+                    ),
+                )
+                .on_hover_text("When the host changes the settings, everyone has to get ready again");
+            });
+            highlight(ui, changed("countdown_s"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.countdown_s, Settings::COUNTDOWN_RANGE)
+                        .text("Countdown [s]")
+                        .clamp_to_range(true),
+                )
+                .on_hover_text("Players can not move for this many seconds at the start");
+            });
+            highlight(ui, changed("magma_after_s"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.magma_after_s, Settings::MAGMA_AFTER_RANGE)
+                        .text("Magma after [s]")
+                        .clamp_to_range(true),
+                )
+                .on_hover_text("After this many seconds, random cells catch fire twice a second, 0 for never");
+            });
+            highlight(ui, changed("wood_regrow_ms"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.wood_regrow_ms, Settings::WOOD_REGROW_RANGE)
+                        .text("Wood Regrows [ms]")
+                        .clamp_to_range(true),
+                )
+                .on_hover_text("An empty cell away from the players grows wood this often, 0 for never");
+            });
+            highlight(ui, changed("bomb_explode_time_ms"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(
+                        &mut settings.bomb_explode_time_ms,
+                        Settings::BOMB_TIME_RANGE,
+                    )
+                    .text("Bomb Time")
+                    .clamp_to_range(true),
+                )
+                .on_hover_text("Time between placing a bomb and its explosion [ms]");
+            });
+            let bomb_time = settings.bomb_explode_time();
+            ui.label(format!(
+                "Bombs explode after {} ticks ({} ms)",
+                bomb_time.ticks(),
+                bomb_time.as_ms()
+            ))
+            .on_hover_text("Times are rounded to whole ticks");
+            highlight(ui, changed("speed_base"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.speed_base, Settings::SPEED_BASE_RANGE)
+                        .text("Base Speed")
+                        .clamp_to_range(false),
+                )
+                .on_hover_text("Speed of the Player without any upgrades [Cells/s/100]");
+            });
+            highlight(ui, changed("speed_multiplyer"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(
+                        &mut settings.speed_multiplyer,
+                        Settings::SPEED_MULTIPLYER_RANGE,
+                    )
+                    .text("Speed Increase")
+                    .clamp_to_range(false),
+                )
+                .on_hover_text("Player speed increase per speed powerup [Cells/s/100]");
+            });
+            ui.label(format!(
+                "Walking: {:.2} Cells/s at start, {:.2} Cells/s with 3 upgrades",
+                settings.walk_speed_cells_per_second(1),
+                settings.walk_speed_cells_per_second(4),
+            ))
+            .on_hover_text("Effective speed, players walk whole distances each update");
+            highlight(ui, changed("bomb_walking_chance"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(
+                        &mut settings.bomb_walking_chance,
+                        Settings::BOMB_WALKING_CHANCE_RANGE,
+                    )
+                    .text("Bomb Walking")
+                    .clamp_to_range(true),
+                )
+                .on_hover_text("Chance that a player can walk over a bomb in an update [%]");
+            });
+            highlight(ui, changed("tombstone_walking_chance"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(
+                        &mut settings.tombstone_walking_chance,
+                        Settings::TOMBSTONE_WALKING_CHANCE_RANGE,
+                    )
+                    .text("Tombstone Walking")
+                    .clamp_to_range(true),
+                )
+                .on_hover_text("Chance that a player can walk over a tombstone in an update [%]");
+            });
+            highlight(ui, changed("upgrade_explosion_power"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(
+                        &mut settings.upgrade_explosion_power,
+                        Settings::UPGRADE_EXPLOSION_POWER_RANGE,
+                    )
+                    .text("Upgrade Explosion")
+                    .clamp_to_range(false),
+                )
+                .on_hover_text("Explosion Range of ignited Powerups [cells]");
+            });
+            highlight(ui, changed("chain_reactions"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Checkbox::new(&mut settings.chain_reactions, "Chain Reactions"),
+                )
+                .on_hover_text("Bombs hit by an explosion explode too, otherwise they are only destroyed");
+            });
+            highlight(ui, changed("explosion_knockback"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Checkbox::new(&mut settings.explosion_knockback, "Knockback"),
+                )
+                .on_hover_text("Exploding bombs shove players next to them one cell away");
+            });
+            highlight(ui, changed("diagonal_blast"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Checkbox::new(&mut settings.diagonal_blast, "Diagonal Blast"),
+                )
+                .on_hover_text("Explosions also spread diagonally");
+            });
+            highlight(ui, changed("fire_destroys_upgrades"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Checkbox::new(
+                        &mut settings.fire_destroys_upgrades,
+                        "Fire Destroys Upgrades",
+                    ),
+                )
+                .on_hover_text("Otherwise explosions pass over upgrades and leave them lying");
+            });
+            highlight(ui, changed("wood_burn_time_ms"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(
+                        &mut settings.wood_burn_time_ms,
+                        Settings::WOOD_BURN_TIME_RANGE,
+                    )
+                    .text("Wood Burn Time")
+                    .clamp_to_range(false),
+                )
+                .on_hover_text("Time that wood burns after igniting [ms]");
+            });
+            highlight(ui, changed("fire_burn_time_ms"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(
+                        &mut settings.fire_burn_time_ms,
+                        Settings::FIRE_BURN_TIME_RANGE,
+                    )
+                    .text("Fire Burn Time")
+                    .clamp_to_range(false),
+                )
+                .on_hover_text("Time that fire burns [ms]");
+            });
+            highlight(ui, changed("bomb_offset"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.bomb_offset, Settings::BOMB_OFFSET_RANGE)
+                        .text("Bomb Placement Offset")
+                        .clamp_to_range(false),
+                )
+                .on_hover_text("While running, how far behind hans a bomb is placed [cells/100]");
+            });
+            highlight(ui, changed("diagonal_walking"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Checkbox::new(&mut settings.diagonal_walking, "Diagonal Walking"),
+                )
+                .on_hover_text("Walk diagonally by holding two perpendicular directions");
+            });
+            highlight(ui, changed("teleports_enabled"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Checkbox::new(&mut settings.teleports_enabled, "Teleports"),
+                )
+                .on_hover_text("Otherwise teleports are obstacles that burn like empty cells");
+            });
+            highlight(ui, changed("teleport_consumed"), |ui| {
+                ui.add_enabled(
+                    enabled && settings.teleports_enabled,
+                    egui::Checkbox::new(&mut settings.teleport_consumed, "Teleports Used Up"),
+                )
+                .on_hover_text("Porting removes both teleports, otherwise they can be used again");
+            });
+            highlight(ui, changed("death_penalty"), |ui| {
+                ui.add_enabled_ui(enabled, |ui| {
+                    egui::ComboBox::from_label("Death Penalty")
+                        .selected_text(death_penalty_name(settings.death_penalty))
+                        .show_ui(ui, |ui| {
+                            for penalty in DeathPenalty::ALL {
+                                ui.selectable_value(
+                                    &mut settings.death_penalty,
+                                    penalty,
+                                    death_penalty_name(penalty),
+                                );
+                            }
+                        })
+                })
+                .response
+                .on_hover_text("What players lose when they die");
+            });
+            for (name, cap, text) in [
+                ("max_power", &mut settings.max_power, "Max Power"),
+                ("max_speed", &mut settings.max_speed, "Max Speed"),
+                ("max_bombs", &mut settings.max_bombs, "Max Bombs"),
+            ] {
+                highlight(ui, changed(name), |ui| {
+                    ui.add_enabled(
+                        enabled,
+                        egui::Slider::new(cap, Settings::UPGRADE_CAP_RANGE)
+                            .text(text)
+                            .clamp_to_range(false),
+                    )
+                    .on_hover_text("Most upgrades of this kind a player can have, 0 for no limit");
+                });
+            }
+            highlight(ui, changed("capped_upgrades_score"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Checkbox::new(
+                        &mut settings.capped_upgrades_score,
+                        "Capped Upgrades Score",
+                    ),
+                )
+                .on_hover_text("Upgrades eaten at their cap count as points, which break ties of kills and deaths, otherwise they are lost");
+            });
+            highlight(ui, changed("hide_opponent_upgrades"), |ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Checkbox::new(
+                        &mut settings.hide_opponent_upgrades,
+                        "Hide Opponent Upgrades",
+                    ),
+                )
+                .on_hover_text("The scoreboard shows only your own upgrades");
+            });
+        });
+        ui.vertical(|ui| {
+            const RATIO_RANGE: std::ops::RangeInclusive<u32> = 0..=50;
+            highlight(ui, changed("ratios"), |ui| {
+                ui.heading("Ratios of cells that burned wood will turn into");
+            });
+            ui.horizontal(|ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.ratios.power, RATIO_RANGE).text("Power Upgrade"),
+                );
+            })
+            .response
+            .on_hover_text("Consuming this will upgrade the player's bomb's explosion range");
+            ui.horizontal(|ui| {
+                ui.add_enabled(
+                    enabled,
+                    egui::Slider::new(&mut settings.ratios.speed, RATIO_RANGE).text("Speed Upgrade"),
+                );
+            })
+            .response
+            .on_hover_text("Consuming this will upgrade the player's walking speed");
+            ui.horizontal(|ui| {
+                ui.add_enabled(enabled, egui::Slider::new(&mut settings.ratios.bombs, RATIO_RANGE).text("Bomb Upgrade"));
+            })
+            .response
+            .on_hover_text(
+                "Consuming this will increase how many bombs the player can place simultaneously",
+            );
+            ui.horizontal(|ui| {
+                ui.add_enabled(enabled, egui::Slider::new(&mut settings.ratios.remote, RATIO_RANGE).text("Remote Upgrade"));
+            })
+            .response
+            .on_hover_text("Consuming this makes the player's bombs wait until they detonate them (E or Backspace)");
+            ui.horizontal(|ui| {
+                ui.add_enabled(enabled, egui::Slider::new(&mut settings.ratios.kick, RATIO_RANGE).text("Kick Upgrade"));
+            })
+            .response
+            .on_hover_text("Consuming this lets the player kick bombs by walking into them");
+            ui.horizontal(|ui| {
+                ui.add_enabled(enabled, egui::Slider::new(&mut settings.ratios.pierce, RATIO_RANGE).text("Pierce Upgrade"));
+            })
+            .response
+            .on_hover_text("Consuming this makes the player's bombs explode past the wood they ignite");
+            ui.horizontal(|ui| { ui.add_enabled(enabled, egui::Slider::new(&mut settings.ratios.teleport, RATIO_RANGE).text("Teleport")); }). response.on_hover_text("Teleport\nWalking into a teleport will move you to another TB and consume both.\nIgniting a Teleport will ignite another TP as well");
+            ui.horizontal(|ui| {
+                ui.add_enabled(enabled, egui::Slider::new(&mut settings.ratios.wall, RATIO_RANGE).text("Wall"));
+            })
+            .response
+            .on_hover_text("Wall\nIf this happens too often, you will be stuck.");
+            ui.horizontal(|ui| {
+                ui.add_enabled(enabled, egui::Slider::new(&mut settings.ratios.wood, RATIO_RANGE).text("Wood"));
+            })
+            .response
+            .on_hover_text("Wood\nYou can try and explode again");
+            ui.horizontal(|ui| {
+                ui.add_enabled(enabled, egui::Slider::new(&mut settings.ratios.clear, RATIO_RANGE).text("Empty Cell"));
+            })
+            .response
+            .on_hover_text("Just a boring empty Cell");
+        });
+        ui.vertical(|ui| {
+            ui.heading("effective Ratios");
+            let image_dims = egui::Vec2 { x: 16.0, y: 16.0 };
+            let percentages = settings.ratios.normalize();
+            ui.horizontal(|ui| {
+                ui.image(textures.get_texture("cell_upgrade_power"), image_dims);
+                ui.label(format!("{}%", percentages.power));
+            })
+            .response
+            .on_hover_text("Consuming this will upgrade the player's bomb's explosion range");
+            ui.horizontal(|ui| {
+                ui.image(textures.get_texture("cell_upgrade_speed"), image_dims);
+                ui.label(format!("{}%", percentages.speed));
+            })
+            .response
+            .on_hover_text("Consuming this will upgrade the player's walking speed");
+            ui.horizontal(|ui| {
+                ui.image(textures.get_texture("cell_upgrade_bomb"), image_dims);
+                ui.label(format!("{}%", percentages.bombs));
+            })
+            .response
+            .on_hover_text(
+                "Consuming this will increase how many bombs the player can place simultaneously",
+            );
+            ui.horizontal(|ui| {
+                ui.image(textures.get_texture("cell_upgrade_remote"), image_dims);
+                ui.label(format!("{}%", percentages.remote));
+            })
+            .response
+            .on_hover_text("Consuming this makes the player's bombs wait until they detonate them (E or Backspace)");
+            ui.horizontal(|ui| {
+                ui.image(textures.get_texture("cell_upgrade_kick"), image_dims);
+                ui.label(format!("{}%", percentages.kick));
+            })
+            .response
+            .on_hover_text("Consuming this lets the player kick bombs by walking into them");
+            ui.horizontal(|ui| {
+                ui.image(textures.get_texture("cell_upgrade_pierce"), image_dims);
+                ui.label(format!("{}%", percentages.pierce));
+            })
+            .response
+            .on_hover_text("Consuming this makes the player's bombs explode past the wood they ignite");
+            ui.horizontal(|ui| { ui.image(textures.get_texture("cell_teleport"), image_dims); ui.label(format!("{}%", percentages.teleport)); }). response.on_hover_text("Teleport\nWalking into a teleport will move you to another TB and consume both.\nIgniting a Teleport will ignite another TP as well");
+            ui.horizontal(|ui| {
+                ui.image(textures.get_texture("cell_wall"), image_dims);
+                ui.label(format!("{}%", percentages.wall));
+            })
+            .response
+            .on_hover_text("Wall\nIf this happens too often, you will be stuck.");
+            ui.horizontal(|ui| {
+                ui.image(textures.get_texture("cell_wood"), image_dims);
+                ui.label(format!("{}%", percentages.wood));
+            })
+            .response
+            .on_hover_text("Wood\nYou can try and explode again");
+            ui.horizontal(|ui| {
+                ui.image(textures.get_texture("cell_empty"), image_dims);
+                ui.label(format!("{}%", percentages.clear));
+            })
+            .response
+            .on_hover_text("Just a boring empty Cell");
+        });
+    });
+
+    changed_settings(old_settings, settings, read_only)
+}
+
+/// `new` if it differs from `old` and changes are allowed
+fn changed_settings(old: &Settings, new: Settings, read_only: ReadOnly) -> Option<Settings> {
+    (read_only == ReadOnly::ReadWrite && new != *old).then_some(new)
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Bomberhans");
+            if let Some(toast) = self.toast.clone() {
+                ui.horizontal(|ui| {
+                    ui.colored_label(Color32::RED, toast);
+                    if ui.button("OK").clicked() {
+                        self.toast = None;
+                    }
+                });
+            }
+            match self.state {
+                State::Initial => self.update_initial(ui),
+                State::GameOver(_) | State::SinglePlayerSettings => {
+                    self.update_singleplayer_settings(ui)
+                }
+                State::Game(_) => self.update_game(ui),
+                State::Desynced(_) => self.update_desynced(ui),
+                State::Results { .. } => self.update_results(ui),
+                State::MultiPlayerConnectingToServer => {
+                    let connection = self.connection.as_ref().unwrap();
+                    match connection.get_server_info() {
+                        Some(Ok(server_info)) => {
+                            self.app_settings
+                                .remember_server(connection.server.to_string());
+                            self.app_settings.save();
+                            self.state = State::MultiPlayerServerView;
+                            self.update_multiplayer_view(ui);
+                        }
+                        Some(Err(err)) => {
+                            let server = connection.server;
+                            self.update_initial(ui);
+                            ui.label(&format!("Error connecting to {}: {}", server, err));
+                        }
+                        None if connection.server_full() => {
+                            ui.label(&format!(
+                                "{} is full, trying again automatically",
+                                connection.server
+                            ));
+                            ui.horizontal(|ui| {
+                                if ui.button("Retry").clicked() {
+                                    connection.retry();
+                                }
+                                if ui.button("Cancel ").clicked() {
+                                    self.state = State::Initial;
+                                }
+                            });
+                            // keep polling, the answer does not cause a repaint
+                            ctx.request_repaint();
+                        }
+                        None => {
+                            ui.label(&format!(
+                                "connecting to {}",
+                                self.connection.as_ref().unwrap().server
+                            ));
+                            if ui.button("Cancel ").clicked() {
+                                self.state = State::Initial;
+                            }
+                        }
+                    }
+                }
+                State::MultiPlayerServerView => self.update_multiplayer_view(ui),
+                State::MpOpeningLobby => {
+                    let opened = self
+                        .connection
+                        .as_ref()
+                        .and_then(Connection::lobby_settings);
+                    if let Some(settings) = opened {
+                        self.app_settings.game_settings = settings;
+                        self.state = State::MultiPlayerServerHost;
+                    } else {
+                        ui.label(&format!("Waiting for new Lobby to open",));
+                        if ui.button("Cancel ").clicked() {
+                            self.state = State::Initial;
+                        }
+                        // keep polling, the answer does not cause a repaint
+                        ctx.request_repaint();
+                    }
+                }
+                State::MpJoiningLobby => {
+                    match self.connection.as_ref().unwrap().join_status() {
+                        Some(Ok(())) => self.state = State::MultiPlayerServerGuest,
+                        Some(Err(reason)) => {
+                            self.toast = Some(format!("Could not join: {reason}"));
+                            self.state = State::MultiPlayerServerView;
+                        }
+                        None => {
+                            ui.label("Joining Game");
+                            // keep polling, the answer does not cause a repaint
+                            ctx.request_repaint();
+                        }
+                    }
+                }
+                State::MultiPlayerServerGuest => self.update_multiplayer_guest(ui),
+                State::MultiPlayerServerHost => self.update_multiplayer_host(ui),
+            }
+        });
+        if !frame.is_web() {
+            egui::gui_zoom::zoom_with_keyboard_shortcuts(ctx, frame.info().native_pixels_per_point);
+        }
+    }
+}
+
+/// Create an image from byte slice
+///
+/// `image_data` the image bytes (e.g. a Bitmap)
+/// `transparent` turn all pixels with the same color as the top left corenr transparent
+fn load_image_from_memory(image_data: &[u8], transparent: bool) -> egui::ColorImage {
+    let image = image::load_from_memory(image_data).expect("resources can be loaded");
+    let size = [image.width() as _, image.height() as _];
+    let mut image_buffer = image.to_rgba8();
+    let top_left = image_buffer[(0, 0)];
+    if transparent {
+        for pixel in image_buffer.pixels_mut() {
+            if *pixel == top_left {
+                pixel[3] = 0;
+            }
+        }
+    }
+    let pixels = image_buffer.as_flat_samples();
+    egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice())
+}
+
+/// Name of the texture that draws `cell`, from the colorblind tile set if requested
+fn cell_texture_key(cell: &Cell, colorblind: bool) -> String {
+    if colorblind {
+        format!("colorblind_cell_{}", cell.name())
+    } else {
+        format!("cell_{}", cell.name())
+    }
+}
+
+/// Load all tiles as textures, and keep their pixels to draw the static layer from
+fn load_tiles(
+    ctx: &egui::Context,
+) -> (
+    HashMap<&'static str, TextureHandle>,
+    HashMap<&'static str, egui::ColorImage>,
+) {
     let mut map = HashMap::new();
+    let mut images = HashMap::new();
 
     macro_rules! load {
-        ($x:expr, $t:expr) => {
+        ($x:expr, $t:expr) => {{
+            let image =
+                load_image_from_memory(include_bytes!(concat!("../../images/", $x, ".bmp")), $t);
+            images.insert($x, image.clone());
             map.insert(
                 $x,
-                ctx.load_texture(
-                    $x,
-                    load_image_from_memory(
-                        include_bytes!(concat!("../../images/", $x, ".bmp")),
-                        $t,
-                    ),
-                    egui::TextureOptions::default(),
-                ),
+                ctx.load_texture($x, image, egui::TextureOptions::default()),
             )
-        };
+        }};
     }
 
     load!("cell_bomb", false);
@@ -717,10 +2501,47 @@ fn load_tiles(ctx: &egui::Context) -> HashMap<&'static str, TextureHandle> {
     load!("cell_upgrade_speed", false);
     load!("cell_upgrade_bomb", false);
     load!("cell_upgrade_power", false);
+    load!("cell_upgrade_remote", false);
+    load!("cell_upgrade_kick", false);
+    load!("cell_upgrade_pierce", false);
     load!("cell_wall", false);
     load!("cell_wood", false);
     load!("cell_wood_burning", false);
 
+    macro_rules! load_colorblind {
+        ($x:expr) => {{
+            let image = load_image_from_memory(
+                include_bytes!(concat!("../../images/colorblind/", $x, ".bmp")),
+                false,
+            );
+            images.insert(concat!("colorblind_", $x), image.clone());
+            map.insert(
+                concat!("colorblind_", $x),
+                ctx.load_texture(
+                    concat!("colorblind_", $x),
+                    image,
+                    egui::TextureOptions::default(),
+                ),
+            )
+        }};
+    }
+
+    load_colorblind!("cell_bomb");
+    load_colorblind!("cell_empty");
+    load_colorblind!("cell_fire");
+    load_colorblind!("cell_start_point");
+    load_colorblind!("cell_teleport");
+    load_colorblind!("cell_tomb_stone");
+    load_colorblind!("cell_upgrade_speed");
+    load_colorblind!("cell_upgrade_bomb");
+    load_colorblind!("cell_upgrade_power");
+    load_colorblind!("cell_upgrade_remote");
+    load_colorblind!("cell_upgrade_kick");
+    load_colorblind!("cell_upgrade_pierce");
+    load_colorblind!("cell_wall");
+    load_colorblind!("cell_wood");
+    load_colorblind!("cell_wood_burning");
+
     load!("hans_placing", true);
     load!("hans_placing2", true);
     load!("hans_standing", true);
@@ -742,5 +2563,660 @@ fn load_tiles(ctx: &egui::Context) -> HashMap<&'static str, TextureHandle> {
             egui::TextureOptions::default(),
         ),
     );
-    map
+    (map, images)
+}
+
+/// Choose between a free camera and following a player
+fn update_camera_target(ui: &mut egui::Ui, camera: &mut Camera, game_static: &GameStatic) {
+    let name = |follow: Option<PlayerId>| {
+        follow
+            .and_then(|id| game_static.players.get(&id))
+            .map_or("Free", |player| player.name.as_str())
+            .to_owned()
+    };
+    egui::ComboBox::from_label("Camera")
+        .selected_text(name(camera.follow))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut camera.follow, None, name(None));
+            for id in game_static.players.keys() {
+                ui.selectable_value(&mut camera.follow, Some(*id), name(Some(*id)));
+            }
+        })
+        .response
+        .on_hover_text("Follow a player when zoomed in, or look around freely by dragging with the middle mouse button");
+}
+
+/// Kills, deaths and upgrades of all players, the local player's speed in Cells/s
+fn update_scoreboard(ui: &mut egui::Ui, game: &mut Game) {
+    let game_static = game.stat().clone();
+    let local_state = game.local_state();
+    let local_speed = local_state.player_states[game_static.local_player].speed;
+    ui.label(format!(
+        "Your Speed: {:.2} Cells/s",
+        game_static
+            .settings
+            .walk_speed_cells_per_second(local_speed)
+    ));
+    egui::Grid::new("scoreboard").striped(true).show(ui, |ui| {
+        for heading in ["Player", "Kills", "Deaths", "Speed", "Bombs", "Power"] {
+            ui.strong(heading);
+        }
+        if game_static.settings.capped_upgrades_score {
+            ui.strong("Points")
+                .on_hover_text("Upgrades eaten at their cap");
+        }
+        ui.end_row();
+        for (id, player_state) in local_state.player_states.iter() {
+            let name = &game_static.players[&id].name;
+            if id == game_static.local_player {
+                ui.strong(name);
+            } else {
+                ui.label(name);
+            }
+            let is_local = id == game_static.local_player;
+            for cell in scoreboard_row(&game_static.settings, player_state, is_local) {
+                ui.label(cell);
+            }
+            ui.end_row();
+        }
+    });
+}
+
+/// An upgrade's scoreboard `text`, marked once the upgrade reached its cap
+fn upgrade_text(text: String, upgrades: u32, cap: Option<u32>) -> String {
+    if cap.is_some_and(|cap| upgrades >= cap) {
+        format!("{text} (max)")
+    } else {
+        text
+    }
+}
+
+/// The scoreboard cells after the player's name.
+///
+/// With `hide_opponent_upgrades`, only the local player's upgrades are shown, the opponents' are
+/// "?"
+fn scoreboard_row(settings: &Settings, player_state: &PlayerState, is_local: bool) -> Vec<String> {
+    let mut row = vec![
+        player_state.kills.to_string(),
+        player_state.deaths.to_string(),
+    ];
+    if settings.hide_opponent_upgrades && !is_local {
+        row.extend(["?", "?", "?"].map(str::to_owned));
+    } else {
+        row.push(upgrade_text(
+            format!(
+                "{:.2} Cells/s",
+                settings.walk_speed_cells_per_second(player_state.speed)
+            ),
+            player_state.speed,
+            settings.upgrade_cap(Upgrade::Speed),
+        ));
+        row.push(upgrade_text(
+            player_state.bombs.to_string(),
+            player_state.bombs,
+            settings.upgrade_cap(Upgrade::Bombs),
+        ));
+        row.push(upgrade_text(
+            player_state.power.to_string(),
+            player_state.power,
+            settings.upgrade_cap(Upgrade::Power),
+        ));
+    }
+    if settings.capped_upgrades_score {
+        row.push(player_state.upgrade_points.to_string());
+    }
+    row
+}
+
+/// The countdown shown over the field at the start of a game, "GO!" for half a second after it
+fn countdown_text(ticks: u32, start_delay_ticks: u32) -> Option<String> {
+    if ticks < start_delay_ticks {
+        let remaining = start_delay_ticks - ticks;
+        Some(remaining.div_ceil(TICKS_PER_SECOND).to_string())
+    } else if start_delay_ticks > 0 && ticks < start_delay_ticks + TICKS_PER_SECOND / 2 {
+        Some("GO!".to_owned())
+    } else {
+        None
+    }
+}
+
+fn death_penalty_name(penalty: DeathPenalty) -> &'static str {
+    match penalty {
+        DeathPenalty::ResetToBase => "Lose All Upgrades",
+        DeathPenalty::HalveUpgrades => "Lose Half the Upgrades",
+        DeathPenalty::KeepAll => "Keep All Upgrades",
+    }
+}
+
+/// The death penalty of the practice checkbox, unchecking it returns to the default
+fn practice_death_penalty(keep_upgrades: bool) -> DeathPenalty {
+    if keep_upgrades {
+        DeathPenalty::KeepAll
+    } else {
+        DeathPenalty::default()
+    }
+}
+
+/// How long inputs take to reach the server with a round trip time of `ping`
+fn input_delay_text(ping: std::time::Duration) -> String {
+    let lead = MultiPlayerGame::prediction_lead_ticks(ping);
+    let delay = bomberhans_lib::utils::TIME_PER_TICK * lead;
+    format!(
+        "input delay: {} ms ({lead} ticks, ping {} ms)",
+        delay.as_millis(),
+        ping.as_millis()
+    )
+}
+
+/// How many players the game waits for before it can start
+fn players_not_ready(players: &[(String, bool)]) -> usize {
+    players.iter().filter(|(_, ready)| !ready).count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_only_settings_never_change() {
+        let old = Settings::default();
+        let mut new = Settings::default();
+        new.width += 2;
+
+        assert_eq!(
+            changed_settings(&old, new.clone(), ReadOnly::ReadWrite),
+            Some(new.clone())
+        );
+        assert_eq!(changed_settings(&old, new, ReadOnly::ReadOnly), None);
+        assert_eq!(
+            changed_settings(&old, old.clone(), ReadOnly::ReadWrite),
+            None
+        );
+    }
+
+    #[test]
+    fn test_shockwaves_grow_with_power() {
+        assert_eq!(shockwave_size(1, 0.0), (0.5, 0.05));
+        assert_eq!(shockwave_size(1, 1.0), (1.5, 0.05));
+        assert_eq!(shockwave_size(4, 0.5), (2.5, 0.2));
+        assert_eq!(shockwave_size(4, 2.0), shockwave_size(4, 1.0));
+        // very strong bombs do not cover the field in a thick ring
+        assert_eq!(shockwave_size(20, 1.0), (20.5, 0.4));
+        for power in 1..10 {
+            let (radius, width) = shockwave_size(power, 1.0);
+            let (bigger_radius, bigger_width) = shockwave_size(power + 1, 1.0);
+            assert!(radius < bigger_radius);
+            assert!(width <= bigger_width);
+        }
+    }
+
+    #[test]
+    fn test_recent_servers_are_unique_and_limited() {
+        let mut app_settings = AppSettings::default();
+        app_settings.remember_server("[::1]:4267".to_owned());
+        app_settings.remember_server("127.0.0.1:4267".to_owned());
+        assert_eq!(
+            app_settings.recent_servers,
+            ["127.0.0.1:4267", "[::1]:4267"]
+        );
+
+        app_settings.remember_server("[::1]:4267".to_owned());
+        assert_eq!(
+            app_settings.recent_servers,
+            ["[::1]:4267", "127.0.0.1:4267"],
+            "reconnecting moves the server to the front"
+        );
+
+        for port in 1..=AppSettings::RECENT_SERVERS {
+            app_settings.remember_server(format!("[::1]:{port}"));
+        }
+        assert_eq!(
+            app_settings.recent_servers.len(),
+            AppSettings::RECENT_SERVERS
+        );
+        assert_eq!(app_settings.recent_servers[0], "[::1]:5");
+        assert!(!app_settings
+            .recent_servers
+            .contains(&"[::1]:4267".to_owned()));
+    }
+
+    #[test]
+    fn test_direction_stack_diagonal() {
+        let mut stack = DirectionStack::new();
+        stack.push(Direction::North);
+        assert_eq!(stack.get_diagonal(), None);
+        stack.push(Direction::South);
+        assert_eq!(stack.get_diagonal(), None, "opposite is not diagonal");
+        stack.push(Direction::East);
+        assert_eq!(stack.get(), Some(Direction::East));
+        assert_eq!(stack.get_diagonal(), Some(Direction::South));
+        stack.remove(Direction::South);
+        assert_eq!(stack.get_diagonal(), Some(Direction::North));
+    }
+
+    #[test]
+    fn test_every_cell_has_a_texture_in_both_palettes() {
+        let (textures, _) = load_tiles(&egui::Context::default());
+        for chr in "_BFDspbrkxTO#+W".chars() {
+            let cell = Cell::from_char(chr).unwrap();
+            for colorblind in [false, true] {
+                let key = cell_texture_key(&cell, colorblind);
+                assert!(textures.contains_key(key.as_str()), "missing {key}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_scales_to_fit_the_window() {
+        // 15 x 11 field, 17 x 13 cells with the border
+        assert_eq!(pixel_per_cell(egui::vec2(340.0, 260.0), 15, 11), 20.0);
+        assert_eq!(pixel_per_cell(egui::vec2(680.0, 520.0), 15, 11), 40.0);
+        assert_eq!(
+            pixel_per_cell(egui::vec2(1000.0, 520.0), 15, 11),
+            40.0,
+            "letterboxed left and right"
+        );
+        assert_eq!(
+            pixel_per_cell(egui::vec2(680.0, 1000.0), 15, 11),
+            40.0,
+            "letterboxed above and below"
+        );
+        assert_eq!(
+            pixel_per_cell(egui::vec2(17.0, 13.0), 15, 11),
+            MIN_PIXEL_PER_CELL,
+            "does not shrink below the minimum"
+        );
+
+        let scale = pixel_per_cell(egui::vec2(680.0, 520.0), 15, 11);
+        assert_eq!(
+            cell_rect(CellPosition::new(14, 10), Pos2::ZERO, scale).max,
+            pos2(640.0, 480.0),
+            "the last cell ends before the border"
+        );
+    }
+
+    #[test]
+    fn test_camera_zoom_and_pan_are_clamped() {
+        let mut camera = Camera::default();
+        camera.zoom_by(0.5);
+        assert_eq!(
+            camera.zoom, MIN_ZOOM,
+            "can not zoom out further than the whole board"
+        );
+        camera.zoom_by(100.0);
+        assert_eq!(camera.zoom, MAX_ZOOM);
+
+        let board = egui::vec2(400.0, 200.0);
+        camera.pan_by(egui::vec2(150.0, -50.0), board);
+        assert_eq!(camera.pan, egui::vec2(150.0, -50.0));
+        camera.pan_by(egui::vec2(150.0, -150.0), board);
+        assert_eq!(
+            camera.pan,
+            egui::vec2(200.0, -100.0),
+            "the view's center stays on the board"
+        );
+
+        camera.zoom_by(0.5);
+        assert_eq!(camera.zoom, MAX_ZOOM / 2.0);
+        assert_eq!(
+            camera.pan,
+            egui::vec2(100.0, -50.0),
+            "zooming keeps the same spot in the center"
+        );
+    }
+
+    #[test]
+    fn test_free_camera_stays_on_a_board_larger_than_the_view() {
+        let cells = egui::vec2(32.0, 22.0);
+        let scale = pixel_per_cell(egui::vec2(640.0, 440.0), 30, 20) * MAX_ZOOM;
+        let board = cells * scale;
+        assert_eq!(board, egui::vec2(2560.0, 1760.0), "4 times the view");
+
+        let mut camera = Camera {
+            follow: Some(PlayerId(0)),
+            ..Camera::default()
+        };
+        camera.zoom_by(MAX_ZOOM);
+        let target = offset_from_board_center(Position::new(0, 0), cells, scale);
+        camera.center_on(target, board);
+        assert_eq!(
+            camera.pan + target,
+            egui::Vec2::ZERO,
+            "the followed player is in the center"
+        );
+
+        camera.pan_by(egui::vec2(2000.0, -2000.0), board);
+        assert_eq!(camera.follow, None, "dragging frees the camera");
+        assert_eq!(
+            camera.pan,
+            egui::vec2(1280.0, -880.0),
+            "the view's center stays on the board"
+        );
+        camera.pan_by(egui::vec2(-5000.0, 5000.0), board);
+        assert_eq!(camera.pan, egui::vec2(-1280.0, 880.0));
+    }
+
+    #[test]
+    fn test_upgrade_text_marks_capped_upgrades() {
+        assert_eq!(upgrade_text("3".to_owned(), 3, None), "3");
+        assert_eq!(upgrade_text("2".to_owned(), 2, Some(3)), "2");
+        assert_eq!(upgrade_text("3".to_owned(), 3, Some(3)), "3 (max)");
+    }
+
+    #[test]
+    fn test_scoreboard_hides_opponent_upgrades() {
+        let player_state = PlayerState {
+            position: Position::new(0, 0),
+            deaths: 2,
+            kills: 1,
+            kill_streak: 0,
+            longest_kill_streak: 1,
+            bombs_placed: 4,
+            distance_walked: 0,
+            upgrades_collected: 3,
+            upgrade_points: 0,
+            power: 2,
+            speed: 1,
+            bombs: 3,
+            current_bombs_placed: 0,
+            has_remote: false,
+            has_kick: false,
+            has_pierce: false,
+            action: Action::idle(),
+        };
+        let mut settings = Settings {
+            max_bombs: 3,
+            ..Settings::default()
+        };
+        let speed = format!("{:.2} Cells/s", settings.walk_speed_cells_per_second(1));
+
+        assert_eq!(
+            scoreboard_row(&settings, &player_state, false),
+            ["1", "2", speed.as_str(), "3 (max)", "2"]
+        );
+
+        settings.hide_opponent_upgrades = true;
+        assert_eq!(
+            scoreboard_row(&settings, &player_state, true),
+            ["1", "2", speed.as_str(), "3 (max)", "2"],
+            "own upgrades are always shown"
+        );
+        assert_eq!(
+            scoreboard_row(&settings, &player_state, false),
+            ["1", "2", "?", "?", "?"]
+        );
+
+        settings.capped_upgrades_score = true;
+        assert_eq!(
+            scoreboard_row(&settings, &player_state, false),
+            ["1", "2", "?", "?", "?", "0"],
+            "points are a score, not an upgrade"
+        );
+    }
+
+    #[test]
+    fn test_countdown_text() {
+        let second = TICKS_PER_SECOND;
+        assert_eq!(countdown_text(0, 3 * second), Some("3".to_owned()));
+        assert_eq!(
+            countdown_text(2 * second + 1, 3 * second),
+            Some("1".to_owned())
+        );
+        assert_eq!(
+            countdown_text(3 * second, 3 * second),
+            Some("GO!".to_owned())
+        );
+        assert_eq!(countdown_text(4 * second, 3 * second), None);
+        assert_eq!(countdown_text(0, 0), None, "no countdown, no GO");
+    }
+
+    #[test]
+    fn test_practice_checkbox_maps_to_death_penalty() {
+        assert_eq!(practice_death_penalty(true), DeathPenalty::KeepAll);
+        assert_eq!(practice_death_penalty(false), DeathPenalty::HalveUpgrades);
+        assert_eq!(
+            practice_death_penalty(false),
+            Settings::default().death_penalty
+        );
+    }
+
+    #[test]
+    fn test_input_delay_text() {
+        let ms = std::time::Duration::from_millis;
+        assert_eq!(
+            input_delay_text(ms(0)),
+            "input delay: 0 ms (0 ticks, ping 0 ms)"
+        );
+        assert_eq!(
+            input_delay_text(ms(35)),
+            "input delay: 20 ms (1 ticks, ping 35 ms)"
+        );
+        assert_eq!(
+            input_delay_text(ms(100)),
+            "input delay: 60 ms (3 ticks, ping 100 ms)",
+            "a started tick counts"
+        );
+    }
+
+    #[test]
+    fn test_cell_label_matches_log_format() {
+        let pos = CellPosition::new(3, 5);
+        assert_eq!(cell_label(pos), "(03/05)");
+        assert_eq!(cell_label(pos), format!("{pos:?}"));
+        assert_eq!(cell_label(CellPosition::new(12, 0)), "(12/00)");
+    }
+
+    #[test]
+    fn test_layers_are_drawn_in_order() {
+        let circle = |x| Shape::circle_filled(pos2(x, 0.0), 1.0, Color32::WHITE);
+        let shapes = layered(vec![
+            (Layer::Hud, circle(5.0)),
+            (Layer::Players, circle(2.0)),
+            (Layer::Names, circle(4.0)),
+            (Layer::Field, circle(0.0)),
+            (Layer::Players, circle(3.0)),
+            (Layer::Field, circle(1.0)),
+        ]);
+        let order: Vec<f32> = shapes
+            .iter()
+            .map(|shape| match shape {
+                Shape::Circle(circle) => circle.center.x,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(order, [0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+
+        let player = |y| PlayerState {
+            position: Position::new(0, y),
+            deaths: 0,
+            kills: 0,
+            kill_streak: 0,
+            longest_kill_streak: 0,
+            bombs_placed: 0,
+            distance_walked: 0,
+            upgrades_collected: 0,
+            upgrade_points: 0,
+            power: 1,
+            speed: 1,
+            bombs: 1,
+            current_bombs_placed: 0,
+            has_remote: false,
+            has_kick: false,
+            has_pierce: false,
+            action: Action::idle(),
+        };
+        let players: PlayerStates = [
+            (PlayerId(0), player(300)),
+            (PlayerId(1), player(100)),
+            (PlayerId(2), player(300)),
+            (PlayerId(3), player(200)),
+        ]
+        .into_iter()
+        .collect();
+        let order: Vec<PlayerId> = player_draw_order(&players)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(
+            order,
+            [PlayerId(1), PlayerId(3), PlayerId(0), PlayerId(2)],
+            "lower players are drawn on top"
+        );
+    }
+
+    #[test]
+    fn test_only_dynamic_cells_are_drawn_each_frame() {
+        let textures = Rc::new(TextureManager::new(&egui::Context::default(), false));
+        let mut field = Field::new(17, 13);
+        let background = textures.get_texture("background");
+        assert_eq!(
+            field_shapes(&textures, &field, background, Pos2::ZERO, 42.0).len(),
+            1,
+            "a new field is only the static layer"
+        );
+
+        field[CellPosition::new(0, 1)] = Cell::from_char('B').unwrap();
+        field[CellPosition::new(1, 0)] = Cell::from_char('F').unwrap();
+        field[CellPosition::new(2, 0)] = Cell::Teleport;
+        assert_eq!(
+            field_shapes(&textures, &field, background, Pos2::ZERO, 42.0).len(),
+            4
+        );
+
+        let image = textures.composite_static_layer(&field);
+        assert_eq!(
+            image.size,
+            [
+                19 * STATIC_LAYER_PIXELS_PER_CELL,
+                15 * STATIC_LAYER_PIXELS_PER_CELL
+            ]
+        );
+        assert_eq!(
+            image.pixels[0], textures.images["cell_wall"].pixels[0],
+            "the border is drawn"
+        );
+    }
+
+    #[test]
+    fn test_atlas_has_every_cell() {
+        let (_, images) = load_tiles(&egui::Context::default());
+        let (atlas, uvs) = pack_atlas(&images);
+        let [width, height] = atlas.size;
+        for chr in "_BFDspbrkxTO#+W".chars() {
+            let cell = Cell::from_char(chr).unwrap();
+            for colorblind in [false, true] {
+                let key = cell_texture_key(&cell, colorblind);
+                let uv = uvs[key.as_str()];
+                let tile = &images[key.as_str()];
+                let left = (uv.min.x * width as f32).round() as usize;
+                let top = (uv.min.y * height as f32).round() as usize;
+                assert_eq!(
+                    (uv.width() * width as f32).round() as usize,
+                    tile.size[0],
+                    "{key}"
+                );
+                assert_eq!(
+                    (uv.height() * height as f32).round() as usize,
+                    tile.size[1],
+                    "{key}"
+                );
+                let last = tile.size[0] - 1;
+                assert_eq!(atlas.pixels[top * width + left], tile.pixels[0], "{key}");
+                assert_eq!(
+                    atlas.pixels[top * width + left + last],
+                    tile.pixels[last],
+                    "{key}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_keyboard_bindings_do_not_overlap() {
+        let keys = |b: KeyBindings| [b.north, b.south, b.west, b.east, b.place];
+        let second = keys(KeyBindings::ARROWS);
+        for key in keys(KeyBindings::WASD) {
+            assert!(!second.contains(&key), "{key:?} is used by both players");
+        }
+    }
+
+    fn held(directions: &[Direction]) -> DirectionStack {
+        let mut stack = DirectionStack::new();
+        for d in directions {
+            stack.push(*d);
+        }
+        stack
+    }
+
+    #[test]
+    fn test_movement_policy_last_pressed() {
+        let policy = MovementPolicy::LastPressed;
+        let stack = held(&[Direction::North, Direction::South]);
+        assert_eq!(stack.resolve(policy), (Some(Direction::South), None));
+        let stack = held(&[Direction::North, Direction::South, Direction::East]);
+        assert_eq!(
+            stack.resolve(policy),
+            (Some(Direction::East), Some(Direction::South))
+        );
+    }
+
+    #[test]
+    fn test_movement_policy_cancel_opposites() {
+        let policy = MovementPolicy::CancelOpposites;
+        let stack = held(&[Direction::North, Direction::South]);
+        assert_eq!(stack.resolve(policy), (None, None));
+        let stack = held(&[Direction::East, Direction::North, Direction::South]);
+        assert_eq!(stack.resolve(policy), (Some(Direction::East), None));
+        let stack = held(&[Direction::North, Direction::East]);
+        assert_eq!(stack.resolve(policy), (Some(Direction::East), None));
+    }
+
+    #[test]
+    fn test_movement_policy_combine() {
+        let policy = MovementPolicy::Combine;
+        let stack = held(&[Direction::West, Direction::East]);
+        assert_eq!(stack.resolve(policy), (None, None));
+        let stack = held(&[Direction::North, Direction::East]);
+        assert_eq!(
+            stack.resolve(policy),
+            (Some(Direction::East), Some(Direction::North))
+        );
+        let stack = held(&[
+            Direction::North,
+            Direction::West,
+            Direction::South,
+            Direction::East,
+        ]);
+        assert_eq!(stack.resolve(policy), (None, None));
+        let stack = held(&[Direction::North, Direction::West, Direction::East]);
+        assert_eq!(stack.resolve(policy), (Some(Direction::North), None));
+    }
+
+    #[test]
+    fn test_local_game_uses_the_single_player_settings() {
+        let app_settings = AppSettings {
+            game_settings: Settings {
+                players: 3,
+                ..Settings::default()
+            },
+            split_keyboard: true,
+            ..AppSettings::default()
+        };
+        let game = app_settings.local_game();
+        assert_eq!(*game.settings(), app_settings.game_settings);
+        assert_eq!(game.stat().players.len(), 3);
+        assert!(game.is_split_keyboard());
+    }
+
+    #[test]
+    fn test_players_not_ready() {
+        assert_eq!(players_not_ready(&[]), 0);
+        let players = [
+            ("Hans".to_owned(), true),
+            ("Grete".to_owned(), false),
+            ("Fritz".to_owned(), false),
+        ];
+        assert_eq!(players_not_ready(&players), 2);
+        assert_eq!(players_not_ready(&players[..1]), 0);
+    }
 }
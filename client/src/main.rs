@@ -1,6 +1,7 @@
 use std::io::Write;
 use std::time::Instant;
 
+mod bug_report;
 mod connection;
 mod game;
 mod gui;
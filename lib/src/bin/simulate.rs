@@ -0,0 +1,111 @@
+//! Run a scripted game without GUI, see `bomberhans_lib::simulation` for the script format
+//!
+//! Usage: `bomberhans-simulate <script> [--settings <shared settings>] [--seed <seed>] [--spec <game spec>] [--ticks <ticks>] [--quiet] [--compare <shared settings>] [--heatmap <player>]`
+//!
+//! `--spec` replays a game with the settings and players that the server logged at its start.
+//! Prints the field after every tick (unless `--quiet`) and the final checksum.
+//!
+//! `--compare` replays the script a second time with other settings and prints the first tick
+//! at which the two games differ instead.
+//!
+//! `--heatmap` prints how long the player spent in each cell at the end, see
+//! `Heatmap::string_grid`.
+
+use std::error::Error;
+
+use bomberhans_lib::settings::Settings;
+use bomberhans_lib::simulation;
+use bomberhans_lib::simulation::GameSpec;
+use bomberhans_lib::simulation::Heatmap;
+use bomberhans_lib::simulation::Replay;
+use bomberhans_lib::utils::PlayerId;
+use bomberhans_lib::utils::TICKS_PER_SECOND;
+
+fn simulate() -> Result<(), Box<dyn Error>> {
+    let mut script_file = None;
+    let mut settings = Settings::default();
+    let mut spec = None;
+    let mut ticks = None;
+    let mut quiet = false;
+    let mut compare = None;
+    let mut heatmap_player = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--settings" => settings = Settings::from_shareable_string(&value()?)?,
+            "--seed" => settings.map_seed = Some(Settings::parse_map_seed(&value()?)?),
+            "--spec" => spec = Some(GameSpec::from_shareable_string(&value()?)?),
+            "--ticks" => ticks = Some(value()?.parse()?),
+            "--quiet" => quiet = true,
+            "--compare" => compare = Some(Settings::from_shareable_string(&value()?)?),
+            "--heatmap" => heatmap_player = Some(PlayerId(value()?.parse()?)),
+            _ if script_file.is_none() => script_file = Some(arg),
+            _ => return Err(format!("unexpected argument {arg}").into()),
+        }
+    }
+    let script_file = script_file.ok_or("no script given")?;
+
+    let script = simulation::parse_script(&std::fs::read_to_string(script_file)?)?;
+    // by default, run until a second after the last action
+    let ticks = ticks.unwrap_or_else(|| {
+        script
+            .last()
+            .map_or(0, |action| action.time.ticks_from_start())
+            + TICKS_PER_SECOND
+    });
+
+    let spec = match spec {
+        Some(spec) => spec,
+        None => GameSpec::new(&simulation::new_game(settings)?.game),
+    };
+
+    if let Some(other_settings) = compare {
+        let other = Replay {
+            spec: GameSpec {
+                settings: other_settings,
+                ..spec.clone()
+            },
+            script: script.clone(),
+        };
+        let replay = Replay { spec, script };
+        match simulation::diff_replays(&replay, &other, ticks)? {
+            Some(divergence) => {
+                println!("diverged at tick {}", divergence.tick);
+                for difference in divergence.differences {
+                    println!("  {difference}");
+                }
+            }
+            None => println!("no difference in {ticks} ticks"),
+        }
+        return Ok(());
+    }
+
+    let mut game_state = spec.game_state();
+    let mut heatmap = heatmap_player.map(|player| (player, Heatmap::new(&game_state.field)));
+    simulation::run(&mut game_state, &script, ticks, |game_state| {
+        if !quiet {
+            println!("{:?}\n{}", game_state.time, game_state.render_ascii());
+        }
+        if let Some((player, heatmap)) = &mut heatmap {
+            heatmap.observe(game_state, *player);
+        }
+    })?;
+    if let Some((player, heatmap)) = heatmap {
+        println!("heatmap of {player:?}\n{}", heatmap.string_grid());
+    }
+    println!("checksum {:08x}", game_state.checksum());
+
+    Ok(())
+}
+
+fn main() {
+    match simulate() {
+        Ok(()) => {}
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    }
+}
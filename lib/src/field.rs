@@ -2,8 +2,10 @@ use core::fmt;
 use std::ops::Index;
 use std::ops::IndexMut;
 
+use crate::settings::Ratios;
 use crate::settings::Settings;
 use crate::utils::CellPosition;
+use crate::utils::Direction;
 use crate::utils::PlayerId;
 use crate::utils::TimeStamp;
 
@@ -12,6 +14,15 @@ pub enum Upgrade {
     Speed,
     Power,
     Bombs,
+
+    /// Bombs wait for their owner to detonate them
+    Remote,
+
+    /// Walking into bombs kicks them away
+    Kick,
+
+    /// Explosions go on past the wood they ignite
+    Pierce,
 }
 
 impl fmt::Debug for Upgrade {
@@ -20,6 +31,9 @@ impl fmt::Debug for Upgrade {
             Upgrade::Speed => write!(f, "👟"),
             Upgrade::Power => write!(f, "💪"),
             Upgrade::Bombs => write!(f, "💣"),
+            Upgrade::Remote => write!(f, "📡"),
+            Upgrade::Kick => write!(f, "👢"),
+            Upgrade::Pierce => write!(f, "🗡"),
         }
     }
 }
@@ -32,10 +46,19 @@ pub enum Cell {
         owner: PlayerId,
         power: u32,
         expire: TimeStamp,
+
+        /// Where the bomb slides after being kicked, one cell per update
+        sliding: Option<Direction>,
+
+        /// The explosion goes on past wood it ignites
+        pierce: bool,
     },
     Fire {
         owner: PlayerId,
         expire: TimeStamp,
+
+        /// Power of the explosion that started here, 0 where the fire only spread to
+        power: u32,
     },
     TombStone(PlayerId),
     Upgrade(Upgrade),
@@ -60,6 +83,9 @@ impl Cell {
                 Upgrade::Speed => 's',
                 Upgrade::Power => 'p',
                 Upgrade::Bombs => 'b',
+                Upgrade::Remote => 'r',
+                Upgrade::Kick => 'k',
+                Upgrade::Pierce => 'x',
             },
             Cell::Teleport => 'T',
             Cell::StartPoint => 'O',
@@ -80,12 +106,21 @@ impl Cell {
                 owner,
                 power,
                 expire,
+                sliding: None,
+                pierce: false,
+            },
+            'F' => Cell::Fire {
+                owner,
+                expire,
+                power: 0,
             },
-            'F' => Cell::Fire { owner, expire },
             'D' => Cell::TombStone(owner),
             's' => Cell::Upgrade(Upgrade::Speed),
             'p' => Cell::Upgrade(Upgrade::Power),
             'b' => Cell::Upgrade(Upgrade::Bombs),
+            'r' => Cell::Upgrade(Upgrade::Remote),
+            'k' => Cell::Upgrade(Upgrade::Kick),
+            'x' => Cell::Upgrade(Upgrade::Pierce),
             'T' => Cell::Teleport,
             'O' => Cell::StartPoint,
             '#' => Cell::Wall,
@@ -106,6 +141,9 @@ impl Cell {
                 Upgrade::Speed => "upgrade_speed",
                 Upgrade::Power => "upgrade_power",
                 Upgrade::Bombs => "upgrade_bomb",
+                Upgrade::Remote => "upgrade_remote",
+                Upgrade::Kick => "upgrade_kick",
+                Upgrade::Pierce => "upgrade_pierce",
             },
             Cell::Teleport => "teleport",
             Cell::StartPoint => "start_point",
@@ -137,19 +175,37 @@ pub struct Field {
 }
 
 impl Field {
+    /// The classic field
     pub fn new(width: u32, height: u32) -> Self {
+        Self::generate(width, height, None)
+    }
+
+    /// A field where `seed` clears some of the wood
+    ///
+    /// The same seed always generates the same field. Like the classic field, it is
+    /// symmetric so no start point has an advantage.
+    pub fn new_seeded(width: u32, height: u32, seed: u64) -> Self {
+        Self::generate(width, height, Some(seed))
+    }
+
+    /// The classic field, with some wood cleared if there is a `seed`
+    fn generate(width: u32, height: u32, seed: Option<u64>) -> Self {
         let cells: Vec<Cell> = (0..height)
             .flat_map(|y| {
                 (0..width).map(move |x| {
-                    let x = if x >= width / 2 { width - x - 1 } else { x };
-                    let y = if y >= height / 2 { height - y - 1 } else { y };
+                    let (x, middle_x) = mirror(x, width);
+                    let (y, middle_y) = mirror(y, height);
 
                     if x == 0 && y == 0 {
                         Cell::StartPoint
                     } else if x + y == 1 {
                         Cell::Empty
-                    } else if (x % 2) == 1 && (y % 2) == 1 {
+                    } else if (x % 2) == 1 && (y % 2) == 1 && !middle_x && !middle_y {
+                        // GAME_RULE: the two middle lanes of an even side have no walls, the
+                        // mirror would put them right next to each other
                         Cell::Wall
+                    } else if seed.is_some_and(|seed| seeded_hash(seed, x, y).is_multiple_of(8)) {
+                        Cell::Empty
                     } else {
                         Cell::Wood
                     }
@@ -164,8 +220,65 @@ impl Field {
         }
     }
 
+    /// The field of a game with `settings`, its custom map or a generated one
+    ///
+    /// A custom map that does not parse, which `Settings::validate` rejects, falls back to the
+    /// generated field.
     pub fn new_from_rules(settings: &Settings) -> Self {
-        Self::new(settings.width, settings.height)
+        if let Some(Ok(field)) = settings
+            .custom_map
+            .as_deref()
+            .map(Self::new_from_string_grid)
+        {
+            return field;
+        }
+        Self::generate(settings.width, settings.height, settings.map_seed)
+            .with_burnt_wood(
+                settings.burnt_wood_percent,
+                &settings.ratios,
+                settings.map_seed.unwrap_or_default(),
+            )
+            .with_start_points(settings.players)
+    }
+
+    /// Turn about `percent` of the wood into what burnt wood turns into with `ratios`
+    ///
+    /// Like `new_seeded`, the same seed always burns the same wood and the field stays
+    /// symmetric.
+    pub fn with_burnt_wood(mut self, percent: u32, ratios: &Ratios, seed: u64) -> Self {
+        for cell in self.iter_indices() {
+            let (x, _) = mirror(cell.x as u32, self.width);
+            let (y, _) = mirror(cell.y as u32, self.height);
+            // independent of the wood that `seed` cleared
+            let hash = seeded_hash(seed ^ u64::from(u32::MAX), x, y);
+            if self[cell] == Cell::Wood && hash % 100 < percent {
+                self[cell] = ratios.random(hash / 100);
+            }
+        }
+        self
+    }
+
+    /// Add start points in the middle of the sides until there is one for each of `players`
+    ///
+    /// The corners seat 4 players. The top and bottom side come before left and right, so 6
+    /// players still start symmetrically. The cells next to a side start point along the side
+    /// are cleared, so its player can always leave it.
+    pub fn with_start_points(mut self, players: u32) -> Self {
+        let (w, h) = (self.width as i32, self.height as i32);
+        let (middle_x, middle_y) = ((w - 1) / 2, (h - 1) / 2);
+        let sides = [
+            (CellPosition::new(middle_x, 0), Direction::East),
+            (CellPosition::new(middle_x, h - 1), Direction::East),
+            (CellPosition::new(0, middle_y), Direction::South),
+            (CellPosition::new(w - 1, middle_y), Direction::South),
+        ];
+        let extra = (players as usize).saturating_sub(4);
+        for (start, along_side) in sides.into_iter().take(extra) {
+            self[start] = Cell::StartPoint;
+            self[start.add(along_side, -1)] = Cell::Empty;
+            self[start.add(along_side, 1)] = Cell::Empty;
+        }
+        self
     }
 
     pub fn is_cell_in_field(&self, cell: CellPosition) -> bool {
@@ -244,8 +357,23 @@ impl Field {
             .flat_map(move |x| (-1..(height + 1) as i32).map(move |y| CellPosition::new(x, y)))
     }
 
+    /// Checksum of size and cells, to notice when server and client generate different fields
+    pub fn checksum(&self) -> u32 {
+        let mut x: u32 = 42;
+        for i in [self.width, self.height]
+            .into_iter()
+            .chain(self.cells.iter().map(|cell| u32::from(cell.to_char())))
+        {
+            x = x.wrapping_add(i).wrapping_mul(31);
+        }
+        x
+    }
+
+    /// The start points, corners first so that fewer players still start in the corners
     pub fn start_positions(&self) -> Vec<CellPosition> {
-        self.iter()
+        let (w, h) = (self.width as i32, self.height as i32);
+        let mut start_positions: Vec<CellPosition> = self
+            .iter()
             .filter_map(|(pos, cell)| {
                 if *cell == Cell::StartPoint {
                     Some(pos)
@@ -253,10 +381,38 @@ impl Field {
                     None
                 }
             })
-            .collect()
+            .collect();
+        start_positions
+            .sort_by_key(|pos| !((pos.x == 0 || pos.x == w - 1) && (pos.y == 0 || pos.y == h - 1)));
+        start_positions
     }
 }
 
+/// Mirror a coordinate on a side of length `len` into its first half
+///
+/// Also returns whether it is one of the two middle lanes of an even side, which mirror onto
+/// each other.
+fn mirror(i: u32, len: u32) -> (u32, bool) {
+    let mirrored = if i >= len / 2 { len - i - 1 } else { i };
+    (mirrored, len.is_multiple_of(2) && mirrored == len / 2 - 1)
+}
+
+/// Well mixed hash of a cell for seeded fields
+///
+/// `utils::random`'s low bits follow the coordinates too closely, clearing whole diagonals.
+/// The high half of the seed only changes the hash of seeds that do not fit a `u32`.
+fn seeded_hash(seed: u64, x: u32, y: u32) -> u32 {
+    let seed = (seed as u32) ^ ((seed >> 32) as u32).wrapping_mul(0x27d4_eb2d);
+    let mut h = seed ^ x.wrapping_mul(0x9e37_79b9) ^ y.wrapping_mul(0x85eb_ca6b);
+    // murmur3 finalizer
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    h
+}
+
 impl Index<CellPosition> for Field {
     type Output = Cell;
 
@@ -332,6 +488,65 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_even_field_has_no_double_walls() {
+        let field = Field::new(8, 6);
+        assert_eq!(
+            field.string_grid(),
+            "
+                O_++++_O
+                _#++++#_
+                ++++++++
+                ++++++++
+                _#++++#_
+                O_++++_O
+            "
+            .trim_start()
+            .replace(' ', "")
+        );
+    }
+
+    #[test]
+    fn test_every_size_is_symmetric_with_clear_starts() {
+        let sizes = Settings::WIDTH_RANGE.flat_map(|w| Settings::HEIGHT_RANGE.map(move |h| (w, h)));
+        for (width, height) in sizes {
+            for seed in [0, 4267] {
+                let field = Field::new_seeded(width, height, seed);
+                let (w, h) = (width as i32, height as i32);
+                for cell in field.iter_indices() {
+                    let mirrored_x = CellPosition::new(w - cell.x - 1, cell.y);
+                    let mirrored_y = CellPosition::new(cell.x, h - cell.y - 1);
+                    assert_eq!(field[cell], field[mirrored_x], "{width}x{height} {cell:?}");
+                    assert_eq!(field[cell], field[mirrored_y], "{width}x{height} {cell:?}");
+
+                    if field[cell] == Cell::Wall {
+                        for next in [cell.add(Direction::East, 1), cell.add(Direction::South, 1)] {
+                            assert_ne!(
+                                field.is_cell_in_field(next).then(|| &field[next]),
+                                Some(&Cell::Wall),
+                                "{width}x{height} walls next to each other at {cell:?}"
+                            );
+                        }
+                    }
+                }
+
+                let corners = [(0, 0), (0, h - 1), (w - 1, 0), (w - 1, h - 1)]
+                    .map(|(x, y)| CellPosition::new(x, y));
+                assert_eq!(field.start_positions(), corners, "{width}x{height}");
+                for corner in corners {
+                    let dx = if corner.x == 0 { 1 } else { -1 };
+                    let dy = if corner.y == 0 { 1 } else { -1 };
+                    for lane in [
+                        CellPosition::new(corner.x + dx, corner.y),
+                        CellPosition::new(corner.x, corner.y + dy),
+                    ] {
+                        assert_eq!(field[lane], Cell::Empty, "{width}x{height} {lane:?}");
+                    }
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_field_from_string() {
         let expected = "
@@ -371,4 +586,147 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn test_start_points_for_more_players() {
+        let sizes = Settings::WIDTH_RANGE.flat_map(|w| Settings::HEIGHT_RANGE.map(move |h| (w, h)));
+        for (width, height) in sizes {
+            for players in 5..=8 {
+                let field = Field::new(width, height).with_start_points(players);
+                let start_positions = field.start_positions();
+                assert_eq!(
+                    start_positions.len(),
+                    players as usize,
+                    "{width}x{height} for {players}"
+                );
+                let distinct: std::collections::BTreeSet<_> =
+                    start_positions.iter().map(|p| (p.x, p.y)).collect();
+                assert_eq!(distinct.len(), start_positions.len());
+                assert_eq!(
+                    start_positions[..4],
+                    Field::new(width, height).start_positions(),
+                    "corners first"
+                );
+                for start in start_positions {
+                    let free = [
+                        Direction::North,
+                        Direction::West,
+                        Direction::South,
+                        Direction::East,
+                    ]
+                    .into_iter()
+                    .any(|direction| field[start.add(direction, 1)] == Cell::Empty);
+                    assert!(
+                        free,
+                        "{width}x{height} for {players}: {start:?} is walled in"
+                    );
+                }
+            }
+        }
+
+        let expected = "
+            O_+_O_+_O
+            _#+#+#+#_
+            +++++++++
+            _#+#+#+#_
+            O_+_O_+_O
+        "
+        .trim_start()
+        .replace(' ', "");
+        assert_eq!(
+            Field::new(9, 5).with_start_points(6).string_grid(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_checksum() {
+        let field = Field::new(11, 13);
+        assert_eq!(field.checksum(), Field::new(11, 13).checksum());
+        assert_ne!(field.checksum(), Field::new(13, 11).checksum());
+
+        let mut other = field.clone();
+        other.cells[1] = Cell::Wall;
+        assert_ne!(field.checksum(), other.checksum());
+    }
+
+    #[test]
+    fn test_burnt_wood_is_symmetric() {
+        let wood = |field: &Field| field.iter().filter(|(_, c)| **c == Cell::Wood).count();
+        let ratios = Ratios {
+            power: 1,
+            speed: 1,
+            bombs: 1,
+            remote: 1,
+            kick: 1,
+            pierce: 1,
+            clear: 1,
+            ..Ratios::NONE
+        };
+        let field = Field::new(25, 25);
+
+        let burnt = field.clone().with_burnt_wood(25, &ratios, 4267);
+        let (before, after) = (wood(&field), wood(&burnt));
+        let percent = (before - after) * 100 / before;
+        assert!((20..=30).contains(&percent), "{percent}% burnt");
+        assert!(burnt
+            .iter()
+            .any(|(_, cell)| matches!(cell, Cell::Upgrade(_))));
+        let (w, h) = (25, 25);
+        for cell in burnt.iter_indices() {
+            let mirrored_x = CellPosition::new(w - cell.x - 1, cell.y);
+            let mirrored_y = CellPosition::new(cell.x, h - cell.y - 1);
+            assert_eq!(burnt[cell], burnt[mirrored_x], "{cell:?}");
+            assert_eq!(burnt[cell], burnt[mirrored_y], "{cell:?}");
+        }
+        assert_eq!(burnt, field.clone().with_burnt_wood(25, &ratios, 4267));
+
+        assert_eq!(wood(&field.clone().with_burnt_wood(100, &ratios, 1)), 0);
+        assert_eq!(field.clone().with_burnt_wood(0, &ratios, 1), field);
+
+        let settings = Settings {
+            burnt_wood_percent: 25,
+            ..Settings::default()
+        };
+        assert_eq!(
+            Field::new_from_rules(&settings),
+            Field::new(settings.width, settings.height).with_burnt_wood(25, &settings.ratios, 0)
+        );
+    }
+
+    #[test]
+    fn test_seeded_field_is_reproducible() {
+        let field = Field::new_seeded(17, 13, 4267);
+        assert_eq!(field, Field::new_seeded(17, 13, 4267));
+        assert_ne!(field, Field::new_seeded(17, 13, 4268));
+        assert_ne!(field, Field::new_seeded(17, 13, 0x1_0000_10ab));
+        assert_ne!(Field::new_seeded(17, 13, 0), Field::new(17, 13));
+        assert_eq!(
+            field.start_positions(),
+            Field::new(17, 13).start_positions()
+        );
+
+        let settings = Settings {
+            map_seed: Some(4267),
+            ..Settings::default()
+        };
+        assert_eq!(Field::new_from_rules(&settings), field);
+        let classic = Settings {
+            map_seed: None,
+            ..settings
+        };
+        assert_eq!(
+            Field::new_from_rules(&classic),
+            Field::new(17, 13).with_start_points(classic.players),
+            "without a seed, the classic field"
+        );
+
+        let grids: std::collections::BTreeSet<String> = (1..=20)
+            .map(|seed| Field::new_seeded(17, 13, seed).string_grid())
+            .collect();
+        assert!(grids.len() >= 19, "seeds usually give different fields");
+        assert!(grids
+            .iter()
+            .all(|grid| *grid != Field::new(17, 13).string_grid()));
+    }
 }
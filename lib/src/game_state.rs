@@ -4,6 +4,7 @@ use serde::Serialize;
 use crate::field::Cell;
 use crate::field::Field;
 use crate::field::Upgrade;
+use crate::settings::DeathPenalty;
 use crate::settings::Settings;
 use crate::utils::random;
 use crate::utils::CellPosition;
@@ -13,12 +14,13 @@ use crate::utils::Idx;
 use crate::utils::PlayerId;
 use crate::utils::Position;
 use crate::utils::TimeStamp;
+use crate::utils::MAX_GAME_TICKS;
 use crate::utils::TICKS_PER_SECOND;
 use std::collections::BTreeMap;
 use std::fmt;
 use std::rc::Rc;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Player {
     /// Name the player chose
     pub name: String,
@@ -28,6 +30,10 @@ pub struct Player {
 
     /// Re-/Spawn place
     pub start_position: Position,
+
+    /// Players of a team win together, `None` if the player is on their own
+    #[serde(default)]
+    pub team: Option<u32>,
 }
 
 impl Player {
@@ -36,6 +42,7 @@ impl Player {
             name,
             id,
             start_position,
+            team: None,
         }
     }
 }
@@ -51,6 +58,24 @@ pub struct PlayerState {
     /// number of kills since the game started
     pub kills: u32,
 
+    /// number of kills since the last death
+    pub kill_streak: u32,
+
+    /// most kills without dying in between
+    pub longest_kill_streak: u32,
+
+    /// number of bombs placed since the game started
+    pub bombs_placed: u32,
+
+    /// distance walked since the game started, in `Position` units
+    pub distance_walked: u32,
+
+    /// number of upgrades eaten since the game started
+    pub upgrades_collected: u32,
+
+    /// upgrades eaten at their cap, with `capped_upgrades_score`
+    pub upgrade_points: u32,
+
     /// current bomb power upgrades
     pub power: u32,
 
@@ -63,9 +88,17 @@ pub struct PlayerState {
     /// current placed bombs. Increased when placing, decreased when exploding.
     pub current_bombs_placed: u32,
 
+    /// bombs placed now wait for `Action::detonate` instead of their timer
+    pub has_remote: bool,
+
+    /// walking into bombs kicks them away
+    pub has_kick: bool,
+
+    /// bombs placed now explode past the wood they ignite
+    pub has_pierce: bool,
+
     /// currently walking or placing?
     pub action: Action,
-    // TODO: track total walking distance, total bombs, ...
 }
 
 impl PlayerState {
@@ -74,10 +107,19 @@ impl PlayerState {
             position,
             deaths: 0,
             kills: 0,
+            kill_streak: 0,
+            longest_kill_streak: 0,
+            bombs_placed: 0,
+            distance_walked: 0,
+            upgrades_collected: 0,
+            upgrade_points: 0,
             power: 1,
             speed: 1,
             bombs: 1,
             current_bombs_placed: 0,
+            has_remote: false,
+            has_kick: false,
+            has_pierce: false,
             action: Action::idle(),
         }
     }
@@ -86,47 +128,275 @@ impl PlayerState {
         self.position = position;
     }
 
-    fn eat(&mut self, upgrade: Upgrade) {
+    /// Move and count the distance as walked
+    fn walk(&mut self, position: Position) {
+        let distance = (position.x - self.position.x).unsigned_abs()
+            + (position.y - self.position.y).unsigned_abs();
+        self.distance_walked = self.distance_walked.saturating_add(distance);
+        self.move_(position);
+    }
+
+    fn eat(&mut self, upgrade: Upgrade, settings: &Settings) {
+        let mut remote = u32::from(self.has_remote);
+        let mut kick = u32::from(self.has_kick);
+        let mut pierce = u32::from(self.has_pierce);
         let up = match upgrade {
             Upgrade::Speed => &mut self.speed,
             Upgrade::Power => &mut self.power,
             Upgrade::Bombs => &mut self.bombs,
+            Upgrade::Remote => &mut remote,
+            Upgrade::Kick => &mut kick,
+            Upgrade::Pierce => &mut pierce,
         };
-        *up = up.saturating_add(1);
+        if settings.upgrade_cap(upgrade).is_some_and(|cap| *up >= cap) {
+            // GAME_RULE: upgrades at their cap are eaten without effect, or count as a point
+            if settings.capped_upgrades_score {
+                self.upgrade_points = self.upgrade_points.saturating_add(1);
+            }
+        } else {
+            *up = up.saturating_add(1);
+        }
+        self.has_remote = remote > 0;
+        self.has_kick = kick > 0;
+        self.has_pierce = pierce > 0;
+        self.upgrades_collected = self.upgrades_collected.saturating_add(1);
+    }
+
+    /// Whether the player has lives left, always with unlimited `lives`
+    pub fn is_alive(&self, settings: &Settings) -> bool {
+        settings.lives == 0 || self.deaths < settings.lives
     }
 
-    fn die(&mut self, _killed_by: PlayerId, start_position: Position) {
-        self.power = u32::max(1, self.power / 2);
-        self.speed = u32::max(1, self.speed / 2);
-        self.bombs = u32::max(1, self.bombs / 2);
+    fn die(&mut self, _killed_by: PlayerId, start_position: Position, penalty: DeathPenalty) {
+        self.deaths += 1;
+        self.kill_streak = 0;
+        self.power = penalty.apply(self.power);
+        self.speed = penalty.apply(self.speed);
+        self.bombs = penalty.apply(self.bombs);
+        // GAME_RULE: the remote is lost like all other upgrades, bombs waiting for it can still
+        // be detonated
+        self.has_remote = self.has_remote && penalty == DeathPenalty::KeepAll;
+        self.has_kick = self.has_kick && penalty == DeathPenalty::KeepAll;
+        self.has_pierce = self.has_pierce && penalty == DeathPenalty::KeepAll;
         self.position = start_position;
         self.action = Action::idle();
     }
 
     fn score(&mut self, _killed: PlayerId) {
         self.kills += 1;
+        self.kill_streak += 1;
+        self.longest_kill_streak = u32::max(self.longest_kill_streak, self.kill_streak);
+    }
+}
+
+/// The players' states in a `Vec` indexed by their id
+///
+/// Ids are dense from 0, but have gaps when players left the lobby or the game, those entries
+/// are `None`. Iterating goes in id order, which keeps updates and `checksum` deterministic.
+#[derive(Clone, Default)]
+pub struct PlayerStates(Vec<Option<PlayerState>>);
+
+impl PlayerStates {
+    pub fn get(&self, id: PlayerId) -> Option<&PlayerState> {
+        self.0.get(id.0)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, id: PlayerId) -> Option<&mut PlayerState> {
+        self.0.get_mut(id.0)?.as_mut()
+    }
+
+    pub fn contains(&self, id: PlayerId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Put `state` at `id`, returns the state that was there
+    pub fn insert(&mut self, id: PlayerId, state: PlayerState) -> Option<PlayerState> {
+        if self.0.len() <= id.0 {
+            self.0.resize(id.0 + 1, None);
+        }
+        self.0[id.0].replace(state)
+    }
+
+    pub fn remove(&mut self, id: PlayerId) -> Option<PlayerState> {
+        self.0.get_mut(id.0)?.take()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// The ids of the players, in order
+    pub fn ids(&self) -> impl Iterator<Item = PlayerId> + '_ {
+        self.iter().map(|(id, _)| id)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &PlayerState> {
+        self.0.iter().flatten()
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut PlayerState> {
+        self.0.iter_mut().flatten()
+    }
+
+    /// The players with their ids, in id order
+    pub fn iter(&self) -> impl Iterator<Item = (PlayerId, &PlayerState)> {
+        self.0
+            .iter()
+            .enumerate()
+            .filter_map(|(id, state)| Some((PlayerId(id), state.as_ref()?)))
+    }
+
+    /// The players with their ids, in id order
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (PlayerId, &mut PlayerState)> {
+        self.0
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(id, state)| Some((PlayerId(id), state.as_mut()?)))
+    }
+}
+
+impl std::ops::Index<PlayerId> for PlayerStates {
+    type Output = PlayerState;
+
+    fn index(&self, id: PlayerId) -> &PlayerState {
+        self.get(id)
+            .unwrap_or_else(|| panic!("{id:?} has no state"))
+    }
+}
+
+impl FromIterator<(PlayerId, PlayerState)> for PlayerStates {
+    fn from_iter<I: IntoIterator<Item = (PlayerId, PlayerState)>>(iter: I) -> Self {
+        let mut states = Self::default();
+        for (id, state) in iter {
+            states.insert(id, state);
+        }
+        states
+    }
+}
+
+/// Equal if the same ids have equal states, regardless of the gaps after the last player
+impl PartialEq for PlayerStates {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl fmt::Debug for PlayerStates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// How well a player did in a game
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Standing {
+    pub player: PlayerId,
+    pub name: String,
+    pub kills: u32,
+    pub deaths: u32,
+    pub longest_kill_streak: u32,
+    pub bombs_placed: u32,
+    /// In cells
+    pub distance_walked: u32,
+    pub upgrades_collected: u32,
+    /// Upgrades eaten at their cap
+    pub upgrade_points: u32,
+}
+
+/// Whether a game is decided, and who won it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Ongoing,
+
+    /// Decided without a single best player
+    Draw,
+
+    /// Decided, in a team game this is the best player of the winning team
+    Winner(PlayerId),
+}
+
+impl Outcome {
+    /// The winner of a decided game, `None` while it is ongoing or a draw
+    pub fn winner(self) -> Option<PlayerId> {
+        match self {
+            Outcome::Winner(winner) => Some(winner),
+            Outcome::Ongoing | Outcome::Draw => None,
+        }
     }
 }
 
+/// The standings of a game and its winner
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Results {
+    /// Best player first
+    pub standings: Vec<Standing>,
+
+    /// `None` if the best players are tied
+    pub winner: Option<PlayerId>,
+
+    /// The team of `winner` in a team game
+    #[serde(default)]
+    pub winning_team: Option<u32>,
+}
+
 /// Constants of an active Game
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GameStatic {
     pub players: BTreeMap<PlayerId, Player>,
     pub settings: Settings,
     pub local_player: PlayerId, // TODO: remove from game_static, into Client::Game or something
 }
 
-#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+impl GameStatic {
+    /// Put the players into the settings' teams, in id order so that the teams stay even when
+    /// players left
+    pub fn deal_teams(&mut self) {
+        for (nth, player) in self.players.values_mut().enumerate() {
+            player.team = self.settings.team_of(nth);
+        }
+    }
+
+    /// Whether two different players are in the same team
+    pub fn are_teammates(&self, a: PlayerId, b: PlayerId) -> bool {
+        let team = |id| self.players.get(&id).and_then(|p: &Player| p.team);
+        a != b && team(a).is_some() && team(a) == team(b)
+    }
+
+    /// Players that win together: the teams, and everyone without a team on their own
+    pub fn sides(&self) -> Vec<Vec<PlayerId>> {
+        let mut teams: BTreeMap<u32, Vec<PlayerId>> = BTreeMap::new();
+        let mut sides = Vec::new();
+        for (id, player) in &self.players {
+            match player.team {
+                Some(team) => teams.entry(team).or_default().push(*id),
+                None => sides.push(vec![*id]),
+            }
+        }
+        sides.extend(teams.into_values());
+        sides
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub struct Action {
     pub walking: Option<Direction>,
+
+    /// Perpendicular to `walking`, to walk diagonally if the settings allow it
+    pub diagonal: Option<Direction>,
+
     pub placing: bool,
+
+    /// Blow up the player's remote bombs
+    pub detonate: bool,
 }
 
 impl Action {
-    fn idle() -> Self {
+    pub fn idle() -> Self {
         Self {
             walking: None,
+            diagonal: None,
             placing: false,
+            detonate: false,
         }
     }
 }
@@ -137,19 +407,39 @@ impl fmt::Debug for Action {
             Some(direction) => write!(f, "Waliking {direction:?}")?,
             None => write!(f, "Standing")?,
         }
+        if let Some(direction) = self.diagonal {
+            write!(f, " and {direction:?}")?;
+        }
         if self.placing {
             write!(f, " & placing")?;
         }
+        if self.detonate {
+            write!(f, " & detonating")?;
+        }
         Ok(())
     }
 }
 
+/// An action that an unmodified client never sends, its player is suspected of cheating
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImpossibleAction {
+    /// Walking diagonally, although the settings do not allow it
+    DiagonalWalkingDisabled,
+
+    /// The diagonal is not perpendicular to the walking direction, or there is none
+    DiagonalNotPerpendicular,
+
+    /// Starting to place bombs while all of the player's bombs are on the field
+    OverCapacityPlacement,
+}
+
 /// The variable state of the game at a given time
 #[derive(Debug, Clone)]
 pub struct GameState {
     pub time: TimeStamp,
     pub field: Field,
-    pub player_states: BTreeMap<PlayerId, PlayerState>,
+
+    pub player_states: PlayerStates,
     pub game: Rc<GameStatic>,
 }
 
@@ -158,7 +448,7 @@ impl GameState {
     pub fn new(game: Rc<GameStatic>) -> Self {
         let time = TimeStamp::default();
 
-        let player_states: BTreeMap<PlayerId, PlayerState> = game
+        let player_states: PlayerStates = game
             .players
             .iter()
             .map(|(id, player)| (*id, PlayerState::new(player.start_position)))
@@ -174,28 +464,409 @@ impl GameState {
         }
     }
 
+    /// All players, most kills first, fewer deaths, more upgrade points and then longer kill
+    /// streaks break ties
+    pub fn standings(&self) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = self
+            .player_states
+            .iter()
+            .map(|(id, state)| Standing {
+                player: id,
+                name: self.game.players[&id].name.clone(),
+                kills: state.kills,
+                deaths: state.deaths,
+                longest_kill_streak: state.longest_kill_streak,
+                bombs_placed: state.bombs_placed,
+                distance_walked: state.distance_walked / Position::ACCURACY as u32,
+                upgrades_collected: state.upgrades_collected,
+                upgrade_points: state.upgrade_points,
+            })
+            .collect();
+        standings.sort_by_key(|s| {
+            (
+                !self.is_alive(s.player),
+                std::cmp::Reverse(s.kills),
+                s.deaths,
+                std::cmp::Reverse(s.upgrade_points),
+                std::cmp::Reverse(s.longest_kill_streak),
+                s.player,
+            )
+        });
+        standings
+    }
+
+    /// The current standings and the winner, if any
+    pub fn results(&self) -> Results {
+        let standings = self.standings();
+        if self.game.players.values().any(|p| p.team.is_some()) {
+            return self.team_results(standings);
+        }
+        let living = self.living_players();
+        if self.game.settings.lives > 0 && living.len() <= 1 {
+            // GAME_RULE: the last player standing wins, when the last ones die together, it is
+            // a draw
+            return Results {
+                standings,
+                winner: living.first().copied(),
+                winning_team: None,
+            };
+        }
+        let winner = match &standings[..] {
+            // GAME_RULE: upgrade points break ties of kills and deaths
+            [first, second, ..]
+                if (first.kills, first.deaths, first.upgrade_points)
+                    == (second.kills, second.deaths, second.upgrade_points) =>
+            {
+                None
+            }
+            [first, ..] => Some(first.player),
+            [] => None,
+        };
+        Results {
+            standings,
+            winner,
+            winning_team: None,
+        }
+    }
+
+    /// The results of a team game, `winner` is the best player of the winning team
+    fn team_results(&self, standings: Vec<Standing>) -> Results {
+        let sides = self.game.sides();
+        let living: Vec<&Vec<PlayerId>> = sides
+            .iter()
+            .filter(|side| side.iter().any(|id| self.is_alive(*id)))
+            .collect();
+        let winning_side = if living.len() <= 1 {
+            // GAME_RULE: the last team standing wins, also when the other teams left the game
+            living.first().copied()
+        } else {
+            // GAME_RULE: the team with the most kills wins, then the one with fewer deaths, then
+            // the one with more upgrade points
+            let score = |side: &Vec<PlayerId>| {
+                let (kills, deaths, points) = side
+                    .iter()
+                    .filter_map(|id| self.player_states.get(*id))
+                    .fold((0, 0, 0), |(kills, deaths, points), state| {
+                        (
+                            kills + state.kills,
+                            deaths + state.deaths,
+                            points + state.upgrade_points,
+                        )
+                    });
+                (kills, std::cmp::Reverse(deaths), points)
+            };
+            let best = sides.iter().map(score).max();
+            let mut best_sides = sides.iter().filter(|side| Some(score(side)) == best);
+            best_sides.next().filter(|_| best_sides.next().is_none())
+        };
+        let winner = winning_side.and_then(|side| {
+            standings
+                .iter()
+                .find(|s| side.contains(&s.player))
+                .map(|s| s.player)
+        });
+        let winning_team = winner.and_then(|w| self.game.players[&w].team);
+        Results {
+            standings,
+            winner,
+            winning_team,
+        }
+    }
+
+    /// Players that have lives left, in id order
+    pub fn living_players(&self) -> Vec<PlayerId> {
+        self.player_states
+            .iter()
+            .filter(|(_, p)| p.is_alive(&self.game.settings))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Whether `player_id` has lives left, players that were removed have none
+    pub fn is_alive(&self, player_id: PlayerId) -> bool {
+        self.player_states
+            .get(player_id)
+            .is_some_and(|state| state.is_alive(&self.game.settings))
+    }
+
+    /// A player reached `kills_to_win`, all but one player are out of lives or time is up
+    ///
+    /// Every player is alive until they died `lives` times, so no game is decided before the
+    /// first death. A game of one player is decided when that player is out of lives.
+    pub fn is_decided(&self) -> bool {
+        let kills_to_win = self.game.settings.kills_to_win;
+        let sides = self.game.sides();
+        // GAME_RULE: the kills of a team add up
+        let won = kills_to_win > 0
+            && sides.iter().any(|side| {
+                side.iter()
+                    .filter_map(|id| self.player_states.get(*id))
+                    .map(|state| state.kills)
+                    .sum::<u32>()
+                    >= kills_to_win
+            });
+        let living_sides = sides
+            .iter()
+            .filter(|side| side.iter().any(|id| self.is_alive(*id)))
+            .count();
+        let last_standing = living_sides < sides.len().min(2);
+        won || last_standing || self.is_over()
+    }
+
+    /// `Ongoing` until the game is decided, see `is_decided`
+    pub fn outcome(&self) -> Outcome {
+        if !self.is_decided() {
+            return Outcome::Ongoing;
+        }
+        match self.results().winner {
+            Some(winner) => Outcome::Winner(winner),
+            None => Outcome::Draw,
+        }
+    }
+
+    /// Players can move from this tick on, before it the countdown runs
+    pub fn start_delay_ticks(&self) -> u32 {
+        self.game
+            .settings
+            .countdown_s
+            .saturating_mul(TICKS_PER_SECOND)
+    }
+
+    /// The countdown at the start of the game is still running
+    pub fn is_counting_down(&self) -> bool {
+        self.time.ticks_from_start() < self.start_delay_ticks()
+    }
+
+    /// The game reached `MAX_GAME_TICKS` and time stops
+    pub fn is_over(&self) -> bool {
+        self.time.ticks_from_start() >= MAX_GAME_TICKS
+    }
+
+    /// The player has a bomb that is not on the field yet
+    pub fn can_place_bomb(&self, player_id: PlayerId) -> bool {
+        let player_state = &self.player_states[player_id];
+        player_state.current_bombs_placed < player_state.bombs
+    }
+
+    /// Why `player_id` can not switch to `action` now, if it can not
+    ///
+    /// Clients only start placing when they think the player has a bomb left, a client that
+    /// thinks wrong because it predicted other players badly is suspected too.
+    pub fn impossible_action(
+        &self,
+        player_id: PlayerId,
+        action: Action,
+    ) -> Option<ImpossibleAction> {
+        if let Some(diagonal) = action.diagonal {
+            if !self.game.settings.diagonal_walking {
+                return Some(ImpossibleAction::DiagonalWalkingDisabled);
+            }
+            let perpendicular = action
+                .walking
+                .is_some_and(|walking| diagonal == walking.left() || diagonal == walking.right());
+            if !perpendicular {
+                return Some(ImpossibleAction::DiagonalNotPerpendicular);
+            }
+        }
+        let starts_placing = action.placing && !self.player_states[player_id].action.placing;
+        if starts_placing && !self.can_place_bomb(player_id) {
+            return Some(ImpossibleAction::OverCapacityPlacement);
+        }
+        None
+    }
+
+    /// Advance the game by one tick
+    ///
+    /// The players to update are collected at the start, players are only removed between
+    /// updates with `remove_player`. Updating a player that is gone anyway does nothing.
     pub fn simulate_1_update(&mut self) {
-        for i in 0..self.player_states.len() {
-            // GAME_RULE: players with lower ID are processed earlier and win,
-            // if both place bombs at the same spot 😎
-            self.update_player(PlayerId(i));
+        if self.is_over() {
+            return;
+        }
+        // GAME_RULE: players stand still during the countdown, their actions apply once it is over
+        if !self.is_counting_down() {
+            // Ids can have gaps when players left the lobby before the game started
+            let player_ids: Vec<PlayerId> = self.player_states.ids().collect();
+            for player_id in player_ids {
+                // GAME_RULE: players out of lives do nothing
+                if !self.is_alive(player_id) {
+                    continue;
+                }
+                // GAME_RULE: players with lower ID are processed earlier and win,
+                // if both place bombs at the same spot 😎
+                self.update_player(player_id);
+            }
         }
+        self.slide_bombs();
         self.update_field();
         self.increment_game_time();
+        #[cfg(debug_assertions)]
+        if let Err(err) = self.validate_invariants() {
+            panic!("{:?}: {err}", self.time);
+        }
+    }
+
+    /// The first broken invariant of the state, to catch rule bugs where they happen
+    ///
+    /// Bombs belong to players of the game, each player's count of placed bombs matches their
+    /// bombs on the field, nothing on the field expired before now and players are in the field.
+    ///
+    /// # Errors
+    ///
+    /// Describes the first invariant that does not hold
+    #[cfg(debug_assertions)]
+    pub fn validate_invariants(&self) -> Result<(), String> {
+        let mut bombs_on_field = BTreeMap::<PlayerId, u32>::new();
+        for (cell, content) in self.field.iter() {
+            let expire = match *content {
+                Cell::Bomb { owner, expire, .. } => {
+                    // players removed from the game still own their bombs
+                    if !self.game.players.contains_key(&owner) {
+                        return Err(format!("{cell:?}: bomb of unknown {owner:?}"));
+                    }
+                    *bombs_on_field.entry(owner).or_default() += 1;
+                    expire
+                }
+                Cell::Fire { expire, .. } | Cell::WoodBurning { expire } => expire,
+                _ => continue,
+            };
+            if expire < self.time {
+                return Err(format!(
+                    "{cell:?}: {content:?} expired before {:?}",
+                    self.time
+                ));
+            }
+        }
+        for (player_id, player_state) in self.player_states.iter() {
+            let bombs = bombs_on_field.get(&player_id).copied().unwrap_or_default();
+            if player_state.current_bombs_placed != bombs {
+                return Err(format!(
+                    "{player_id:?} placed {} bombs, {bombs} are on the field",
+                    player_state.current_bombs_placed
+                ));
+            }
+            let cell = player_state.position.as_cell_pos();
+            if !self.field.is_cell_in_field(cell) {
+                return Err(format!("{player_id:?} is outside the field at {cell:?}"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Version of the canonical form that `checksum` hashes, bump it when that form changes
+    pub const CHECKSUM_VERSION: u32 = 3;
+
+    /// Checksum of time, field and players, to notice when two simulations diverge
+    ///
+    /// Hashes a canonical form: the version, the field's cells, the players in id order and the
+    /// time. Only the values listed here go in, so new fields or another container for the
+    /// players do not change it by accident.
+    pub fn checksum(&self) -> u32 {
+        let players = self.player_states.iter().flat_map(|(id, p)| {
+            [
+                id.0 as u32,
+                p.position.x as u32,
+                p.position.y as u32,
+                p.deaths,
+                p.kills,
+                p.upgrade_points,
+                p.power,
+                p.speed,
+                p.bombs,
+                u32::from(p.has_remote),
+                u32::from(p.has_kick),
+                u32::from(p.has_pierce),
+            ]
+        });
+        let canonical = [Self::CHECKSUM_VERSION, self.field.checksum()]
+            .into_iter()
+            .chain(players)
+            .chain(std::iter::once(self.time.ticks_from_start()));
+        canonical.fold(0, |x, i| x.wrapping_add(i).wrapping_mul(31))
+    }
+
+    /// Everything that differs between two states of the same game, empty if they are equal
+    ///
+    /// Compares time, field and players, the `GameStatic`s may differ.
+    pub fn diff(&self, other: &GameState) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.time != other.time {
+            differences.push(format!("time: {:?} != {:?}", self.time, other.time));
+        }
+        if (self.field.width, self.field.height) == (other.field.width, other.field.height) {
+            for cell in self.field.iter_indices() {
+                if self.field[cell] != other.field[cell] {
+                    differences.push(format!(
+                        "{cell:?}: {:?} != {:?}",
+                        self.field[cell], other.field[cell]
+                    ));
+                }
+            }
+        } else {
+            differences.push(format!(
+                "field size: {}x{} != {}x{}",
+                self.field.width, self.field.height, other.field.width, other.field.height
+            ));
+        }
+        let ids: std::collections::BTreeSet<PlayerId> = self
+            .player_states
+            .ids()
+            .chain(other.player_states.ids())
+            .collect();
+        for id in ids {
+            match (self.player_states.get(id), other.player_states.get(id)) {
+                (Some(a), Some(b)) if a == b => {}
+                (a, b) => differences.push(format!("{id:?}: {a:?} != {b:?}")),
+            }
+        }
+        differences
+    }
+
+    /// The field as in `Field::string_grid` with each player drawn as their id
+    pub fn render_ascii(&self) -> String {
+        let mut grid: Vec<Vec<char>> = self
+            .field
+            .string_grid()
+            .lines()
+            .map(|line| line.chars().collect())
+            .collect();
+        for (id, player) in self.player_states.iter() {
+            let cell = player.position.as_cell_pos();
+            if self.field.is_cell_in_field(cell) {
+                grid[cell.y as usize][cell.x as usize] =
+                    char::from_digit(id.0 as u32, 36).unwrap_or('?');
+            }
+        }
+        let mut s = String::new();
+        for line in grid {
+            s.extend(line);
+            s.push('\n');
+        }
+        s
+    }
+
+    /// Take a player out of the game between updates, their bombs still explode
+    pub fn remove_player(&mut self, player_id: PlayerId) -> Option<PlayerState> {
+        log::info!("{:?} {player_id:?} removed", self.time);
+        self.player_states.remove(player_id)
     }
 
     /// Set Player Action
     ///
-    /// return true if this changed the player's current action
+    /// return true if this changed the player's current action, false for players that are
+    /// not (or no longer) in the game
     pub fn set_player_action(&mut self, player_id: PlayerId, action: Action) -> bool {
-        let player_state = self.player_states.get_mut(&player_id).unwrap();
+        let Some(player_state) = self.player_states.get_mut(player_id) else {
+            log::warn!("{:?} {player_id:?} is gone, not setting action", self.time);
+            return false;
+        };
 
         let new = player_state.action != action;
         if new {
             log::debug!("{:?} {:?}.action := {:?}", self.time, player_id, action);
             player_state.action = action;
         }
-        return new;
+        new
     }
 }
 
@@ -207,7 +878,14 @@ impl GameState {
 
     /// advance a player 1 tick
     fn update_player(&mut self, player_id: PlayerId) {
-        let action = self.player_states[&player_id].action;
+        let Some(player_state) = self.player_states.get(player_id) else {
+            log::warn!("{:?} {player_id:?} is gone, not updating", self.time);
+            return;
+        };
+        let action = player_state.action;
+        if action.detonate {
+            self.detonate(player_id);
+        }
         if action.placing {
             self.place_bomb(player_id);
         }
@@ -217,21 +895,31 @@ impl GameState {
     }
 
     fn walk(&mut self, player_id: PlayerId) {
-        let player = &self.game.players[&player_id];
-        let player_state = &self.player_states[&player_id];
-
-        let direction = player_state
-            .action
+        let action = self.player_states[player_id].action;
+        let direction = action
             .walking
             .expect("only call walking if player is walking");
+        self.walk_in_direction(player_id, direction);
+
+        // GAME_RULE: walking diagonally is walking along both axes, each stopped by walls on its
+        // own, so players slide along walls
+        if let Some(diagonal) = action.diagonal {
+            let perpendicular = diagonal == direction.left() || diagonal == direction.right();
+            // players that died on the first axis stand at their start position now
+            let still_walking = self.player_states[player_id].action == action;
+            if self.game.settings.diagonal_walking && perpendicular && still_walking {
+                self.walk_in_direction(player_id, diagonal);
+            }
+        }
+    }
+
+    fn walk_in_direction(&mut self, player_id: PlayerId, direction: Direction) {
+        let player_state = &self.player_states[player_id];
 
         let mut walk_distance = self
             .game
             .settings
-            .get_update_walk_distance(player_state.speed)
-            .try_into()
-            .expect("walked distance fits i32");
-        walk_distance = walk_distance * Position::ACCURACY / TICKS_PER_SECOND as i32 / 100;
+            .walk_distance_per_update(player_state.speed);
 
         let current_cell_pos = player_state.position.as_cell_pos();
         let cell_ahead = &self.field[current_cell_pos.add(direction, 1)];
@@ -240,25 +928,34 @@ impl GameState {
         let cell_ahead_right =
             &self.field[current_cell_pos.add(direction, 1).add(direction.right(), 1)];
 
+        let mut position = player_state.position;
         if cell_ahead.walkable() {
-            if !cell_ahead_left.walkable() {
-                // TODO: move away from left wall by distance_to_border - ACC/5
+            // GAME_RULE: players passing a corner slide away from it, keeping the same distance
+            // as from walls ahead
+            for (corner, side) in [
+                (cell_ahead_left, direction.left()),
+                (cell_ahead_right, direction.right()),
+            ] {
+                let nudge = Position::ACCURACY / 5 - position.distance_to_border(side);
+                if !corner.walkable() && nudge > 0 {
+                    position = position.add(side.opposite(), nudge);
+                }
             }
         } else {
             let distance_to_wall =
-                player_state.position.distance_to_border(direction) - (Position::ACCURACY / 5);
+                position.distance_to_border(direction) - (Position::ACCURACY / 5);
             walk_distance = i32::min(distance_to_wall, walk_distance);
         }
 
         if walk_distance > 0 {
-            let new_position = player_state.position.add(direction, walk_distance);
-            self.walk_on_cell(player_id, new_position);
+            let new_position = position.add(direction, walk_distance);
+            self.walk_on_cell(player_id, new_position, direction);
         }
     }
 
-    fn walk_on_cell(&mut self, player_id: PlayerId, new_position: Position) {
+    fn walk_on_cell(&mut self, player_id: PlayerId, new_position: Position, direction: Direction) {
         let player = &self.game.players[&player_id];
-        let player_state = self.player_states.get_mut(&player_id).unwrap();
+        let player_state = self.player_states.get_mut(player_id).unwrap();
         let cell_position = new_position.as_cell_pos();
         let cell = &self.field[cell_position];
         log::debug!(
@@ -272,7 +969,25 @@ impl GameState {
         );
         match *cell {
             Cell::StartPoint | Cell::Empty => {
-                player_state.move_(new_position);
+                player_state.walk(new_position);
+            }
+            Cell::Bomb { .. }
+                if player_state.has_kick
+                    && cell_position != player_state.position.as_cell_pos() =>
+            {
+                // GAME_RULE: players with the kick upgrade kick bombs they walk into instead of
+                // walking onto them
+                if let Cell::Bomb { sliding, .. } = &mut self.field[cell_position] {
+                    *sliding = Some(direction);
+                }
+                log::info!(
+                    "{:?} {:?} @ {:?} kicked bomb at {:?} {:?}",
+                    self.time,
+                    player_id,
+                    player_state.position,
+                    cell_position,
+                    direction
+                );
             }
             Cell::Bomb { .. } => {
                 if random(self.time, new_position.x, new_position.y) % 100
@@ -280,7 +995,7 @@ impl GameState {
                 {
                     // GAME_RULE: walking on bombs randomly happens or doesn't, decided
                     // each update.
-                    player_state.move_(new_position);
+                    player_state.walk(new_position);
                 }
             }
             Cell::TombStone { .. } => {
@@ -289,17 +1004,30 @@ impl GameState {
                 {
                     // GAME_RULE: walking on tombstones randomly happens or doesn't, decided
                     // each update.
-                    player_state.move_(new_position);
+                    player_state.walk(new_position);
                 }
             }
+            Cell::Fire { owner, .. }
+                if !self.game.settings.friendly_fire
+                    && self.game.are_teammates(owner, player_id) =>
+            {
+                // GAME_RULE: without friendly fire, teammates walk through each other's fire
+                player_state.walk(new_position);
+            }
             Cell::Fire { owner, .. } => {
                 // GAME_RULE: walking into fire counts as kill by fire owner
                 // TODO: seperate counter?
-                player_state.die(owner, player.start_position);
-                self.player_states
-                    .get_mut(&player_id)
-                    .unwrap()
-                    .score(player_id);
+                player_state.die(
+                    owner,
+                    player.start_position,
+                    self.game.settings.death_penalty,
+                );
+                // GAME_RULE: killing a teammate or yourself does not score
+                if owner != player_id && !self.game.are_teammates(owner, player_id) {
+                    if let Some(killer) = self.player_states.get_mut(owner) {
+                        killer.score(player_id);
+                    }
+                }
                 self.field[cell_position] = Cell::TombStone(player_id);
 
                 log::info!(
@@ -310,8 +1038,8 @@ impl GameState {
                 );
             }
             Cell::Upgrade(upgrade) => {
-                player_state.move_(new_position);
-                player_state.eat(upgrade);
+                player_state.walk(new_position);
+                player_state.eat(upgrade, &self.game.settings);
                 self.field[cell_position] = Cell::Empty;
 
                 log::info!(
@@ -323,51 +1051,73 @@ impl GameState {
                     player_state
                 );
             }
-            Cell::Teleport => {
-                let targets: Vec<(CellPosition, &Cell)> = self
-                    .field
-                    .iter()
-                    .filter(|&(target_position, target_cell)| {
-                        *target_cell == Cell::Teleport && target_position != cell_position
-                    })
-                    .collect();
-                if targets.is_empty() {
-                    log::info!(
-                        "{:?} {:?} @ {:?} can not walk onto Teleport, it is not connected",
-                        self.time,
-                        player_id,
-                        cell_position,
-                    );
-                    // GAME_RULE: you can not walk onto an unconnected TP :P
-                    // player_state.move_(position);
-                } else {
-                    let target = targets[random(self.time, new_position.x, new_position.y)
-                        as usize
-                        % targets.len()];
-                    let (to, target_cell): (_, &Cell) = target;
-                    assert_eq!(*target_cell, Cell::Teleport);
-
-                    player_state.move_(Position::from_cell_position(to));
-
-                    debug_assert_eq!(self.field[cell_position], Cell::Teleport);
-                    debug_assert_eq!(self.field[to], Cell::Teleport);
-                    self.field[cell_position] = Cell::Empty;
-                    self.field[to] = Cell::Empty;
-                    log::info!(
-                        "{:?} {:?} @ {:?} ported to {:?}",
-                        self.time,
-                        player_id,
-                        cell_position,
-                        to
-                    );
-                }
+            Cell::Teleport if !self.game.settings.teleports_enabled => {
+                // GAME_RULE: disabled teleports are obstacles
+            }
+            Cell::Teleport if player_state.position.as_cell_pos() == cell_position => {
+                // GAME_RULE: a player that ported onto a teleport that stays walks off it
+                // normally, ports only happen when stepping onto one
+                player_state.walk(new_position);
             }
+            Cell::Teleport => self.walk_on_teleport(player_id, new_position),
             Cell::Wall | Cell::Wood | Cell::WoodBurning { .. } => {} /* no walking through walls */
         }
     }
 
+    /// The teleports except the one at `cell`, where porting from `cell` can lead
+    fn other_teleports(&self, cell: CellPosition) -> Vec<CellPosition> {
+        self.field
+            .iter()
+            .filter_map(|(i_pos, i_cell)| {
+                (*i_cell == Cell::Teleport && i_pos != cell).then_some(i_pos)
+            })
+            .collect()
+    }
+
+    /// Port `player_id` from the teleport at `new_position` to a random other one
+    fn walk_on_teleport(&mut self, player_id: PlayerId, new_position: Position) {
+        let cell_position = new_position.as_cell_pos();
+        let targets = self.other_teleports(cell_position);
+        let player_state = self.player_states.get_mut(player_id).unwrap();
+        if targets.is_empty() {
+            log::info!(
+                "{:?} {:?} @ {:?} can not walk onto Teleport, it is not connected",
+                self.time,
+                player_id,
+                cell_position,
+            );
+            // GAME_RULE: you can not walk onto an unconnected TP :P
+            // player_state.move_(position);
+        } else {
+            let to =
+                targets[random(self.time, new_position.x, new_position.y) as usize % targets.len()];
+
+            player_state.move_(Position::from_cell_position(to));
+
+            // GAME_RULE: porting uses up both teleports, unless `teleport_consumed` is
+            // off. If two players step onto the two ends of used up teleports in the
+            // same update, the lower id ports, because players are updated in id order,
+            // and the other one walks onto the empty cell
+            debug_assert_eq!(self.field[cell_position], Cell::Teleport);
+            debug_assert_eq!(self.field[to], Cell::Teleport);
+            if self.game.settings.teleport_consumed {
+                self.field[cell_position] = Cell::Empty;
+                self.field[to] = Cell::Empty;
+            }
+            log::info!(
+                "{:?} {:?} @ {:?} ported to {:?}",
+                self.time,
+                player_id,
+                cell_position,
+                to
+            );
+        }
+    }
+
     fn place_bomb(&mut self, player_id: PlayerId) {
-        let player_state = self.player_states.get_mut(&player_id).unwrap();
+        let bombs_on_field = self.bombs_on_field();
+        let max_bombs_on_field = self.max_bombs_on_field();
+        let player_state = self.player_states.get_mut(player_id).unwrap();
         // GAME RULE: can not place more bombs than you have bomb powerups
         if player_state.current_bombs_placed >= player_state.bombs {
             log::info!(
@@ -376,6 +1126,14 @@ impl GameState {
                 player_id,
                 player_state.bombs
             );
+        } else if bombs_on_field >= max_bombs_on_field {
+            // GAME RULE: a full field takes no more bombs, so explosions stay cheap to simulate
+            log::info!(
+                "{:?} {:?} field is full with {:?} bombs",
+                self.time,
+                player_id,
+                bombs_on_field
+            );
         } else {
             let position = match player_state.action.walking {
                 Some(direction) => player_state.position.add(
@@ -400,7 +1158,7 @@ impl GameState {
                         player_state.position,
                         upgrade,
                     );
-                    player_state.eat(upgrade);
+                    player_state.eat(upgrade, &self.game.settings);
                 }
 
                 // TODO: placing Bombs into TP and have the Bomb Port would be funny
@@ -410,11 +1168,19 @@ impl GameState {
                 // there were)
                 if Cell::Empty == *cell {
                     player_state.current_bombs_placed += 1;
+                    player_state.bombs_placed += 1;
+                    let expire = if player_state.has_remote {
+                        Self::remote_bomb_expire()
+                    } else {
+                        self.time + self.game.settings.bomb_explode_time()
+                    };
                     *cell = Cell::Bomb {
                         owner: player_id,
-                        expire: self.time + self.game.settings.bomb_explode_time(),
+                        expire,
                         // GAME_RULE: power is set AFTER eating powerups at cell
                         power: player_state.power,
+                        sliding: None,
+                        pierce: player_state.has_pierce,
                     };
                     log::info!(
                         "{:?} {:?} @ {:?} placed  {:?}",
@@ -437,94 +1203,200 @@ impl GameState {
         }
     }
 
-    /// set a cell on fire.
-    ///
-    /// `consider_tp` if target is a teleport, explode a random other teleport too.
-    ///
-    /// returns if the fire should continue further in that direction
-    fn set_on_fire(&mut self, cell: CellPosition, owner: PlayerId, consider_tp: bool) -> bool {
-        let (explodes, power, owner) = match self.field[cell] {
-            // TODO: Tombstone Explodes based on players schinken?
-            // TODO: Tombstone gives upgrade that player had most of?
-            Cell::Fire { .. } | Cell::Empty | Cell::TombStone(..) => (true, 0, owner),
-            Cell::Bomb {
-                power,
-                owner: bomb_owner,
+    /// When remote bombs expire: never, the game is over before
+    fn remote_bomb_expire() -> TimeStamp {
+        TimeStamp::default() + Duration::from_ticks(MAX_GAME_TICKS)
+    }
+
+    /// Make the remote bombs of `player_id` explode in this update
+    fn detonate(&mut self, player_id: PlayerId) {
+        let remote_bomb_expire = Self::remote_bomb_expire();
+        for cell in self.field.iter_indices() {
+            if let Cell::Bomb { owner, expire, .. } = &mut self.field[cell] {
+                if *owner == player_id && *expire == remote_bomb_expire {
+                    log::info!("{:?} {player_id:?} detonates {cell:?}", self.time);
+                    *expire = self.time;
+                }
+            }
+        }
+    }
+
+    /// Move each kicked bomb one cell on. It stops before anything but an empty cell, and before
+    /// players
+    fn slide_bombs(&mut self) {
+        let sliding_bombs: Vec<(CellPosition, Direction)> = self
+            .field
+            .iter()
+            .filter_map(|(cell, content)| match *content {
+                Cell::Bomb {
+                    sliding: Some(direction),
+                    ..
+                } => Some((cell, direction)),
+                _ => None,
+            })
+            .collect();
+        // GAME_RULE: sliding bombs keep their timer and explode wherever they are by then
+        for (cell, direction) in sliding_bombs {
+            let next = cell.add(direction, 1);
+            let blocked = !self.field.is_cell_in_field(next)
+                || self.field[next] != Cell::Empty
+                || self.player_states.values().any(|player_state| {
+                    player_state.is_alive(&self.game.settings)
+                        && player_state.position.as_cell_pos() == next
+                });
+            if blocked {
+                if let Cell::Bomb { sliding, .. } = &mut self.field[cell] {
+                    *sliding = None;
+                }
+            } else {
+                self.field[next] = std::mem::take(&mut self.field[cell]);
+            }
+        }
+    }
+
+    /// Cells per bomb that may be on the field at the same time
+    const CELLS_PER_BOMB: usize = 4;
+
+    /// The most bombs that may be on the field at the same time, whoever placed them, at least
+    /// one even on tiny fields
+    fn max_bombs_on_field(&self) -> usize {
+        let cells = self.field.width as usize * self.field.height as usize;
+        (cells / Self::CELLS_PER_BOMB).max(1)
+    }
+
+    /// Bombs on the field that did not explode yet
+    fn bombs_on_field(&self) -> usize {
+        self.field
+            .iter()
+            .filter(|(_, cell)| matches!(cell, Cell::Bomb { .. }))
+            .count()
+    }
+
+    /// Directions an explosion spreads in, the diagonals only with `diagonal_blast`
+    fn blast_directions(&self) -> &'static [(isize, isize)] {
+        const DIRECTIONS: [(isize, isize); 8] = [
+            (-1, 0),
+            (1, 0),
+            (0, 1),
+            (0, -1),
+            (-1, -1),
+            (1, -1),
+            (-1, 1),
+            (1, 1),
+        ];
+        if self.game.settings.diagonal_blast {
+            &DIRECTIONS
+        } else {
+            &DIRECTIONS[..4]
+        }
+    }
+
+    /// set a cell on fire.
+    ///
+    /// `pierce` if the fire should continue past wood it ignites.
+    ///
+    /// `consider_tp` if target is a teleport, explode a random other teleport too.
+    ///
+    /// returns if the fire should continue further in that direction
+    fn set_on_fire(
+        &mut self,
+        cell: CellPosition,
+        owner: PlayerId,
+        pierce: bool,
+        consider_tp: bool,
+    ) -> bool {
+        // a blast that reaches the center of an earlier one does not hide it
+        let earlier_power = match self.field[cell] {
+            Cell::Fire { power, .. } => power,
+            _ => 0,
+        };
+        let upgrade_power = self.game.settings.upgrade_explosion_power;
+        let (explodes, power, owner, pierce) = match self.field[cell] {
+            // TODO: Tombstone Explodes based on players schinken?
+            // TODO: Tombstone gives upgrade that player had most of?
+            Cell::Fire { .. } | Cell::Empty | Cell::TombStone(..) => (true, 0, owner, pierce),
+            Cell::Bomb {
+                power,
+                owner: bomb_owner,
+                expire,
+                pierce: bomb_pierce,
                 ..
             } => {
                 log::info!("{cell:?}: destroying {owner:?}'s bomb");
-                self.player_states
-                    .get_mut(&bomb_owner)
-                    .unwrap()
-                    .current_bombs_placed -= 1;
+                // the owner may have been removed since placing it
+                if let Some(owner_state) = self.player_states.get_mut(bomb_owner) {
+                    owner_state.current_bombs_placed -= 1;
+                }
 
-                // GAME_RULE: owner of secondary Bomb takes the credit
-                (true, power, bomb_owner)
+                if expire == self.time || self.game.settings.chain_reactions {
+                    // GAME_RULE: owner of secondary Bomb takes the credit
+                    (true, power, bomb_owner, bomb_pierce)
+                } else {
+                    // GAME_RULE: without chain reactions, the blast just passes through
+                    (true, 0, owner, pierce)
+                }
+            }
+            Cell::Upgrade(upgrade) if !self.game.settings.fire_destroys_upgrades => {
+                // GAME_RULE: without `fire_destroys_upgrades`, the blast passes over upgrades
+                log::info!("{cell:?}: sparing {upgrade:?}");
+                return true;
             }
             Cell::Upgrade(upgrade) => {
                 log::info!("{cell:?}: destroying {upgrade:?}");
 
-                (true, self.game.settings.upgrade_explosion_power, owner)
+                (true, upgrade_power, owner, pierce)
+            }
+            Cell::Teleport if !self.game.settings.teleports_enabled => {
+                // GAME_RULE: disabled teleports burn like empty cells, without tunneling
+                log::info!("{cell:?}: destroying disabled Teleport");
+                (true, 0, owner, pierce)
             }
             Cell::Teleport => {
                 let explodes = if consider_tp {
-                    let ports: Vec<CellPosition> = self
-                        .field
-                        .iter()
-                        .filter_map(|(i_pos, i_cell)| {
-                            if *i_cell == Cell::Teleport && i_pos != cell {
-                                Some(i_pos)
-                            } else {
-                                None
-                            }
-                        })
-                        .collect();
+                    let ports = self.other_teleports(cell);
                     if ports.is_empty() {
                         log::info!("{cell:?}: destroying Teleport (no remote TP found)");
                         false
                     } else {
                         let other = ports[random(self.time, cell.x, cell.y).idx() % ports.len()];
                         log::info!("{cell:?}: destroying Teleport, tunneling to {other:?}");
-                        self.set_on_fire(other, owner, false);
+                        self.set_on_fire(other, owner, pierce, false);
                         true
                     }
                 } else {
                     true
                 };
-                (explodes, self.game.settings.upgrade_explosion_power, owner)
+                (explodes, upgrade_power, owner, pierce)
             }
-            Cell::StartPoint | Cell::WoodBurning { .. } | Cell::Wall => (false, 0, owner),
+            Cell::StartPoint | Cell::WoodBurning { .. } | Cell::Wall => (false, 0, owner, pierce),
             Cell::Wood => {
                 let expire = self.time + self.game.settings.wood_burn_time();
                 self.field[cell] = Cell::WoodBurning { expire };
                 log::info!("{cell:?}: setting wall on fire until {expire:?}");
-                (false, 0, owner)
+                // GAME_RULE: piercing fire goes on past the wood it ignites
+                return pierce;
             }
         };
         if explodes {
             self.field[cell] = Cell::Fire {
                 owner,
                 expire: self.time + self.game.settings.fire_burn_time(),
+                power: power.max(earlier_power),
             };
-            for (id, p) in self.player_states.iter_mut() {
-                if p.position.as_cell_pos() == cell {
-                    p.die(owner, self.game.players[&id].start_position);
-                    self.field[cell] = Cell::TombStone(*id);
-                }
-            }
+            self.burn_players(cell, owner);
 
             let power: isize = power.try_into().expect("power fits");
             if power > 0 {
                 let x = cell.x as isize;
                 let y = cell.y as isize;
-                for (dx, dy) in [(-1, 0), (1, 0), (0, 1), (0, -1)] {
+                for &(dx, dy) in self.blast_directions() {
                     for i in 1..=power {
                         let x = x + dx * i;
                         let y = y + dy * i;
                         if x >= 0 && y >= 0 {
                             let pos = CellPosition::new(x as i32, y as i32);
                             if self.field.is_cell_in_field(pos)
-                                && !self.set_on_fire(pos, owner, true)
+                                && !self.set_on_fire(pos, owner, pierce, true)
                             {
                                 break;
                             }
@@ -533,19 +1405,148 @@ impl GameState {
                         }
                     }
                 }
+                if self.game.settings.explosion_knockback {
+                    self.knock_back(cell);
+                }
             }
         }
         explodes
     }
 
+    /// Kill the players in a cell that caught fire
+    fn burn_players(&mut self, cell: CellPosition, owner: PlayerId) {
+        let mut victims = Vec::new();
+        let friendly_fire = self.game.settings.friendly_fire;
+        for (id, p) in self.player_states.iter_mut() {
+            if !friendly_fire && self.game.are_teammates(owner, id) {
+                continue;
+            }
+            if p.position.as_cell_pos() == cell && p.is_alive(&self.game.settings) {
+                p.die(
+                    owner,
+                    self.game.players[&id].start_position,
+                    self.game.settings.death_penalty,
+                );
+                self.field[cell] = Cell::TombStone(id);
+                victims.push(id);
+            }
+        }
+        // GAME_RULE: burning counts as kill by fire owner, but not of a teammate or themself
+        victims.retain(|victim| *victim != owner && !self.game.are_teammates(owner, *victim));
+        if let Some(killer) = self.player_states.get_mut(owner) {
+            for victim in victims {
+                killer.score(victim);
+            }
+        }
+    }
+
+    /// Shove surviving players around `center` one cell away from it
+    fn knock_back(&mut self, center: CellPosition) {
+        for (id, player_state) in self.player_states.iter_mut() {
+            let position = player_state.position.as_cell_pos();
+            let (dx, dy) = (position.x - center.x, position.y - center.y);
+            if (dx, dy) == (0, 0) || dx.abs() > 1 || dy.abs() > 1 {
+                continue;
+            }
+            if !player_state.is_alive(&self.game.settings) {
+                continue;
+            }
+            if let Cell::Fire { .. } | Cell::TombStone(..) = self.field[position] {
+                continue;
+            }
+            let target = CellPosition::new(position.x + dx, position.y + dy);
+            // GAME_RULE: players are only shoved onto cells they could walk onto
+            if self.field.is_cell_in_field(target)
+                && matches!(self.field[target], Cell::Empty | Cell::StartPoint)
+            {
+                log::info!(
+                    "{:?} {id:?} @ {position:?} knocked back to {target:?}",
+                    self.time
+                );
+                player_state.move_(Position::from_cell_position(target));
+            }
+        }
+    }
+
+    /// Once magma rises, it sets a cell on fire this often
+    const MAGMA_INTERVAL_TICKS: u32 = TICKS_PER_SECOND / 2;
+
+    /// Magma rose and sets a cell on fire in this update
+    fn magma_erupts(&self) -> bool {
+        let after = self.game.settings.magma_after_s;
+        let start = after.saturating_mul(TICKS_PER_SECOND);
+        let ticks = self.time.ticks_from_start();
+        after != 0 && ticks >= start && (ticks - start).is_multiple_of(Self::MAGMA_INTERVAL_TICKS)
+    }
+
+    /// Set a random empty cell on fire, killing players on it
+    fn erupt_magma(&mut self) {
+        let cells: Vec<CellPosition> = self
+            .field
+            .iter()
+            .filter(|(_, cell)| matches!(cell, Cell::Empty | Cell::TombStone(..)))
+            .map(|(pos, _)| pos)
+            .collect();
+        if cells.is_empty() {
+            return;
+        }
+        let cell = cells[random(self.time, -1, -1).idx() % cells.len()];
+        log::info!("{:?} {cell:?}: magma erupts", self.time);
+        // GAME_RULE: magma is nobody's fire, dying in it scores for nobody
+        self.set_on_fire(cell, PlayerId::NOBODY, false, false);
+    }
+
+    /// Wood regrows on an empty cell in this update
+    fn wood_regrows(&self) -> bool {
+        let every = self.game.settings.wood_regrow_time().ticks();
+        let ticks = self.time.ticks_from_start();
+        every != 0 && ticks != 0 && ticks.is_multiple_of(every)
+    }
+
+    /// Turn a random empty cell into wood
+    fn regrow_wood(&mut self) {
+        // GAME_RULE: wood never grows on or next to a player or a start point, so nobody gets
+        // walled in
+        let keep_clear: Vec<CellPosition> = self
+            .player_states
+            .values()
+            .map(|p| p.position.as_cell_pos())
+            .chain(self.field.start_positions())
+            .collect();
+        let cells: Vec<CellPosition> = self
+            .field
+            .iter()
+            .filter(|(pos, cell)| {
+                **cell == Cell::Empty
+                    && keep_clear
+                        .iter()
+                        .all(|p| (p.x - pos.x).abs() > 1 || (p.y - pos.y).abs() > 1)
+            })
+            .map(|(pos, _)| pos)
+            .collect();
+        if cells.is_empty() {
+            return;
+        }
+        let cell = cells[random(self.time, -2, -2).idx() % cells.len()];
+        log::info!("{:?} {cell:?}: wood regrows", self.time);
+        self.field[cell] = Cell::Wood;
+    }
+
     fn update_field(&mut self) {
+        // GAME_RULE: magma erupts before fire expires, so it burns at least one update
+        if self.magma_erupts() {
+            self.erupt_magma();
+        }
+        if self.wood_regrows() {
+            self.regrow_wood();
+        }
         for cell_idx in self.field.iter_indices() {
             let cell = &mut self.field[cell_idx];
             match *cell {
                 Cell::Bomb { owner, expire, .. } => {
                     assert!(expire >= self.time);
                     if expire == self.time {
-                        self.set_on_fire(cell_idx, owner, true);
+                        self.set_on_fire(cell_idx, owner, false, true);
                     }
                 }
                 Cell::Fire { expire, .. } => {
@@ -577,6 +1578,7 @@ impl GameState {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::settings::Ratios;
 
     #[test]
     fn test_random() {
@@ -590,9 +1592,12 @@ mod test {
     fn game() -> GameState {
         let player1 = Player::new("test player 1".to_owned(), PlayerId(0), Position::new(0, 0));
         let local_player = player1.id;
-        let settings = Settings::default();
+        let settings = Settings {
+            countdown_s: 0,
+            ..Settings::default()
+        };
         let game = GameStatic {
-            players: vec![player1],
+            players: BTreeMap::from([(local_player, player1)]),
             settings,
             local_player,
         };
@@ -600,12 +1605,446 @@ mod test {
         let game = Rc::new(game);
 
         let mut gs = GameState::new(game);
-        gs.player_states[0].current_bombs_placed = 42; // Hack, so bombs can explode without int
-                                                       // underrun. If a test cares, it should set
-                                                       // this correctly
+        gs.player_states
+            .get_mut(PlayerId(0))
+            .unwrap()
+            .current_bombs_placed = 42; // Hack, so bombs can explode without int
+                                        // underrun. If a test cares, it should set
+                                        // this correctly
         gs
     }
 
+    /// Set the players' counts of placed bombs to their bombs on the field, undoing the hack
+    /// in `game` for tests that simulate, `validate_invariants` checks them after each update
+    fn count_placed_bombs(gs: &mut GameState) {
+        for (id, player_state) in gs.player_states.iter_mut() {
+            player_state.current_bombs_placed = gs
+                .field
+                .iter()
+                .filter(|(_, cell)| matches!(cell, Cell::Bomb { owner, .. } if *owner == id))
+                .count() as u32;
+        }
+    }
+
+    #[test]
+    fn test_players_stand_still_during_the_countdown() {
+        let mut gs = game();
+        let mut game_static = (*gs.game).clone();
+        game_static.settings.countdown_s = 1;
+        gs.game = Rc::new(game_static);
+        gs.field = Field::new_from_string_grid("_____").unwrap();
+        let start = Position::from_cell_position(CellPosition::new(0, 0));
+        let player = gs.player_states.get_mut(PlayerId(0)).unwrap();
+        player.position = start;
+        player.current_bombs_placed = 0;
+        gs.set_player_action(
+            PlayerId(0),
+            Action {
+                walking: Some(Direction::East),
+                diagonal: None,
+                placing: true,
+                detonate: false,
+            },
+        );
+
+        for _ in 0..TICKS_PER_SECOND {
+            assert!(gs.is_counting_down());
+            gs.simulate_1_update();
+        }
+        assert_eq!(gs.player_states[PlayerId(0)].position, start);
+        assert_eq!(gs.field[CellPosition::new(0, 0)], Cell::Empty);
+
+        assert!(!gs.is_counting_down());
+        gs.simulate_1_update();
+        assert_ne!(gs.player_states[PlayerId(0)].position, start);
+        assert!(matches!(
+            gs.field[CellPosition::new(0, 0)],
+            Cell::Bomb { .. }
+        ));
+    }
+
+    #[test]
+    fn test_game_ends_before_time_overflows() {
+        let mut gs = game();
+        count_placed_bombs(&mut gs);
+        gs.time = TimeStamp::default() + Duration::from_ticks(MAX_GAME_TICKS - 1);
+        assert!(!gs.is_over());
+        gs.simulate_1_update();
+        assert!(gs.is_over());
+        let end = gs.time;
+        gs.simulate_1_update();
+        assert_eq!(gs.time, end, "time stops at the end of the game");
+    }
+
+    #[test]
+    fn test_outcome_when_a_player_has_enough_kills() {
+        let mut gs = game();
+        let mut game_static = (*gs.game).clone();
+        game_static.players.insert(
+            PlayerId(1),
+            Player::new("test player 2".to_owned(), PlayerId(1), Position::new(0, 0)),
+        );
+        gs.game = Rc::new(game_static);
+        gs.player_states
+            .insert(PlayerId(1), PlayerState::new(Position::new(0, 0)));
+
+        let kills_to_win = gs.game.settings.kills_to_win;
+        gs.player_states.get_mut(PlayerId(1)).unwrap().kills = kills_to_win - 1;
+        assert_eq!(gs.outcome(), Outcome::Ongoing);
+        assert_eq!(gs.results().winner, Some(PlayerId(1)));
+
+        gs.player_states.get_mut(PlayerId(0)).unwrap().kills = kills_to_win - 1;
+        assert_eq!(gs.results().winner, None, "tied");
+
+        let winner = gs.player_states.get_mut(PlayerId(1)).unwrap();
+        winner.kills = kills_to_win;
+        winner.deaths = 3;
+        assert_eq!(
+            gs.outcome(),
+            Outcome::Winner(PlayerId(1)),
+            "the game is over"
+        );
+        let outcome = gs.results();
+        assert_eq!(
+            outcome.standings,
+            vec![
+                Standing {
+                    player: PlayerId(1),
+                    name: "test player 2".to_owned(),
+                    kills: kills_to_win,
+                    deaths: 3,
+                    longest_kill_streak: 0,
+                    bombs_placed: 0,
+                    distance_walked: 0,
+                    upgrades_collected: 0,
+                    upgrade_points: 0,
+                },
+                Standing {
+                    player: PlayerId(0),
+                    name: "test player 1".to_owned(),
+                    kills: kills_to_win - 1,
+                    deaths: 0,
+                    longest_kill_streak: 0,
+                    bombs_placed: 0,
+                    distance_walked: 0,
+                    upgrades_collected: 0,
+                    upgrade_points: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_standings_summarize_the_match() {
+        let mut gs = game();
+        let mut game_static = (*gs.game).clone();
+        for (id, cell) in [(1, CellPosition::new(4, 1)), (2, CellPosition::new(0, 1))] {
+            let player = Player::new(
+                format!("test player {}", id + 1),
+                PlayerId(id),
+                Position::from_cell_position(cell),
+            );
+            gs.player_states
+                .insert(player.id, PlayerState::new(player.start_position));
+            game_static.players.insert(player.id, player);
+        }
+        gs.game = Rc::new(game_static);
+        gs.field = Field::new_from_string_grid("_s___\n_____").unwrap();
+        let player = gs.player_states.get_mut(PlayerId(0)).unwrap();
+        player.position = Position::new(50, 50);
+        player.current_bombs_placed = 0;
+
+        let walking = |walking, placing| Action {
+            walking,
+            diagonal: None,
+            placing,
+            detonate: false,
+        };
+        gs.set_player_action(PlayerId(0), walking(Some(Direction::East), false));
+        for _ in 0..100 {
+            if gs.player_states[PlayerId(0)].position.x >= 250 {
+                break;
+            }
+            gs.simulate_1_update();
+        }
+        gs.set_player_action(PlayerId(0), walking(None, true));
+        gs.simulate_1_update();
+
+        for p in gs.player_states.values_mut() {
+            p.current_bombs_placed = 42;
+        }
+        let kill = |gs: &mut GameState, killer, victim| {
+            let cell = gs.player_states[PlayerId(victim)].position.as_cell_pos();
+            gs.field[cell] = Cell::Bomb {
+                owner: PlayerId(killer),
+                power: 0,
+                expire: gs.time,
+                sliding: None,
+                pierce: false,
+            };
+            gs.update_field();
+        };
+        kill(&mut gs, 0, 1);
+        kill(&mut gs, 0, 2);
+        kill(&mut gs, 1, 0);
+        kill(&mut gs, 0, 1);
+        kill(&mut gs, 2, 1);
+
+        let standings = gs.standings();
+        let order: Vec<PlayerId> = standings.iter().map(|s| s.player).collect();
+        assert_eq!(order, [PlayerId(0), PlayerId(2), PlayerId(1)]);
+        assert_eq!(
+            standings[0],
+            Standing {
+                player: PlayerId(0),
+                name: "test player 1".to_owned(),
+                kills: 3,
+                deaths: 1,
+                longest_kill_streak: 2,
+                bombs_placed: 1,
+                distance_walked: 2,
+                upgrades_collected: 1,
+                upgrade_points: 0,
+            }
+        );
+        assert_eq!((standings[1].kills, standings[1].deaths), (1, 1));
+        assert_eq!((standings[2].kills, standings[2].deaths), (1, 3));
+    }
+
+    #[test]
+    fn test_removing_players_between_updates_is_safe() {
+        let mut gs = game();
+        let mut game_static = (*gs.game).clone();
+        let player = Player::new(
+            "test player 2".to_owned(),
+            PlayerId(1),
+            Position::new(250, 50),
+        );
+        gs.player_states
+            .insert(player.id, PlayerState::new(player.start_position));
+        game_static.players.insert(player.id, player);
+        gs.game = Rc::new(game_static);
+        gs.field = Field::new_from_string_grid("_____").unwrap();
+        count_placed_bombs(&mut gs);
+
+        gs.set_player_action(
+            PlayerId(1),
+            Action {
+                walking: None,
+                diagonal: None,
+                placing: true,
+                detonate: false,
+            },
+        );
+        gs.simulate_1_update();
+        assert!(matches!(
+            gs.field[CellPosition::new(2, 0)],
+            Cell::Bomb {
+                owner: PlayerId(1),
+                ..
+            }
+        ));
+
+        assert!(gs.remove_player(PlayerId(1)).is_some());
+        assert!(gs.remove_player(PlayerId(1)).is_none());
+        // the removed player's bomb explodes without its owner
+        for _ in 0..=gs.game.settings.bomb_explode_time().ticks() {
+            gs.simulate_1_update();
+        }
+        assert!(matches!(
+            gs.field[CellPosition::new(2, 0)],
+            Cell::Fire {
+                owner: PlayerId(1),
+                ..
+            }
+        ));
+
+        // an id that is gone during an update is skipped
+        gs.update_player(PlayerId(1));
+        assert!(
+            !gs.set_player_action(PlayerId(1), Action::idle()),
+            "late actions of a removed player are ignored"
+        );
+        assert_eq!(gs.player_states.ids().collect::<Vec<_>>(), [PlayerId(0)]);
+
+        // the removed player is still listed in the game, but out of it
+        assert!(!gs.is_alive(PlayerId(1)));
+        assert_eq!(gs.outcome(), Outcome::Winner(PlayerId(0)));
+        assert_eq!(gs.results().standings.len(), 1);
+
+        let mut game_static = (*gs.game).clone();
+        for (team, player) in game_static.players.values_mut().enumerate() {
+            player.team = Some(team as u32);
+        }
+        gs.game = Rc::new(game_static);
+        assert_eq!(gs.outcome(), Outcome::Winner(PlayerId(0)));
+        assert_eq!(gs.results().winning_team, Some(0));
+    }
+
+    #[test]
+    fn test_player_states_are_indexed_by_id() {
+        let state = |x| PlayerState::new(Position::new(x, 50));
+        let mut states: PlayerStates = [(PlayerId(3), state(350)), (PlayerId(1), state(150))]
+            .into_iter()
+            .collect();
+        assert_eq!(states.ids().collect::<Vec<_>>(), [PlayerId(1), PlayerId(3)]);
+        assert_eq!(states[PlayerId(3)].position.x, 350);
+        assert!(states.get(PlayerId(0)).is_none());
+        assert!(states.get(PlayerId(9)).is_none());
+
+        assert_eq!(states.insert(PlayerId(0), state(50)), None);
+        assert_eq!(
+            states
+                .iter()
+                .map(|(id, p)| (id, p.position.x))
+                .collect::<Vec<_>>(),
+            [(PlayerId(0), 50), (PlayerId(1), 150), (PlayerId(3), 350)]
+        );
+
+        let mut without_last = states.clone();
+        assert_eq!(without_last.remove(PlayerId(3)), Some(state(350)));
+        assert_eq!(without_last.remove(PlayerId(3)), None);
+        assert!(!without_last.contains(PlayerId(3)));
+        let fresh: PlayerStates = [(PlayerId(0), state(50)), (PlayerId(1), state(150))]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            without_last, fresh,
+            "the gap of the last player does not count"
+        );
+        assert_ne!(without_last, states);
+        assert_eq!(
+            format!("{fresh:?}"),
+            format!("{:?}", fresh.iter().collect::<BTreeMap<_, _>>())
+        );
+    }
+
+    #[test]
+    fn test_players_are_processed_in_id_order_despite_gaps() {
+        let placing = Action {
+            walking: None,
+            diagonal: None,
+            placing: true,
+            detonate: false,
+        };
+        let simulate = |ids: [usize; 3]| {
+            let mut gs = game();
+            let mut game_static = (*gs.game).clone();
+            game_static.players.clear();
+            gs.player_states.clear();
+            for id in ids {
+                let player = Player::new(format!("{id}"), PlayerId(id), Position::new(150, 50));
+                gs.player_states
+                    .insert(player.id, PlayerState::new(player.start_position));
+                game_static.players.insert(player.id, player);
+            }
+            gs.game = Rc::new(game_static);
+            gs.field = Field::new_from_string_grid("___").unwrap();
+            for id in ids {
+                gs.set_player_action(PlayerId(id), placing);
+            }
+            gs.simulate_1_update();
+            gs
+        };
+
+        let gs = simulate([7, 2, 5]);
+        // GAME_RULE: the lowest id gets the spot
+        assert!(matches!(
+            gs.field[CellPosition::new(1, 0)],
+            Cell::Bomb {
+                owner: PlayerId(2),
+                ..
+            }
+        ));
+        assert_eq!(gs.player_states[PlayerId(2)].bombs_placed, 1);
+        assert_eq!(gs.player_states[PlayerId(5)].bombs_placed, 0);
+        assert_eq!(
+            gs.checksum(),
+            simulate([5, 7, 2]).checksum(),
+            "independent of the order players were added in"
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_corrupted_states_fail_validation() {
+        let mut gs = game();
+        gs.field = Field::new_from_string_grid("_____").unwrap();
+        count_placed_bombs(&mut gs);
+        assert_eq!(gs.validate_invariants(), Ok(()));
+
+        let cell = CellPosition::new(1, 0);
+        let validate_corrupted = |corrupt: &dyn Fn(&mut GameState)| {
+            let mut corrupted = gs.clone();
+            corrupt(&mut corrupted);
+            corrupted.validate_invariants()
+        };
+
+        assert!(validate_corrupted(&|gs| {
+            gs.field[cell] = Cell::Bomb {
+                owner: PlayerId(7),
+                power: 1,
+                expire: gs.time,
+                sliding: None,
+                pierce: false,
+            };
+        })
+        .is_err());
+        assert!(validate_corrupted(&|gs| {
+            let player = gs.player_states.get_mut(PlayerId(0)).unwrap();
+            player.current_bombs_placed = 1;
+        })
+        .is_err());
+        assert!(validate_corrupted(&|gs| {
+            gs.field[cell] = Cell::WoodBurning { expire: gs.time };
+            gs.time = gs.time + Duration::from_ticks(1);
+        })
+        .is_err());
+        assert!(validate_corrupted(&|gs| {
+            let player = gs.player_states.get_mut(PlayerId(0)).unwrap();
+            player.position = Position::from_cell_position(CellPosition::new(5, 0));
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_checksum_of_a_known_state() {
+        let mut gs = game();
+        gs.field = Field::new_from_string_grid("_B_\n#T+").unwrap();
+        gs.time = TimeStamp::default() + Duration::from_ticks(42);
+        let player = gs.player_states.get_mut(PlayerId(0)).unwrap();
+        player.position = Position::new(150, 50);
+        player.kills = 3;
+        player.bombs_placed = 7;
+
+        // changes when the canonical form changes, bump `CHECKSUM_VERSION` then
+        assert_eq!(GameState::CHECKSUM_VERSION, 3);
+        assert_eq!(gs.checksum(), 1_798_267_296);
+
+        let mut other = gs.clone();
+        other
+            .player_states
+            .get_mut(PlayerId(0))
+            .unwrap()
+            .bombs_placed = 8;
+        assert_eq!(other.checksum(), gs.checksum(), "statistics are not hashed");
+        other.player_states.get_mut(PlayerId(0)).unwrap().has_kick = true;
+        assert_ne!(other.checksum(), gs.checksum());
+        other.player_states.get_mut(PlayerId(0)).unwrap().has_kick = false;
+        other.time = other.time + Duration::from_ticks(1);
+        assert_ne!(other.checksum(), gs.checksum());
+    }
+
+    #[test]
+    fn test_render_ascii_draws_players_as_digits() {
+        let mut gs = game();
+        gs.field = Field::new_from_string_grid("___\n_#_\n___").unwrap();
+        gs.player_states.get_mut(PlayerId(0)).unwrap().position =
+            Position::from_cell_position(CellPosition::new(2, 1));
+
+        assert_eq!(gs.render_ascii(), "___\n_#0\n___\n");
+    }
+
     fn test_static_cells_dont_explode() {
         let mut gs = game();
 
@@ -653,6 +2092,37 @@ mod test {
             eq
         }
     }
+
+    #[test]
+    fn test_full_field_takes_no_more_bombs() {
+        let mut gs = game();
+        gs.field = Field::new_from_string_grid("____\n____").unwrap();
+        for y in 0..2 {
+            gs.field[CellPosition::new(3, y)] = Cell::Bomb {
+                owner: PlayerId(0),
+                power: 0,
+                expire: gs.time + Duration::from_ticks(1),
+                sliding: None,
+                pierce: false,
+            };
+        }
+        let player = gs.player_states.get_mut(PlayerId(0)).unwrap();
+        player.position = Position::from_cell_position(CellPosition::new(0, 0));
+        player.bombs = 5;
+        player.current_bombs_placed = 2;
+
+        gs.place_bomb(PlayerId(0));
+        assert_eq!(gs.field[CellPosition::new(0, 0)], Cell::Empty);
+
+        gs.increment_game_time();
+        gs.update_field();
+        gs.place_bomb(PlayerId(0));
+        assert!(matches!(
+            gs.field[CellPosition::new(0, 0)],
+            Cell::Bomb { .. }
+        ));
+    }
+
     #[test]
     fn test_bomb_explodes_after_time() {
         let mut gs = game();
@@ -661,6 +2131,8 @@ mod test {
             owner: PlayerId(0),
             power: 1,
             expire: gs.time + Duration::from_ticks(3),
+            sliding: None,
+            pierce: false,
         };
         gs.increment_game_time();
         gs.update_field();
@@ -683,6 +2155,176 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_remote_bombs_explode_on_detonation_only() {
+        let mut gs = game();
+        gs.field = Field::new_from_string_grid("_____").unwrap();
+        let bomb = CellPosition::new(2, 0);
+        let player = gs.player_states.get_mut(PlayerId(0)).unwrap();
+        player.position = Position::from_cell_position(bomb);
+        player.current_bombs_placed = 0;
+        player.eat(Upgrade::Remote, &gs.game.settings);
+        assert!(player.has_remote);
+
+        gs.set_player_action(
+            PlayerId(0),
+            Action {
+                placing: true,
+                ..Action::idle()
+            },
+        );
+        gs.simulate_1_update();
+        gs.set_player_action(PlayerId(0), Action::idle());
+        for _ in 0..gs.game.settings.bomb_explode_time().ticks() * 2 {
+            gs.simulate_1_update();
+        }
+        assert!(
+            matches!(gs.field[bomb], Cell::Bomb { .. }),
+            "the timer does not set off remote bombs"
+        );
+
+        gs.set_player_action(
+            PlayerId(0),
+            Action {
+                detonate: true,
+                ..Action::idle()
+            },
+        );
+        gs.simulate_1_update();
+        assert!(matches!(
+            gs.field[CellPosition::new(3, 0)],
+            Cell::Fire { .. }
+        ));
+        assert_eq!(gs.player_states[PlayerId(0)].current_bombs_placed, 0);
+        assert_eq!(gs.player_states[PlayerId(0)].deaths, 1, "standing on it");
+    }
+
+    /// Blow up a bomb of power 5 in front of three woods, return the cells of the row
+    fn explode_into_woods(pierce: bool) -> Vec<Cell> {
+        let mut gs = game();
+        gs.field = Field::new_from_string_grid("_+++__\n______").unwrap();
+        gs.player_states.get_mut(PlayerId(0)).unwrap().position =
+            Position::from_cell_position(CellPosition::new(5, 1));
+        gs.field[CellPosition::new(0, 0)] = Cell::Bomb {
+            owner: PlayerId(0),
+            power: 5,
+            expire: gs.time,
+            sliding: None,
+            pierce,
+        };
+        count_placed_bombs(&mut gs);
+        gs.simulate_1_update();
+        (1..6)
+            .map(|x| gs.field[CellPosition::new(x, 0)].clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_pierce_bombs_burn_through_wood() {
+        let row = explode_into_woods(true);
+        assert!(row[..3]
+            .iter()
+            .all(|cell| matches!(cell, Cell::WoodBurning { .. })));
+        assert!(row[3..]
+            .iter()
+            .all(|cell| matches!(cell, Cell::Fire { .. })));
+    }
+
+    #[test]
+    fn test_bombs_stop_at_the_first_wood() {
+        let row = explode_into_woods(false);
+        assert!(matches!(row[0], Cell::WoodBurning { .. }));
+        assert_eq!(row[1..3], [Cell::Wood, Cell::Wood]);
+        assert_eq!(row[3..], [Cell::Empty, Cell::Empty]);
+    }
+
+    #[test]
+    fn test_kicked_bombs_slide_until_they_hit_a_wall() {
+        let mut gs = game();
+        gs.field = Field::new_from_string_grid("______#").unwrap();
+        let expire = gs.time + Duration::from_ticks(1000);
+        gs.field[CellPosition::new(1, 0)] = Cell::Bomb {
+            owner: PlayerId(0),
+            power: 3,
+            expire,
+            sliding: None,
+            pierce: false,
+        };
+        count_placed_bombs(&mut gs);
+        let player = gs.player_states.get_mut(PlayerId(0)).unwrap();
+        player.eat(Upgrade::Kick, &gs.game.settings);
+        assert!(player.has_kick);
+
+        gs.set_player_action(
+            PlayerId(0),
+            Action {
+                walking: Some(Direction::East),
+                ..Action::idle()
+            },
+        );
+        for _ in 0..100 {
+            gs.simulate_1_update();
+            if gs.field[CellPosition::new(1, 0)] == Cell::Empty {
+                break;
+            }
+        }
+        gs.set_player_action(PlayerId(0), Action::idle());
+        for _ in 0..10 {
+            gs.simulate_1_update();
+        }
+
+        assert_eq!(
+            gs.field[CellPosition::new(5, 0)],
+            Cell::Bomb {
+                owner: PlayerId(0),
+                power: 3,
+                expire,
+                sliding: None,
+                pierce: false,
+            }
+        );
+        assert_eq!(
+            gs.player_states[PlayerId(0)].position.as_cell_pos(),
+            CellPosition::new(0, 0),
+            "kicking does not move the player onto the bomb"
+        );
+    }
+
+    #[test]
+    fn test_detonating_leaves_timed_bombs_alone() {
+        let mut gs = game();
+        gs.field = Field::new_from_string_grid("B____").unwrap();
+        count_placed_bombs(&mut gs);
+        gs.player_states.get_mut(PlayerId(0)).unwrap().position =
+            Position::from_cell_position(CellPosition::new(4, 0));
+        let expire = gs.time + Duration::from_ticks(10);
+        gs.field[CellPosition::new(0, 0)] = Cell::Bomb {
+            owner: PlayerId(0),
+            power: 1,
+            expire,
+            sliding: None,
+            pierce: false,
+        };
+        gs.set_player_action(
+            PlayerId(0),
+            Action {
+                detonate: true,
+                ..Action::idle()
+            },
+        );
+        gs.simulate_1_update();
+        assert_eq!(
+            gs.field[CellPosition::new(0, 0)],
+            Cell::Bomb {
+                owner: PlayerId(0),
+                power: 1,
+                expire,
+                sliding: None,
+                pierce: false,
+            }
+        );
+    }
+
     #[test]
     fn test_bomb_explodes() {
         let mut gs = game();
@@ -716,6 +2358,296 @@ mod test {
         assert!(field_looks_equal(&gs.field, expected));
     }
 
+    fn explode_next_to_bomb(chain_reactions: bool) -> Field {
+        let mut gs = game();
+        let mut game_static = (*gs.game).clone();
+        game_static.settings.chain_reactions = chain_reactions;
+        gs.game = Rc::new(game_static);
+        gs.field = Field::new_from_string_grid(
+            "
+            _______
+            _______
+            _______
+            _______
+            _______
+        ",
+        )
+        .unwrap();
+        gs.field[CellPosition::new(1, 1)] = Cell::Bomb {
+            owner: PlayerId(0),
+            power: 1,
+            expire: gs.time,
+            sliding: None,
+            pierce: false,
+        };
+        gs.field[CellPosition::new(2, 1)] = Cell::Bomb {
+            owner: PlayerId(0),
+            power: 2,
+            expire: gs.time + Duration::from_ticks(100),
+            sliding: None,
+            pierce: false,
+        };
+        gs.update_field();
+        gs.field
+    }
+
+    #[test]
+    fn test_bombs_chain_react() {
+        let expected = "
+            _FF____
+            FFFFF__
+            _FF____
+            __F____
+            _______
+            ";
+        let field = explode_next_to_bomb(true);
+        assert!(field_looks_equal(&field, expected));
+
+        // the fire remembers the power of the bomb that exploded there
+        let power = |x, y| match field[CellPosition::new(x, y)] {
+            Cell::Fire { power, .. } => power,
+            ref cell => panic!("{cell:?} is not on fire"),
+        };
+        assert_eq!(power(1, 1), 1);
+        assert_eq!(power(2, 1), 2);
+        assert_eq!(power(0, 1), 0);
+    }
+
+    #[test]
+    fn test_bombs_without_chain_reactions_are_destroyed() {
+        let expected = "
+            _F_____
+            FFF____
+            _F_____
+            _______
+            _______
+            ";
+        assert!(field_looks_equal(&explode_next_to_bomb(false), expected));
+    }
+
+    fn explode_in_the_middle(diagonal_blast: bool) -> Field {
+        let mut gs = game();
+        let mut game_static = (*gs.game).clone();
+        game_static.settings.diagonal_blast = diagonal_blast;
+        gs.game = Rc::new(game_static);
+        gs.field = Field::new_from_string_grid(
+            "
+            _______
+            _______
+            _______
+            _______
+            __#____
+            _______
+            _______
+        ",
+        )
+        .unwrap();
+        gs.field[CellPosition::new(3, 3)] = Cell::Bomb {
+            owner: PlayerId(0),
+            power: 2,
+            expire: gs.time,
+            sliding: None,
+            pierce: false,
+        };
+        gs.update_field();
+        gs.field
+    }
+
+    fn explode_next_to_upgrade(fire_destroys_upgrades: bool) -> Field {
+        let mut gs = game();
+        let mut game_static = (*gs.game).clone();
+        game_static.settings.fire_destroys_upgrades = fire_destroys_upgrades;
+        game_static.settings.upgrade_explosion_power = 1;
+        gs.game = Rc::new(game_static);
+        gs.field = Field::new_from_string_grid(
+            "
+            _______
+            __p____
+            _______
+        ",
+        )
+        .unwrap();
+        gs.field[CellPosition::new(1, 1)] = Cell::Bomb {
+            owner: PlayerId(0),
+            power: 3,
+            expire: gs.time,
+            sliding: None,
+            pierce: false,
+        };
+        gs.update_field();
+        gs.field
+    }
+
+    #[test]
+    fn test_fire_destroys_upgrades() {
+        let expected = "
+            _FF____
+            FFFFF__
+            _FF____
+            ";
+        assert!(field_looks_equal(&explode_next_to_upgrade(true), expected));
+    }
+
+    #[test]
+    fn test_fire_passes_over_upgrades() {
+        let expected = "
+            _F_____
+            FFpFF__
+            _F_____
+            ";
+        assert!(field_looks_equal(&explode_next_to_upgrade(false), expected));
+    }
+
+    #[test]
+    fn test_diagonal_blast_spreads_along_diagonals() {
+        let expected = "
+            _______
+            _F_F_F_
+            __FFF__
+            _FFFFF_
+            __#FF__
+            ___F_F_
+            _______
+            ";
+        assert!(field_looks_equal(&explode_in_the_middle(true), expected));
+
+        let expected = "
+            _______
+            ___F___
+            ___F___
+            _FFFFF_
+            __#F___
+            ___F___
+            _______
+            ";
+        assert!(field_looks_equal(&explode_in_the_middle(false), expected));
+    }
+
+    /// Cells of players around a bomb after it exploded
+    fn explode_between_players(explosion_knockback: bool) -> Vec<CellPosition> {
+        let mut gs = game();
+        let mut game_static = (*gs.game).clone();
+        game_static.settings.explosion_knockback = explosion_knockback;
+        let cells = [(4, 0), (1, 2), (3, 3), (1, 1)];
+        for (id, (x, y)) in cells.into_iter().enumerate() {
+            let position = Position::from_cell_position(CellPosition::new(x, y));
+            game_static.players.insert(
+                PlayerId(id),
+                Player::new(format!("test player {id}"), PlayerId(id), position),
+            );
+            gs.player_states
+                .insert(PlayerId(id), PlayerState::new(position));
+        }
+        gs.game = Rc::new(game_static);
+        gs.field = Field::new_from_string_grid(
+            "
+            #____
+            _____
+            _OB__
+            _____
+            _____
+        ",
+        )
+        .unwrap();
+        gs.player_states
+            .get_mut(PlayerId(0))
+            .unwrap()
+            .current_bombs_placed = 1;
+        gs.update_field();
+        gs.player_states
+            .values()
+            .map(|p| p.position.as_cell_pos())
+            .collect()
+    }
+
+    #[test]
+    fn test_explosion_knocks_back_players_next_to_it() {
+        assert_eq!(
+            explode_between_players(true),
+            vec![
+                CellPosition::new(4, 0), // not next to the bomb
+                CellPosition::new(0, 2), // start points do not burn
+                CellPosition::new(4, 4), // diagonal, away from the bomb
+                CellPosition::new(1, 1), // blocked by the wall
+            ]
+        );
+        assert_eq!(
+            explode_between_players(false),
+            vec![
+                CellPosition::new(4, 0),
+                CellPosition::new(1, 2),
+                CellPosition::new(3, 3),
+                CellPosition::new(1, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wood_regrows_on_schedule_away_from_players() {
+        let mut gs = game();
+        let mut game_static = (*gs.game).clone();
+        game_static.settings.wood_regrow_ms = 500;
+        gs.game = Rc::new(game_static);
+        gs.field = Field::new_from_string_grid("_______").unwrap();
+        count_placed_bombs(&mut gs);
+        let every = gs.game.settings.wood_regrow_time().ticks();
+        let wood = |gs: &GameState| gs.field.iter().filter(|(_, c)| **c == Cell::Wood).count();
+
+        gs.simulate_1_update();
+        assert_eq!(wood(&gs), 0, "not at the start");
+        for regrown in 1..=5 {
+            for _ in 1..every {
+                gs.simulate_1_update();
+                assert_eq!(wood(&gs), regrown - 1, "at {:?}", gs.time);
+            }
+            gs.simulate_1_update();
+            assert_eq!(wood(&gs), regrown, "at {:?}", gs.time);
+        }
+        for _ in 0..every {
+            gs.simulate_1_update();
+        }
+        assert!(
+            field_looks_equal(&gs.field, "__+++++"),
+            "never on or next to the player"
+        );
+    }
+
+    #[test]
+    fn test_magma_erupts_on_schedule_and_kills() {
+        let mut gs = game();
+        let mut game_static = (*gs.game).clone();
+        game_static.settings.magma_after_s = 1;
+        gs.game = Rc::new(game_static);
+        gs.field = Field::new_from_string_grid("O##\n#_#\n###").unwrap();
+        count_placed_bombs(&mut gs);
+        let player = gs.player_states.get_mut(PlayerId(0)).unwrap();
+        player.position = Position::from_cell_position(CellPosition::new(1, 1));
+        let magma = CellPosition::new(1, 1);
+
+        for _ in 0..TICKS_PER_SECOND {
+            gs.simulate_1_update();
+            assert_eq!(gs.field[magma], Cell::Empty, "at {:?}", gs.time);
+        }
+        gs.simulate_1_update();
+        assert_eq!(gs.field[magma], Cell::TombStone(PlayerId(0)));
+        assert_eq!(
+            gs.player_states[PlayerId(0)].position,
+            Position::new(0, 0),
+            "the player died and respawned"
+        );
+
+        gs.field[magma] = Cell::Empty;
+        for _ in 1..GameState::MAGMA_INTERVAL_TICKS {
+            gs.simulate_1_update();
+            assert_eq!(gs.field[magma], Cell::Empty, "at {:?}", gs.time);
+        }
+        gs.simulate_1_update();
+        assert!(
+            matches!(gs.field[magma], Cell::Fire { .. }),
+            "erupts again after the interval"
+        );
+    }
+
     #[test]
     fn test_bomb_explosion_counts_placed_bombs() {
         let mut gs = game();
@@ -723,11 +2655,41 @@ mod test {
             owner: PlayerId(0),
             power: 1,
             expire: gs.time,
+            sliding: None,
+            pierce: false,
         };
-        gs.player_states[0].current_bombs_placed = 42;
+        gs.player_states
+            .get_mut(PlayerId(0))
+            .unwrap()
+            .current_bombs_placed = 42;
         gs.update_field();
-        assert_eq!(gs.player_states[0].current_bombs_placed, 41);
+        assert_eq!(gs.player_states[PlayerId(0)].current_bombs_placed, 41);
+    }
+
+    #[test]
+    fn test_wood_burns_down_with_degenerate_ratios() {
+        for ratios in [
+            Ratios::NONE,
+            Ratios {
+                clear: 1,
+                ..Ratios::NONE
+            },
+        ] {
+            let mut gs = game();
+            let mut game_static = (*gs.game).clone();
+            game_static.settings.ratios = ratios;
+            gs.game = Rc::new(game_static);
+            gs.field = Field::new_from_string_grid("______").unwrap();
+            for x in 0..6 {
+                gs.field[CellPosition::new(x, 0)] = Cell::WoodBurning { expire: gs.time };
+            }
+
+            gs.update_field();
+
+            assert!(field_looks_equal(&gs.field, "______"));
+        }
     }
+
     #[test]
     fn test_walls_catch_fire() {
         let mut gs = game();
@@ -787,4 +2749,621 @@ mod test {
             ";
         assert!(field_looks_equal(&gs.field, expected));
     }
+
+    /// A game of `players` with `lives` each, player `i` starts in cell `(i, 0)`
+    fn with_lives(players: i32, lives: u32) -> GameState {
+        let mut gs = game();
+        let mut game_static = (*gs.game).clone();
+        game_static.settings.lives = lives;
+        game_static.players.clear();
+        gs.player_states.clear();
+        for id in 0..players {
+            let start = Position::from_cell_position(CellPosition::new(id, 0));
+            let player = Player::new(
+                format!("test player {}", id + 1),
+                PlayerId(id as usize),
+                start,
+            );
+            gs.player_states.insert(player.id, PlayerState::new(start));
+            game_static.players.insert(player.id, player);
+        }
+        gs.game = Rc::new(game_static);
+        gs.field = Field::new_from_string_grid("_____").unwrap();
+        gs
+    }
+
+    /// Blow up the cell of `victim`
+    fn bomb_player(gs: &mut GameState, victim: usize) {
+        bomb_player_by(gs, victim, victim);
+    }
+
+    /// Blow up the cell of `victim` with a bomb of `owner`
+    fn bomb_player_by(gs: &mut GameState, owner: usize, victim: usize) {
+        let cell = gs.player_states[PlayerId(victim)].position.as_cell_pos();
+        gs.field[cell] = Cell::Bomb {
+            owner: PlayerId(owner),
+            power: 0,
+            expire: gs.time,
+            sliding: None,
+            pierce: false,
+        };
+        gs.player_states
+            .get_mut(PlayerId(owner))
+            .unwrap()
+            .current_bombs_placed += 1;
+        gs.update_field();
+        gs.simulate_1_update();
+    }
+
+    /// Like `with_lives(4, lives)`, players 0 and 2 play against 1 and 3
+    fn with_teams(lives: u32, friendly_fire: bool) -> GameState {
+        let mut gs = with_lives(4, lives);
+        let mut game_static = (*gs.game).clone();
+        game_static.settings.teams = 2;
+        game_static.settings.friendly_fire = friendly_fire;
+        game_static.deal_teams();
+        gs.game = Rc::new(game_static);
+        gs
+    }
+
+    #[test]
+    fn test_teammates_fire_kills_only_with_friendly_fire() {
+        let mut gs = with_teams(0, false);
+        bomb_player_by(&mut gs, 0, 2);
+        assert_eq!(gs.player_states[PlayerId(2)].deaths, 0, "teammate spared");
+        bomb_player_by(&mut gs, 0, 1);
+        assert_eq!(gs.player_states[PlayerId(1)].deaths, 1);
+        assert_eq!(gs.player_states[PlayerId(0)].kills, 1);
+
+        let mut gs = with_teams(0, true);
+        bomb_player_by(&mut gs, 0, 2);
+        assert_eq!(gs.player_states[PlayerId(2)].deaths, 1);
+        assert_eq!(
+            gs.player_states[PlayerId(0)].kills,
+            0,
+            "killing a teammate does not score"
+        );
+    }
+
+    #[test]
+    fn test_suicide_does_not_score() {
+        let mut gs = with_lives(2, 0);
+        bomb_player(&mut gs, 0);
+        assert_eq!(gs.player_states[PlayerId(0)].deaths, 1);
+        assert_eq!(gs.player_states[PlayerId(0)].kills, 0);
+
+        let mut gs = with_lives(1, 0);
+        gs.field[CellPosition::new(1, 0)] = Cell::Fire {
+            owner: PlayerId(0),
+            expire: gs.time + Duration::from_ticks(100),
+            power: 0,
+        };
+        gs.set_player_action(
+            PlayerId(0),
+            Action {
+                walking: Some(Direction::East),
+                ..Action::idle()
+            },
+        );
+        for _ in 0..30 {
+            gs.simulate_1_update();
+        }
+        assert_eq!(gs.player_states[PlayerId(0)].deaths, 1, "walked into fire");
+        assert_eq!(gs.player_states[PlayerId(0)].kills, 0);
+    }
+
+    #[test]
+    fn test_magma_scores_for_nobody() {
+        let mut gs = with_lives(2, 0);
+        gs.field = Field::new_from_string_grid("#_###").unwrap();
+        gs.erupt_magma();
+        assert_eq!(gs.player_states[PlayerId(1)].deaths, 1);
+        assert_eq!(gs.player_states[PlayerId(0)].kills, 0);
+    }
+
+    #[test]
+    fn test_teams_win_together() {
+        let mut gs = with_teams(0, true);
+        let kills_to_win = gs.game.settings.kills_to_win;
+        gs.player_states.get_mut(PlayerId(0)).unwrap().kills = kills_to_win - 2;
+        gs.player_states.get_mut(PlayerId(2)).unwrap().kills = 1;
+        gs.player_states.get_mut(PlayerId(1)).unwrap().kills = kills_to_win - 1;
+        assert_eq!(gs.outcome(), Outcome::Ongoing);
+        assert_eq!(gs.results().winner, None, "the teams are tied");
+
+        gs.player_states.get_mut(PlayerId(2)).unwrap().kills = 2;
+        assert_eq!(
+            gs.outcome(),
+            Outcome::Winner(PlayerId(0)),
+            "the kills of a team add up, the best of the team wins"
+        );
+        assert_eq!(gs.results().winning_team, Some(0));
+
+        let mut gs = with_teams(1, true);
+        bomb_player_by(&mut gs, 0, 1);
+        assert_eq!(
+            gs.outcome(),
+            Outcome::Ongoing,
+            "player 3 still stands for team 1"
+        );
+        bomb_player_by(&mut gs, 2, 3);
+        assert_ne!(gs.outcome(), Outcome::Ongoing, "one team is left");
+        assert_eq!(gs.results().winning_team, Some(0));
+    }
+
+    #[test]
+    fn test_one_player_game_ends_when_out_of_lives() {
+        let mut gs = with_lives(1, 2);
+        assert_eq!(gs.outcome(), Outcome::Ongoing, "alive on the start point");
+        gs.simulate_1_update();
+        assert_eq!(gs.outcome(), Outcome::Ongoing);
+
+        bomb_player(&mut gs, 0);
+        assert_eq!(gs.living_players(), [PlayerId(0)], "one life left");
+        assert_eq!(gs.outcome(), Outcome::Ongoing);
+
+        bomb_player(&mut gs, 0);
+        assert!(gs.living_players().is_empty());
+        assert_eq!(gs.outcome(), Outcome::Draw);
+    }
+
+    #[test]
+    fn test_last_players_dying_together_is_a_draw() {
+        let mut gs = with_lives(2, 1);
+        let position = gs.player_states[PlayerId(0)].position;
+        gs.player_states.get_mut(PlayerId(1)).unwrap().position = position;
+
+        bomb_player(&mut gs, 0);
+        assert!(gs.living_players().is_empty());
+        assert_eq!(gs.outcome(), Outcome::Draw);
+    }
+
+    #[test]
+    fn test_last_player_standing_wins() {
+        let mut gs = with_lives(3, 1);
+        gs.player_states.get_mut(PlayerId(0)).unwrap().kills = 3;
+
+        bomb_player(&mut gs, 0);
+        assert_eq!(gs.living_players(), [PlayerId(1), PlayerId(2)]);
+        assert_eq!(gs.outcome(), Outcome::Ongoing);
+        let position = gs.player_states[PlayerId(0)].position;
+        bomb_player(&mut gs, 0);
+        assert_eq!(
+            gs.player_states[PlayerId(0)].deaths,
+            1,
+            "players out of lives do not die again"
+        );
+        assert_eq!(gs.player_states[PlayerId(0)].position, position);
+
+        bomb_player(&mut gs, 1);
+        assert_eq!(
+            gs.outcome(),
+            Outcome::Winner(PlayerId(2)),
+            "one player is left, despite fewer kills"
+        );
+        assert_eq!(gs.results().standings[0].player, PlayerId(2));
+    }
+
+    fn with_teleports(teleports_enabled: bool) -> GameState {
+        let mut gs = game();
+        let mut game_static = (*gs.game).clone();
+        game_static.settings.teleports_enabled = teleports_enabled;
+        gs.game = Rc::new(game_static);
+        count_placed_bombs(&mut gs);
+        gs
+    }
+
+    #[test]
+    fn test_disabled_teleports_do_not_port() {
+        let walk_east = |teleports_enabled| {
+            let mut gs = with_teleports(teleports_enabled);
+            gs.field = Field::new_from_string_grid("_T_T_").unwrap();
+            gs.player_states.get_mut(PlayerId(0)).unwrap().position = Position::new(50, 50);
+            gs.set_player_action(
+                PlayerId(0),
+                Action {
+                    walking: Some(Direction::East),
+                    diagonal: None,
+                    placing: false,
+                    detonate: false,
+                },
+            );
+            for _ in 0..30 {
+                gs.simulate_1_update();
+            }
+            gs
+        };
+
+        let gs = walk_east(true);
+        assert!(field_looks_equal(&gs.field, "_____"), "both teleports used");
+        assert!(gs.player_states[PlayerId(0)].position.x >= 300);
+
+        let gs = walk_east(false);
+        assert!(field_looks_equal(&gs.field, "_T_T_"));
+        assert!(
+            gs.player_states[PlayerId(0)].position.x < 100,
+            "teleport blocks the way"
+        );
+    }
+
+    #[test]
+    fn test_impossible_actions() {
+        let mut gs = game();
+        let action = |walking, diagonal, placing| Action {
+            walking,
+            diagonal,
+            placing,
+            detonate: false,
+        };
+        let north_east = action(Some(Direction::North), Some(Direction::East), false);
+        assert_eq!(
+            gs.impossible_action(PlayerId(0), north_east),
+            Some(ImpossibleAction::DiagonalWalkingDisabled)
+        );
+
+        let mut game_static = (*gs.game).clone();
+        game_static.settings.diagonal_walking = true;
+        gs.game = Rc::new(game_static);
+        assert_eq!(gs.impossible_action(PlayerId(0), north_east), None);
+        for diagonal in [
+            action(Some(Direction::North), Some(Direction::South), false),
+            action(None, Some(Direction::East), false),
+        ] {
+            assert_eq!(
+                gs.impossible_action(PlayerId(0), diagonal),
+                Some(ImpossibleAction::DiagonalNotPerpendicular)
+            );
+        }
+
+        // `game` placed all bombs
+        let place = action(None, None, true);
+        assert_eq!(
+            gs.impossible_action(PlayerId(0), place),
+            Some(ImpossibleAction::OverCapacityPlacement)
+        );
+        gs.set_player_action(PlayerId(0), place);
+        assert_eq!(
+            gs.impossible_action(PlayerId(0), place),
+            None,
+            "holding the key while out of bombs"
+        );
+    }
+
+    #[test]
+    fn test_each_upgrade_caps_independently() {
+        let settings = Settings {
+            max_power: 2,
+            max_speed: 3,
+            max_bombs: 0,
+            ..Settings::default()
+        };
+        let mut player = PlayerState::new(Position::new(50, 50));
+        for _ in 0..5 {
+            for upgrade in [
+                Upgrade::Power,
+                Upgrade::Speed,
+                Upgrade::Bombs,
+                Upgrade::Remote,
+                Upgrade::Kick,
+                Upgrade::Pierce,
+            ] {
+                player.eat(upgrade, &settings);
+            }
+        }
+        assert_eq!((player.power, player.speed, player.bombs), (2, 3, 6));
+        assert!(player.has_remote);
+        assert!(player.has_kick);
+        assert!(player.has_pierce);
+        assert_eq!(player.upgrades_collected, 30);
+        assert_eq!(player.upgrade_points, 0, "capped upgrades are lost");
+    }
+
+    #[test]
+    fn test_capped_upgrades_score() {
+        let settings = Settings {
+            max_power: 2,
+            capped_upgrades_score: true,
+            ..Settings::default()
+        };
+        let mut player = PlayerState::new(Position::new(50, 50));
+        for _ in 0..4 {
+            player.eat(Upgrade::Power, &settings);
+        }
+        player.eat(Upgrade::Speed, &settings);
+        assert_eq!((player.power, player.speed), (2, 2));
+        assert_eq!(player.upgrade_points, 3);
+    }
+
+    #[test]
+    fn test_upgrade_points_break_ties() {
+        let mut gs = with_lives(2, 0);
+        assert_eq!(gs.results().winner, None, "tied");
+
+        gs.player_states
+            .get_mut(PlayerId(1))
+            .unwrap()
+            .upgrade_points = 1;
+        assert_eq!(gs.results().winner, Some(PlayerId(1)));
+        assert_eq!(gs.results().standings[0].player, PlayerId(1));
+
+        gs.player_states.get_mut(PlayerId(0)).unwrap().kills = 1;
+        assert_eq!(gs.results().winner, Some(PlayerId(0)), "kills count more");
+    }
+
+    #[test]
+    fn test_death_penalty() {
+        let upgrades_after_death = |penalty| {
+            let mut player = PlayerState::new(Position::new(50, 50));
+            player.power = 5;
+            player.speed = 4;
+            player.bombs = 1;
+            player.die(PlayerId(1), Position::new(150, 50), penalty);
+            assert_eq!(player.position, Position::new(150, 50));
+            (player.power, player.speed, player.bombs)
+        };
+        assert_eq!(upgrades_after_death(DeathPenalty::ResetToBase), (1, 1, 1));
+        assert_eq!(upgrades_after_death(DeathPenalty::HalveUpgrades), (2, 2, 1));
+        assert_eq!(upgrades_after_death(DeathPenalty::KeepAll), (5, 4, 1));
+    }
+
+    /// Walk player 0 in `direction` until it reaches column `until`, the columns it was in
+    fn columns_walked(gs: &mut GameState, direction: Direction, until: i32) -> Vec<i32> {
+        gs.set_player_action(
+            PlayerId(0),
+            Action {
+                walking: Some(direction),
+                diagonal: None,
+                placing: false,
+                detonate: false,
+            },
+        );
+        let column = |gs: &GameState| gs.player_states[PlayerId(0)].position.as_cell_pos().x;
+        let mut columns = vec![column(gs)];
+        for _ in 0..500 {
+            gs.simulate_1_update();
+            if column(gs) != *columns.last().unwrap() {
+                columns.push(column(gs));
+            }
+            if column(gs) == until {
+                return columns;
+            }
+        }
+        panic!("did not reach {until}: {columns:?}");
+    }
+
+    #[test]
+    fn test_teleports_stay_unless_consumed() {
+        let walk_there_and_back = |teleport_consumed| {
+            let mut gs = with_teleports(true);
+            let mut game_static = (*gs.game).clone();
+            game_static.settings.teleport_consumed = teleport_consumed;
+            gs.game = Rc::new(game_static);
+            gs.field = Field::new_from_string_grid("_T_T_").unwrap();
+            gs.player_states.get_mut(PlayerId(0)).unwrap().position = Position::new(50, 50);
+            let there = columns_walked(&mut gs, Direction::East, 4);
+            let field = gs.field.clone();
+            let back = columns_walked(&mut gs, Direction::West, 0);
+            (there, field, back)
+        };
+
+        let (there, field, back) = walk_there_and_back(true);
+        assert_eq!(there, [0, 3, 4]);
+        assert!(field_looks_equal(&field, "_____"));
+        assert_eq!(back, [4, 3, 2, 1, 0], "no teleports left");
+
+        let (there, field, back) = walk_there_and_back(false);
+        assert_eq!(there, [0, 3, 4], "walked off the teleport it ported to");
+        assert!(field_looks_equal(&field, "_T_T_"));
+        assert_eq!(back, [4, 1, 0], "ported again");
+    }
+
+    /// Player 0 and 1 walk towards each other onto both ends of a teleport pair, player 0 from
+    /// the west if `player_0_from_west`
+    ///
+    /// Returns the field and the cells of player 0 and 1 after the update the teleports were used
+    /// in.
+    fn step_onto_teleport_pair(player_0_from_west: bool) -> (Field, CellPosition, CellPosition) {
+        let mut gs = with_teleports(true);
+        let mut game_static = (*gs.game).clone();
+        let player = Player::new("test player 2".to_owned(), PlayerId(1), Position::new(0, 0));
+        gs.player_states
+            .insert(player.id, PlayerState::new(player.start_position));
+        game_static.players.insert(player.id, player);
+        gs.game = Rc::new(game_static);
+        gs.field = Field::new_from_string_grid("_T_T_").unwrap();
+
+        // the same distance from their teleport, so both reach it in the same update
+        let west = (Position::new(50, 50), Direction::East);
+        let east = (Position::new(449, 50), Direction::West);
+        let starts = if player_0_from_west {
+            [west, east]
+        } else {
+            [east, west]
+        };
+        for (id, (position, direction)) in starts.into_iter().enumerate() {
+            gs.player_states.get_mut(PlayerId(id)).unwrap().position = position;
+            gs.set_player_action(
+                PlayerId(id),
+                Action {
+                    walking: Some(direction),
+                    diagonal: None,
+                    placing: false,
+                    detonate: false,
+                },
+            );
+        }
+
+        for _ in 0..30 {
+            gs.simulate_1_update();
+            if !gs.field.iter().any(|(_, cell)| *cell == Cell::Teleport) {
+                let cell = |id| gs.player_states[PlayerId(id)].position.as_cell_pos();
+                return (gs.field.clone(), cell(0), cell(1));
+            }
+        }
+        panic!("nobody used the teleports");
+    }
+
+    #[test]
+    fn test_simultaneous_teleport_use_ports_the_lower_player_id() {
+        let (field, player_0, player_1) = step_onto_teleport_pair(true);
+        assert!(field_looks_equal(&field, "_____"), "both teleports used");
+        assert_eq!(player_0, CellPosition::new(3, 0), "ported to the other end");
+        assert_eq!(
+            player_1,
+            CellPosition::new(3, 0),
+            "walked onto the used end"
+        );
+
+        let (field, player_0, player_1) = step_onto_teleport_pair(false);
+        assert!(field_looks_equal(&field, "_____"));
+        assert_eq!(
+            player_0,
+            CellPosition::new(1, 0),
+            "the side does not matter"
+        );
+        assert_eq!(player_1, CellPosition::new(1, 0));
+    }
+
+    #[test]
+    fn test_disabled_teleports_do_not_tunnel_fire() {
+        let grid = "
+            BT___
+            _____
+            ____T
+        ";
+        let far_teleport = CellPosition::new(4, 2);
+
+        let mut gs = with_teleports(true);
+        gs.field = Field::new_from_string_grid(grid).unwrap();
+        count_placed_bombs(&mut gs);
+        gs.update_field();
+        assert!(matches!(gs.field[far_teleport], Cell::Fire { .. }));
+
+        let mut gs = with_teleports(false);
+        gs.field = Field::new_from_string_grid(grid).unwrap();
+        count_placed_bombs(&mut gs);
+        gs.update_field();
+        // the player standing on the bomb dies
+        let expected = "
+            DFFF_
+            F____
+            F___T
+            ";
+        assert!(field_looks_equal(&gs.field, expected));
+    }
+
+    #[test]
+    fn test_players_slide_around_corners_into_gaps() {
+        let gap = "
+            #####
+            ##_##
+            ##_##
+            #___#
+            #####
+        ";
+        let walk_north_from = |x| {
+            let player = Player::new("Hans".to_owned(), PlayerId(0), Position::new(x, 350));
+            let game = GameStatic {
+                players: BTreeMap::from([(PlayerId(0), player)]),
+                settings: Settings {
+                    countdown_s: 0,
+                    ..Settings::default()
+                },
+                local_player: PlayerId(0),
+            };
+            let mut gs = GameState::new(Rc::new(game));
+            gs.field = Field::new_from_string_grid(gap).unwrap();
+            gs.set_player_action(
+                PlayerId(0),
+                Action {
+                    walking: Some(Direction::North),
+                    diagonal: None,
+                    placing: false,
+                    detonate: false,
+                },
+            );
+            let mut positions = Vec::new();
+            for _ in 0..100 {
+                gs.simulate_1_update();
+                positions.push(gs.player_states[PlayerId(0)].position);
+            }
+            positions
+        };
+
+        let positions = walk_north_from(205);
+        assert_eq!(positions[0].x, 220, "slides away from the left corner");
+        assert!(
+            positions.iter().all(|p| p.x == 220),
+            "and then walks straight"
+        );
+        assert_eq!(
+            positions.last().unwrap().as_cell_pos(),
+            CellPosition::new(2, 1),
+            "through the gap up to the wall"
+        );
+
+        let positions = walk_north_from(290);
+        assert_eq!(positions[0].x, 280, "slides away from the right corner");
+        assert_eq!(
+            positions.last().unwrap().as_cell_pos(),
+            CellPosition::new(2, 1)
+        );
+
+        let positions = walk_north_from(250);
+        assert!(
+            positions.iter().all(|p| p.x == 250),
+            "centered players walk straight"
+        );
+    }
+
+    #[test]
+    fn test_diagonal_walking_stops_at_walls_per_axis() {
+        let walled_room = "
+            ######
+            #____#
+            #____#
+            #____#
+            ######
+        ";
+        let walk = |diagonal_walking| {
+            let player = Player::new("Hans".to_owned(), PlayerId(0), Position::new(150, 150));
+            let game = GameStatic {
+                players: BTreeMap::from([(PlayerId(0), player)]),
+                settings: Settings {
+                    diagonal_walking,
+                    countdown_s: 0,
+                    ..Settings::default()
+                },
+                local_player: PlayerId(0),
+            };
+            let mut gs = GameState::new(Rc::new(game));
+            gs.field = Field::new_from_string_grid(walled_room).unwrap();
+            gs.set_player_action(
+                PlayerId(0),
+                Action {
+                    walking: Some(Direction::East),
+                    diagonal: Some(Direction::North),
+                    placing: false,
+                    detonate: false,
+                },
+            );
+            let mut positions = Vec::new();
+            for _ in 0..200 {
+                gs.simulate_1_update();
+                positions.push(gs.player_states[PlayerId(0)].position);
+            }
+            positions
+        };
+
+        let positions = walk(true);
+        assert_eq!(positions[0], Position::new(153, 147), "both axes move");
+        // the wall to the north stops only the northward part
+        assert_eq!(positions[20], Position::new(213, 120));
+        // then the wall to the east stops the rest
+        assert_eq!(positions[199], Position::new(480, 120));
+
+        let positions = walk(false);
+        assert_eq!(positions[0], Position::new(153, 150), "diagonal ignored");
+        assert_eq!(positions[199], Position::new(480, 150));
+    }
 }
@@ -2,6 +2,7 @@ pub mod field;
 pub mod game_state;
 pub mod network;
 pub mod settings;
+pub mod simulation;
 pub mod utils;
 
 pub static VERSION: &str = env!("VERSION");
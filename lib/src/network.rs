@@ -1,10 +1,20 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::time::Instant;
+
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::field::Field;
 use crate::game_state::Action;
 use crate::game_state::GameStatic;
+use crate::game_state::Player;
+use crate::game_state::Results;
+use crate::settings::Settings;
 use crate::utils::PlayerId;
 use crate::utils::TimeStamp;
+use crate::utils::TIME_PER_TICK;
 
 pub const BOMBERHANS_MAGIC_NO_V1: u32 = 0x1f4a3__001; // 💣
 
@@ -25,7 +35,42 @@ impl GameId {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Secret that the server's operators send with admin requests
+#[derive(Clone, Eq, Serialize, Deserialize)]
+pub struct AdminToken(pub String);
+
+impl PartialEq for AdminToken {
+    /// Compares every byte, so how long it takes does not tell how much of a guess was right
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = (self.0.as_bytes(), other.0.as_bytes());
+        a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+    }
+}
+
+impl std::fmt::Debug for AdminToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // messages are logged, the token should not be
+        write!(f, "AdminToken(..)")
+    }
+}
+
+/// Number of a message that is retransmitted until the receiver acknowledges it
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+pub struct PacketNumber(u32);
+
+impl PacketNumber {
+    pub fn new(val: u32) -> Self {
+        Self(val)
+    }
+
+    /// The number of the message sent after this one
+    #[must_use]
+    pub fn next(self) -> Self {
+        Self(self.0.wrapping_add(1))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ClientHello {
     /// Identifying the protocol
     pub magic: u32,
@@ -38,7 +83,21 @@ pub struct ClientHello {
     pub player_name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl ClientHello {
+    /// Longest player name in bytes, so 8 players and the settings fit one lobby update
+    pub const MAX_PLAYER_NAME_LEN: usize = 16;
+
+    /// The player's name, cut at a char boundary to at most `MAX_PLAYER_NAME_LEN` bytes
+    pub fn capped_player_name(&self) -> &str {
+        let mut end = self.player_name.len().min(Self::MAX_PLAYER_NAME_LEN);
+        while !self.player_name.is_char_boundary(end) {
+            end -= 1;
+        }
+        &self.player_name[..end]
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ServerHello {
     /// nonce of the ClientHello
     pub clients_nonce: u32,
@@ -51,20 +110,190 @@ pub struct ServerHello {
     pub lobbies: Vec<(GameId, String)>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ClientJoinLobby {
+    pub client_id: ClientId,
+
     pub lobby: GameId,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Why the server sends a client away
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisconnectReason {
+    /// The lobby has as many players as its settings allow
+    GameFull,
+    /// The game is already running, joining is only possible in the lobby
+    GameStarted,
+    /// There is no lobby with that id, maybe it was closed
+    UnknownGame,
+    /// An admin closed the lobby or game the client was in
+    GameClosed,
+    /// The game can not start, its field has fewer start points than the settings allow
+    /// players. Everyone stays in the lobby for the host to change the settings.
+    NotEnoughStartPoints,
+    /// The host's new settings are not playable, the lobby keeps its old ones
+    InvalidSettings,
+    /// The game already started, its settings only change in the lobby again
+    SettingsLocked,
+    /// The server has as many clients as it takes, connecting again later might work
+    ServerFull,
+    /// The client sent too many actions that an unmodified client does not send
+    Kicked,
+}
+
+impl DisconnectReason {
+    /// Whether the client left its lobby or game, otherwise only its request was rejected
+    pub fn ends_game(self) -> bool {
+        !matches!(
+            self,
+            DisconnectReason::NotEnoughStartPoints
+                | DisconnectReason::InvalidSettings
+                | DisconnectReason::SettingsLocked
+        )
+    }
+}
+
+impl std::fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisconnectReason::GameFull => write!(f, "Game Full"),
+            DisconnectReason::GameStarted => write!(f, "Game Started"),
+            DisconnectReason::UnknownGame => write!(f, "Game does not exist"),
+            DisconnectReason::GameClosed => write!(f, "Game was closed by the server"),
+            DisconnectReason::NotEnoughStartPoints => write!(
+                f,
+                "The field has not enough start points for all players, change the size or players"
+            ),
+            DisconnectReason::InvalidSettings => {
+                write!(f, "The server rejected the settings, the old ones stay")
+            }
+            DisconnectReason::SettingsLocked => {
+                write!(f, "The game already started, its settings are locked")
+            }
+            DisconnectReason::ServerFull => write!(f, "Server Full, try again later"),
+            DisconnectReason::Kicked => write!(f, "Kicked for suspected cheating"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ServerLobbyUpdate {
-    client_player_id: PlayerId,
+    pub client_player_id: PlayerId,
+
+    pub game: GameStatic,
+
+    /// Players that are ready to start the game
+    pub players_ready: BTreeSet<PlayerId>,
+
+    /// Checksum of the field the server generates from the settings
+    pub field_checksum: u32,
+}
+
+impl ServerLobbyUpdate {
+    pub fn new(
+        client_player_id: PlayerId,
+        game: GameStatic,
+        players_ready: BTreeSet<PlayerId>,
+    ) -> Self {
+        let field_checksum = Field::new_from_rules(&game.settings).checksum();
+        Self {
+            client_player_id,
+            game,
+            players_ready,
+            field_checksum,
+        }
+    }
+
+    /// Does the field we generate from the settings match the server's
+    ///
+    /// If not, the server runs a different version and the game would desync right away.
+    pub fn field_matches(&self) -> bool {
+        Field::new_from_rules(&self.game.settings).checksum() == self.field_checksum
+    }
+}
+
+/// The server started the game, clients stop showing the lobby and start simulating
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerGameStart {
+    pub game: GameStatic,
+
+    /// Server time when this was sent, so clients start their clock at the same tick even if
+    /// this message took longer to reach some of them
+    pub time: TimeStamp,
+}
+
+/// What the server knows about a lobby or running game, for admins
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdminGameInfo {
+    pub game_id: GameId,
+
+    /// Whether the game is running or still a lobby
+    pub started: bool,
+
+    pub players: BTreeMap<PlayerId, Player>,
+
+    /// Current server time of the game, 0 in lobbies
+    pub time: TimeStamp,
 
-    game: GameStatic,
+    pub settings: Settings,
+}
+
+/// Wins of the players in a tournament of `best_of` games
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TournamentStatus {
+    pub best_of: u32,
+
+    /// Games each player won, players without wins are missing
+    pub wins: BTreeMap<PlayerId, u32>,
+
+    /// Games played so far, including draws
+    pub games_played: u32,
+
+    /// `Some` once the tournament is decided
+    pub winner: Option<PlayerId>,
+}
+
+impl TournamentStatus {
+    pub fn new(best_of: u32) -> Self {
+        Self {
+            best_of,
+            wins: BTreeMap::new(),
+            games_played: 0,
+            winner: None,
+        }
+    }
+
+    /// Count a finished game, `winner` is `None` for a draw
+    ///
+    /// The tournament is won by the first player with more than half of `best_of` wins. Once
+    /// `best_of` games had a winner, the player with the most wins wins, if several are tied,
+    /// they play on until one of them wins another game.
+    pub fn record(&mut self, winner: Option<PlayerId>) {
+        self.games_played += 1;
+        if let Some(winner) = winner {
+            *self.wins.entry(winner).or_default() += 1;
+        }
+
+        let Some(most) = self.wins.values().copied().max() else {
+            return;
+        };
+        let leaders: Vec<PlayerId> = self
+            .wins
+            .iter()
+            .filter(|(_, &wins)| wins == most)
+            .map(|(&player, _)| player)
+            .collect();
+        let decided_games: u32 = self.wins.values().sum();
+        if let [leader] = leaders[..] {
+            if most > self.best_of / 2 || decided_games >= self.best_of {
+                self.winner = Some(leader);
+            }
+        }
+    }
 }
 
 /// Periodic Client to Server update
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ClientUpdate {
     pub client_id: ClientId,
 
@@ -79,7 +308,7 @@ pub struct ClientUpdate {
 }
 
 /// Periodic Server to Client update
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ServerUpdate {
     /// Current Server Time
     pub time: TimeStamp,
@@ -92,7 +321,7 @@ pub struct ServerUpdate {
 }
 
 /// An Update is when the player changed their current action
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Update {
     pub player: PlayerId,
     pub action: Action,
@@ -100,20 +329,208 @@ pub struct Update {
 }
 
 /// A Message from Client to Server
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ClientMessage {
     Hello(ClientHello),
     OpenNewLobby(ClientId),
+    /// Ask for the current state of the client's lobby again, to resync
+    GetLobby(ClientId),
+    /// Send all updates of the client's game again, so a desynced client can replay it from
+    /// the start
+    GetGame(ClientId),
+    JoinLobby(ClientJoinLobby),
+    /// The player is (not) ready, the game starts when enough players are
+    SetReady(ClientId, bool),
+    /// The host starts the game with the players that are ready, the others are dropped
+    ForceStart(ClientId),
+    /// The host changes the lobby's settings
+    SetSettings(ClientId, Box<Settings>),
     Update(ClientUpdate),
+    /// Sent periodically, so the server knows the client is still there even without updates
+    KeepAlive(ClientId),
+    /// Send updates at most this often per second, to save bandwidth. 0 for every tick
+    ///
+    /// The client's deterministic simulation fills the ticks in between, so only the other
+    /// players' actions arrive later and nothing needs to be interpolated.
+    SetUpdateRate(ClientId, u32),
+    /// The client received `GameOver`, the server can stop sending it
+    GameOverAck(ClientId),
     Bye(ClientId),
+    /// A message that the server has to acknowledge
+    Reliable(PacketNumber, Box<ClientMessage>),
+    /// List all lobbies and games, only for admins
+    AdminListGames(AdminToken),
+    /// Inspect one lobby or game, only for admins
+    AdminGameInfo(AdminToken, GameId),
+    /// End a lobby or game and send its players away, only for admins
+    AdminCloseGame(AdminToken, GameId),
+}
+
+impl ClientMessage {
+    /// The client that sent this message, `None` before the server assigned an id and for
+    /// admin requests
+    pub fn client_id(&self) -> Option<ClientId> {
+        match self {
+            ClientMessage::Hello(_)
+            | ClientMessage::AdminListGames(_)
+            | ClientMessage::AdminGameInfo(..)
+            | ClientMessage::AdminCloseGame(..) => None,
+            ClientMessage::OpenNewLobby(client_id)
+            | ClientMessage::GetLobby(client_id)
+            | ClientMessage::GetGame(client_id)
+            | ClientMessage::JoinLobby(ClientJoinLobby { client_id, .. })
+            | ClientMessage::SetReady(client_id, _)
+            | ClientMessage::ForceStart(client_id)
+            | ClientMessage::SetSettings(client_id, _)
+            | ClientMessage::Update(ClientUpdate { client_id, .. })
+            | ClientMessage::KeepAlive(client_id)
+            | ClientMessage::SetUpdateRate(client_id, _)
+            | ClientMessage::GameOverAck(client_id)
+            | ClientMessage::Bye(client_id) => Some(*client_id),
+            ClientMessage::Reliable(_, message) => message.client_id(),
+        }
+    }
 }
 
 /// A Message from Server to Client
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ServerMessage {
     Hello(ServerHello),
     Update(ServerUpdate),
     LobbyUpdate(ServerLobbyUpdate),
+    GameStart(ServerGameStart),
+    /// The server received the client's `Reliable` message
+    Ack(PacketNumber),
+    /// The server received the client's `Bye`, the client can stop sending it
+    ByeAck,
+    /// The server rejects the client's request to join a game
+    Bye(DisconnectReason),
+    /// The game ended, with the final standings. Sent again until the client sends
+    /// `GameOverAck`
+    GameOver(Results),
+    /// The wins so far, sent after each game of a tournament
+    TournamentStatus(TournamentStatus),
+    /// All lobbies and games, with their names
+    AdminGames(Vec<(GameId, String)>),
+    AdminGameInfo(AdminGameInfo),
+    /// The admin token is wrong, or the server has none
+    AdminRejected,
+}
+
+/// Messages that are retransmitted until they are acknowledged
+///
+/// Each message has its own retransmit timer, so a message does not delay others.
+#[derive(Debug)]
+pub struct ReliableQueue<M> {
+    next_packet_number: u32,
+
+    retransmit_interval: std::time::Duration,
+
+    /// Unacknowledged messages and when to send them again
+    unacknowledged: BTreeMap<PacketNumber, (Instant, M)>,
+}
+
+impl<M: Clone> ReliableQueue<M> {
+    pub fn new(retransmit_interval: std::time::Duration) -> Self {
+        Self {
+            next_packet_number: 0,
+            retransmit_interval,
+            unacknowledged: BTreeMap::new(),
+        }
+    }
+
+    /// Add a message, which should be sent right away with the returned number
+    pub fn push(&mut self, message: M, now: Instant) -> PacketNumber {
+        let packet_number = PacketNumber(self.next_packet_number);
+        self.next_packet_number = self.next_packet_number.wrapping_add(1);
+        self.unacknowledged
+            .insert(packet_number, (now + self.retransmit_interval, message));
+        packet_number
+    }
+
+    /// Replace unacknowledged copies of `message` by it with a new number, which should be sent
+    /// right away
+    ///
+    /// The receiver only acknowledges numbers it already handled, under a new number it handles
+    /// the message again in case just its answer got lost.
+    pub fn resend(&mut self, message: M, now: Instant) -> PacketNumber
+    where
+        M: PartialEq,
+    {
+        self.unacknowledged
+            .retain(|_, (_, unacknowledged)| *unacknowledged != message);
+        self.push(message, now)
+    }
+
+    /// Stop retransmitting an acknowledged message
+    pub fn acknowledge(&mut self, packet_number: PacketNumber) {
+        self.unacknowledged.remove(&packet_number);
+    }
+
+    /// Messages that should be retransmitted now
+    pub fn due(&mut self, now: Instant) -> Vec<(PacketNumber, M)> {
+        self.unacknowledged
+            .iter_mut()
+            .filter(|(_, (retransmit_time, _))| *retransmit_time <= now)
+            .map(|(packet_number, (retransmit_time, message))| {
+                *retransmit_time = now + self.retransmit_interval;
+                (*packet_number, message.clone())
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.unacknowledged.is_empty()
+    }
+}
+
+/// Holds back messages stamped with server time, to release them on a steady cadence
+///
+/// Each message is released `delay_ticks` after the earliest moment it could have arrived, so
+/// messages that arrive late by less than that still come out evenly spaced.
+#[derive(Debug)]
+pub struct JitterBuffer<M> {
+    delay: std::time::Duration,
+
+    /// When the server was at tick 0, by the fastest message so far
+    clock_start: Option<Instant>,
+
+    /// Messages that are not released yet, ordered by time
+    pending: VecDeque<(TimeStamp, M)>,
+}
+
+impl<M> JitterBuffer<M> {
+    pub fn new(delay_ticks: u32) -> Self {
+        Self {
+            delay: TIME_PER_TICK * delay_ticks,
+            clock_start: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Add a message the server sent at `time`, which was `received` at
+    pub fn push(&mut self, time: TimeStamp, message: M, received: Instant) {
+        if let Some(start) = received.checked_sub(TIME_PER_TICK * time.ticks_from_start()) {
+            self.clock_start = Some(self.clock_start.map_or(start, |s| s.min(start)));
+        }
+        let index = self.pending.partition_point(|(t, _)| *t <= time);
+        self.pending.insert(index, (time, message));
+    }
+
+    /// Messages that are due at `now`, oldest first
+    pub fn release(&mut self, now: Instant) -> Vec<M> {
+        let Some(start) = self.clock_start else {
+            return Vec::new();
+        };
+        let mut released = Vec::new();
+        while let Some((time, _)) = self.pending.front() {
+            if start + TIME_PER_TICK * time.ticks_from_start() + self.delay > now {
+                break;
+            }
+            released.extend(self.pending.pop_front().map(|(_, message)| message));
+        }
+        released
+    }
 }
 
 pub fn encode<S>(value: &S) -> Vec<u8>
@@ -126,6 +543,457 @@ where
     result
 }
 
+/// The number of bytes `encode` makes of `value`, without asserting that they fit a datagram
+pub fn encoded_len<S: Serialize>(value: &S) -> usize {
+    postcard::to_allocvec(value).map_or(usize::MAX, |bytes| bytes.len())
+}
+
 pub fn decode<T: for<'a> Deserialize<'a>>(data: &[u8]) -> Option<T> {
     postcard::from_bytes::<T>(&data).ok()
 }
+
+/// The URL safe base64 alphabet, its strings survive chat programs and URLs
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// `encode` into an unpadded base64 string that players can copy and paste
+pub fn encode_base64<S>(value: &S) -> String
+where
+    S: Serialize,
+    S: std::fmt::Debug,
+{
+    let bytes = encode(value);
+    let mut string = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0_u32, |bits, (i, &byte)| {
+            bits | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..=chunk.len() {
+            let sextet = (bits >> (18 - 6 * i)) & 0x3f;
+            string.push(char::from(BASE64_ALPHABET[sextet as usize]));
+        }
+    }
+    string
+}
+
+/// Decode a string from `encode_base64`, `None` if it is not valid
+pub fn decode_base64<T: for<'a> Deserialize<'a>>(string: &str) -> Option<T> {
+    let string = string.trim().as_bytes();
+    if string.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(string.len() / 4 * 3 + 2);
+    for chunk in string.chunks(4) {
+        let mut bits = 0_u32;
+        for (i, chr) in chunk.iter().enumerate() {
+            let sextet = BASE64_ALPHABET.iter().position(|c| c == chr)?;
+            bits |= (sextet as u32) << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            bytes.push((bits >> (16 - 8 * i)) as u8);
+        }
+    }
+    decode(&bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::game_state::Standing;
+    use crate::utils::CellPosition;
+    use crate::utils::Direction;
+    use crate::utils::Duration;
+    use crate::utils::Position;
+
+    fn assert_round_trip<T>(value: &T)
+    where
+        T: Serialize + for<'a> Deserialize<'a> + PartialEq + std::fmt::Debug,
+    {
+        let data = encode(value);
+        assert_eq!(decode::<T>(&data).as_ref(), Some(value));
+    }
+
+    fn time(ticks: u32) -> TimeStamp {
+        TimeStamp::default() + Duration::from_ticks(ticks)
+    }
+
+    #[test]
+    fn test_server_update_eq() {
+        let update = || ServerUpdate {
+            time: time(100),
+            checksum: 42,
+            updates: vec![Update {
+                player: PlayerId(0),
+                action: Action {
+                    walking: Some(Direction::North),
+                    diagonal: None,
+                    placing: false,
+                    detonate: false,
+                },
+                time: time(99),
+            }],
+        };
+        assert_eq!(update(), update());
+
+        let mut other = update();
+        other.checksum = 43;
+        assert_ne!(update(), other);
+
+        let mut other = update();
+        other.updates[0].action.placing = true;
+        assert_ne!(update(), other);
+    }
+
+    #[test]
+    fn test_client_messages_round_trip() {
+        let client_id = ClientId::new(0x1f4a3_1f4a3_1f4a3);
+        let messages = [
+            ClientMessage::Hello(ClientHello {
+                magic: BOMBERHANS_MAGIC_NO_V1,
+                nonce: 4267,
+                player_name: "Hans 💣".to_owned(),
+            }),
+            ClientMessage::OpenNewLobby(client_id),
+            ClientMessage::GetLobby(client_id),
+            ClientMessage::GetGame(client_id),
+            ClientMessage::JoinLobby(ClientJoinLobby {
+                client_id,
+                lobby: GameId::new(42),
+            }),
+            ClientMessage::SetReady(client_id, true),
+            ClientMessage::ForceStart(client_id),
+            ClientMessage::Update(ClientUpdate {
+                client_id,
+                last_server_update: time(42),
+                current_player_action: Action {
+                    walking: Some(Direction::West),
+                    diagonal: None,
+                    placing: true,
+                    detonate: false,
+                },
+                current_action_start_time: time(40),
+            }),
+            ClientMessage::KeepAlive(client_id),
+            ClientMessage::SetUpdateRate(client_id, 4),
+            ClientMessage::GameOverAck(client_id),
+            ClientMessage::Bye(client_id),
+            ClientMessage::Reliable(
+                PacketNumber(7),
+                Box::new(ClientMessage::OpenNewLobby(client_id)),
+            ),
+            ClientMessage::AdminListGames(AdminToken("secret".to_owned())),
+            ClientMessage::AdminGameInfo(AdminToken("secret".to_owned()), GameId::new(42)),
+            ClientMessage::AdminCloseGame(AdminToken("secret".to_owned()), GameId::new(42)),
+        ];
+
+        for message in &messages {
+            assert_round_trip(message);
+        }
+    }
+
+    #[test]
+    fn test_capped_player_name() {
+        let hello = |name: &str| ClientHello {
+            magic: BOMBERHANS_MAGIC_NO_V1,
+            nonce: 1,
+            player_name: name.to_owned(),
+        };
+        assert_eq!(hello("Hans").capped_player_name(), "Hans");
+        assert_eq!(
+            hello("Player Number 0abcde").capped_player_name(),
+            "Player Number 0a"
+        );
+        // the bomb takes 4 bytes and does not fit behind the 14th byte
+        assert_eq!(
+            hello("Hans Hansen H 💣").capped_player_name(),
+            "Hans Hansen H "
+        );
+    }
+
+    #[test]
+    fn test_largest_lobby_fits_a_datagram() {
+        let mut settings = Settings {
+            players: *Settings::PLAYERS_RANGE.end(),
+            custom_map: Some(String::new()),
+            ..Settings::default()
+        };
+        while encoded_len(&settings) < Settings::MAX_ENCODED_LEN {
+            settings.custom_map.as_mut().unwrap().push('_');
+        }
+        assert_eq!(encoded_len(&settings), Settings::MAX_ENCODED_LEN);
+
+        let name = "💣".repeat(ClientHello::MAX_PLAYER_NAME_LEN / 4);
+        let players = (0..*Settings::PLAYERS_RANGE.end() as usize)
+            .map(|id| {
+                let corner = CellPosition::new(
+                    *Settings::WIDTH_RANGE.end() as i32 - 1,
+                    *Settings::HEIGHT_RANGE.end() as i32 - 1,
+                );
+                let position = Position::from_cell_position(corner);
+                let mut player = Player::new(name.clone(), PlayerId(id), position);
+                player.team = Some(*Settings::TEAMS_RANGE.end() - 1);
+                (PlayerId(id), player)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let game = GameStatic {
+            players,
+            settings,
+            local_player: PlayerId(0),
+        };
+        let ready = game.players.keys().copied().collect();
+
+        let lobby_update = ServerMessage::LobbyUpdate(ServerLobbyUpdate {
+            client_player_id: PlayerId(7),
+            game: game.clone(),
+            players_ready: ready,
+            field_checksum: u32::MAX,
+        });
+        let game_start = ServerMessage::GameStart(ServerGameStart {
+            game,
+            time: time(u32::MAX),
+        });
+        assert!(
+            encoded_len(&lobby_update) < 1000,
+            "{}",
+            encoded_len(&lobby_update)
+        );
+        assert!(
+            encoded_len(&game_start) < 1000,
+            "{}",
+            encoded_len(&game_start)
+        );
+    }
+
+    #[test]
+    fn test_server_messages_round_trip() {
+        let player = Player::new("Hans".to_owned(), PlayerId(1), Position::new(150, 50));
+        let messages = [
+            ServerMessage::Hello(ServerHello {
+                clients_nonce: 4267,
+                client_id: ClientId::new(u64::MAX),
+                server_name: "HansServer".to_owned(),
+                lobbies: vec![
+                    (GameId::new(1), "A Game of Bomberhans".to_owned()),
+                    (GameId::new(2), "Another Game".to_owned()),
+                ],
+            }),
+            ServerMessage::Update(ServerUpdate {
+                time: time(100),
+                checksum: 0xdead_beef,
+                updates: vec![
+                    Update {
+                        player: PlayerId(0),
+                        action: Action {
+                            walking: None,
+                            diagonal: None,
+                            placing: true,
+                            detonate: false,
+                        },
+                        time: time(98),
+                    },
+                    Update {
+                        player: PlayerId(1),
+                        action: Action {
+                            walking: Some(Direction::South),
+                            diagonal: None,
+                            placing: false,
+                            detonate: false,
+                        },
+                        time: time(99),
+                    },
+                ],
+            }),
+            ServerMessage::LobbyUpdate(ServerLobbyUpdate::new(
+                PlayerId(1),
+                GameStatic {
+                    players: BTreeMap::from([(PlayerId(1), player)]),
+                    settings: Settings::default(),
+                    local_player: PlayerId(1),
+                },
+                BTreeSet::from([PlayerId(1)]),
+            )),
+            ServerMessage::GameStart(ServerGameStart {
+                game: GameStatic {
+                    players: BTreeMap::new(),
+                    settings: Settings::default(),
+                    local_player: PlayerId(0),
+                },
+                time: time(3),
+            }),
+            ServerMessage::Ack(PacketNumber(7)),
+            ServerMessage::ByeAck,
+            ServerMessage::Bye(DisconnectReason::GameFull),
+            ServerMessage::Bye(DisconnectReason::GameStarted),
+            ServerMessage::Bye(DisconnectReason::GameClosed),
+            ServerMessage::Bye(DisconnectReason::InvalidSettings),
+            ServerMessage::Bye(DisconnectReason::SettingsLocked),
+            ServerMessage::GameOver(Results {
+                standings: vec![Standing {
+                    player: PlayerId(1),
+                    name: "Hans".to_owned(),
+                    kills: 3,
+                    deaths: 1,
+                    longest_kill_streak: 2,
+                    bombs_placed: 12,
+                    distance_walked: 80,
+                    upgrades_collected: 4,
+                    upgrade_points: 1,
+                }],
+                winner: Some(PlayerId(1)),
+                winning_team: None,
+            }),
+            ServerMessage::AdminGames(vec![(GameId::new(1), "A Game".to_owned())]),
+            ServerMessage::AdminGameInfo(AdminGameInfo {
+                game_id: GameId::new(1),
+                started: true,
+                players: BTreeMap::new(),
+                time: time(4267),
+                settings: Settings::default(),
+            }),
+            ServerMessage::TournamentStatus(TournamentStatus {
+                best_of: 3,
+                wins: BTreeMap::from([(PlayerId(1), 2), (PlayerId(3), 1)]),
+                games_played: 4,
+                winner: Some(PlayerId(1)),
+            }),
+            ServerMessage::AdminRejected,
+        ];
+
+        for message in &messages {
+            assert_round_trip(message);
+        }
+    }
+
+    #[test]
+    fn test_tournament_ties_play_on() {
+        let mut status = TournamentStatus::new(3);
+        for winner in [
+            Some(PlayerId(0)),
+            None,
+            Some(PlayerId(1)),
+            Some(PlayerId(2)),
+        ] {
+            status.record(winner);
+            assert_eq!(status.winner, None);
+        }
+        assert_eq!(status.games_played, 4);
+
+        // three players with one win each, the next winner takes it
+        status.record(Some(PlayerId(2)));
+        assert_eq!(status.winner, Some(PlayerId(2)));
+    }
+
+    #[test]
+    fn test_lobby_update_field_matches() {
+        let game = GameStatic {
+            players: BTreeMap::new(),
+            settings: Settings::default(),
+            local_player: PlayerId(0),
+        };
+        let mut update = ServerLobbyUpdate::new(PlayerId(0), game, BTreeSet::new());
+        assert!(update.field_matches());
+
+        // a server that generates fields differently
+        update.field_checksum += 1;
+        assert!(!update.field_matches());
+    }
+
+    #[test]
+    fn test_reliable_queue_retransmits_until_acknowledged() {
+        let interval = std::time::Duration::from_millis(100);
+        let start = Instant::now();
+        let mut queue = ReliableQueue::new(interval);
+
+        // both are lost on their first transmission
+        let first = queue.push("first", start);
+        let second = queue.push("second", start);
+        assert_ne!(first, second);
+        assert!(queue.due(start).is_empty());
+
+        let now = start + interval;
+        assert_eq!(queue.due(now), vec![(first, "first"), (second, "second")]);
+        assert!(queue.due(now).is_empty(), "timer restarted");
+
+        // first one arrives, its ack too
+        queue.acknowledge(first);
+        let now = now + interval;
+        assert_eq!(queue.due(now), vec![(second, "second")]);
+
+        queue.acknowledge(second);
+        assert!(queue.due(now + interval).is_empty());
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_reliable_queue_resend_replaces_the_copy() {
+        let interval = std::time::Duration::from_millis(100);
+        let start = Instant::now();
+        let mut queue = ReliableQueue::new(interval);
+
+        let first = queue.push("open", start);
+        let other = queue.push("other", start);
+        let again = queue.resend("open", start);
+        assert_ne!(first, again, "handled again by the receiver");
+        assert_eq!(
+            queue.due(start + interval),
+            vec![(other, "other"), (again, "open")]
+        );
+    }
+
+    #[test]
+    fn test_jitter_buffer_releases_steadily() {
+        let start = Instant::now();
+        let mut buffer = JitterBuffer::new(3);
+
+        // sent every tick, delayed by up to 2 ticks, some overtake each other
+        let arrival_delays = [0, 2, 1, 0, 2, 2, 0, 1];
+        let mut releases = Vec::new();
+        for now in 0..20 {
+            for (tick, delay) in arrival_delays.into_iter().enumerate() {
+                let tick = tick as u32;
+                if tick + delay == now {
+                    buffer.push(time(tick), tick, start + TIME_PER_TICK * now);
+                }
+            }
+            for message in buffer.release(start + TIME_PER_TICK * now) {
+                releases.push((now, message));
+            }
+        }
+        let expected: Vec<(u32, u32)> = (0..8).map(|tick| (tick + 3, tick)).collect();
+        assert_eq!(releases, expected, "one per tick, in order, 3 ticks late");
+    }
+
+    #[test]
+    fn test_admin_token_eq() {
+        let token = |s: &str| AdminToken(s.to_owned());
+        assert_eq!(token("secret"), token("secret"));
+        assert_ne!(token("secret"), token("secreT"));
+        assert_ne!(token("secret"), token("Secret"));
+        assert_ne!(token("secret"), token("secret!"));
+        assert_ne!(token(""), token("secret"));
+    }
+
+    #[test]
+    fn test_base64_round_trips() {
+        assert_eq!(encode_base64(b"Man"), "TWFu");
+        assert_eq!(encode_base64(&[0xfb_u8, 0xff]), "-_8");
+        for len in 0..8 {
+            let bytes: Vec<u8> = (0..len).map(|i| i * 37).collect();
+            let string = encode_base64(&bytes);
+            assert_eq!(decode_base64(&string), Some(bytes));
+        }
+        assert_eq!(
+            decode_base64::<Vec<u8>>("AAAAA"),
+            None,
+            "dangling character"
+        );
+        assert_eq!(
+            decode_base64::<Vec<u8>>("AA+A"),
+            None,
+            "not in the alphabet"
+        );
+    }
+}
@@ -4,15 +4,28 @@ use serde::Deserialize;
 use serde::Serialize;
 
 use crate::field::Cell;
+use crate::field::Field;
 use crate::field::Upgrade;
+use crate::network::decode_base64;
+use crate::network::encode_base64;
+use crate::network::encoded_len;
 use crate::utils::Duration;
+use crate::utils::Idx as _;
+use crate::utils::Position;
+use crate::utils::TICKS_PER_SECOND;
 
 /// Ratios of Wood turning into those cell types:
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Ratios {
     pub power: u32,
     pub speed: u32,
     pub bombs: u32,
+    #[serde(default)]
+    pub remote: u32,
+    #[serde(default)]
+    pub kick: u32,
+    #[serde(default)]
+    pub pierce: u32,
     pub teleport: u32,
     pub wall: u32,
     pub wood: u32,
@@ -25,6 +38,9 @@ impl Default for Ratios {
             power: 8,
             speed: 9,
             bombs: 7,
+            remote: 0,
+            kick: 0,
+            pierce: 0,
             teleport: 2,
             wall: 0,
             wood: 1,
@@ -34,31 +50,38 @@ impl Default for Ratios {
 }
 
 impl Ratios {
-    pub fn new(
-        power: u32,
-        speed: u32,
-        bombs: u32,
-        teleport: u32,
-        wall: u32,
-        wood: u32,
-        clear: u32,
-    ) -> Self {
-        Self {
-            power,
-            speed,
-            bombs,
-            teleport,
-            wall,
-            wood,
-            clear,
-        }
-    }
+    /// Nothing for burnt wood to turn into, which `Settings::validate` rejects
+    pub const NONE: Ratios = Ratios {
+        power: 0,
+        speed: 0,
+        bombs: 0,
+        remote: 0,
+        kick: 0,
+        pierce: 0,
+        teleport: 0,
+        wall: 0,
+        wood: 0,
+        clear: 0,
+    };
 
     pub fn sum(&self) -> u32 {
-        self.power + self.speed + self.bombs + self.teleport + self.wall + self.wood + self.clear
+        self.power
+            + self.speed
+            + self.bombs
+            + self.remote
+            + self.kick
+            + self.pierce
+            + self.teleport
+            + self.wall
+            + self.wood
+            + self.clear
     }
+    /// The cell that burnt wood turns into. If all ratios are 0, that is always `Cell::Empty`
     pub fn random(&self, random: u32) -> Cell {
         let sum = self.sum();
+        if sum == 0 {
+            return Cell::Empty;
+        }
 
         let mut random = random % sum;
 
@@ -77,6 +100,21 @@ impl Ratios {
         }
         random -= self.bombs;
 
+        if random < self.remote {
+            return Cell::Upgrade(Upgrade::Remote);
+        }
+        random -= self.remote;
+
+        if random < self.kick {
+            return Cell::Upgrade(Upgrade::Kick);
+        }
+        random -= self.kick;
+
+        if random < self.pierce {
+            return Cell::Upgrade(Upgrade::Pierce);
+        }
+        random -= self.pierce;
+
         if random < self.teleport {
             return Cell::Teleport;
         }
@@ -101,6 +139,9 @@ impl Ratios {
         let power = (self.power as f32 * ratio).round() as u32;
         let speed = (self.speed as f32 * ratio).round() as u32;
         let bombs = (self.bombs as f32 * ratio).round() as u32;
+        let remote = (self.remote as f32 * ratio).round() as u32;
+        let kick = (self.kick as f32 * ratio).round() as u32;
+        let pierce = (self.pierce as f32 * ratio).round() as u32;
         let teleport = (self.teleport as f32 * ratio).round() as u32;
         let wall = (self.wall as f32 * ratio).round() as u32;
         let wood = (self.wood as f32 * ratio).round() as u32;
@@ -110,6 +151,9 @@ impl Ratios {
             power,
             speed,
             bombs,
+            remote,
+            kick,
+            pierce,
             teleport,
             wall,
             wood,
@@ -118,7 +162,167 @@ impl Ratios {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// What a player loses when dying
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DeathPenalty {
+    /// All upgrades are lost
+    ResetToBase,
+    /// Half of each upgrade is lost
+    #[default]
+    HalveUpgrades,
+    /// Nothing is lost, for practice
+    KeepAll,
+}
+
+impl DeathPenalty {
+    pub const ALL: [DeathPenalty; 3] = [
+        DeathPenalty::ResetToBase,
+        DeathPenalty::HalveUpgrades,
+        DeathPenalty::KeepAll,
+    ];
+
+    /// The upgrade a player keeps of `upgrade` when dying
+    pub fn apply(self, upgrade: u32) -> u32 {
+        match self {
+            DeathPenalty::ResetToBase => 1,
+            DeathPenalty::HalveUpgrades => u32::max(1, upgrade / 2),
+            DeathPenalty::KeepAll => upgrade,
+        }
+    }
+}
+
+/// Settings that can change from round to round of a tournament
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoundSettings {
+    /// like `Settings::map_seed`
+    pub map_seed: Option<u64>,
+
+    /// like `Settings::custom_map`
+    pub custom_map: Option<String>,
+
+    pub ratios: Ratios,
+}
+
+/// A problem that makes settings unplayable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingsError {
+    /// `width` or `height` is below `WIDTH_RANGE` or `HEIGHT_RANGE`
+    FieldTooSmall { width: u32, height: u32 },
+    /// `width` or `height` is above `WIDTH_RANGE` or `HEIGHT_RANGE`
+    FieldTooLarge { width: u32, height: u32 },
+    /// `players` is not in `PLAYERS_RANGE`
+    PlayersOutOfRange(u32),
+    /// More players are needed to start than can join
+    MinPlayersToStartOutOfRange {
+        min_players_to_start: u32,
+        players: u32,
+    },
+    /// `teams` is not in `TEAMS_RANGE` or there are fewer `players` than teams
+    TeamsOutOfRange { teams: u32, players: u32 },
+    /// Burned down wood would not know what to turn into
+    RatiosAllZero,
+    /// A tournament never gets to its next round if its games stay on the results, because
+    /// `return_to_lobby_after_s` is 0
+    TournamentStaysOnResults { best_of: u32 },
+    /// The encoded settings take more bytes than `MAX_ENCODED_LEN`, e.g. a long playlist
+    TooLarge(usize),
+    /// The field has fewer start points than `players`
+    TooManyPlayersForField { players: u32, start_points: usize },
+    /// The custom map has more characters than `CUSTOM_MAP_MAX_LEN`
+    CustomMapTooLong(usize),
+    /// The custom map is not a grid of cell characters
+    CustomMapMalformed(String),
+    /// The custom map's size differs from `width` and `height`
+    CustomMapWrongSize { width: u32, height: u32 },
+    /// The custom map has a cell that can only appear during a game, like a bomb
+    CustomMapInvalidCell { cell: &'static str, x: i32, y: i32 },
+    /// The settings of a round from the playlist have a problem
+    InPlaylist {
+        round: usize,
+        error: Box<SettingsError>,
+    },
+}
+
+impl std::fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::FieldTooSmall { width, height } => write!(
+                f,
+                "field {width}x{height} is smaller than {}x{}",
+                Settings::WIDTH_RANGE.start(),
+                Settings::HEIGHT_RANGE.start()
+            ),
+            SettingsError::FieldTooLarge { width, height } => write!(
+                f,
+                "field {width}x{height} is larger than {}x{}",
+                Settings::WIDTH_RANGE.end(),
+                Settings::HEIGHT_RANGE.end()
+            ),
+            SettingsError::PlayersOutOfRange(players) => {
+                write!(f, "players {players} not in {:?}", Settings::PLAYERS_RANGE)
+            }
+            SettingsError::MinPlayersToStartOutOfRange {
+                min_players_to_start,
+                players,
+            } => write!(
+                f,
+                "min players to start {min_players_to_start} not in 1..={players}"
+            ),
+            SettingsError::TeamsOutOfRange { teams, players } => write!(
+                f,
+                "teams {teams} not in {:?} or more than {players} players",
+                Settings::TEAMS_RANGE
+            ),
+            SettingsError::RatiosAllZero => write!(f, "all ratios are 0"),
+            SettingsError::TournamentStaysOnResults { best_of } => write!(
+                f,
+                "best of {best_of} needs the games to return to the lobby after the results"
+            ),
+            SettingsError::TooLarge(len) => write!(
+                f,
+                "settings take {len} bytes, only {} fit into a message",
+                Settings::MAX_ENCODED_LEN
+            ),
+            SettingsError::TooManyPlayersForField {
+                players,
+                start_points,
+            } => write!(
+                f,
+                "field has {start_points} start points for {players} players"
+            ),
+            SettingsError::CustomMapTooLong(len) => write!(
+                f,
+                "custom map has {len} characters, more than {}",
+                Settings::CUSTOM_MAP_MAX_LEN
+            ),
+            SettingsError::CustomMapMalformed(err) => write!(f, "custom map: {err}"),
+            SettingsError::CustomMapWrongSize { width, height } => {
+                write!(f, "custom map is {width}x{height}, not the field size")
+            }
+            SettingsError::CustomMapInvalidCell { cell, x, y } => {
+                write!(
+                    f,
+                    "custom map: {cell} at {x}/{y} can not be placed in a map"
+                )
+            }
+            SettingsError::InPlaylist { round, error } => {
+                write!(f, "playlist round {round}: {error}")
+            }
+        }
+    }
+}
+
+/// All of `errors` in one line
+pub fn describe_errors(errors: &[SettingsError]) -> String {
+    errors
+        .iter()
+        .map(SettingsError::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)] // each rule a host can switch on or off is a bool
 pub struct Settings {
     /// Name of the game
     pub game_name: String,
@@ -132,6 +336,74 @@ pub struct Settings {
     /// number of players that can join
     pub players: u32,
 
+    /// the game only starts when this many players are ready, unless the host forces it
+    #[serde(default = "Settings::min_players_to_start_default")]
+    pub min_players_to_start: u32,
+
+    /// seed for the layout of the field, `None` for the classic layout
+    #[serde(default)]
+    pub map_seed: Option<u64>,
+
+    /// percent of the generated field's wood that starts burnt down into what `ratios` gives,
+    /// so the game starts with some upgrades lying around
+    #[serde(default)]
+    pub burnt_wood_percent: u32,
+
+    /// a hand-designed field, one line of cell characters per row like `Field::string_grid`,
+    /// instead of a generated one. It must have the settings' size and enough start points
+    #[serde(default)]
+    pub custom_map: Option<String>,
+
+    /// the game ends when a player has this many kills, 0 to play until stopped
+    #[serde(default = "Settings::kills_to_win_default")]
+    pub kills_to_win: u32,
+
+    /// after the results were shown this many seconds, the game turns back into its lobby, 0
+    /// to stay on the results
+    #[serde(default = "Settings::return_to_lobby_after_s_default")]
+    pub return_to_lobby_after_s: u32,
+
+    /// the lobby's players play a series of this many games, the first to win most of them wins
+    /// the tournament. 0 or 1 for single games. The next game starts when the last one returned
+    /// to the lobby
+    #[serde(default)]
+    pub best_of: u32,
+
+    /// the rounds of a tournament cycle through these maps and ratios, starting with the first.
+    /// Empty to play every round like the first
+    #[serde(default)]
+    pub playlist: Vec<RoundSettings>,
+
+    /// when the host changes the settings in the lobby, the other players have to get ready
+    /// again
+    #[serde(default = "Settings::unready_on_settings_change_default")]
+    pub unready_on_settings_change: bool,
+
+    /// players are out of the game after dying this many times, 0 to respawn forever. The last
+    /// player left wins.
+    #[serde(default)]
+    pub lives: u32,
+
+    /// players are split into this many teams that win together, 0 for everyone on their own
+    #[serde(default)]
+    pub teams: u32,
+
+    /// fire kills teammates, otherwise they are not harmed by each other's bombs
+    #[serde(default = "Settings::friendly_fire_default")]
+    pub friendly_fire: bool,
+
+    /// players can not move for this many seconds at the start, to look at the field first
+    #[serde(default = "Settings::countdown_s_default")]
+    pub countdown_s: u32,
+
+    /// after this many seconds, magma rises and sets random cells on fire, 0 for never
+    #[serde(default)]
+    pub magma_after_s: u32,
+
+    /// an empty cell away from the players grows wood again this often, 0 for never
+    #[serde(default)]
+    pub wood_regrow_ms: u32,
+
     /// time after bomb placement that the bomb explodes
     pub bomb_explode_time_ms: u32,
 
@@ -150,6 +422,22 @@ pub struct Settings {
     /// Power of Upgrade Packets exploding
     pub upgrade_explosion_power: u32,
 
+    /// bombs hit by an explosion explode too, otherwise they are only destroyed
+    #[serde(default = "Settings::chain_reactions_default")]
+    pub chain_reactions: bool,
+
+    /// exploding bombs shove players next to them one cell away
+    #[serde(default)]
+    pub explosion_knockback: bool,
+
+    /// explosions also spread along the diagonals
+    #[serde(default)]
+    pub diagonal_blast: bool,
+
+    /// upgrades hit by an explosion burn and explode, otherwise the fire passes over them
+    #[serde(default = "Settings::fire_destroys_upgrades_default")]
+    pub fire_destroys_upgrades: bool,
+
     /// how long before burning wood turns into something
     pub wood_burn_time_ms: u32,
 
@@ -159,6 +447,44 @@ pub struct Settings {
     /// how far behind the player the bomb is placed [cell/100]
     pub bomb_offset: u32,
 
+    /// players can walk diagonally by holding two perpendicular directions
+    #[serde(default)]
+    pub diagonal_walking: bool,
+
+    /// teleports port players and tunnel fire, otherwise they are obstacles that burn like
+    /// empty cells
+    #[serde(default = "Settings::teleports_enabled_default")]
+    pub teleports_enabled: bool,
+
+    /// porting uses up both teleports, otherwise they stay for the next port
+    #[serde(default = "Settings::teleport_consumed_default")]
+    pub teleport_consumed: bool,
+
+    /// what players lose when they die
+    #[serde(default)]
+    pub death_penalty: DeathPenalty,
+
+    /// most power upgrades a player can have, 0 for no limit
+    #[serde(default)]
+    pub max_power: u32,
+
+    /// most speed upgrades a player can have, 0 for no limit
+    #[serde(default)]
+    pub max_speed: u32,
+
+    /// most bomb upgrades a player can have, 0 for no limit
+    #[serde(default)]
+    pub max_bombs: u32,
+
+    /// upgrades eaten at their cap count as points, which break ties of kills and deaths,
+    /// otherwise they are lost
+    #[serde(default)]
+    pub capped_upgrades_score: bool,
+
+    /// the scoreboard shows players only their own upgrades, of the others only kills and deaths
+    #[serde(default)]
+    pub hide_opponent_upgrades: bool,
+
     /// Ratios what comes out of burned down walls
     pub ratios: Ratios,
 }
@@ -170,13 +496,41 @@ impl Default for Settings {
             width: Self::WIDTH_DEFAULT,
             height: Self::HEIGHT_DEFAULT,
             players: Self::PLAYERS_DEFAULT,
+            min_players_to_start: Self::MIN_PLAYERS_TO_START_DEFAULT,
+            map_seed: None,
+            burnt_wood_percent: 0,
+            custom_map: None,
+            kills_to_win: Self::KILLS_TO_WIN_DEFAULT,
+            return_to_lobby_after_s: Self::RETURN_TO_LOBBY_AFTER_DEFAULT,
+            best_of: 0,
+            playlist: Vec::new(),
+            unready_on_settings_change: true,
+            lives: 0,
+            teams: 0,
+            friendly_fire: true,
+            countdown_s: Self::COUNTDOWN_DEFAULT,
+            magma_after_s: 0,
+            wood_regrow_ms: 0,
             bomb_offset: Self::BOMB_OFFSET_DEFAULT,
+            diagonal_walking: false,
+            teleports_enabled: true,
+            teleport_consumed: true,
+            death_penalty: DeathPenalty::default(),
+            max_power: 0,
+            max_speed: 0,
+            max_bombs: 0,
+            capped_upgrades_score: false,
+            hide_opponent_upgrades: false,
             bomb_explode_time_ms: Self::BOMB_TIME_DEFAULT,
             speed_multiplyer: Self::SPEED_MULTIPLYER_DEFAULT,
             speed_base: Self::SPEED_BASE_DEFAULT,
             bomb_walking_chance: Self::BOMB_WALKING_CHANCE_DEFAULT,
             tombstone_walking_chance: Self::TOMBSTONE_WALKING_CHANCE_DEFAULT,
             upgrade_explosion_power: Self::UPGRADE_EXPLOSION_POWER_DEFAULT,
+            chain_reactions: true,
+            explosion_knockback: false,
+            diagonal_blast: false,
+            fire_destroys_upgrades: true,
             wood_burn_time_ms: Self::WOOD_BURN_TIME_DEFAULT,
             fire_burn_time_ms: Self::FIRE_BURN_TIME_DEFAULT,
             ratios: Ratios::default(),
@@ -185,31 +539,111 @@ impl Default for Settings {
 }
 
 impl Settings {
+    pub const BEST_OF_RANGE: RangeInclusive<u32> = 0..=9;
     pub const BOMB_OFFSET_DEFAULT: u32 = 49;
     pub const BOMB_OFFSET_RANGE: RangeInclusive<u32> = 0..=100;
     pub const BOMB_TIME_DEFAULT: u32 = 4267;
     pub const BOMB_TIME_RANGE: RangeInclusive<u32> = 100..=10_000;
     pub const BOMB_WALKING_CHANCE_DEFAULT: u32 = 80;
     pub const BOMB_WALKING_CHANCE_RANGE: RangeInclusive<u32> = 0..=100;
+    pub const BURNT_WOOD_RANGE: RangeInclusive<u32> = 0..=100;
+    pub const COUNTDOWN_DEFAULT: u32 = 3;
+    pub const COUNTDOWN_RANGE: RangeInclusive<u32> = 0..=10;
+    /// The largest field, with a line break after each row
+    pub const CUSTOM_MAP_MAX_LEN: usize =
+        (*Self::WIDTH_RANGE.end() as usize + 1) * *Self::HEIGHT_RANGE.end() as usize;
     pub const FIRE_BURN_TIME_DEFAULT: u32 = 400;
     pub const FIRE_BURN_TIME_RANGE: RangeInclusive<u32> = 0..=10_000;
     pub const HEIGHT_DEFAULT: u32 = 13;
     pub const HEIGHT_RANGE: RangeInclusive<u32> = Self::WIDTH_RANGE;
+    pub const KILLS_TO_WIN_DEFAULT: u32 = 5;
+    pub const KILLS_TO_WIN_RANGE: RangeInclusive<u32> = 0..=50;
+    pub const LIVES_RANGE: RangeInclusive<u32> = 0..=10;
+    pub const MAGMA_AFTER_RANGE: RangeInclusive<u32> = 0..=600;
+    /// Lobby updates carry the settings and up to 8 players in one datagram of less than 1000
+    /// bytes, the players with their capped names take about 300
+    pub const MAX_ENCODED_LEN: usize = 768;
+    pub const MIN_PLAYERS_TO_START_DEFAULT: u32 = 2;
     pub const PLAYERS_DEFAULT: u32 = 4;
-    pub const PLAYERS_RANGE: RangeInclusive<u32> = 1..=4; // TODO: generate maps with more players
+    pub const PLAYERS_RANGE: RangeInclusive<u32> = 1..=8;
     pub const RATIOS_RANGE: RangeInclusive<u32> = 0..=100;
+    pub const RETURN_TO_LOBBY_AFTER_DEFAULT: u32 = 30;
+    pub const RETURN_TO_LOBBY_AFTER_RANGE: RangeInclusive<u32> = 0..=300;
     pub const SPEED_BASE_DEFAULT: u32 = 100;
     pub const SPEED_BASE_RANGE: RangeInclusive<u32> = 10..=500;
     pub const SPEED_MULTIPLYER_DEFAULT: u32 = 50;
     pub const SPEED_MULTIPLYER_RANGE: RangeInclusive<u32> = 0..=200;
+    pub const TEAMS_RANGE: RangeInclusive<u32> = 0..=4;
     pub const TOMBSTONE_WALKING_CHANCE_DEFAULT: u32 = 40;
     pub const TOMBSTONE_WALKING_CHANCE_RANGE: RangeInclusive<u32> = 0..=100;
+    pub const UPGRADE_CAP_RANGE: RangeInclusive<u32> = 0..=20;
     pub const UPGRADE_EXPLOSION_POWER_DEFAULT: u32 = 1;
     pub const UPGRADE_EXPLOSION_POWER_RANGE: RangeInclusive<u32> = 0..=15;
     pub const WIDTH_DEFAULT: u32 = 17;
     pub const WIDTH_RANGE: RangeInclusive<u32> = 5..=25;
     pub const WOOD_BURN_TIME_DEFAULT: u32 = 1200;
     pub const WOOD_BURN_TIME_RANGE: RangeInclusive<u32> = 0..=10_000;
+    pub const WOOD_REGROW_RANGE: RangeInclusive<u32> = 0..=30_000;
+
+    fn min_players_to_start_default() -> u32 {
+        Self::MIN_PLAYERS_TO_START_DEFAULT
+    }
+
+    fn kills_to_win_default() -> u32 {
+        Self::KILLS_TO_WIN_DEFAULT
+    }
+
+    fn return_to_lobby_after_s_default() -> u32 {
+        Self::RETURN_TO_LOBBY_AFTER_DEFAULT
+    }
+
+    fn countdown_s_default() -> u32 {
+        Self::COUNTDOWN_DEFAULT
+    }
+
+    fn unready_on_settings_change_default() -> bool {
+        true
+    }
+
+    fn friendly_fire_default() -> bool {
+        true
+    }
+
+    fn chain_reactions_default() -> bool {
+        true
+    }
+
+    fn fire_destroys_upgrades_default() -> bool {
+        true
+    }
+
+    fn teleports_enabled_default() -> bool {
+        true
+    }
+
+    fn teleport_consumed_default() -> bool {
+        true
+    }
+
+    /// The team of the `nth` player in id order, `None` if everyone plays on their own
+    ///
+    /// Players are dealt into the teams, so 4 players in 2 teams play the 1st and 3rd against
+    /// the 2nd and 4th, and no team has more than one player more than another.
+    pub fn team_of(&self, nth: usize) -> Option<u32> {
+        (self.teams > 1).then(|| (nth % self.teams.idx()) as u32)
+    }
+
+    /// The most upgrades of a kind a player can have, `None` for no limit
+    pub fn upgrade_cap(&self, upgrade: Upgrade) -> Option<u32> {
+        let cap = match upgrade {
+            Upgrade::Power => self.max_power,
+            Upgrade::Speed => self.max_speed,
+            Upgrade::Bombs => self.max_bombs,
+            // there is only one remote, kick or pierce to have
+            Upgrade::Remote | Upgrade::Kick | Upgrade::Pierce => 1,
+        };
+        (cap != 0).then_some(cap)
+    }
 
     /// Walking Speed based on `speed_powerup`
     /// returned speed is returned in `Cells/100s`
@@ -219,6 +653,28 @@ impl Settings {
         self.speed_base + (player_speed * self.speed_multiplyer)
     }
 
+    /// Distance a player walks each update [`Position::ACCURACY`/Cell]
+    ///
+    /// # Panics
+    ///
+    /// If the speed settings are far outside their ranges
+    pub fn walk_distance_per_update(&self, player_speed: u32) -> i32 {
+        let walk_distance: i32 = self
+            .get_update_walk_distance(player_speed)
+            .try_into()
+            .expect("walked distance fits i32");
+        walk_distance * Position::ACCURACY / TICKS_PER_SECOND as i32 / 100
+    }
+
+    /// Effective walking speed [Cells/s]
+    ///
+    /// Slower than `get_update_walk_distance` suggests, when that is not a multiple of
+    /// whole distances per update
+    pub fn walk_speed_cells_per_second(&self, player_speed: u32) -> f32 {
+        (self.walk_distance_per_update(player_speed) * TICKS_PER_SECOND as i32) as f32
+            / Position::ACCURACY as f32
+    }
+
     pub fn bomb_explode_time(&self) -> Duration {
         Duration::from_ms(self.bomb_explode_time_ms)
     }
@@ -228,6 +684,316 @@ impl Settings {
     pub fn fire_burn_time(&self) -> Duration {
         Duration::from_ms(self.fire_burn_time_ms)
     }
+    pub fn wood_regrow_time(&self) -> Duration {
+        Duration::from_ms(self.wood_regrow_ms)
+    }
+
+    /// Check that the settings describe a playable game
+    ///
+    /// # Errors
+    ///
+    /// All problems the settings have
+    pub fn validate(&self) -> Result<(), Vec<SettingsError>> {
+        let mut errors = Vec::new();
+        let (width, height) = (self.width, self.height);
+        if width < *Self::WIDTH_RANGE.start() || height < *Self::HEIGHT_RANGE.start() {
+            errors.push(SettingsError::FieldTooSmall { width, height });
+        }
+        if width > *Self::WIDTH_RANGE.end() || height > *Self::HEIGHT_RANGE.end() {
+            errors.push(SettingsError::FieldTooLarge { width, height });
+        }
+        let len = encoded_len(self);
+        if len > Self::MAX_ENCODED_LEN {
+            errors.push(SettingsError::TooLarge(len));
+        }
+        // do not build fields of absurd sizes, or absurdly many
+        let field_buildable = errors.is_empty();
+        if !Self::PLAYERS_RANGE.contains(&self.players) {
+            errors.push(SettingsError::PlayersOutOfRange(self.players));
+        }
+        if !(1..=self.players).contains(&self.min_players_to_start) {
+            errors.push(SettingsError::MinPlayersToStartOutOfRange {
+                min_players_to_start: self.min_players_to_start,
+                players: self.players,
+            });
+        }
+        if !Self::TEAMS_RANGE.contains(&self.teams) || self.teams > self.players {
+            errors.push(SettingsError::TeamsOutOfRange {
+                teams: self.teams,
+                players: self.players,
+            });
+        }
+        if self.ratios.sum() == 0 {
+            errors.push(SettingsError::RatiosAllZero);
+        }
+        if self.best_of >= 2 && self.return_to_lobby_after_s == 0 {
+            errors.push(SettingsError::TournamentStaysOnResults {
+                best_of: self.best_of,
+            });
+        }
+        if field_buildable {
+            errors.extend(self.field_errors());
+            for round in 0..self.playlist.len() {
+                let settings = self.for_round(round);
+                let round_errors = (settings.ratios.sum() == 0)
+                    .then_some(SettingsError::RatiosAllZero)
+                    .into_iter()
+                    .chain(settings.field_errors());
+                errors.extend(round_errors.map(|error| SettingsError::InPlaylist {
+                    round,
+                    error: Box::new(error),
+                }));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The settings of the `round`th game of a tournament, counting from 0
+    ///
+    /// Rounds take their map and ratios from the playlist, after its last entry it starts over.
+    pub fn for_round(&self, round: usize) -> Settings {
+        let mut settings = self.clone();
+        if let Some(entry) = self.playlist.get(round % self.playlist.len().max(1)) {
+            settings.map_seed = entry.map_seed;
+            settings.custom_map.clone_from(&entry.custom_map);
+            settings.ratios = entry.ratios.clone();
+        }
+        settings
+    }
+
+    /// Problems of the field these settings play on
+    fn field_errors(&self) -> Vec<SettingsError> {
+        let field = match &self.custom_map {
+            None => Field::new_from_rules(self),
+            Some(map) if map.len() > Self::CUSTOM_MAP_MAX_LEN => {
+                return vec![SettingsError::CustomMapTooLong(map.len())];
+            }
+            Some(map) => match Field::new_from_string_grid(map) {
+                Ok(field) => field,
+                Err(err) => return vec![SettingsError::CustomMapMalformed(err)],
+            },
+        };
+
+        let mut errors = Vec::new();
+        if (field.width, field.height) != (self.width, self.height) {
+            errors.push(SettingsError::CustomMapWrongSize {
+                width: field.width,
+                height: field.height,
+            });
+        }
+        // bombs, fire and the like need an owner and a time, a map starts without them
+        if let Some((pos, cell)) = field.iter().find(|(_, cell)| {
+            !matches!(
+                cell,
+                Cell::Empty
+                    | Cell::Wall
+                    | Cell::Wood
+                    | Cell::StartPoint
+                    | Cell::Teleport
+                    | Cell::Upgrade(_)
+            )
+        }) {
+            errors.push(SettingsError::CustomMapInvalidCell {
+                cell: cell.name(),
+                x: pos.x,
+                y: pos.y,
+            });
+        }
+        let start_points = field.start_positions().len();
+        if start_points < self.players.idx() {
+            errors.push(SettingsError::TooManyPlayersForField {
+                players: self.players,
+                start_points,
+            });
+        }
+        errors
+    }
+
+    /// Encode the settings into a string that can be shared with other players
+    pub fn to_shareable_string(&self) -> String {
+        encode_base64(self)
+    }
+
+    /// Decode and validate settings that were encoded with `to_shareable_string`
+    ///
+    /// # Errors
+    ///
+    /// Why the string does not decode to valid settings
+    pub fn from_shareable_string(string: &str) -> Result<Self, String> {
+        let settings: Self =
+            decode_base64(string).ok_or_else(|| "not a settings string".to_owned())?;
+        settings
+            .validate()
+            .map_err(|errors| describe_errors(&errors))?;
+        Ok(settings)
+    }
+
+    /// Parse a map seed that a player shared
+    ///
+    /// # Errors
+    ///
+    /// Why the seed is not a number
+    pub fn parse_map_seed(seed: &str) -> Result<u64, String> {
+        seed.trim()
+            .parse()
+            .map_err(|err: std::num::ParseIntError| format!("invalid seed: {err}"))
+    }
+
+    /// Names of the settings whose value differs between `self` and `other`
+    #[allow(clippy::too_many_lines)] // a few lines for every setting
+    pub fn differences(&self, other: &Self) -> Vec<&'static str> {
+        // destructure, so new settings can not be forgotten here
+        let Self {
+            game_name,
+            width,
+            height,
+            players,
+            min_players_to_start,
+            map_seed,
+            burnt_wood_percent,
+            custom_map,
+            kills_to_win,
+            return_to_lobby_after_s,
+            best_of,
+            playlist,
+            unready_on_settings_change,
+            lives,
+            teams,
+            friendly_fire,
+            countdown_s,
+            magma_after_s,
+            wood_regrow_ms,
+            bomb_explode_time_ms,
+            speed_base,
+            speed_multiplyer,
+            bomb_walking_chance,
+            tombstone_walking_chance,
+            upgrade_explosion_power,
+            chain_reactions,
+            explosion_knockback,
+            diagonal_blast,
+            fire_destroys_upgrades,
+            wood_burn_time_ms,
+            fire_burn_time_ms,
+            bomb_offset,
+            diagonal_walking,
+            teleports_enabled,
+            teleport_consumed,
+            death_penalty,
+            max_power,
+            max_speed,
+            max_bombs,
+            capped_upgrades_score,
+            hide_opponent_upgrades,
+            ratios,
+        } = self;
+
+        [
+            ("game_name", *game_name != other.game_name),
+            ("width", *width != other.width),
+            ("height", *height != other.height),
+            ("players", *players != other.players),
+            (
+                "min_players_to_start",
+                *min_players_to_start != other.min_players_to_start,
+            ),
+            ("map_seed", *map_seed != other.map_seed),
+            (
+                "burnt_wood_percent",
+                *burnt_wood_percent != other.burnt_wood_percent,
+            ),
+            ("custom_map", *custom_map != other.custom_map),
+            ("kills_to_win", *kills_to_win != other.kills_to_win),
+            (
+                "return_to_lobby_after_s",
+                *return_to_lobby_after_s != other.return_to_lobby_after_s,
+            ),
+            ("best_of", *best_of != other.best_of),
+            ("playlist", *playlist != other.playlist),
+            (
+                "unready_on_settings_change",
+                *unready_on_settings_change != other.unready_on_settings_change,
+            ),
+            ("lives", *lives != other.lives),
+            ("teams", *teams != other.teams),
+            ("friendly_fire", *friendly_fire != other.friendly_fire),
+            ("countdown_s", *countdown_s != other.countdown_s),
+            ("magma_after_s", *magma_after_s != other.magma_after_s),
+            ("wood_regrow_ms", *wood_regrow_ms != other.wood_regrow_ms),
+            (
+                "bomb_explode_time_ms",
+                *bomb_explode_time_ms != other.bomb_explode_time_ms,
+            ),
+            ("speed_base", *speed_base != other.speed_base),
+            (
+                "speed_multiplyer",
+                *speed_multiplyer != other.speed_multiplyer,
+            ),
+            (
+                "bomb_walking_chance",
+                *bomb_walking_chance != other.bomb_walking_chance,
+            ),
+            (
+                "tombstone_walking_chance",
+                *tombstone_walking_chance != other.tombstone_walking_chance,
+            ),
+            (
+                "upgrade_explosion_power",
+                *upgrade_explosion_power != other.upgrade_explosion_power,
+            ),
+            ("chain_reactions", *chain_reactions != other.chain_reactions),
+            (
+                "explosion_knockback",
+                *explosion_knockback != other.explosion_knockback,
+            ),
+            ("diagonal_blast", *diagonal_blast != other.diagonal_blast),
+            (
+                "fire_destroys_upgrades",
+                *fire_destroys_upgrades != other.fire_destroys_upgrades,
+            ),
+            (
+                "wood_burn_time_ms",
+                *wood_burn_time_ms != other.wood_burn_time_ms,
+            ),
+            (
+                "fire_burn_time_ms",
+                *fire_burn_time_ms != other.fire_burn_time_ms,
+            ),
+            ("bomb_offset", *bomb_offset != other.bomb_offset),
+            (
+                "diagonal_walking",
+                *diagonal_walking != other.diagonal_walking,
+            ),
+            (
+                "teleports_enabled",
+                *teleports_enabled != other.teleports_enabled,
+            ),
+            (
+                "teleport_consumed",
+                *teleport_consumed != other.teleport_consumed,
+            ),
+            ("death_penalty", *death_penalty != other.death_penalty),
+            ("max_power", *max_power != other.max_power),
+            ("max_speed", *max_speed != other.max_speed),
+            ("max_bombs", *max_bombs != other.max_bombs),
+            (
+                "capped_upgrades_score",
+                *capped_upgrades_score != other.capped_upgrades_score,
+            ),
+            (
+                "hide_opponent_upgrades",
+                *hide_opponent_upgrades != other.hide_opponent_upgrades,
+            ),
+            ("ratios", *ratios != other.ratios),
+        ]
+        .into_iter()
+        .filter_map(|(name, differs)| differs.then_some(name))
+        .collect()
+    }
 }
 #[cfg(test)]
 mod test {
@@ -235,7 +1001,18 @@ mod test {
 
     #[test]
     fn test_ratios() {
-        let r = Ratios::new(2, 2, 2, 2, 2, 2, 2);
+        let r = Ratios {
+            power: 2,
+            speed: 2,
+            bombs: 2,
+            remote: 2,
+            kick: 2,
+            pierce: 2,
+            teleport: 2,
+            wall: 2,
+            wood: 2,
+            clear: 2,
+        };
 
         assert_eq!(Cell::Upgrade(Upgrade::Power), r.random(0));
         assert_eq!(Cell::Upgrade(Upgrade::Power), r.random(1));
@@ -243,14 +1020,34 @@ mod test {
         assert_eq!(Cell::Upgrade(Upgrade::Speed), r.random(3));
         assert_eq!(Cell::Upgrade(Upgrade::Bombs), r.random(4));
         assert_eq!(Cell::Upgrade(Upgrade::Bombs), r.random(5));
-        assert_eq!(Cell::Teleport, r.random(6));
-        assert_eq!(Cell::Teleport, r.random(7));
-        assert_eq!(Cell::Wood, r.random(8));
-        assert_eq!(Cell::Wood, r.random(9));
-        assert_eq!(Cell::Wall, r.random(10));
-        assert_eq!(Cell::Wall, r.random(11));
-        assert_eq!(Cell::Empty, r.random(12));
-        assert_eq!(Cell::Empty, r.random(13));
+        assert_eq!(Cell::Upgrade(Upgrade::Remote), r.random(6));
+        assert_eq!(Cell::Upgrade(Upgrade::Remote), r.random(7));
+        assert_eq!(Cell::Upgrade(Upgrade::Kick), r.random(8));
+        assert_eq!(Cell::Upgrade(Upgrade::Kick), r.random(9));
+        assert_eq!(Cell::Upgrade(Upgrade::Pierce), r.random(10));
+        assert_eq!(Cell::Upgrade(Upgrade::Pierce), r.random(11));
+        assert_eq!(Cell::Teleport, r.random(12));
+        assert_eq!(Cell::Teleport, r.random(13));
+        assert_eq!(Cell::Wood, r.random(14));
+        assert_eq!(Cell::Wood, r.random(15));
+        assert_eq!(Cell::Wall, r.random(16));
+        assert_eq!(Cell::Wall, r.random(17));
+        assert_eq!(Cell::Empty, r.random(18));
+        assert_eq!(Cell::Empty, r.random(19));
+    }
+
+    #[test]
+    fn test_ratios_all_zero_give_empty_cells() {
+        let r = Ratios::NONE;
+        assert_eq!(Cell::Empty, r.random(0));
+        assert_eq!(Cell::Empty, r.random(u32::MAX));
+
+        let r = Ratios {
+            clear: 1,
+            ..Ratios::NONE
+        };
+        assert_eq!(Cell::Empty, r.random(0));
+        assert_eq!(Cell::Empty, r.random(u32::MAX));
     }
 
     #[test]
@@ -260,4 +1057,416 @@ mod test {
         assert_eq!(r.get_update_walk_distance(1), 150);
         assert_eq!(r.get_update_walk_distance(2), 200);
     }
+
+    #[test]
+    fn test_walk_speed_cells_per_second() {
+        let r = Settings::default();
+        assert!((r.walk_speed_cells_per_second(0) - 1.0).abs() < f32::EPSILON);
+        assert!((r.walk_speed_cells_per_second(1) - 1.5).abs() < f32::EPSILON);
+        assert!((r.walk_speed_cells_per_second(4) - 3.0).abs() < f32::EPSILON);
+
+        // 1.6 Cells/s is 3.2 per update, players can only walk 3
+        let r = Settings {
+            speed_base: 110,
+            ..Settings::default()
+        };
+        assert!((r.walk_speed_cells_per_second(1) - 1.5).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_differences_to_default() {
+        type Change = (&'static str, fn(&mut Settings));
+
+        let default = Settings::default();
+        assert!(default.differences(&Settings::default()).is_empty());
+
+        let changes: [Change; 42] = [
+            ("game_name", |s| s.game_name.push('!')),
+            ("width", |s| s.width += 2),
+            ("height", |s| s.height += 2),
+            ("players", |s| s.players -= 1),
+            ("min_players_to_start", |s| s.min_players_to_start += 1),
+            ("map_seed", |s| s.map_seed = Some(0)),
+            ("burnt_wood_percent", |s| s.burnt_wood_percent += 10),
+            ("custom_map", |s| s.custom_map = Some("O".to_owned())),
+            ("kills_to_win", |s| s.kills_to_win += 1),
+            ("return_to_lobby_after_s", |s| {
+                s.return_to_lobby_after_s += 1;
+            }),
+            ("best_of", |s| s.best_of += 3),
+            ("playlist", |s| {
+                s.playlist.push(RoundSettings {
+                    map_seed: Some(1),
+                    custom_map: None,
+                    ratios: Ratios::default(),
+                });
+            }),
+            ("unready_on_settings_change", |s| {
+                s.unready_on_settings_change = false;
+            }),
+            ("lives", |s| s.lives += 1),
+            ("teams", |s| s.teams = 2),
+            ("friendly_fire", |s| s.friendly_fire = false),
+            ("countdown_s", |s| s.countdown_s += 1),
+            ("magma_after_s", |s| s.magma_after_s += 1),
+            ("wood_regrow_ms", |s| s.wood_regrow_ms += 1),
+            ("bomb_explode_time_ms", |s| s.bomb_explode_time_ms += 1),
+            ("speed_base", |s| s.speed_base += 1),
+            ("speed_multiplyer", |s| s.speed_multiplyer += 1),
+            ("bomb_walking_chance", |s| s.bomb_walking_chance += 1),
+            ("tombstone_walking_chance", |s| {
+                s.tombstone_walking_chance += 1;
+            }),
+            ("upgrade_explosion_power", |s| {
+                s.upgrade_explosion_power += 1;
+            }),
+            ("chain_reactions", |s| s.chain_reactions = false),
+            ("explosion_knockback", |s| s.explosion_knockback = true),
+            ("diagonal_blast", |s| s.diagonal_blast = true),
+            ("fire_destroys_upgrades", |s| {
+                s.fire_destroys_upgrades = false;
+            }),
+            ("wood_burn_time_ms", |s| s.wood_burn_time_ms += 1),
+            ("fire_burn_time_ms", |s| s.fire_burn_time_ms += 1),
+            ("bomb_offset", |s| s.bomb_offset += 1),
+            ("diagonal_walking", |s| s.diagonal_walking = true),
+            ("teleports_enabled", |s| s.teleports_enabled = false),
+            ("teleport_consumed", |s| s.teleport_consumed = false),
+            ("death_penalty", |s| s.death_penalty = DeathPenalty::KeepAll),
+            ("max_power", |s| s.max_power = 3),
+            ("max_speed", |s| s.max_speed = 3),
+            ("max_bombs", |s| s.max_bombs = 3),
+            ("capped_upgrades_score", |s| s.capped_upgrades_score = true),
+            ("hide_opponent_upgrades", |s| {
+                s.hide_opponent_upgrades = true;
+            }),
+            ("ratios", |s| s.ratios.wall += 1),
+        ];
+        for (name, change) in changes {
+            let mut settings = Settings::default();
+            change(&mut settings);
+            assert_eq!(settings.differences(&default), vec![name]);
+        }
+    }
+
+    #[test]
+    fn test_shareable_string() {
+        let settings = Settings {
+            game_name: "Hans' Game 💣".to_owned(),
+            width: 25,
+            ratios: Ratios {
+                power: 1,
+                speed: 2,
+                bombs: 3,
+                remote: 4,
+                kick: 8,
+                pierce: 9,
+                teleport: 42,
+                wall: 5,
+                wood: 6,
+                clear: 7,
+            },
+            ..Settings::default()
+        };
+
+        let string = settings.to_shareable_string();
+        assert_eq!(Settings::from_shareable_string(&string), Ok(settings));
+        assert!(Settings::from_shareable_string(&format!("  {string}\n")).is_ok());
+    }
+
+    #[test]
+    fn test_shareable_string_malformed() {
+        let string = Settings::default().to_shareable_string();
+
+        assert!(Settings::from_shareable_string("").is_err());
+        assert!(Settings::from_shareable_string("Hans").is_err());
+        assert!(Settings::from_shareable_string(&string[1..]).is_err());
+        assert!(Settings::from_shareable_string(&string[..string.len() - 2]).is_err());
+        assert!(Settings::from_shareable_string(&string.replace('0', "ö")).is_err());
+
+        let settings = Settings {
+            ratios: Ratios::NONE,
+            ..Settings::default()
+        };
+        assert!(Settings::from_shareable_string(&settings.to_shareable_string()).is_err());
+    }
+
+    #[test]
+    fn test_custom_map() {
+        let map = "O_+_O\n_#_#_\n+_T_+\n_#_#_\nO_+_O\n";
+        let settings = Settings {
+            width: 5,
+            height: 5,
+            players: 4,
+            custom_map: Some(map.to_owned()),
+            ..Settings::default()
+        };
+        assert_eq!(settings.validate(), Ok(()));
+        assert_eq!(Field::new_from_rules(&settings).string_grid(), map);
+
+        let wrong_size = Settings {
+            width: 7,
+            ..settings.clone()
+        };
+        assert_eq!(
+            wrong_size.validate(),
+            Err(vec![SettingsError::CustomMapWrongSize {
+                width: 5,
+                height: 5
+            }])
+        );
+
+        let too_few_start_points = Settings {
+            players: 5,
+            ..settings.clone()
+        };
+        assert_eq!(
+            too_few_start_points.validate(),
+            Err(vec![SettingsError::TooManyPlayersForField {
+                players: 5,
+                start_points: 4
+            }])
+        );
+
+        let malformed = Settings {
+            custom_map: Some(map.replace('T', "?")),
+            ..settings.clone()
+        };
+        assert_eq!(
+            malformed.validate(),
+            Err(vec![SettingsError::CustomMapMalformed(
+                "Character for Cell 2/2 invalid: Invalid character ?".to_owned()
+            )])
+        );
+        assert_eq!(
+            Field::new_from_rules(&malformed),
+            Field::new(5, 5).with_start_points(4),
+            "not parseable, generated instead"
+        );
+
+        let too_long = Settings {
+            custom_map: Some(format!("{map}{}", " ".repeat(Settings::CUSTOM_MAP_MAX_LEN))),
+            ..settings.clone()
+        };
+        assert_eq!(
+            too_long.validate(),
+            Err(vec![SettingsError::CustomMapTooLong(
+                map.len() + Settings::CUSTOM_MAP_MAX_LEN
+            )])
+        );
+
+        let with_bomb = Settings {
+            custom_map: Some(map.replace('T', "B")),
+            ..settings
+        };
+        assert_eq!(
+            with_bomb.validate(),
+            Err(vec![SettingsError::CustomMapInvalidCell {
+                cell: "bomb",
+                x: 2,
+                y: 2
+            }])
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_each_error() {
+        type Change = fn(&mut Settings);
+
+        assert_eq!(Settings::default().validate(), Ok(()));
+
+        let cases: [(Change, Vec<SettingsError>); 7] = [
+            (
+                |s| s.width = 4,
+                vec![SettingsError::FieldTooSmall {
+                    width: 4,
+                    height: 13,
+                }],
+            ),
+            (
+                |s| s.height = 26,
+                vec![SettingsError::FieldTooLarge {
+                    width: 17,
+                    height: 26,
+                }],
+            ),
+            (
+                |s| s.players = 9,
+                vec![
+                    SettingsError::PlayersOutOfRange(9),
+                    SettingsError::TooManyPlayersForField {
+                        players: 9,
+                        start_points: 8,
+                    },
+                ],
+            ),
+            (
+                |s| s.min_players_to_start = 5,
+                vec![SettingsError::MinPlayersToStartOutOfRange {
+                    min_players_to_start: 5,
+                    players: 4,
+                }],
+            ),
+            (
+                |s| s.ratios = Ratios::NONE,
+                vec![SettingsError::RatiosAllZero],
+            ),
+            (
+                |s| {
+                    let round = RoundSettings {
+                        map_seed: None,
+                        custom_map: None,
+                        ratios: Ratios::default(),
+                    };
+                    let no_ratios = RoundSettings {
+                        ratios: Ratios::NONE,
+                        ..round.clone()
+                    };
+                    s.playlist = vec![round, no_ratios];
+                },
+                vec![SettingsError::InPlaylist {
+                    round: 1,
+                    error: Box::new(SettingsError::RatiosAllZero),
+                }],
+            ),
+            (
+                |s| {
+                    s.players = 0;
+                    s.ratios = Ratios::NONE;
+                },
+                vec![
+                    SettingsError::PlayersOutOfRange(0),
+                    SettingsError::MinPlayersToStartOutOfRange {
+                        min_players_to_start: 2,
+                        players: 0,
+                    },
+                    SettingsError::RatiosAllZero,
+                ],
+            ),
+        ];
+        for (change, expected) in cases {
+            let mut settings = Settings::default();
+            change(&mut settings);
+            assert_eq!(settings.validate(), Err(expected));
+        }
+
+        assert_eq!(
+            describe_errors(&[
+                SettingsError::PlayersOutOfRange(9),
+                SettingsError::RatiosAllZero
+            ]),
+            "players 9 not in 1..=8, all ratios are 0"
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_more_teams_than_players() {
+        let settings = Settings {
+            players: 2,
+            min_players_to_start: 2,
+            teams: 2,
+            ..Settings::default()
+        };
+        assert_eq!(settings.validate(), Ok(()));
+        let settings = Settings {
+            teams: 3,
+            ..settings
+        };
+        assert_eq!(
+            settings.validate(),
+            Err(vec![SettingsError::TeamsOutOfRange {
+                teams: 3,
+                players: 2
+            }])
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_tournaments_that_stay_on_the_results() {
+        let settings = Settings {
+            best_of: 3,
+            return_to_lobby_after_s: 0,
+            ..Settings::default()
+        };
+        assert_eq!(
+            settings.validate(),
+            Err(vec![SettingsError::TournamentStaysOnResults { best_of: 3 }])
+        );
+
+        let single_game = Settings {
+            best_of: 1,
+            ..settings.clone()
+        };
+        assert_eq!(single_game.validate(), Ok(()));
+
+        let returning = Settings {
+            return_to_lobby_after_s: 1,
+            ..settings
+        };
+        assert_eq!(returning.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_rejects_playlists_that_do_not_fit_a_message() {
+        let round = RoundSettings {
+            map_seed: None,
+            custom_map: None,
+            ratios: Ratios::default(),
+        };
+        let mut settings = Settings {
+            playlist: vec![round; 50],
+            ..Settings::default()
+        };
+        assert_eq!(settings.validate(), Ok(()));
+
+        settings.playlist.extend(settings.playlist.clone());
+        assert_eq!(
+            settings.validate(),
+            Err(vec![SettingsError::TooLarge(1274)])
+        );
+    }
+
+    #[test]
+    fn test_validate_checks_the_field_unless_its_size_is_invalid() {
+        let malformed = Settings {
+            custom_map: Some("?".to_owned()),
+            ..Settings::default()
+        };
+        let no_ratios = Settings {
+            ratios: Ratios::NONE,
+            ..malformed.clone()
+        };
+        assert_eq!(
+            no_ratios.validate(),
+            Err(vec![
+                SettingsError::RatiosAllZero,
+                SettingsError::CustomMapMalformed(
+                    "Character for Cell 0/0 invalid: Invalid character ?".to_owned(),
+                ),
+            ])
+        );
+
+        let too_small = Settings {
+            width: 4,
+            ..malformed
+        };
+        assert_eq!(
+            too_small.validate(),
+            Err(vec![SettingsError::FieldTooSmall {
+                width: 4,
+                height: 13,
+            }])
+        );
+    }
+
+    #[test]
+    fn test_parse_map_seed() {
+        assert_eq!(Settings::parse_map_seed("4267"), Ok(4267));
+        assert_eq!(Settings::parse_map_seed(" 0\n"), Ok(0));
+        assert!(Settings::parse_map_seed("").is_err());
+        assert!(Settings::parse_map_seed("-1").is_err());
+        assert_eq!(Settings::parse_map_seed("4294967296"), Ok(1 << 32));
+        assert!(Settings::parse_map_seed("18446744073709551616").is_err());
+        assert!(Settings::parse_map_seed("Hans").is_err());
+    }
 }
@@ -0,0 +1,857 @@
+//! Run a game without GUI or network, driven by a script of player actions
+//!
+//! Each script line is `<tick> <player> <action>`, e.g. `10 0 north+place`. Empty lines and
+//! lines starting with `#` are ignored. Actions are `idle`, `place`, a direction (`north`,
+//! `west`, `south`, `east`), two directions to walk diagonally (`north-east`) or either of
+//! them followed by `+place`. Any action can end in `+detonate` to blow up remote bombs.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::rc::Rc;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::field::Cell;
+use crate::field::Field;
+use crate::game_state::Action;
+use crate::game_state::GameState;
+use crate::game_state::GameStatic;
+use crate::game_state::Player;
+use crate::network::decode_base64;
+use crate::network::encode_base64;
+use crate::settings::describe_errors;
+use crate::settings::Settings;
+use crate::utils::CellPosition;
+use crate::utils::Direction;
+use crate::utils::Duration;
+use crate::utils::PlayerId;
+use crate::utils::Position;
+use crate::utils::TimeStamp;
+
+/// A player changing their action at a given time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptedAction {
+    pub time: TimeStamp,
+    pub player: PlayerId,
+    pub action: Action,
+}
+
+/// Parse one action of a script, see the module documentation
+///
+/// # Errors
+///
+/// Names the action if it is unknown
+pub fn parse_action(action: &str) -> Result<Action, String> {
+    let (action, detonate) = match action.strip_suffix("+detonate") {
+        Some(action) => (action, true),
+        None => (action, false),
+    };
+    let (walking, placing) = match action.strip_suffix("+place") {
+        Some(directions) => (directions, true),
+        None => (action, false),
+    };
+    let direction = |direction| match direction {
+        "north" => Ok(Direction::North),
+        "west" => Ok(Direction::West),
+        "south" => Ok(Direction::South),
+        "east" => Ok(Direction::East),
+        _ => Err(format!("unknown action {action:?}")),
+    };
+    let (walking, diagonal) = match walking.split_once('-') {
+        _ if placing && (walking == "idle" || walking == "place") => {
+            return Err(format!("unknown action {action:?}"));
+        }
+        None if walking == "idle" => (None, None),
+        None if walking == "place" => (None, None),
+        None => (Some(direction(walking)?), None),
+        Some((walking, diagonal)) => (Some(direction(walking)?), Some(direction(diagonal)?)),
+    };
+    Ok(Action {
+        walking,
+        diagonal,
+        placing: placing || action == "place",
+        detonate,
+    })
+}
+
+/// The inverse of `parse_action`
+pub fn format_action(action: Action) -> String {
+    let direction = |direction| match direction {
+        Direction::North => "north",
+        Direction::West => "west",
+        Direction::South => "south",
+        Direction::East => "east",
+    };
+    let walking = match (action.walking, action.diagonal) {
+        (None, _) if action.placing => "place".to_owned(),
+        (None, _) => "idle".to_owned(),
+        (Some(walking), None) => direction(walking).to_owned(),
+        (Some(walking), Some(diagonal)) => {
+            format!("{}-{}", direction(walking), direction(diagonal))
+        }
+    };
+    let placing = if action.placing && action.walking.is_some() {
+        format!("{walking}+place")
+    } else {
+        walking
+    };
+    if action.detonate {
+        format!("{placing}+detonate")
+    } else {
+        placing
+    }
+}
+
+/// A script that `parse_script` reads back as `actions`
+pub fn format_script(actions: &[ScriptedAction]) -> String {
+    let mut script = String::new();
+    for a in actions {
+        writeln!(
+            script,
+            "{} {} {}",
+            a.time.ticks_from_start(),
+            a.player.0,
+            format_action(a.action)
+        )
+        .expect("can write to string");
+    }
+    script
+}
+
+/// Parse a script, the actions are sorted by time
+///
+/// # Errors
+///
+/// Names the first line that is not `<tick> <player> <action>`
+pub fn parse_script(script: &str) -> Result<Vec<ScriptedAction>, String> {
+    let mut actions = script
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| {
+            let [tick, player, action] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+                return Err(format!(
+                    "line {line_number}: expected <tick> <player> <action>"
+                ));
+            };
+            let tick: u32 = tick
+                .parse()
+                .map_err(|err| format!("line {line_number}: invalid tick: {err}"))?;
+            let player: usize = player
+                .parse()
+                .map_err(|err| format!("line {line_number}: invalid player: {err}"))?;
+            let action =
+                parse_action(action).map_err(|err| format!("line {line_number}: {err}"))?;
+            Ok(ScriptedAction {
+                time: TimeStamp::default() + Duration::from_ticks(tick),
+                player: PlayerId(player),
+                action,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    actions.sort_by_key(|a| a.time.ticks_from_start());
+    Ok(actions)
+}
+
+/// Everything that determines a game besides the players' actions
+///
+/// The field follows from the settings and their map seed, all other randomness from the time
+/// and place it happens at, so a spec and the actions always replay the same game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameSpec {
+    pub settings: Settings,
+    pub players: BTreeMap<PlayerId, Player>,
+}
+
+impl GameSpec {
+    pub fn new(game: &GameStatic) -> Self {
+        Self {
+            settings: game.settings.clone(),
+            players: game.players.clone(),
+        }
+    }
+
+    /// Encode the spec into a string that can be logged and passed to `bomberhans-simulate`
+    pub fn to_shareable_string(&self) -> String {
+        encode_base64(self)
+    }
+
+    /// Decode and validate a spec that was encoded with `to_shareable_string`
+    ///
+    /// # Errors
+    ///
+    /// Why the string does not decode to a valid game spec
+    pub fn from_shareable_string(string: &str) -> Result<Self, String> {
+        let spec: Self = decode_base64(string).ok_or_else(|| "not a game spec".to_owned())?;
+        spec.settings
+            .validate()
+            .map_err(|errors| describe_errors(&errors))?;
+        Ok(spec)
+    }
+
+    /// The game at its start
+    pub fn game_state(&self) -> GameState {
+        let local_player = self.players.keys().next().copied().unwrap_or(PlayerId(0));
+        GameState::new(Rc::new(GameStatic {
+            players: self.players.clone(),
+            settings: self.settings.clone(),
+            local_player,
+        }))
+    }
+}
+
+/// A game with all players on their start positions, like a local game
+///
+/// # Errors
+///
+/// Describes why the settings are not valid
+pub fn new_game(settings: Settings) -> Result<GameState, String> {
+    settings
+        .validate()
+        .map_err(|errors| describe_errors(&errors))?;
+    let start_positions = Field::new_from_rules(&settings).start_positions();
+
+    let players: BTreeMap<PlayerId, Player> = (0..settings.players as usize)
+        .map(|id| {
+            let mut player = Player::new(
+                format!("Player {id}"),
+                PlayerId(id),
+                Position::from_cell_position(start_positions[id]),
+            );
+            player.team = settings.team_of(id);
+            (PlayerId(id), player)
+        })
+        .collect();
+
+    Ok(GameState::new(Rc::new(GameStatic {
+        players,
+        settings,
+        local_player: PlayerId(0),
+    })))
+}
+
+/// Where a player spent their time and placed their bombs, to review their play
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heatmap {
+    pub width: u32,
+    pub height: u32,
+
+    /// Updates the player spent in each cell, in the order of `Field::cells`
+    pub visits: Vec<u32>,
+
+    /// Bombs the player placed in each cell, in the order of `Field::cells`
+    pub bombs: Vec<u32>,
+
+    /// Cells that held a bomb of the player at the last `observe`
+    own_bombs: Vec<bool>,
+
+    /// The player's `bombs_placed` at the last `observe`
+    bombs_placed: u32,
+}
+
+impl Heatmap {
+    pub fn new(field: &Field) -> Self {
+        let cells = field.cells.len();
+        Self {
+            width: field.width,
+            height: field.height,
+            visits: vec![0; cells],
+            bombs: vec![0; cells],
+            own_bombs: vec![false; cells],
+            bombs_placed: 0,
+        }
+    }
+
+    fn index(&self, cell: CellPosition) -> Option<usize> {
+        let in_field =
+            cell.x >= 0 && cell.y >= 0 && cell.x < self.width as i32 && cell.y < self.height as i32;
+        in_field.then(|| (cell.y * self.width as i32 + cell.x) as usize)
+    }
+
+    /// Count where `player` is after an update and the bomb they placed in it
+    pub fn observe(&mut self, game_state: &GameState, player: PlayerId) {
+        let Some(state) = game_state.player_states.get(player) else {
+            return;
+        };
+        self.visit(state.position);
+        // a bomb of the player that was not there before, if they placed one
+        let placed = state.bombs_placed > self.bombs_placed;
+        self.bombs_placed = state.bombs_placed;
+        for (cell, c) in game_state.field.iter() {
+            let Some(i) = self.index(cell) else {
+                continue;
+            };
+            let own = matches!(*c, Cell::Bomb { owner, .. } if owner == player);
+            if own && placed && !self.own_bombs[i] {
+                self.bombs[i] += 1;
+            }
+            self.own_bombs[i] = own;
+        }
+    }
+
+    /// Count one update spent at `position`, in the cell that contains it
+    pub fn visit(&mut self, position: Position) {
+        if let Some(i) = self.index(position.as_cell_pos()) {
+            self.visits[i] += 1;
+        }
+    }
+
+    /// Updates spent in the cell the player spent the most time in
+    pub fn max_visits(&self) -> u32 {
+        self.visits.iter().copied().max().unwrap_or(0)
+    }
+
+    /// One character per cell, `.` if never visited, else `0` to `9` relative to `max_visits`
+    pub fn string_grid(&self) -> String {
+        let max = self.max_visits();
+        let mut s = String::new();
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                let visits = self.visits(CellPosition::new(x, y));
+                s.push(if visits == 0 {
+                    '.'
+                } else {
+                    char::from_digit(visits * 9 / max, 10).unwrap_or('?')
+                });
+            }
+            s.push('\n');
+        }
+        s
+    }
+
+    /// Updates spent in `cell`
+    pub fn visits(&self, cell: CellPosition) -> u32 {
+        self.index(cell).map_or(0, |i| self.visits[i])
+    }
+
+    /// Bombs placed in `cell`
+    pub fn bombs(&self, cell: CellPosition) -> u32 {
+        self.index(cell).map_or(0, |i| self.bombs[i])
+    }
+}
+
+/// Steps a game through a script, one update at a time
+struct Playback<'a> {
+    script: std::iter::Peekable<std::slice::Iter<'a, ScriptedAction>>,
+}
+
+impl<'a> Playback<'a> {
+    fn new(script: &'a [ScriptedAction]) -> Self {
+        Self {
+            script: script.iter().peekable(),
+        }
+    }
+
+    /// Apply the actions whose time has come and simulate one update
+    fn step(&mut self, game_state: &mut GameState) -> Result<(), String> {
+        while let Some(scripted) = self.script.next_if(|s| s.time <= game_state.time) {
+            if !game_state.player_states.contains(scripted.player) {
+                return Err(format!("unknown player {:?}", scripted.player));
+            }
+            game_state.set_player_action(scripted.player, scripted.action);
+        }
+        game_state.simulate_1_update();
+        Ok(())
+    }
+}
+
+/// Simulate `ticks` updates, applying each scripted action once its time has come
+///
+/// `on_tick` is called with the state after each update.
+///
+/// # Errors
+///
+/// If the script has an action for a player that is not in the game
+pub fn run(
+    game_state: &mut GameState,
+    script: &[ScriptedAction],
+    ticks: u32,
+    mut on_tick: impl FnMut(&GameState),
+) -> Result<(), String> {
+    let mut playback = Playback::new(script);
+    for _ in 0..ticks {
+        playback.step(game_state)?;
+        on_tick(game_state);
+    }
+    Ok(())
+}
+
+/// A game and the actions to play in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub spec: GameSpec,
+    pub script: Vec<ScriptedAction>,
+}
+
+/// The first tick at which two replays differ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Ticks from the start of the game
+    pub tick: u32,
+
+    /// As in `GameState::diff`
+    pub differences: Vec<String>,
+}
+
+/// Play two replays side by side for up to `ticks` updates and find where they first differ
+///
+/// `None` if they stay the same, e.g. because a changed rule never came into play.
+///
+/// # Errors
+///
+/// If a script has an action for a player that is not in its game
+pub fn diff_replays(a: &Replay, b: &Replay, ticks: u32) -> Result<Option<Divergence>, String> {
+    let (mut state_a, mut state_b) = (a.spec.game_state(), b.spec.game_state());
+    let (mut playback_a, mut playback_b) = (Playback::new(&a.script), Playback::new(&b.script));
+    for i in 0..=ticks {
+        if i > 0 {
+            playback_a.step(&mut state_a)?;
+            playback_b.step(&mut state_b)?;
+        }
+        let differences = state_a.diff(&state_b);
+        if !differences.is_empty() {
+            return Ok(Some(Divergence {
+                tick: state_a.time.ticks_from_start(),
+                differences,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::DeathPenalty;
+    use crate::settings::Ratios;
+
+    /// Default settings without the countdown, so scripts move players from the first tick
+    fn quick_start() -> Settings {
+        Settings {
+            countdown_s: 0,
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_action() {
+        assert_eq!(
+            parse_action("idle"),
+            Ok(Action {
+                walking: None,
+                diagonal: None,
+                placing: false,
+                detonate: false,
+            })
+        );
+        assert_eq!(
+            parse_action("place"),
+            Ok(Action {
+                walking: None,
+                diagonal: None,
+                placing: true,
+                detonate: false,
+            })
+        );
+        assert_eq!(
+            parse_action("south"),
+            Ok(Action {
+                walking: Some(Direction::South),
+                diagonal: None,
+                placing: false,
+                detonate: false,
+            })
+        );
+        assert_eq!(
+            parse_action("east+place"),
+            Ok(Action {
+                walking: Some(Direction::East),
+                diagonal: None,
+                placing: true,
+                detonate: false,
+            })
+        );
+        assert_eq!(
+            parse_action("north-east+place"),
+            Ok(Action {
+                walking: Some(Direction::North),
+                diagonal: Some(Direction::East),
+                placing: true,
+                detonate: false,
+            })
+        );
+        assert_eq!(
+            parse_action("idle+detonate"),
+            Ok(Action {
+                detonate: true,
+                ..Action::idle()
+            })
+        );
+        assert_eq!(
+            parse_action("west+place+detonate"),
+            Ok(Action {
+                walking: Some(Direction::West),
+                diagonal: None,
+                placing: true,
+                detonate: true
+            })
+        );
+        assert!(parse_action("north-up").is_err());
+        assert!(parse_action("detonate+place").is_err());
+        assert!(parse_action("place+place").is_err());
+        assert!(parse_action("idle+place").is_err());
+        assert!(parse_action("up").is_err());
+    }
+
+    #[test]
+    fn test_game_spec_reproduces_game() {
+        let settings = Settings {
+            map_seed: Some(4267),
+            ..quick_start()
+        };
+        let spec = GameSpec::new(&new_game(settings).unwrap().game);
+        let string = spec.to_shareable_string();
+        assert_eq!(GameSpec::from_shareable_string(&string), Ok(spec.clone()));
+        assert!(GameSpec::from_shareable_string(&string[2..]).is_err());
+        assert_eq!(
+            GameSpec::from_shareable_string(&string)
+                .unwrap()
+                .game_state()
+                .field,
+            Field::new_from_rules(&spec.settings)
+        );
+
+        // the bomb burns wood, which draws random numbers for what it turns into
+        let script = parse_script("0 0 east\n30 0 place\n31 0 west").unwrap();
+        let mut original = new_game(quick_start()).unwrap();
+        let mut replayed =
+            GameSpec::from_shareable_string(&GameSpec::new(&original.game).to_shareable_string())
+                .unwrap()
+                .game_state();
+        run(&mut original, &script, 300, |_| {}).unwrap();
+        run(&mut replayed, &script, 300, |_| {}).unwrap();
+        assert_ne!(original.field, Field::new_from_rules(&Settings::default()));
+        assert_eq!(replayed.field, original.field);
+        assert_eq!(replayed.checksum(), original.checksum());
+    }
+
+    #[test]
+    fn test_replays_with_different_rules_diverge_when_the_rule_matters() {
+        let script = parse_script("0 0 east\n30 0 place\n31 0 west").unwrap();
+        let replay = |settings| Replay {
+            spec: GameSpec::new(&new_game(settings).unwrap().game),
+            script: script.clone(),
+        };
+        let normal = replay(quick_start());
+
+        assert_eq!(diff_replays(&normal, &normal.clone(), 300), Ok(None));
+
+        // the bomb is placed at tick 30 and lands in the state after that update
+        let quick_bombs = replay(Settings {
+            bomb_explode_time_ms: Settings::BOMB_TIME_DEFAULT / 2,
+            ..quick_start()
+        });
+        let divergence = diff_replays(&normal, &quick_bombs, 300)
+            .unwrap()
+            .expect("the bombs differ");
+        assert_eq!(divergence.tick, 31);
+        assert_eq!(divergence.differences.len(), 1, "{divergence:?}");
+        assert!(divergence.differences[0].contains("Bomb"));
+
+        // nobody walks on a bomb in the first 10 ticks
+        let bomb_walkers = replay(Settings {
+            bomb_walking_chance: 100,
+            ..quick_start()
+        });
+        assert_eq!(diff_replays(&normal, &bomb_walkers, 10), Ok(None));
+    }
+
+    #[test]
+    fn test_format_script_is_read_back() {
+        let script =
+            parse_script("0 0 east\n3 1 place\n5 0 north-west+place\n7 0 south+place\n9 1 idle\n")
+                .unwrap();
+        assert_eq!(
+            format_script(&script),
+            "0 0 east\n3 1 place\n5 0 north-west+place\n7 0 south+place\n9 1 idle\n"
+        );
+        assert_eq!(parse_script(&format_script(&script)), Ok(script));
+    }
+
+    #[test]
+    fn test_heatmap_counts_visits_and_bombs() {
+        let script = parse_script("0 0 east\n30 0 place\n31 0 west").unwrap();
+        let mut game_state = new_game(quick_start()).unwrap();
+        let mut heatmap = Heatmap::new(&game_state.field);
+        run(&mut game_state, &script, 100, |game_state| {
+            heatmap.observe(game_state, PlayerId(0));
+        })
+        .unwrap();
+
+        assert_eq!(heatmap.visits.iter().sum::<u32>(), 100);
+        assert!(heatmap.visits(CellPosition::new(0, 0)) > 0);
+        assert!(heatmap.visits(CellPosition::new(1, 0)) > 0);
+        assert_eq!(heatmap.bombs.iter().sum::<u32>(), 1);
+        assert_eq!(heatmap.visits(CellPosition::new(-1, 0)), 0);
+    }
+
+    #[test]
+    fn test_heatmap_accumulates_a_path() {
+        let field = Field::new(5, 3);
+        let mut heatmap = Heatmap::new(&field);
+        let step = Position::ACCURACY / 4;
+        let mut position = Position::from_cell_position(CellPosition::new(0, 1));
+        // a quarter cell per update to the east, then stand still in the last cell
+        for _ in 0..12 {
+            heatmap.visit(position);
+            position.x += step;
+        }
+        for _ in 0..7 {
+            heatmap.visit(position);
+        }
+        heatmap.visit(Position::new(-Position::ACCURACY, 0));
+
+        assert_eq!(heatmap.visits(CellPosition::new(0, 1)), 2);
+        assert_eq!(heatmap.visits(CellPosition::new(1, 1)), 4);
+        assert_eq!(heatmap.visits(CellPosition::new(2, 1)), 4);
+        assert_eq!(heatmap.visits(CellPosition::new(3, 1)), 9);
+        assert_eq!(heatmap.visits.iter().sum::<u32>(), 19);
+        assert_eq!(heatmap.max_visits(), 9);
+        assert_eq!(heatmap.string_grid(), ".....\n2449.\n.....\n");
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let script = parse_script("# comment\n\n20 1 west\n 10 0 place \n").unwrap();
+        assert_eq!(script.len(), 2);
+        assert_eq!(script[0].player, PlayerId(0));
+        assert_eq!(
+            script[1].time,
+            TimeStamp::default() + Duration::from_ticks(20)
+        );
+
+        assert!(parse_script("10 0").is_err());
+        assert!(parse_script("10 0 north extra").is_err());
+        assert!(parse_script("-1 0 north").is_err());
+    }
+
+    #[test]
+    fn test_run_script() {
+        let script = parse_script(
+            "
+            # walk onto the empty cell next to the start point and place a bomb there
+            0 0 east
+            30 0 place
+            31 0 west
+            40 1 north+place
+            45 1 idle
+            ",
+        )
+        .unwrap();
+
+        let simulate = || {
+            let mut game_state = new_game(quick_start()).unwrap();
+            let mut ticks = 0;
+            run(&mut game_state, &script, 300, |_| ticks += 1).unwrap();
+            assert_eq!(ticks, 300);
+            game_state
+        };
+
+        let game_state = simulate();
+        assert_eq!(game_state.time.ticks_from_start(), 300);
+        assert_eq!(
+            game_state.checksum(),
+            simulate().checksum(),
+            "deterministic"
+        );
+        // changes when the rules change, update deliberately
+        assert_eq!(game_state.checksum(), 2_345_993_187);
+
+        // the bomb burned some wood, the players moved
+        let fresh = new_game(quick_start()).unwrap();
+        assert_ne!(game_state.field, fresh.field);
+        assert_ne!(game_state.player_states, fresh.player_states);
+    }
+
+    #[test]
+    fn test_run_unknown_player() {
+        let script = parse_script("0 7 north").unwrap();
+        let mut game_state = new_game(quick_start()).unwrap();
+        assert!(run(&mut game_state, &script, 1, |_| {}).is_err());
+    }
+
+    /// Small deterministic generator, so each seed is the same game on every run
+    struct Rng(u32);
+
+    impl Rng {
+        fn new(seed: u32) -> Self {
+            Self(seed.wrapping_mul(0x9e37_79b9) | 1)
+        }
+
+        fn next(&mut self, range: std::ops::RangeInclusive<u32>) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            range.start() + self.0 % (range.end() - range.start() + 1)
+        }
+
+        fn chance(&mut self, percent: u32) -> bool {
+            self.next(0..=99) < percent
+        }
+    }
+
+    fn random_settings(rng: &mut Rng) -> Settings {
+        let mut settings = Settings {
+            width: rng.next(Settings::WIDTH_RANGE),
+            height: rng.next(Settings::HEIGHT_RANGE),
+            players: rng.next(Settings::PLAYERS_RANGE),
+            map_seed: Some(u64::from(rng.next(0..=u32::MAX - 1))),
+            kills_to_win: rng.next(0..=3),
+            lives: rng.next(1..=3),
+            burnt_wood_percent: rng.next(0..=50),
+            teams: rng.next(0..=2),
+            friendly_fire: rng.chance(50),
+            countdown_s: rng.next(Settings::COUNTDOWN_RANGE),
+            // limited lives and magma end every game eventually, unless regrowing wood walls
+            // the last players in for good
+            magma_after_s: rng.next(1..=30),
+            wood_regrow_ms: 0,
+            bomb_explode_time_ms: rng.next(100..=3000),
+            speed_base: rng.next(Settings::SPEED_BASE_RANGE),
+            speed_multiplyer: rng.next(Settings::SPEED_MULTIPLYER_RANGE),
+            bomb_walking_chance: rng.next(Settings::BOMB_WALKING_CHANCE_RANGE),
+            tombstone_walking_chance: rng.next(Settings::TOMBSTONE_WALKING_CHANCE_RANGE),
+            upgrade_explosion_power: rng.next(Settings::UPGRADE_EXPLOSION_POWER_RANGE),
+            chain_reactions: rng.chance(50),
+            explosion_knockback: rng.chance(50),
+            diagonal_blast: rng.chance(50),
+            fire_destroys_upgrades: rng.chance(50),
+            wood_burn_time_ms: rng.next(0..=3000),
+            fire_burn_time_ms: rng.next(0..=3000),
+            bomb_offset: rng.next(Settings::BOMB_OFFSET_RANGE),
+            diagonal_walking: rng.chance(50),
+            teleports_enabled: rng.chance(80),
+            teleport_consumed: rng.chance(50),
+            death_penalty: DeathPenalty::ALL[rng.next(0..=2) as usize],
+            max_power: rng.next(0..=5),
+            max_speed: rng.next(0..=5),
+            max_bombs: rng.next(0..=5),
+            capped_upgrades_score: rng.chance(50),
+            hide_opponent_upgrades: rng.chance(50),
+            ratios: Ratios {
+                power: rng.next(0..=10),
+                speed: rng.next(0..=10),
+                bombs: rng.next(0..=10),
+                remote: rng.next(0..=10),
+                kick: rng.next(0..=10),
+                pierce: rng.next(0..=10),
+                teleport: rng.next(0..=10),
+                wall: rng.next(0..=10),
+                wood: rng.next(0..=10),
+                clear: rng.next(1..=10),
+            },
+            ..Settings::default()
+        };
+        settings.min_players_to_start = rng.next(1..=settings.players);
+        settings
+    }
+
+    /// Every player changes their mind every few ticks
+    fn random_script(rng: &mut Rng, game_state: &GameState, ticks: u32) -> Vec<ScriptedAction> {
+        let directions = [
+            Direction::North,
+            Direction::West,
+            Direction::South,
+            Direction::East,
+        ];
+        let mut script = Vec::new();
+        for player in game_state.player_states.ids() {
+            let mut tick = 0;
+            while tick < ticks {
+                let walking = directions.get(rng.next(0..=4) as usize).copied();
+                let diagonal = walking.filter(|_| rng.chance(30)).map(|d| {
+                    if rng.chance(50) {
+                        d.left()
+                    } else {
+                        d.right()
+                    }
+                });
+                let action = Action {
+                    walking,
+                    diagonal,
+                    placing: rng.chance(20),
+                    detonate: rng.chance(5),
+                };
+                let time = game_state.time + Duration::from_ticks(tick);
+                script.push(ScriptedAction {
+                    time,
+                    player,
+                    action,
+                });
+                tick += rng.next(1..=40);
+            }
+        }
+        script.sort_by_key(|scripted| scripted.time.ticks_from_start());
+        script
+    }
+
+    /// A random game played until it is decided, at most `MAX_TICKS` long, and the checksum
+    /// after each tick
+    fn random_game(seed: u32) -> (GameState, Vec<u32>) {
+        const TICKS: u32 = 3000;
+        const MAX_TICKS: u32 = 30_000;
+        let mut rng = Rng::new(seed);
+        let mut game_state = loop {
+            if let Ok(game_state) = new_game(random_settings(&mut rng)) {
+                break game_state;
+            }
+        };
+        let script = random_script(&mut rng, &game_state, TICKS);
+        let mut checksums = Vec::new();
+        run(&mut game_state, &script, TICKS, |state| {
+            for player in state.player_states.values() {
+                assert!(
+                    state.field.is_cell_in_field(player.position.as_cell_pos()),
+                    "seed {seed}: player left the field at {:?}",
+                    player.position
+                );
+            }
+            checksums.push(state.checksum());
+        })
+        .unwrap();
+        while !game_state.is_decided() && checksums.len() < MAX_TICKS as usize {
+            game_state.simulate_1_update();
+            checksums.push(game_state.checksum());
+        }
+        (game_state, checksums)
+    }
+
+    #[test]
+    fn test_random_games_run_to_completion() {
+        const SEEDS: [u32; 12] = [
+            1,
+            2,
+            3,
+            42,
+            4267,
+            1337,
+            9001,
+            65535,
+            123_456,
+            7_777_777,
+            0xdead_beef,
+            0xffff_fffe,
+        ];
+        for seed in SEEDS {
+            let (game_state, checksums) = random_game(seed);
+            assert!(game_state.is_decided(), "seed {seed}: game did not end");
+            assert_eq!(
+                random_game(seed).1,
+                checksums,
+                "seed {seed}: same game, different result"
+            );
+        }
+    }
+}
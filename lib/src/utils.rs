@@ -1,4 +1,5 @@
 use core::fmt;
+use std::collections::VecDeque;
 
 use serde::Deserialize;
 use serde::Serialize;
@@ -32,8 +33,12 @@ where
 pub const TICKS_PER_SECOND: u32 = 50;
 pub const TIME_PER_TICK: std::time::Duration = std::time::Duration::from_millis(20);
 
+/// Games end after this many ticks (about 1.3 years), long before bombs, fire or burning wood
+/// placed near the end could expire at a time that does not fit into a `TimeStamp`
+pub const MAX_GAME_TICKS: u32 = u32::MAX / 2;
+
 /// A Time Stamp (not a duration)
-#[derive(Default, Copy, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[derive(Default, Copy, Clone, PartialEq, Eq, PartialOrd, Serialize, Deserialize)]
 pub struct TimeStamp {
     inner: u32,
 }
@@ -55,7 +60,7 @@ impl std::ops::Add<Duration> for TimeStamp {
 
     fn add(self, rhs: Duration) -> Self::Output {
         Self {
-            inner: self.inner + rhs.ticks,
+            inner: self.inner.saturating_add(rhs.ticks),
         }
     }
 }
@@ -75,13 +80,22 @@ impl Duration {
         let ticks = if milliseconds == 0 {
             0
         } else {
-            u32::max(1, (milliseconds * TICKS_PER_SECOND + 499) / 1000)
+            let ticks = (u64::from(milliseconds) * u64::from(TICKS_PER_SECOND) + 499) / 1000;
+            u32::max(1, u32::try_from(ticks).unwrap_or(u32::MAX))
         };
         Self { ticks }
     }
     pub fn ticks(self) -> u32 {
         self.ticks
     }
+
+    /// The time this actually lasts, `from_ms` rounds to whole ticks
+    ///
+    /// Saturates at `u32::MAX` for durations longer than about 49 days
+    pub fn as_ms(self) -> u32 {
+        let ms = u64::from(self.ticks) * 1000 / u64::from(TICKS_PER_SECOND);
+        u32::try_from(ms).unwrap_or(u32::MAX)
+    }
 }
 
 impl fmt::Debug for Duration {
@@ -93,13 +107,18 @@ impl fmt::Debug for Duration {
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct PlayerId(pub usize);
 
+impl PlayerId {
+    /// Owns fire that no player set, like magma. No player has this id, so nobody scores with it
+    pub const NOBODY: PlayerId = PlayerId(usize::MAX);
+}
+
 impl fmt::Debug for PlayerId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Player{}", self.0)
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
     North,
     West,
@@ -124,6 +143,9 @@ impl Direction {
             Direction::East => Direction::South,
         }
     }
+    pub fn opposite(self) -> Self {
+        self.left().left()
+    }
 }
 
 impl fmt::Debug for Direction {
@@ -169,7 +191,7 @@ impl fmt::Debug for CellPosition {
 }
 
 /// Player positions
-#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
@@ -225,10 +247,115 @@ impl Position {
     }
 }
 
+/// Keeps the most recent `capacity` items, dropping the oldest
+#[derive(Debug, Clone)]
+pub struct RingBuffer<T> {
+    capacity: usize,
+    items: VecDeque<T>,
+}
+
+impl<T> RingBuffer<T> {
+    /// # Panics
+    ///
+    /// If `capacity` is 0
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "RingBuffer needs room for at least one item");
+        Self {
+            capacity,
+            items: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.items.len() == self.capacity {
+            self.items.pop_front();
+        }
+        self.items.push_back(item);
+    }
+
+    /// Iterate from oldest to newest
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator {
+        self.items.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_ring_buffer_keeps_most_recent() {
+        let mut buffer = RingBuffer::new(3);
+        assert!(buffer.is_empty());
+        for nonce in 0..10 {
+            buffer.push((TimeStamp::default() + Duration::from_ticks(nonce), nonce));
+        }
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(
+            buffer.iter().map(|(_, nonce)| *nonce).collect::<Vec<_>>(),
+            vec![7, 8, 9]
+        );
+
+        // looking up the send time of a recent packet, like the ping computation does
+        let found = buffer.iter().rfind(|(_, nonce)| *nonce == 8);
+        assert_eq!(
+            found,
+            Some(&(TimeStamp::default() + Duration::from_ticks(8), 8))
+        );
+        assert_eq!(buffer.iter().rfind(|(_, nonce)| *nonce == 2), None);
+    }
+
+    #[test]
+    fn test_duration_ms_rounding() {
+        let ticks = |ms| Duration::from_ms(ms).ticks();
+        assert_eq!(ticks(0), 0);
+        assert_eq!(ticks(1), 1, "nonzero durations last at least 1 tick");
+        assert_eq!(ticks(19), 1);
+        assert_eq!(ticks(20), 1);
+        assert_eq!(ticks(21), 1);
+        assert_eq!(ticks(30), 1, "exactly half a tick rounds down");
+        assert_eq!(ticks(31), 2);
+
+        assert_eq!(Duration::from_ms(0).as_ms(), 0);
+        assert_eq!(Duration::from_ms(1).as_ms(), 20);
+        assert_eq!(Duration::from_ms(21).as_ms(), 20);
+        assert_eq!(Duration::from_ms(4267).as_ms(), 4260);
+    }
+
+    #[test]
+    fn test_long_durations_do_not_overflow() {
+        let day = 24 * 60 * 60 * 1000;
+        assert_eq!(Duration::from_ms(day).ticks(), day / 20);
+        assert_eq!(Duration::from_ms(day).as_ms(), day);
+        assert_eq!(Duration::from_ms(u32::MAX).ticks(), 214_748_365);
+        assert_eq!(
+            Duration::from_ticks(u32::MAX / 20).as_ms(),
+            u32::MAX / 20 * 20
+        );
+        assert_eq!(
+            Duration::from_ticks(u32::MAX / 20 + 1).as_ms(),
+            u32::MAX,
+            "saturates"
+        );
+        assert_eq!(Duration::from_ticks(MAX_GAME_TICKS).as_ms(), u32::MAX);
+    }
+
+    #[test]
+    fn test_time_stamp_saturates() {
+        let end = TimeStamp::default() + Duration::from_ticks(u32::MAX - 1);
+        assert_eq!((end + Duration::from_ticks(1)).ticks_from_start(), u32::MAX);
+        assert_eq!((end + Duration::from_ticks(5)).ticks_from_start(), u32::MAX);
+        assert!(end + Duration::from_ticks(5) > end);
+    }
+
     #[test]
     fn test_random() {
         let r = random(TimeStamp::default(), 0, 0);
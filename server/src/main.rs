@@ -8,44 +8,132 @@ use std::thread::sleep;
 
 use bomberhans_lib::network::*;
 
+mod send_queue;
 mod server;
 
+use send_queue::SendQueue;
+
+/// Messages kept when the network is slower than the server, unless
+/// `BOMBERHANS_SEND_QUEUE` says otherwise
+const SEND_QUEUE_DEFAULT: usize = 1024;
+
+/// Port the server listens on, unless `BOMBERHANS_BIND` says otherwise
+const DEFAULT_PORT: u16 = 4267;
+
+/// What the server loop needs from its socket, so tests can replace it
+trait Socket {
+    fn recv_from(&self, buf: &mut [u8]) -> std::io::Result<(usize, SocketAddr)>;
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> std::io::Result<usize>;
+}
+
+impl Socket for UdpSocket {
+    fn recv_from(&self, buf: &mut [u8]) -> std::io::Result<(usize, SocketAddr)> {
+        UdpSocket::recv_from(self, buf)
+    }
+
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> std::io::Result<usize> {
+        UdpSocket::send_to(self, buf, addr)
+    }
+}
+
+/// Send queued messages until the socket would block
+///
+/// A message that fails for another reason is lost, the others are still sent.
+fn flush(queue: &mut SendQueue, socket: &impl Socket) {
+    while let Some((adr, msg)) = queue.pop() {
+        log::debug!("sending to {adr}: {msg:#?}");
+        match socket.send_to(&encode(&msg), adr) {
+            Ok(_) => {}
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                queue.unpop(adr, msg);
+                return;
+            }
+            Err(err) => log::warn!("can not send to {adr}: {err}"),
+        }
+    }
+}
+
+/// Handle the messages that arrived, then send updates to all games
+///
+/// Network errors only lose the datagram they happened with, they never stop the server.
+fn serve_round(
+    server: &mut server::Server,
+    socket: &impl Socket,
+    queue: &mut SendQueue,
+    buf: &mut [u8],
+) {
+    for _ in 0..15 {
+        match socket.recv_from(buf) {
+            Ok((received_bytes, client_address)) => {
+                if let Some(msg) = decode::<ClientMessage>(&buf[..received_bytes]) {
+                    for response in server.handle_client_message(msg, client_address) {
+                        queue.push(client_address, response);
+                    }
+                }
+            }
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(e) => log::warn!("can not receive: {e}"),
+        }
+        flush(queue, socket);
+        sleep(std::time::Duration::from_millis(1));
+    }
+    server.remove_inactive_clients(std::time::Instant::now());
+    let updates = server.periodic_update(std::time::Instant::now());
+    for (adr, update) in updates {
+        queue.push(adr, ServerMessage::Update(update));
+    }
+    for (adr, msg) in server.take_outgoing() {
+        queue.push(adr, msg);
+    }
+    flush(queue, socket);
+}
+
+/// Parse `BOMBERHANS_BIND`, either an address with port like `127.0.0.1:4267` or
+/// `[::1]:4267`, or only a port to listen on all interfaces.
+fn parse_bind_address(bind: &str) -> Result<SocketAddr, String> {
+    if let Ok(port) = bind.parse() {
+        return Ok(SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port));
+    }
+    bind.parse().map_err(|_| {
+        format!("BOMBERHANS_BIND={bind} is neither a port nor an address with port like [::1]:{DEFAULT_PORT}")
+    })
+}
+
 fn serve() -> Result<(), Box<dyn Error>> {
-    let addr = SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 4267); // TODO: make port / ip configurable
-    let socket = UdpSocket::bind(addr)?;
+    let addr = match std::env::var("BOMBERHANS_BIND") {
+        Ok(bind) => parse_bind_address(&bind)?,
+        Err(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), DEFAULT_PORT),
+    };
+    let socket = UdpSocket::bind(addr).map_err(|err| format!("can not listen on {addr}: {err}"))?;
     log::info!("Listening on {addr}");
     socket.set_nonblocking(true)?;
 
-    let mut server = server::Server::new("HansServer".to_owned());
+    let admin_token = std::env::var("BOMBERHANS_ADMIN_TOKEN").ok().map(AdminToken);
+    if admin_token.is_none() {
+        log::info!("BOMBERHANS_ADMIN_TOKEN is not set, admin requests are rejected");
+    }
+    let mut server = server::Server::new("HansServer".to_owned(), admin_token);
+    if std::env::var("BOMBERHANS_KICK_CHEATERS").is_ok() {
+        log::info!("BOMBERHANS_KICK_CHEATERS is set, clients suspected of cheating are kicked");
+        server.set_kick_suspected_cheaters(true);
+    }
+
+    let send_queue = match std::env::var("BOMBERHANS_SEND_QUEUE") {
+        Ok(capacity) => capacity
+            .parse()
+            .ok()
+            .filter(|capacity| *capacity > 0)
+            .ok_or(format!(
+                "BOMBERHANS_SEND_QUEUE={capacity} is not a positive number"
+            ))?,
+        Err(_) => SEND_QUEUE_DEFAULT,
+    };
+    let mut queue = SendQueue::new(send_queue);
 
     let mut buf = [0; 1024];
 
     loop {
-        for _ in 0..15 {
-            match socket.recv_from(&mut buf) {
-                Ok((received_bytes, client_address)) => {
-                    if let Some(msg) = decode::<ClientMessage>(&buf[..received_bytes]) {
-                        let response = server.handle_client_message(msg, client_address);
-                        if let Some(response) = response {
-                            log::debug!("sending to {client_address}: {response:#?}");
-                            let data = encode(&response);
-                            socket.send_to(&data, client_address)?;
-                        }
-                    }
-                }
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
-                Err(e) => {
-                    return Err(e.into());
-                }
-            }
-            sleep(std::time::Duration::from_millis(1))
-        }
-        let updates = server.periodic_update();
-        for (adr, msg) in updates {
-            log::debug!("sending to {adr}: {msg:#?}");
-            let data = encode(&msg);
-            socket.send_to(&data, adr)?;
-        }
+        serve_round(&mut server, &socket, &mut queue, &mut buf);
     }
 }
 
@@ -72,3 +160,96 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+
+    /// A datagram and its sender, or why receiving failed
+    type Received = std::io::Result<(Vec<u8>, SocketAddr)>;
+
+    /// Delivers `incoming` one by one, fails to send to `unreachable`, records what was sent
+    struct MockSocket {
+        incoming: RefCell<VecDeque<Received>>,
+        unreachable: SocketAddr,
+        sent: RefCell<Vec<(SocketAddr, ServerMessage)>>,
+    }
+
+    impl Socket for MockSocket {
+        fn recv_from(&self, buf: &mut [u8]) -> std::io::Result<(usize, SocketAddr)> {
+            let (data, addr) = self
+                .incoming
+                .borrow_mut()
+                .pop_front()
+                .unwrap_or_else(|| Err(std::io::ErrorKind::WouldBlock.into()))?;
+            buf[..data.len()].copy_from_slice(&data);
+            Ok((data.len(), addr))
+        }
+
+        fn send_to(&self, buf: &[u8], addr: SocketAddr) -> std::io::Result<usize> {
+            if addr == self.unreachable {
+                return Err(std::io::ErrorKind::ConnectionRefused.into());
+            }
+            let msg = decode(buf).expect("server sends valid messages");
+            self.sent.borrow_mut().push((addr, msg));
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn test_parse_bind_address() {
+        assert_eq!(
+            parse_bind_address("4268"),
+            Ok(SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 4268))
+        );
+        assert_eq!(
+            parse_bind_address("127.0.0.1:4269"),
+            Ok("127.0.0.1:4269".parse().unwrap())
+        );
+        assert_eq!(
+            parse_bind_address("[::1]:4270"),
+            Ok("[::1]:4270".parse().unwrap())
+        );
+        assert!(parse_bind_address("localhost").is_err());
+        assert!(parse_bind_address("127.0.0.1").is_err(), "port is missing");
+        assert!(parse_bind_address("70000").is_err());
+    }
+
+    #[test]
+    fn test_network_errors_do_not_stop_the_server() {
+        let hello = || {
+            encode(&ClientMessage::Hello(ClientHello {
+                magic: BOMBERHANS_MAGIC_NO_V1,
+                nonce: 1,
+                player_name: "Hans".to_owned(),
+            }))
+        };
+        let unreachable: SocketAddr = "[::1]:1001".parse().unwrap();
+        let reachable: SocketAddr = "[::1]:1002".parse().unwrap();
+        let socket = MockSocket {
+            incoming: RefCell::new(VecDeque::from([
+                Ok((hello(), unreachable)),
+                Err(std::io::ErrorKind::ConnectionReset.into()),
+                Ok((hello(), reachable)),
+            ])),
+            unreachable,
+            sent: RefCell::new(Vec::new()),
+        };
+        let mut server = server::Server::new("Test".to_owned(), None);
+
+        serve_round(
+            &mut server,
+            &socket,
+            &mut SendQueue::new(10),
+            &mut [0; 1024],
+        );
+
+        let sent = socket.sent.borrow();
+        let [(addr, ServerMessage::Hello(_))] = &sent[..] else {
+            panic!("the second client gets its hello: {sent:?}");
+        };
+        assert_eq!(*addr, reachable);
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+
+use bomberhans_lib::network::ServerMessage;
+
+/// Messages waiting to be sent, so a slow network makes the server drop messages instead of
+/// stalling it
+pub struct SendQueue {
+    /// The most messages that are kept, the oldest are dropped beyond this
+    capacity: usize,
+
+    messages: VecDeque<(SocketAddr, ServerMessage)>,
+
+    /// Messages dropped because the queue was full
+    dropped: u64,
+}
+
+impl SendQueue {
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "SendQueue needs room for at least one message"
+        );
+        Self {
+            capacity,
+            messages: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Queue `msg` for `addr`
+    ///
+    /// A lobby update replaces the one still queued for the same client, only the latest
+    /// state of the lobby matters. If the queue is full, the oldest message is dropped.
+    pub fn push(&mut self, addr: SocketAddr, msg: ServerMessage) {
+        if let ServerMessage::LobbyUpdate(_) = msg {
+            let stale = self
+                .messages
+                .iter_mut()
+                .find(|(a, m)| *a == addr && matches!(m, ServerMessage::LobbyUpdate(_)));
+            if let Some((_, stale)) = stale {
+                *stale = msg;
+                return;
+            }
+        }
+        if self.messages.len() == self.capacity {
+            let (dropped_addr, _) = self.messages.pop_front().expect("queue is full");
+            self.dropped += 1;
+            log::warn!("send queue is full, dropping oldest message for {dropped_addr}");
+        }
+        self.messages.push_back((addr, msg));
+    }
+
+    /// The next message to send
+    pub fn pop(&mut self) -> Option<(SocketAddr, ServerMessage)> {
+        self.messages.pop_front()
+    }
+
+    /// Put a message that could not be sent yet back in front, to try again later
+    pub fn unpop(&mut self, addr: SocketAddr, msg: ServerMessage) {
+        self.messages.push_front((addr, msg));
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Messages dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bomberhans_lib::game_state::GameStatic;
+    use bomberhans_lib::network::DisconnectReason;
+    use bomberhans_lib::network::ServerLobbyUpdate;
+    use bomberhans_lib::settings::Settings;
+    use bomberhans_lib::utils::PlayerId;
+    use std::collections::BTreeMap;
+    use std::collections::BTreeSet;
+
+    fn lobby_update(ready: &[usize]) -> ServerMessage {
+        let game = GameStatic {
+            players: BTreeMap::new(),
+            settings: Settings::default(),
+            local_player: PlayerId(0),
+        };
+        let ready = ready.iter().copied().map(PlayerId).collect::<BTreeSet<_>>();
+        ServerMessage::LobbyUpdate(ServerLobbyUpdate::new(PlayerId(0), game, ready))
+    }
+
+    #[test]
+    fn test_lobby_updates_for_one_client_are_coalesced() {
+        let a: SocketAddr = "[::1]:1001".parse().unwrap();
+        let b: SocketAddr = "[::1]:1002".parse().unwrap();
+        let mut queue = SendQueue::new(10);
+        queue.push(a, lobby_update(&[]));
+        queue.push(b, lobby_update(&[]));
+        queue.push(a, ServerMessage::ByeAck);
+        queue.push(a, lobby_update(&[0]));
+        queue.push(a, lobby_update(&[0, 1]));
+
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.pop(), Some((a, lobby_update(&[0, 1]))));
+        assert_eq!(queue.pop(), Some((b, lobby_update(&[]))));
+        assert_eq!(queue.pop(), Some((a, ServerMessage::ByeAck)));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn test_full_queue_drops_the_oldest_message() {
+        let a: SocketAddr = "[::1]:1001".parse().unwrap();
+        let mut queue = SendQueue::new(2);
+        queue.push(a, ServerMessage::Bye(DisconnectReason::GameFull));
+        queue.push(a, ServerMessage::Bye(DisconnectReason::GameStarted));
+        queue.push(a, ServerMessage::ByeAck);
+
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(
+            queue.pop(),
+            Some((a, ServerMessage::Bye(DisconnectReason::GameStarted)))
+        );
+        queue.unpop(a, ServerMessage::AdminRejected);
+        assert_eq!(queue.pop(), Some((a, ServerMessage::AdminRejected)));
+        assert_eq!(queue.pop(), Some((a, ServerMessage::ByeAck)));
+    }
+}
@@ -1,15 +1,27 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::hash::Hash as _;
 use std::hash::Hasher as _;
 
 use std::net::SocketAddr;
 use std::rc::Rc;
+use std::time::Duration;
 use std::time::Instant;
 
+use bomberhans_lib::field::Field;
 use bomberhans_lib::game_state::*;
 use bomberhans_lib::network::*;
+use bomberhans_lib::settings::describe_errors;
+use bomberhans_lib::settings::Settings;
+use bomberhans_lib::simulation::GameSpec;
+use bomberhans_lib::utils::Idx as _;
 use bomberhans_lib::utils::PlayerId;
+use bomberhans_lib::utils::Position;
 use bomberhans_lib::utils::TimeStamp;
+use bomberhans_lib::utils::TICKS_PER_SECOND;
+use bomberhans_lib::utils::TIME_PER_TICK;
 
 enum Game {
     Lobby(Lobby),
@@ -18,24 +30,186 @@ enum Game {
 impl Game {
     fn remove_player(&mut self, player_id: PlayerId) {
         match self {
-            Game::Lobby(lobby) => lobby.game_static.players.remove(&player_id),
-            Game::Started(game) => todo!(),
-        };
+            Game::Lobby(lobby) => {
+                lobby.game_static.players.remove(&player_id);
+                lobby.game_static.deal_teams();
+                lobby.players_ready.remove(&player_id);
+            }
+            Game::Started(game) => {
+                log::info!("{player_id:?} left, their Hans stays on the field");
+            }
+        }
+    }
+
+    fn host_mut(&mut self) -> &mut ClientId {
+        match self {
+            Game::Lobby(lobby) => &mut lobby.host,
+            Game::Started(game) => &mut game.host,
+        }
     }
 }
 
 struct Lobby {
     id: GameId,
+
+    /// The client that opened the lobby, or the one with the lowest player id after the host
+    /// left
+    host: ClientId,
+
     game_static: GameStatic,
+    players_ready: BTreeSet<PlayerId>,
+}
+
+impl Lobby {
+    /// Add a player with the lowest free id on the least occupied start position
+    ///
+    /// Ids and start positions do not depend on the order players joined in, so players that
+    /// leave do not cause duplicates for players that join later.
+    fn add_player(&mut self, name: String) -> PlayerId {
+        // n players leave one of the first n + 1 ids free
+        let player_id = (0..=self.game_static.players.len())
+            .map(PlayerId)
+            .find(|id| !self.game_static.players.contains_key(id))
+            .expect("there is a free player id");
+        let start_position = self.least_used_start_position();
+        let player = Player::new(name, player_id, start_position);
+        self.game_static.players.insert(player_id, player);
+        self.game_static.deal_teams();
+        player_id
+    }
+
+    /// Whether the field has a start point for each player the settings allow
+    ///
+    /// Otherwise players would have to share start points, the game must not start.
+    fn seats_all_players(&self) -> bool {
+        let settings = &self.game_static.settings;
+        Field::new_from_rules(settings).start_positions().len() >= settings.players.idx()
+    }
+
+    /// The start position of the field that the fewest players start on
+    fn least_used_start_position(&self) -> Position {
+        let players = &self.game_static.players;
+        Field::new_from_rules(&self.game_static.settings)
+            .start_positions()
+            .iter()
+            .map(|cell| Position::from_cell_position(*cell))
+            .min_by_key(|position| {
+                players
+                    .values()
+                    .filter(|player| player.start_position == *position)
+                    .count()
+            })
+            .expect("fields have start positions")
+    }
+
+    /// Play with other settings, the players get start positions on the new field and their
+    /// teams
+    ///
+    /// If the settings say so, everyone but the host has to get ready again.
+    fn change_settings(&mut self, settings: Settings, host_player: PlayerId) {
+        self.game_static.settings = settings;
+        for player in std::mem::take(&mut self.game_static.players).into_values() {
+            let start_position = self.least_used_start_position();
+            self.game_static.players.insert(
+                player.id,
+                Player {
+                    start_position,
+                    ..player
+                },
+            );
+        }
+        self.game_static.deal_teams();
+        if self.game_static.settings.unready_on_settings_change {
+            self.players_ready.retain(|id| *id == host_player);
+        }
+    }
 }
 
 struct StartedGame {
     id: GameId,
+
+    /// The host of the lobby, hosts it again after the game
+    host: ClientId,
+
     game_static: Rc<GameStatic>,
+
+    /// The settings of the lobby, `game_static` has those of the round from the playlist
+    lobby_settings: Settings,
+
     game_state: GameState,
     updates: Vec<Update>,
     future_updates: Vec<Update>,
     old_updates: Vec<Update>,
+
+    /// Checksum of the state by the ticks that changed an action, for updates that are sent in
+    /// chunks and stop at such a tick
+    checksums: HashMap<u32, u32>,
+
+    /// When the next tick is due, ticks follow the clock and not the clients
+    next_tick: Instant,
+
+    /// When the players were sent the outcome
+    finished_at: Option<Instant>,
+}
+
+impl StartedGame {
+    /// Simulate one tick, then apply the actions that are due by then
+    ///
+    /// Players keep their latest action until a new one arrives, so clients can send input less
+    /// often than the server ticks. Returns the players that switched to impossible actions, they
+    /// are applied anyway, the simulation keeps them harmless.
+    fn simulate_tick(&mut self) -> Vec<(PlayerId, ImpossibleAction)> {
+        let mut updates: Vec<Update> = Vec::new();
+        std::mem::swap(&mut updates, &mut self.future_updates);
+
+        self.game_state.simulate_1_update();
+
+        let mut impossible_actions = Vec::new();
+        for u in updates {
+            if u.time > self.game_state.time {
+                self.future_updates.push(u);
+                continue;
+            }
+            if let Some(impossible) = self.game_state.impossible_action(u.player, u.action) {
+                impossible_actions.push((u.player, impossible));
+            }
+            if self.game_state.set_player_action(u.player, u.action) {
+                self.updates.push(Update {
+                    time: self.game_state.time,
+                    ..u
+                });
+                self.checksums.insert(
+                    self.game_state.time.ticks_from_start(),
+                    self.game_state.checksum(),
+                );
+            }
+        }
+        impossible_actions
+    }
+
+    /// The update for a client that has everything up to `acknowledged`
+    ///
+    /// Too many updates for one datagram, e.g. for a client that resyncs, are sent in chunks.
+    /// A chunk stops at the tick of its last update, with the checksum of that tick, the next
+    /// one follows once the client acknowledged it.
+    fn update_since(&self, acknowledged: TimeStamp) -> ServerUpdate {
+        let pending = &self.updates[self.updates.partition_point(|u| u.time <= acknowledged)..];
+        if pending.len() <= MAX_UPDATES_PER_MESSAGE {
+            return ServerUpdate {
+                time: self.game_state.time,
+                checksum: self.game_state.checksum(),
+                updates: pending.to_vec(),
+            };
+        }
+        // a tick's updates are not split, the checksum is only known after all of them
+        let time = pending[MAX_UPDATES_PER_MESSAGE - 1].time;
+        let end = pending.partition_point(|u| u.time <= time);
+        ServerUpdate {
+            time,
+            checksum: self.checksums[&time.ticks_from_start()],
+            updates: pending[..end].to_vec(),
+        }
+    }
 }
 
 struct ClientGame {
@@ -46,6 +220,44 @@ struct ClientGame {
 
     /// The time of the most recent information the client acknowledged having
     pub last_acknowledge_time: TimeStamp,
+
+    /// When `GameOver` was last sent, it is sent again until the client acknowledges it
+    pub game_over_sent: Option<Instant>,
+
+    /// The client sent `GameOverAck` for the current game
+    pub game_over_acknowledged: bool,
+}
+
+impl ClientGame {
+    fn new(game_id: GameId, player_id: PlayerId) -> Self {
+        Self {
+            game_id,
+            player_id,
+            last_acknowledge_time: TimeStamp::default(),
+            game_over_sent: None,
+            game_over_acknowledged: false,
+        }
+    }
+
+    /// Forget what the client acknowledged of the previous game
+    fn restart(&mut self) {
+        self.last_acknowledge_time = TimeStamp::default();
+        self.game_over_sent = None;
+        self.game_over_acknowledged = false;
+    }
+
+    /// Whether `GameOver` has to be sent at `now`, for the first time or again
+    fn game_over_due(&mut self, now: Instant) -> bool {
+        if self.game_over_acknowledged
+            || self
+                .game_over_sent
+                .is_some_and(|sent| now < sent + GAME_OVER_RETRANSMIT_INTERVAL)
+        {
+            return false;
+        }
+        self.game_over_sent = Some(now);
+        true
+    }
 }
 
 struct Client {
@@ -58,18 +270,91 @@ struct Client {
     /// The client's Address, only accept packets from there, send updates there
     pub address: SocketAddr,
 
+    /// When the client's most recent message arrived
+    pub last_message: Instant,
+
+    /// Reliable messages above `reliable_window_start` that were already handled, to ignore
+    /// retransmissions
+    pub received_reliable: HashSet<PacketNumber>,
+
+    /// Every reliable message below this was handled
+    pub reliable_window_start: PacketNumber,
+
+    /// Minimum time between updates, `None` to send every update
+    pub update_interval: Option<Duration>,
+
+    /// When the client was last sent an update
+    pub last_update_sent: Option<Instant>,
+
+    /// How many impossible actions the client sent
+    pub suspicions: u32,
+
     /// The Client's Game if any
     game: Option<ClientGame>,
 }
 
+impl Client {
+    /// Remember that a reliable message arrived, false if it was handled before
+    ///
+    /// Only messages that arrived out of order are kept, the ones below the window are
+    /// forgotten once every message before them arrived.
+    fn receive_reliable(&mut self, packet_number: PacketNumber) -> bool {
+        if packet_number < self.reliable_window_start
+            || !self.received_reliable.insert(packet_number)
+        {
+            return false;
+        }
+        while self.received_reliable.remove(&self.reliable_window_start) {
+            self.reliable_window_start = self.reliable_window_start.next();
+        }
+        true
+    }
+}
+
+/// Most updates in one `ServerUpdate`, so that it fits into the 1024 bytes that clients receive
+/// even with a player's updates on top that share the tick of the last one
+const MAX_UPDATES_PER_MESSAGE: usize = 32;
+
+/// `GameOver` is sent again this long after it was not acknowledged
+const GAME_OVER_RETRANSMIT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Clients that did not send anything for this long are removed
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The most clients a server takes, later ones are told to try again
+const MAX_CLIENTS: usize = 256;
+
+/// Clients that sent this many impossible actions are kicked, if the server kicks at all. Clients
+/// that predicted other players wrong send some by accident.
+const SUSPICIONS_TO_KICK: u32 = 10;
+
+/// The most ticks a game catches up in one `periodic_update`, if it fell further behind, the
+/// missed time is skipped
+const MAX_CATCH_UP_TICKS: u32 = TICKS_PER_SECOND;
+
 pub struct Server {
     name: String,
     games: HashMap<GameId, Game>,
     clients: HashMap<ClientId, Client>,
+
+    /// Tournaments that are not decided yet, by the game their rounds are played in
+    tournaments: HashMap<GameId, TournamentStatus>,
+
+    /// New clients are rejected once there are this many
+    max_clients: usize,
+
+    /// Admin requests must carry this token, without one they are all rejected
+    admin_token: Option<AdminToken>,
+
+    /// Kick clients after `SUSPICIONS_TO_KICK` impossible actions, otherwise they are only logged
+    kick_suspected_cheaters: bool,
+
+    /// Messages for other clients than the one whose message is handled
+    outgoing: Vec<(SocketAddr, ServerMessage)>,
 }
 
 impl Server {
-    pub fn new(name: String) -> Self {
+    pub fn new(name: String, admin_token: Option<AdminToken>) -> Self {
         let games = HashMap::new();
         let clients = HashMap::new();
 
@@ -77,84 +362,311 @@ impl Server {
             name,
             games,
             clients,
+            tournaments: HashMap::new(),
+            max_clients: MAX_CLIENTS,
+            admin_token,
+            kick_suspected_cheaters: false,
+            outgoing: Vec::new(),
         }
     }
 
+    pub fn set_kick_suspected_cheaters(&mut self, kick: bool) {
+        self.kick_suspected_cheaters = kick;
+    }
+
+    /// Messages to send that are not responses to the handled message
+    pub fn take_outgoing(&mut self) -> Vec<(SocketAddr, ServerMessage)> {
+        std::mem::take(&mut self.outgoing)
+    }
+
     pub fn handle_client_message(
         &mut self,
         msg: ClientMessage,
         client_address: SocketAddr,
-    ) -> Option<ServerMessage> {
+    ) -> Vec<ServerMessage> {
         log::debug!("Received from {client_address}: {msg:#?}");
 
-        match msg {
-            ClientMessage::OpenNewLobby(client_id)
-            | ClientMessage::Update(ClientUpdate { client_id, .. })
-            | ClientMessage::Bye(client_id) => {
-                if let Some(client) = self.clients.get_mut(&client_id) {
-                    if client.address != client_address {
-                        log::warn!(
-                            "discarding message from {} for {:#?} whose hello-address was {}",
-                            client_address,
-                            client_id,
-                            client.address
-                        );
-                        return None;
-                    }
-                } else {
-                    log::warn!("discarding message from {client_address} for unknown client {client_id:?}: {msg:#?}");
-                    return None;
+        if let Some(client_id) = msg.client_id() {
+            if let Some(client) = self.clients.get_mut(&client_id) {
+                if client.address != client_address {
+                    log::warn!(
+                        "discarding message from {} for {:#?} whose hello-address was {}",
+                        client_address,
+                        client_id,
+                        client.address
+                    );
+                    return Vec::new();
                 }
+                client.last_message = Instant::now();
+            } else if let ClientMessage::Bye(_) = msg {
+                // The client is gone, probably our previous ByeAck was lost
+                return vec![ServerMessage::ByeAck];
+            } else {
+                log::warn!("discarding message from {client_address} for unknown client {client_id:?}: {msg:#?}");
+                return Vec::new();
             }
-            _ => (),
         }
 
         match msg {
             ClientMessage::Hello(msg) => self
-                .handle_client_helo(msg, client_address)
-                .map(|msg| ServerMessage::Hello(msg)),
+                .handle_client_helo(&msg, client_address)
+                .into_iter()
+                .collect(),
             ClientMessage::OpenNewLobby(msg) => self
                 .handle_client_open_new_lobby(msg, client_address)
-                .map(|msg| ServerMessage::LobbyUpdate(msg)),
+                .into_iter()
+                .map(ServerMessage::LobbyUpdate)
+                .collect(),
+            ClientMessage::JoinLobby(msg) => {
+                self.handle_client_join_lobby(&msg).into_iter().collect()
+            }
+            ClientMessage::SetReady(client_id, ready) => self
+                .handle_client_set_ready(client_id, ready)
+                .into_iter()
+                .collect(),
+            ClientMessage::ForceStart(client_id) => self
+                .handle_client_force_start(client_id)
+                .into_iter()
+                .collect(),
+            ClientMessage::SetSettings(client_id, settings) => self
+                .handle_client_set_settings(client_id, *settings)
+                .into_iter()
+                .collect(),
+            ClientMessage::GetLobby(client_id) => self
+                .lobby_update(client_id)
+                .into_iter()
+                .map(ServerMessage::LobbyUpdate)
+                .collect(),
+            ClientMessage::GetGame(client_id) => {
+                self.handle_client_get_game(client_id);
+                Vec::new()
+            }
             ClientMessage::Update(msg) => {
                 self.handle_client_update(msg, client_address);
-                None
+                Vec::new()
+            }
+            ClientMessage::KeepAlive(_) => Vec::new(),
+            ClientMessage::SetUpdateRate(client_id, rate) => {
+                self.handle_client_set_update_rate(client_id, rate);
+                Vec::new()
+            }
+            ClientMessage::GameOverAck(client_id) => {
+                self.handle_client_game_over_ack(client_id);
+                Vec::new()
             }
             ClientMessage::Bye(client_id) => {
-                let client = self.clients.remove(&client_id).unwrap();
+                self.remove_client(client_id);
+                vec![ServerMessage::ByeAck]
+            }
+            ClientMessage::Reliable(packet_number, msg) => {
+                self.handle_client_reliable(packet_number, *msg, client_address)
+            }
+            ClientMessage::AdminListGames(token) => {
+                vec![self.handle_admin(&token, client_address, |server| {
+                    ServerMessage::AdminGames(server.admin_list_games())
+                })]
+            }
+            ClientMessage::AdminGameInfo(token, game_id) => {
+                vec![self.handle_admin(&token, client_address, |server| {
+                    server
+                        .admin_game_info(game_id)
+                        .map_or(ServerMessage::Bye(DisconnectReason::UnknownGame), |info| {
+                            ServerMessage::AdminGameInfo(info)
+                        })
+                })]
+            }
+            ClientMessage::AdminCloseGame(token, game_id) => {
+                vec![self.handle_admin(&token, client_address, |server| {
+                    if server.close_game(game_id) {
+                        ServerMessage::AdminGames(server.admin_list_games())
+                    } else {
+                        ServerMessage::Bye(DisconnectReason::UnknownGame)
+                    }
+                })]
+            }
+        }
+    }
+
+    /// Respond to an admin request if `token` is the server's admin token
+    fn handle_admin(
+        &mut self,
+        token: &AdminToken,
+        client_address: SocketAddr,
+        respond: impl FnOnce(&mut Self) -> ServerMessage,
+    ) -> ServerMessage {
+        // `AdminToken`s compare in constant time
+        if self.admin_token.as_ref() == Some(token) {
+            log::info!("admin request from {client_address}");
+            respond(self)
+        } else {
+            log::warn!("rejecting admin request with wrong token from {client_address}");
+            ServerMessage::AdminRejected
+        }
+    }
+
+    fn admin_list_games(&self) -> Vec<(GameId, String)> {
+        self.games
+            .iter()
+            .map(|(id, game)| {
+                let name = match game {
+                    Game::Lobby(lobby) => &lobby.game_static.settings.game_name,
+                    Game::Started(game) => &game.game_static.settings.game_name,
+                };
+                (*id, name.clone())
+            })
+            .collect()
+    }
+
+    /// Remove a lobby or game and tell its clients, returns whether it existed
+    fn close_game(&mut self, game_id: GameId) -> bool {
+        if self.games.remove(&game_id).is_none() {
+            return false;
+        }
+        self.tournaments.remove(&game_id);
+        for client in self.clients.values_mut() {
+            if client.game.as_ref().map(|cg| cg.game_id) == Some(game_id) {
+                client.game = None;
+                self.outgoing.push((
+                    client.address,
+                    ServerMessage::Bye(DisconnectReason::GameClosed),
+                ));
+            }
+        }
+        log::info!("closed {game_id:?}");
+        true
+    }
+
+    fn admin_game_info(&self, game_id: GameId) -> Option<AdminGameInfo> {
+        let (started, game_static, time) = match self.games.get(&game_id)? {
+            Game::Lobby(lobby) => (false, &lobby.game_static, TimeStamp::default()),
+            Game::Started(game) => (true, &*game.game_static, game.game_state.time),
+        };
+        Some(AdminGameInfo {
+            game_id,
+            started,
+            players: game_static.players.clone(),
+            time,
+            settings: game_static.settings.clone(),
+        })
+    }
+
+    /// Acknowledge a reliable message and handle it, unless it is a retransmission
+    fn handle_client_reliable(
+        &mut self,
+        packet_number: PacketNumber,
+        msg: ClientMessage,
+        client_address: SocketAddr,
+    ) -> Vec<ServerMessage> {
+        let Some(client) = msg.client_id().and_then(|id| self.clients.get_mut(&id)) else {
+            log::warn!("discarding reliable message without client {msg:#?}");
+            return Vec::new();
+        };
+
+        // Acknowledge duplicates too, the previous Ack might have been lost
+        let mut responses = vec![ServerMessage::Ack(packet_number)];
+        if client.receive_reliable(packet_number) {
+            responses.extend(self.handle_client_message(msg, client_address));
+        } else {
+            log::debug!("ignoring retransmitted {packet_number:?}");
+        }
+        responses
+    }
+
+    /// Forget a client and take its player out of its game
+    ///
+    /// A host that leaves hands the game to the remaining client with the lowest player id. A
+    /// started game that the last client left is closed, nobody would see it anymore.
+    fn remove_client(&mut self, client_id: ClientId) {
+        let Some(client) = self.clients.remove(&client_id) else {
+            log::warn!("removing unknown {client_id:?}");
+            return;
+        };
 
-                if let Some(game) = client.game {
-                    self.games
-                        .get_mut(&game.game_id)
-                        .unwrap()
-                        .remove_player(game.player_id);
+        if let Some(client_game) = client.game {
+            let game_id = client_game.game_id;
+            let Some(game) = self.games.get_mut(&game_id) else {
+                log::warn!("{client_id:?} was in unknown {game_id:?}");
+                return;
+            };
+            game.remove_player(client_game.player_id);
+            if *game.host_mut() == client_id {
+                let next_host = self
+                    .clients
+                    .values()
+                    .filter_map(|c| {
+                        let cg = c.game.as_ref()?;
+                        (cg.game_id == game_id).then_some((cg.player_id, c.id))
+                    })
+                    .min_by_key(|(player_id, _)| *player_id);
+                if let Some((player_id, next_host)) = next_host {
+                    log::info!(
+                        "the host left {game_id:?}, {next_host:?} of {player_id:?} hosts it"
+                    );
+                    *game.host_mut() = next_host;
                 }
-                None
             }
+            let started = matches!(game, Game::Started(_));
+            let abandoned = !self
+                .clients
+                .values()
+                .any(|c| c.game.as_ref().map(|cg| cg.game_id) == Some(game_id));
+            if started && abandoned {
+                log::info!("everyone left {game_id:?}");
+                self.close_game(game_id);
+            } else if !started {
+                self.send_lobby_update_to_others(game_id, client_id);
+            }
+        }
+    }
+
+    /// Remove clients that have not sent anything for `CLIENT_TIMEOUT`
+    pub fn remove_inactive_clients(&mut self, now: Instant) {
+        let inactive: Vec<ClientId> = self
+            .clients
+            .values()
+            .filter(|c| now.saturating_duration_since(c.last_message) > CLIENT_TIMEOUT)
+            .map(|c| c.id)
+            .collect();
+        for client_id in inactive {
+            log::info!("removing inactive client {client_id:?}");
+            self.remove_client(client_id);
         }
     }
 
+    /// Welcome a client, unless the server is full
+    ///
+    /// A client whose earlier hello was answered is welcomed again, it only lost the response.
     fn handle_client_helo(
         &mut self,
-        message: ClientHello,
+        message: &ClientHello,
         client_address: SocketAddr,
-    ) -> Option<ServerHello> {
+    ) -> Option<ServerMessage> {
         if message.magic != BOMBERHANS_MAGIC_NO_V1 {
             return None;
         }
 
+        let name = message.capped_player_name().to_owned();
         let mut h = std::hash::DefaultHasher::new();
         client_address.hash(&mut h);
-        message.player_name.hash(&mut h);
+        name.hash(&mut h);
         let cookie = h.finish();
         let cookie = ClientId::new(cookie);
 
-        let last_communication = Instant::now();
+        if self.clients.len() >= self.max_clients && !self.clients.contains_key(&cookie) {
+            log::warn!("rejecting {client_address}, server is full");
+            return Some(ServerMessage::Bye(DisconnectReason::ServerFull));
+        }
 
         let client = Client {
-            name: message.player_name,
+            name,
             id: cookie,
             address: client_address,
+            last_message: Instant::now(),
+            received_reliable: HashSet::new(),
+            reliable_window_start: PacketNumber::new(0),
+            update_interval: None,
+            last_update_sent: None,
+            suspicions: 0,
             game: None,
         };
 
@@ -170,12 +682,12 @@ impl Server {
             })
             .collect();
 
-        return Some(ServerHello {
+        return Some(ServerMessage::Hello(ServerHello {
             server_name,
             client_id: cookie,
             lobbies,
             clients_nonce: message.nonce,
-        });
+        }));
     }
 
     fn handle_client_update(&mut self, msg: ClientUpdate, client_address: SocketAddr) {
@@ -224,61 +736,2127 @@ impl Server {
         });
     }
 
-    pub fn periodic_update(&mut self) -> Vec<(SocketAddr, ServerUpdate)> {
+    /// Forget what the client acknowledged, so its next update has all updates of the game
+    fn handle_client_get_game(&mut self, client_id: ClientId) {
+        let Some(client) = self.clients.get_mut(&client_id) else {
+            return;
+        };
+        let Some(client_game) = &mut client.game else {
+            log::warn!("{client_id:?} wants its game but is not in one");
+            return;
+        };
+        log::info!("{client_id:?} resyncs {:?}", client_game.game_id);
+        client_game.last_acknowledge_time = TimeStamp::default();
+        client.last_update_sent = None;
+    }
+
+    fn handle_client_set_update_rate(&mut self, client_id: ClientId, rate: u32) {
+        let Some(client) = self.clients.get_mut(&client_id) else {
+            return;
+        };
+        // Faster than every tick is every tick
+        client.update_interval =
+            (rate != 0 && rate < TICKS_PER_SECOND).then(|| Duration::from_secs(1) / rate);
+        log::info!("{client_id:?} wants {rate} updates per second");
+    }
+
+    fn handle_client_game_over_ack(&mut self, client_id: ClientId) {
+        let Some(client_game) = self
+            .clients
+            .get_mut(&client_id)
+            .and_then(|c| c.game.as_mut())
+        else {
+            return;
+        };
+        client_game.game_over_acknowledged = true;
+    }
+
+    /// Simulate the ticks of all running games that are due at `now` and the updates for the
+    /// clients that are due one
+    ///
+    /// Once a game is decided, it stops and its outcome is queued for its players until they
+    /// acknowledge it. After `return_to_lobby_after_s`, it turns back into its lobby.
+    pub fn periodic_update(&mut self, now: Instant) -> Vec<(SocketAddr, ServerUpdate)> {
+        let mut impossible_actions = Vec::new();
+        let mut finished = Vec::new();
         for g in self.games.values_mut() {
             let Game::Started(game) = g else {
                 continue;
             };
 
-            let mut updates: Vec<Update> = Vec::new();
-            std::mem::swap(&mut updates, &mut game.future_updates);
-
-            game.game_state.simulate_1_update();
-
-            for u in updates {
-                if u.time > game.game_state.time {
-                    game.future_updates.push(u);
-                } else {
-                    if game.game_state.set_player_action(u.player, u.action) {
-                        game.updates.push(Update {
-                            time: game.game_state.time,
-                            ..u
-                        });
-                    }
+            let mut ticks = 0;
+            // a decided game stands still until it returns to the lobby
+            while game.next_tick <= now && !game.game_state.is_decided() {
+                if ticks == MAX_CATCH_UP_TICKS {
+                    log::warn!("{:?} fell behind, skipping ticks", game.id);
+                    game.next_tick = now + TIME_PER_TICK;
+                    break;
+                }
+                for (player_id, impossible) in game.simulate_tick() {
+                    impossible_actions.push((game.id, player_id, impossible));
                 }
+                game.next_tick += TIME_PER_TICK;
+                ticks += 1;
+            }
+
+            let outcome = game.game_state.outcome();
+            if game.finished_at.is_none() && outcome != Outcome::Ongoing {
+                game.finished_at = Some(now);
+                log::info!("{:?} is over: {outcome:?}", game.id);
+                finished.push((game.id, game.game_static.settings.best_of, outcome.winner()));
             }
         }
 
-        self.clients
+        for (game_id, best_of, winner) in finished {
+            self.count_tournament_game(game_id, best_of, winner);
+        }
+
+        for (game_id, player_id, impossible) in impossible_actions {
+            self.suspect_cheating(game_id, player_id, impossible);
+        }
+
+        let back_to_lobby: Vec<GameId> = self
+            .games
             .values()
+            .filter_map(|g| {
+                let Game::Started(game) = g else {
+                    return None;
+                };
+                let after = game.game_static.settings.return_to_lobby_after_s;
+                let finished_at = game.finished_at?;
+                (after != 0 && now >= finished_at + Duration::from_secs(after.into()))
+                    .then_some(game.id)
+            })
+            .collect();
+        for game_id in back_to_lobby {
+            self.return_to_lobby(game_id);
+        }
+
+        let games = &self.games;
+        let outgoing = &mut self.outgoing;
+        self.clients
+            .values_mut()
             .filter_map(|c| {
-                let cgs = c.game.as_ref()?;
-                let Game::Started(game) = &self.games[&cgs.game_id] else {
+                let cgs = c.game.as_mut()?;
+                let Game::Started(game) = &games[&cgs.game_id] else {
                     return None;
                 };
-                Some((
-                    c.address,
-                    ServerUpdate {
-                        time: game.game_state.time,
-                        checksum: 0,
-                        updates: game
-                            .updates
-                            .iter()
-                            .filter(|u| u.time > cgs.last_acknowledge_time)
-                            .map(Update::clone)
-                            .collect(),
-                    },
-                ))
+                if game.finished_at.is_some() && cgs.game_over_due(now) {
+                    let results = game.game_state.results();
+                    outgoing.push((c.address, ServerMessage::GameOver(results)));
+                }
+                if let (Some(interval), Some(last)) = (c.update_interval, c.last_update_sent) {
+                    if now.saturating_duration_since(last) < interval {
+                        return None;
+                    }
+                }
+                c.last_update_sent = Some(now);
+                Some((c.address, game.update_since(cgs.last_acknowledge_time)))
             })
             .collect()
     }
 
+    /// Log that the client of `player_id` sent an impossible action, kick it if it does that a lot
+    fn suspect_cheating(
+        &mut self,
+        game_id: GameId,
+        player_id: PlayerId,
+        impossible: ImpossibleAction,
+    ) {
+        let Some(client) = self.clients.values_mut().find(|c| {
+            c.game
+                .as_ref()
+                .is_some_and(|cg| cg.game_id == game_id && cg.player_id == player_id)
+        }) else {
+            return;
+        };
+        client.suspicions += 1;
+        log::warn!(
+            "{} {:?} as {player_id:?} in {game_id:?} is suspected of cheating ({}): {impossible:?}",
+            client.address,
+            client.id,
+            client.suspicions,
+        );
+        if self.kick_suspected_cheaters && client.suspicions >= SUSPICIONS_TO_KICK {
+            let (client_id, address) = (client.id, client.address);
+            log::warn!("kicking {address} {client_id:?}");
+            self.outgoing
+                .push((address, ServerMessage::Bye(DisconnectReason::Kicked)));
+            self.remove_client(client_id);
+        }
+    }
+
+    /// Open a new Lobby with the client as host
+    ///
+    /// If the client already opened one, the response was probably lost, answer with that lobby
+    /// again.
     fn handle_client_open_new_lobby(
         &mut self,
-        msg: ClientId,
+        client_id: ClientId,
         client_address: SocketAddr,
     ) -> Option<ServerLobbyUpdate> {
+        if self.clients.get(&client_id)?.game.is_some() {
+            let update = self.lobby_update(client_id);
+            if update.is_none() {
+                log::warn!("{client_address} wants to open a lobby while in a started game");
+            }
+            return update;
+        }
+
+        let client = self.clients.get_mut(&client_id)?;
+
         let game_id = GameId::new(rand::random());
-        todo!();
+        let mut lobby = Lobby {
+            id: game_id,
+            host: client_id,
+            game_static: GameStatic {
+                players: BTreeMap::new(),
+                settings: Settings::default(),
+                local_player: PlayerId(0),
+            },
+            players_ready: BTreeSet::new(),
+        };
+        let player_id = lobby.add_player(client.name.clone());
+        lobby.game_static.local_player = player_id;
+        let game_static = lobby.game_static.clone();
+
+        client.game = Some(ClientGame::new(game_id, player_id));
+        self.games.insert(game_id, Game::Lobby(lobby));
+        log::info!("{client_id:?} opened lobby {game_id:?}");
+
+        Some(ServerLobbyUpdate::new(
+            player_id,
+            game_static,
+            BTreeSet::new(),
+        ))
+    }
+
+    /// Add the client to a lobby, or tell it why it can not join
+    fn handle_client_join_lobby(&mut self, msg: &ClientJoinLobby) -> Option<ServerMessage> {
+        let client = self.clients.get_mut(&msg.client_id)?;
+
+        if let Some(client_game) = &client.game {
+            if client_game.game_id != msg.lobby {
+                log::warn!(
+                    "{:?} wants to join {:?} while in {:?}",
+                    msg.client_id,
+                    msg.lobby,
+                    client_game.game_id
+                );
+                return None;
+            }
+            // The response was lost, the client asks again
+            return self
+                .lobby_update(msg.client_id)
+                .map(ServerMessage::LobbyUpdate);
+        }
+
+        let lobby = match self.games.get_mut(&msg.lobby) {
+            None => return Some(ServerMessage::Bye(DisconnectReason::UnknownGame)),
+            Some(Game::Started(_)) => {
+                return Some(ServerMessage::Bye(DisconnectReason::GameStarted))
+            }
+            Some(Game::Lobby(lobby)) => lobby,
+        };
+        let game_static = &lobby.game_static;
+        if game_static.players.len() >= game_static.settings.players.idx() {
+            return Some(ServerMessage::Bye(DisconnectReason::GameFull));
+        }
+
+        let player_id = lobby.add_player(client.name.clone());
+        client.game = Some(ClientGame::new(msg.lobby, player_id));
+        log::info!("{:?} joined lobby {:?}", msg.client_id, msg.lobby);
+
+        self.send_lobby_update_to_others(msg.lobby, msg.client_id);
+        self.lobby_update(msg.client_id)
+            .map(ServerMessage::LobbyUpdate)
+    }
+
+    /// Start the host's game with the players that are ready
+    ///
+    /// The host always plays, players that are not ready are dropped from the game and told so,
+    /// the others get their `GameStart` queued. If the field can not seat all players, the host
+    /// is told so and stays in the lobby.
+    fn handle_client_force_start(&mut self, client_id: ClientId) -> Option<ServerMessage> {
+        let client_game = self.clients.get(&client_id)?.game.as_ref()?;
+        let game_id = client_game.game_id;
+        let host_player = client_game.player_id;
+        let Some(Game::Lobby(lobby)) = self.games.get(&game_id) else {
+            log::warn!("{client_id:?} wants to start {game_id:?} which is not a lobby");
+            return None;
+        };
+        if lobby.host != client_id {
+            log::warn!("{client_id:?} wants to start {game_id:?} but is not the host");
+            return None;
+        }
+        if !lobby.seats_all_players() {
+            log::warn!("{client_id:?} wants to start {game_id:?} without enough start points");
+            return Some(ServerMessage::Bye(DisconnectReason::NotEnoughStartPoints));
+        }
+
+        let mut game_static = lobby.game_static.clone();
+        game_static
+            .players
+            .retain(|id, _| *id == host_player || lobby.players_ready.contains(id));
+        game_static.deal_teams();
+
+        for client in self.clients.values_mut() {
+            if let Some(cg) = &client.game {
+                if cg.game_id == game_id && !game_static.players.contains_key(&cg.player_id) {
+                    log::info!(
+                        "{:?} was not ready, dropping them from {game_id:?}",
+                        client.id
+                    );
+                    client.game = None;
+                    self.outgoing.push((
+                        client.address,
+                        ServerMessage::Bye(DisconnectReason::GameStarted),
+                    ));
+                }
+            }
+        }
+
+        log::info!("{client_id:?} forced {game_id:?} to start");
+        self.start_lobby_game(game_id, client_id, game_static, client_id)
+    }
+
+    /// Let the host change the settings of their lobby and tell the other players about it
+    ///
+    /// Invalid settings are refused with a `Bye` that keeps the host in the lobby, too few
+    /// places for the players already in the lobby are ignored. Once the game started, its
+    /// settings are locked and late changes are refused the same way.
+    fn handle_client_set_settings(
+        &mut self,
+        client_id: ClientId,
+        settings: Settings,
+    ) -> Option<ServerMessage> {
+        let client_game = self.clients.get(&client_id)?.game.as_ref()?;
+        let game_id = client_game.game_id;
+        let host_player = client_game.player_id;
+        let lobby = match self.games.get_mut(&game_id) {
+            Some(Game::Lobby(lobby)) => lobby,
+            Some(Game::Started(_)) => {
+                log::warn!("{client_id:?} wants to change settings of {game_id:?} which started");
+                return Some(ServerMessage::Bye(DisconnectReason::SettingsLocked));
+            }
+            None => {
+                log::warn!("{client_id:?} wants to change settings of unknown {game_id:?}");
+                return None;
+            }
+        };
+        if lobby.host != client_id {
+            log::warn!("{client_id:?} wants to change settings of {game_id:?} but is not the host");
+            return None;
+        }
+        if let Err(errors) = settings.validate() {
+            log::warn!(
+                "{client_id:?} sent invalid settings for {game_id:?}: {}",
+                describe_errors(&errors)
+            );
+            return Some(ServerMessage::Bye(DisconnectReason::InvalidSettings));
+        }
+        if settings.players.idx() < lobby.game_static.players.len() {
+            log::warn!(
+                "{client_id:?} wants room for {} players in {game_id:?} which has {}",
+                settings.players,
+                lobby.game_static.players.len()
+            );
+        } else if settings != lobby.game_static.settings {
+            lobby.change_settings(settings, host_player);
+            log::info!("{client_id:?} changed the settings of {game_id:?}");
+            self.send_lobby_update_to_others(game_id, client_id);
+        }
+        self.lobby_update(client_id).map(ServerMessage::LobbyUpdate)
+    }
+
+    /// Queue the current lobby for every client in `game_id` but `except`
+    fn send_lobby_update_to_others(&mut self, game_id: GameId, except: ClientId) {
+        for client in self.clients.values() {
+            if client.id == except || client.game.as_ref().map(|cg| cg.game_id) != Some(game_id) {
+                continue;
+            }
+            if let Some(update) = self.lobby_update(client.id) {
+                self.outgoing
+                    .push((client.address, ServerMessage::LobbyUpdate(update)));
+            }
+        }
+    }
+
+    /// Mark the client's player as (not) ready, start the game if enough players are
+    ///
+    /// The game's other players get their `GameStart` queued.
+    fn handle_client_set_ready(
+        &mut self,
+        client_id: ClientId,
+        ready: bool,
+    ) -> Option<ServerMessage> {
+        let client_game = self.clients.get(&client_id)?.game.as_ref()?;
+        let game_id = client_game.game_id;
+        let player_id = client_game.player_id;
+        let Some(Game::Lobby(lobby)) = self.games.get_mut(&game_id) else {
+            log::warn!("{client_id:?} is ready in {game_id:?} which is not a lobby");
+            return None;
+        };
+        if ready {
+            lobby.players_ready.insert(player_id);
+        } else {
+            lobby.players_ready.remove(&player_id);
+        }
+
+        let game_static = &lobby.game_static;
+        let everyone_ready = game_static
+            .players
+            .keys()
+            .all(|id| lobby.players_ready.contains(id));
+        let enough_players =
+            game_static.players.len() >= game_static.settings.min_players_to_start.idx();
+        if everyone_ready && enough_players && !lobby.seats_all_players() {
+            log::warn!("everyone is ready in {game_id:?} but there are not enough start points");
+            return Some(ServerMessage::Bye(DisconnectReason::NotEnoughStartPoints));
+        } else if everyone_ready && enough_players {
+            let game_static = game_static.clone();
+            let host = lobby.host;
+            return self.start_lobby_game(game_id, host, game_static, client_id);
+        }
+        self.send_lobby_update_to_others(game_id, client_id);
+        self.lobby_update(client_id).map(ServerMessage::LobbyUpdate)
+    }
+
+    /// Start the lobby's game and queue its `GameStart` for every player but `client_id`
+    ///
+    /// Returns the `GameStart` message for `client_id`
+    fn start_lobby_game(
+        &mut self,
+        game_id: GameId,
+        host: ClientId,
+        game_static: GameStatic,
+        client_id: ClientId,
+    ) -> Option<ServerMessage> {
+        let local_player = self.clients.get(&client_id)?.game.as_ref()?.player_id;
+        let start = self.start_game(game_id, host, game_static, local_player, 0);
+        let mut response = None;
+        for client in self.clients.values_mut() {
+            let Some(cg) = &mut client.game else {
+                continue;
+            };
+            if cg.game_id != game_id {
+                continue;
+            }
+            cg.restart();
+            let mut start = start.clone();
+            start.game.local_player = cg.player_id;
+            if client.id == client_id {
+                response = Some(ServerMessage::GameStart(start));
+            } else {
+                self.outgoing
+                    .push((client.address, ServerMessage::GameStart(start)));
+            }
+        }
+        response
+    }
+
+    /// Turn the lobby into a running game of `game_static`'s players, the `round`th of its
+    /// tournament
+    ///
+    /// Returns the `GameStart` message for `local_player`
+    fn start_game(
+        &mut self,
+        game_id: GameId,
+        host: ClientId,
+        game_static: GameStatic,
+        local_player: PlayerId,
+        round: usize,
+    ) -> ServerGameStart {
+        let lobby_settings = game_static.settings.clone();
+        let mut lobby = Lobby {
+            id: game_id,
+            host,
+            game_static,
+            players_ready: BTreeSet::new(),
+        };
+        if !lobby_settings.playlist.is_empty() {
+            // the round's map can have other start positions
+            lobby.change_settings(lobby_settings.for_round(round), local_player);
+        }
+        let mut game_static = lobby.game_static;
+        game_static.local_player = local_player;
+        let game_static = Rc::new(game_static);
+        let game_state = GameState::new(Rc::clone(&game_static));
+        let start = ServerGameStart {
+            game: (*game_static).clone(),
+            time: game_state.time,
+        };
+        log::info!(
+            "{game_id:?} started with {} players, spec: {}",
+            game_static.players.len(),
+            GameSpec::new(&game_static).to_shareable_string()
+        );
+        self.games.insert(
+            game_id,
+            Game::Started(StartedGame {
+                id: game_id,
+                host,
+                game_static,
+                lobby_settings,
+                game_state,
+                updates: Vec::new(),
+                future_updates: Vec::new(),
+                old_updates: Vec::new(),
+                checksums: HashMap::new(),
+                next_tick: Instant::now() + TIME_PER_TICK,
+                finished_at: None,
+            }),
+        );
+        start
+    }
+
+    /// Count the finished game for the tournament it belongs to and tell its clients the wins
+    ///
+    /// Games with `best_of` below 2 are no tournament.
+    fn count_tournament_game(&mut self, game_id: GameId, best_of: u32, winner: Option<PlayerId>) {
+        if best_of < 2 {
+            return;
+        }
+        let status = self
+            .tournaments
+            .entry(game_id)
+            .or_insert_with(|| TournamentStatus::new(best_of));
+        status.record(winner);
+        log::info!(
+            "{game_id:?} tournament after {} games: {:?}, winner: {:?}",
+            status.games_played,
+            status.wins,
+            status.winner
+        );
+        let status = status.clone();
+        if status.winner.is_some() {
+            self.tournaments.remove(&game_id);
+        }
+        for client in self.clients.values() {
+            if client.game.as_ref().map(|cg| cg.game_id) == Some(game_id) {
+                self.outgoing.push((
+                    client.address,
+                    ServerMessage::TournamentStatus(status.clone()),
+                ));
+            }
+        }
+    }
+
+    /// Turn a finished game back into its lobby, so its players can get ready for another round
+    ///
+    /// Players whose clients left are dropped, a game without clients is removed. During a
+    /// tournament, the next round starts right away with the remaining players.
+    fn return_to_lobby(&mut self, game_id: GameId) {
+        let Some(Game::Started(game)) = self.games.remove(&game_id) else {
+            return;
+        };
+        let in_game: BTreeMap<PlayerId, ClientId> = self
+            .clients
+            .values()
+            .filter_map(|c| {
+                let cg = c.game.as_ref()?;
+                (cg.game_id == game_id).then_some((cg.player_id, c.id))
+            })
+            .collect();
+        if in_game.is_empty() {
+            log::info!("{game_id:?} is over and everyone left, removing it");
+            self.tournaments.remove(&game_id);
+            return;
+        }
+
+        let mut lobby = Lobby {
+            id: game_id,
+            host: game.host,
+            game_static: (*game.game_static).clone(),
+            players_ready: BTreeSet::new(),
+        };
+        lobby
+            .game_static
+            .players
+            .retain(|id, _| in_game.contains_key(id));
+        lobby.game_static.deal_teams();
+        if lobby.game_static.settings != game.lobby_settings {
+            // back to the lobby's own map and its start positions
+            let host_player = in_game
+                .iter()
+                .find(|(_, client_id)| **client_id == lobby.host)
+                .map_or(lobby.game_static.local_player, |(player_id, _)| *player_id);
+            lobby.change_settings(game.lobby_settings, host_player);
+        }
+
+        if let Some(status) = self.tournaments.get(&game_id) {
+            if in_game.len() >= lobby.game_static.settings.min_players_to_start.idx() {
+                let round = status.games_played as usize;
+                self.start_next_tournament_round(lobby, round, &in_game);
+                return;
+            }
+            log::info!("{game_id:?} has too few players left to continue its tournament");
+            self.tournaments.remove(&game_id);
+        }
+
+        self.games.insert(game_id, Game::Lobby(lobby));
+        log::info!(
+            "{game_id:?} is back to its lobby with {} players",
+            in_game.len()
+        );
+
+        for client_id in in_game.into_values() {
+            let client = self.clients.get_mut(&client_id).expect("client exists");
+            if let Some(cg) = &mut client.game {
+                cg.restart();
+            }
+            let address = client.address;
+            let update = self
+                .lobby_update(client_id)
+                .expect("client is in the lobby");
+            self.outgoing
+                .push((address, ServerMessage::LobbyUpdate(update)));
+        }
+    }
+
+    /// Start the next game of a tournament with the players of the last one, without waiting
+    /// for them to get ready
+    fn start_next_tournament_round(
+        &mut self,
+        lobby: Lobby,
+        round: usize,
+        in_game: &BTreeMap<PlayerId, ClientId>,
+    ) {
+        let local_player = lobby.game_static.local_player;
+        let start = self.start_game(lobby.id, lobby.host, lobby.game_static, local_player, round);
+        for (&player_id, client_id) in in_game {
+            let client = self.clients.get_mut(client_id).expect("client exists");
+            if let Some(cg) = &mut client.game {
+                cg.restart();
+            }
+            let mut start = start.clone();
+            start.game.local_player = player_id;
+            self.outgoing
+                .push((client.address, ServerMessage::GameStart(start)));
+        }
+    }
+
+    /// The current state of the client's lobby, `None` if the client is not in one
+    fn lobby_update(&self, client_id: ClientId) -> Option<ServerLobbyUpdate> {
+        let client_game = self.clients.get(&client_id)?.game.as_ref()?;
+        let Game::Lobby(lobby) = &self.games[&client_game.game_id] else {
+            return None;
+        };
+        Some(ServerLobbyUpdate::new(
+            client_game.player_id,
+            lobby.game_static.clone(),
+            lobby.players_ready.clone(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bomberhans_lib::field::Cell;
+    use bomberhans_lib::settings::Ratios;
+    use bomberhans_lib::settings::RoundSettings;
+    use bomberhans_lib::utils::Direction;
+    use bomberhans_lib::utils::MAX_GAME_TICKS;
+
+    fn hello(server: &mut Server, client_address: SocketAddr) -> ClientId {
+        let [ServerMessage::Hello(hello)] = &server.handle_client_message(
+            ClientMessage::Hello(ClientHello {
+                magic: BOMBERHANS_MAGIC_NO_V1,
+                nonce: 1,
+                player_name: "Hans".to_owned(),
+            }),
+            client_address,
+        )[..] else {
+            panic!("server responds to hello");
+        };
+        hello.client_id
+    }
+
+    #[test]
+    fn test_full_server_rejects_new_clients() {
+        let mut server = Server::new("Test".to_owned(), None);
+        server.max_clients = 1;
+        let address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let client = hello(&mut server, address);
+        assert_eq!(hello(&mut server, address), client, "hello again is fine");
+
+        let response = server.handle_client_message(
+            ClientMessage::Hello(ClientHello {
+                magic: BOMBERHANS_MAGIC_NO_V1,
+                nonce: 1,
+                player_name: "Hans".to_owned(),
+            }),
+            "[::1]:1001".parse().unwrap(),
+        );
+        assert_eq!(
+            response,
+            vec![ServerMessage::Bye(DisconnectReason::ServerFull)]
+        );
+        assert_eq!(server.clients.len(), 1);
+    }
+
+    #[test]
+    fn test_long_player_names_are_capped() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let address: SocketAddr = "[::1]:1000".parse().unwrap();
+        server.handle_client_message(
+            ClientMessage::Hello(ClientHello {
+                magic: BOMBERHANS_MAGIC_NO_V1,
+                nonce: 1,
+                player_name: "Player Number 0abcde".to_owned(),
+            }),
+            address,
+        );
+        let client = server.clients.values().next().unwrap();
+        assert_eq!(client.name, "Player Number 0a");
+    }
+
+    #[test]
+    fn test_removing_a_client_of_a_missing_game_is_logged() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let client_id = hello(&mut server, address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(client_id), address);
+        let game_id = server.clients[&client_id].game.as_ref().unwrap().game_id;
+        server.games.remove(&game_id);
+
+        server.remove_client(client_id);
+        assert!(server.clients.is_empty());
+        server.remove_client(client_id);
+    }
+
+    #[test]
+    fn test_keep_alive_prevents_removal() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let idle_address: SocketAddr = "[::1]:1001".parse().unwrap();
+        let silent_address: SocketAddr = "[::1]:1002".parse().unwrap();
+        let idle = hello(&mut server, idle_address);
+        let silent = hello(&mut server, silent_address);
+
+        // both said hello 4s ago, only the idle client keeps sending keep alives
+        for client in server.clients.values_mut() {
+            client.last_message -= Duration::from_secs(4);
+        }
+        server.handle_client_message(ClientMessage::KeepAlive(idle), idle_address);
+
+        server.remove_inactive_clients(Instant::now() + Duration::from_secs(2));
+        assert!(server.clients.contains_key(&idle));
+        assert!(!server.clients.contains_key(&silent));
+    }
+
+    #[test]
+    fn test_reliable_messages_are_acknowledged_and_handled_once() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let address: SocketAddr = "[::1]:1001".parse().unwrap();
+        let client_id = hello(&mut server, address);
+
+        let message = || {
+            ClientMessage::Reliable(
+                PacketNumber::new(3),
+                Box::new(ClientMessage::KeepAlive(client_id)),
+            )
+        };
+        assert_eq!(
+            server.handle_client_message(message(), address),
+            vec![ServerMessage::Ack(PacketNumber::new(3))]
+        );
+        assert_eq!(
+            server.handle_client_message(message(), address),
+            vec![ServerMessage::Ack(PacketNumber::new(3))],
+            "retransmissions are acknowledged again"
+        );
+        assert_eq!(server.clients[&client_id].received_reliable.len(), 1);
+    }
+
+    #[test]
+    fn test_received_reliable_messages_are_forgotten_once_all_before_arrived() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let address: SocketAddr = "[::1]:1001".parse().unwrap();
+        let client_id = hello(&mut server, address);
+        let client = server.clients.get_mut(&client_id).unwrap();
+
+        assert!(client.receive_reliable(PacketNumber::new(0)));
+        assert!(client.receive_reliable(PacketNumber::new(2)));
+        assert_eq!(client.received_reliable.len(), 1, "1 is missing");
+        assert!(client.receive_reliable(PacketNumber::new(1)));
+        assert!(client.received_reliable.is_empty());
+        assert_eq!(client.reliable_window_start, PacketNumber::new(3));
+
+        for packet_number in 0..3 {
+            assert!(
+                !client.receive_reliable(PacketNumber::new(packet_number)),
+                "retransmitted {packet_number} is not handled again"
+            );
+        }
+    }
+
+    #[test]
+    fn test_open_new_lobby_twice_returns_same_lobby() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let address: SocketAddr = "[::1]:1001".parse().unwrap();
+        let client_id = hello(&mut server, address);
+
+        let first = server.handle_client_message(ClientMessage::OpenNewLobby(client_id), address);
+        // the response was lost, client asks again
+        let second = server.handle_client_message(ClientMessage::OpenNewLobby(client_id), address);
+
+        let [ServerMessage::LobbyUpdate(lobby)] = &first[..] else {
+            panic!("server responds with lobby: {first:?}");
+        };
+        assert_eq!(lobby.game.players[&lobby.client_player_id].name, "Hans");
+        assert!(lobby.field_matches());
+        assert_eq!(first, second);
+        assert_eq!(server.games.len(), 1);
+
+        let resync = server.handle_client_message(ClientMessage::GetLobby(client_id), address);
+        assert_eq!(first, resync);
+    }
+
+    #[test]
+    fn test_join_full_lobby_is_rejected() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let host_address: SocketAddr = "[::1]:1001".parse().unwrap();
+        let host = hello(&mut server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+        let Game::Lobby(game) = server.games.get_mut(&lobby).unwrap() else {
+            panic!("host opened a lobby");
+        };
+        game.game_static.settings.players = 2;
+
+        let join = |client_id| ClientMessage::JoinLobby(ClientJoinLobby { client_id, lobby });
+
+        let guest_address: SocketAddr = "[::1]:1002".parse().unwrap();
+        let guest = hello(&mut server, guest_address);
+        let [ServerMessage::LobbyUpdate(update)] =
+            &server.handle_client_message(join(guest), guest_address)[..]
+        else {
+            panic!("guest joins");
+        };
+        assert_eq!(update.client_player_id, PlayerId(1));
+        assert_eq!(update.game.players.len(), 2);
+
+        let late_address: SocketAddr = "[::1]:1003".parse().unwrap();
+        let late = hello(&mut server, late_address);
+        assert_eq!(
+            server.handle_client_message(join(late), late_address),
+            vec![ServerMessage::Bye(DisconnectReason::GameFull)]
+        );
+        assert!(server.clients[&late].game.is_none());
+    }
+
+    #[test]
+    fn test_lobby_update_lists_players_by_id_after_rejoin() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let host_address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let host = hello(&mut server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+
+        let join = |server: &mut Server, name: &str, port| {
+            let address: SocketAddr = format!("[::1]:{port}").parse().unwrap();
+            let [ServerMessage::Hello(hello)] = &server.handle_client_message(
+                ClientMessage::Hello(ClientHello {
+                    magic: BOMBERHANS_MAGIC_NO_V1,
+                    nonce: 1,
+                    player_name: name.to_owned(),
+                }),
+                address,
+            )[..] else {
+                panic!("server responds to hello");
+            };
+            let client_id = hello.client_id;
+            let response = server.handle_client_message(
+                ClientMessage::JoinLobby(ClientJoinLobby { client_id, lobby }),
+                address,
+            );
+            (client_id, address, response)
+        };
+        let (leaver, leaver_address, _) = join(&mut server, "Fritz", 1001);
+        join(&mut server, "Grete", 1002);
+        server.handle_client_message(ClientMessage::Bye(leaver), leaver_address);
+        let (_, _, response) = join(&mut server, "Susi", 1003);
+
+        // as the client sees it
+        let response: Vec<ServerMessage> = response
+            .iter()
+            .map(|msg| decode(&encode(msg)).unwrap())
+            .collect();
+        let [ServerMessage::LobbyUpdate(update)] = &response[..] else {
+            panic!("Susi joins: {response:?}");
+        };
+        assert_eq!(update.client_player_id, PlayerId(1), "Susi takes Fritz' id");
+        let players: Vec<(PlayerId, &str)> = update
+            .game
+            .players
+            .iter()
+            .map(|(id, player)| (*id, player.name.as_str()))
+            .collect();
+        assert_eq!(
+            players,
+            [
+                (PlayerId(0), "Hans"),
+                (PlayerId(1), "Susi"),
+                (PlayerId(2), "Grete")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_player_rejoining_gets_free_id_and_start_position() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let host_address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let host = hello(&mut server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+
+        let guests: Vec<(ClientId, SocketAddr)> = (1..=3)
+            .map(|i| {
+                let address: SocketAddr = format!("[::1]:100{i}").parse().unwrap();
+                let client_id = hello(&mut server, address);
+                server.handle_client_message(
+                    ClientMessage::JoinLobby(ClientJoinLobby { client_id, lobby }),
+                    address,
+                );
+                (client_id, address)
+            })
+            .collect();
+
+        // a player in the middle leaves, another one joins
+        let (middle, middle_address) = guests[1];
+        server.handle_client_message(ClientMessage::Bye(middle), middle_address);
+        let new_address: SocketAddr = "[::1]:1009".parse().unwrap();
+        let new = hello(&mut server, new_address);
+        server.handle_client_message(
+            ClientMessage::JoinLobby(ClientJoinLobby {
+                client_id: new,
+                lobby,
+            }),
+            new_address,
+        );
+
+        let update = server.lobby_update(new).unwrap();
+        assert_eq!(
+            update.client_player_id,
+            PlayerId(2),
+            "the free id is reused"
+        );
+        let players = &update.game.players;
+        assert_eq!(players.len(), 4);
+        let start_positions: BTreeSet<(i32, i32)> = players
+            .values()
+            .map(|player| (player.start_position.x, player.start_position.y))
+            .collect();
+        assert_eq!(start_positions.len(), 4, "start positions are unique");
+    }
+
+    #[test]
+    fn test_teams_stay_even_when_players_leave() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let host_address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let host = hello(&mut server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+        let settings = Settings {
+            teams: 2,
+            ..Settings::default()
+        };
+        server.handle_client_message(
+            ClientMessage::SetSettings(host, Box::new(settings)),
+            host_address,
+        );
+
+        let guests: Vec<(ClientId, SocketAddr)> = (1..=3)
+            .map(|i| {
+                let address: SocketAddr = format!("[::1]:100{i}").parse().unwrap();
+                let client_id = hello(&mut server, address);
+                server.handle_client_message(
+                    ClientMessage::JoinLobby(ClientJoinLobby { client_id, lobby }),
+                    address,
+                );
+                (client_id, address)
+            })
+            .collect();
+        let teams = |server: &Server| {
+            let update = server.lobby_update(host).unwrap();
+            let players = update.game.players.into_values();
+            players.map(|p| (p.id, p.team)).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            teams(&server),
+            [
+                (PlayerId(0), Some(0)),
+                (PlayerId(1), Some(1)),
+                (PlayerId(2), Some(0)),
+                (PlayerId(3), Some(1))
+            ]
+        );
+
+        // both players of team 1 leave
+        for (client_id, address) in [guests[0], guests[2]] {
+            server.handle_client_message(ClientMessage::Bye(client_id), address);
+        }
+        assert_eq!(
+            teams(&server),
+            [(PlayerId(0), Some(0)), (PlayerId(2), Some(1))]
+        );
+    }
+
+    #[test]
+    fn test_host_force_starts_without_unready_guest() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let host_address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let host = hello(&mut server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+
+        let mut join = |port| {
+            let address: SocketAddr = format!("[::1]:{port}").parse().unwrap();
+            let client_id = hello(&mut server, address);
+            server.handle_client_message(
+                ClientMessage::JoinLobby(ClientJoinLobby { client_id, lobby }),
+                address,
+            );
+            (client_id, address)
+        };
+        let (afk, afk_address) = join(1001);
+        let (ready, ready_address) = join(1002);
+        let Game::Lobby(game) = server.games.get_mut(&lobby).unwrap() else {
+            panic!("guests joined a lobby");
+        };
+        game.players_ready.insert(PlayerId(2));
+
+        assert!(
+            server
+                .handle_client_message(ClientMessage::ForceStart(afk), afk_address)
+                .is_empty(),
+            "only the host can force the start"
+        );
+        server.take_outgoing();
+
+        let [ServerMessage::GameStart(start)] =
+            &server.handle_client_message(ClientMessage::ForceStart(host), host_address)[..]
+        else {
+            panic!("host starts the game");
+        };
+        assert_eq!(
+            start.game.players.keys().copied().collect::<Vec<_>>(),
+            vec![PlayerId(0), PlayerId(2)]
+        );
+        assert!(matches!(server.games[&lobby], Game::Started(_)));
+        assert!(server.clients[&afk].game.is_none());
+        assert!(server.clients[&ready].game.is_some());
+
+        let mut outgoing = server.take_outgoing();
+        outgoing.sort_by_key(|(address, _)| *address);
+        let [(bye_address, ServerMessage::Bye(reason)), (start_address, ServerMessage::GameStart(guest_start))] =
+            &outgoing[..]
+        else {
+            panic!("the guests hear of the start: {outgoing:?}");
+        };
+        assert_eq!(
+            (*bye_address, *reason),
+            (afk_address, DisconnectReason::GameStarted)
+        );
+        assert_eq!(*start_address, ready_address);
+        assert_eq!(guest_start.game.local_player, PlayerId(2));
+        assert_eq!(guest_start.game.players, start.game.players);
+
+        let updates = server.periodic_update(Instant::now());
+        assert_eq!(updates.len(), 2, "both players get updates");
+    }
+
+    #[test]
+    fn test_client_with_low_update_rate_gets_fewer_updates() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let host_address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let host = hello(&mut server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+        let slow_address: SocketAddr = "[::1]:1001".parse().unwrap();
+        let slow = hello(&mut server, slow_address);
+        server.handle_client_message(
+            ClientMessage::JoinLobby(ClientJoinLobby {
+                client_id: slow,
+                lobby,
+            }),
+            slow_address,
+        );
+        server.handle_client_message(ClientMessage::SetUpdateRate(slow, 4), slow_address);
+        server.handle_client_message(ClientMessage::SetReady(host, true), host_address);
+        server.handle_client_message(ClientMessage::SetReady(slow, true), slow_address);
+        assert!(matches!(server.games[&lobby], Game::Started(_)));
+
+        let start = Instant::now();
+        let mut received = HashMap::<SocketAddr, u32>::new();
+        // 2 seconds
+        for tick in 0..2 * TICKS_PER_SECOND {
+            let now = start + TIME_PER_TICK * tick;
+            for (address, _) in server.periodic_update(now) {
+                *received.entry(address).or_default() += 1;
+            }
+        }
+        assert_eq!(received[&host_address], 2 * TICKS_PER_SECOND);
+        assert_eq!(received[&slow_address], 8);
+    }
+
+    #[test]
+    fn test_action_is_applied_between_client_updates() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let (game_id, host_address, _) = start_two_player_game(&mut server);
+        let (&host, client) = server
+            .clients
+            .iter()
+            .find(|(_, c)| c.address == host_address)
+            .unwrap();
+        let player = client.game.as_ref().unwrap().player_id;
+        // time, next tick and distance walked of the host's player
+        let game = |server: &Server| {
+            let Game::Started(game) = &server.games[&game_id] else {
+                panic!("game started");
+            };
+            let walked = game.game_state.player_states[player].distance_walked;
+            (game.game_state.time, game.next_tick, walked)
+        };
+        let (_, start, _) = game(&server);
+        let Game::Started(started) = server.games.get_mut(&game_id).unwrap() else {
+            panic!("game started");
+        };
+        let countdown = started.game_state.start_delay_ticks();
+        started.game_state.time =
+            TimeStamp::default() + bomberhans_lib::utils::Duration::from_ticks(countdown);
+        // the client acknowledges a server update with its input, so the game needs to be going
+        server.periodic_update(start);
+
+        let mut distances = Vec::new();
+        for tick in 1..10 {
+            let (time, _, _) = game(&server);
+            if tick % 3 == 1 {
+                server.handle_client_message(
+                    ClientMessage::Update(ClientUpdate {
+                        client_id: host,
+                        last_server_update: time,
+                        current_player_action: Action {
+                            walking: Some(Direction::South),
+                            diagonal: None,
+                            placing: false,
+                            detonate: false,
+                        },
+                        current_action_start_time: time,
+                    }),
+                    host_address,
+                );
+            }
+            server.periodic_update(start + TIME_PER_TICK * tick);
+            distances.push(game(&server).2);
+        }
+        assert_eq!(
+            game(&server).0.ticks_from_start(),
+            countdown + 10,
+            "one tick per interval"
+        );
+        assert!(
+            distances.windows(2).all(|d| d[0] < d[1]),
+            "the player walks every tick: {distances:?}"
+        );
+    }
+
+    #[test]
+    fn test_get_game_resends_all_updates() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let (game_id, host_address, _) = start_two_player_game(&mut server);
+        let host = server
+            .clients
+            .values()
+            .find(|c| c.address == host_address)
+            .unwrap()
+            .id;
+        let start = Instant::now();
+        let update = |server: &mut Server, tick| {
+            let updates = server.periodic_update(start + TIME_PER_TICK * tick);
+            let Game::Started(game) = &server.games[&game_id] else {
+                panic!("game started");
+            };
+            let (_, update) = updates
+                .into_iter()
+                .find(|(address, _)| *address == host_address)
+                .unwrap();
+            assert_eq!(update.checksum, game.game_state.checksum());
+            update
+        };
+        let client_update = |time| {
+            ClientMessage::Update(ClientUpdate {
+                client_id: host,
+                last_server_update: time,
+                current_player_action: Action {
+                    walking: Some(Direction::South),
+                    diagonal: None,
+                    placing: false,
+                    detonate: false,
+                },
+                current_action_start_time: time,
+            })
+        };
+
+        let first = update(&mut server, 1);
+        server.handle_client_message(client_update(first.time), host_address);
+        let walked = update(&mut server, 2);
+        assert_eq!(walked.updates.len(), 1);
+        server.handle_client_message(client_update(walked.time), host_address);
+        assert!(update(&mut server, 3).updates.is_empty(), "acknowledged");
+
+        server.handle_client_message(ClientMessage::GetGame(host), host_address);
+        assert_eq!(update(&mut server, 4).updates, walked.updates);
+    }
+
+    #[test]
+    fn test_many_updates_are_sent_in_chunks_that_fit_a_datagram() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let (game_id, _, _) = start_two_player_game(&mut server);
+        let Game::Started(game) = server.games.get_mut(&game_id).unwrap() else {
+            panic!("game started");
+        };
+        for tick in 0..50 {
+            let walking = if tick % 2 == 0 {
+                Direction::North
+            } else {
+                Direction::South
+            };
+            for player in [PlayerId(0), PlayerId(1)] {
+                game.future_updates.push(Update {
+                    player,
+                    action: Action {
+                        walking: Some(walking),
+                        ..Action::idle()
+                    },
+                    time: game.game_state.time + bomberhans_lib::utils::Duration::from_ticks(1),
+                });
+            }
+            game.simulate_tick();
+        }
+        assert_eq!(game.updates.len(), 100);
+
+        // a client that resyncs replays the chunks and acknowledges each
+        let mut replayed = GameState::new(Rc::clone(&game.game_static));
+        let mut acknowledged = TimeStamp::default();
+        let mut chunks = Vec::new();
+        loop {
+            let update = game.update_since(acknowledged);
+            assert!(encode(&ServerMessage::Update(update.clone())).len() <= 1024);
+            while replayed.time < update.time {
+                replayed.simulate_1_update();
+                let time = replayed.time;
+                for u in update.updates.iter().filter(|u| u.time == time) {
+                    replayed.set_player_action(u.player, u.action);
+                }
+            }
+            assert_eq!(replayed.checksum(), update.checksum);
+            chunks.push(update.updates.len());
+            if update.time == game.game_state.time {
+                break;
+            }
+            acknowledged = update.time;
+        }
+        assert_eq!(chunks, [32, 32, 32, 4]);
+    }
+
+    #[test]
+    fn test_over_capacity_placement_is_suspected() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let (game_id, host_address, _) = start_two_player_game(&mut server);
+        let host = server
+            .clients
+            .values()
+            .find(|c| c.address == host_address)
+            .unwrap()
+            .id;
+        let Game::Started(started) = server.games.get_mut(&game_id).unwrap() else {
+            panic!("game started");
+        };
+        let countdown = started.game_state.start_delay_ticks();
+        started.game_state.time =
+            TimeStamp::default() + bomberhans_lib::utils::Duration::from_ticks(countdown);
+        // bombs can not be placed onto the start point
+        let player = server.clients[&host].game.as_ref().unwrap().player_id;
+        let cell = started.game_state.player_states[player]
+            .position
+            .as_cell_pos();
+        started.game_state.field[cell] = bomberhans_lib::field::Cell::Empty;
+        let start = started.next_tick;
+        server.periodic_update(start);
+
+        let mut tick = 0;
+        let mut act = |server: &mut Server, placing| {
+            let Game::Started(game) = &server.games[&game_id] else {
+                panic!("game started");
+            };
+            let time = game.game_state.time;
+            server.handle_client_message(
+                ClientMessage::Update(ClientUpdate {
+                    client_id: host,
+                    last_server_update: time,
+                    current_player_action: Action {
+                        walking: None,
+                        diagonal: None,
+                        placing,
+                        detonate: false,
+                    },
+                    current_action_start_time: time,
+                }),
+                host_address,
+            );
+            tick += 1;
+            server.periodic_update(start + TIME_PER_TICK * tick);
+            server.clients.get(&host).map(|c| c.suspicions)
+        };
+
+        assert_eq!(act(&mut server, true), Some(0), "places the only bomb");
+        assert_eq!(act(&mut server, false), Some(0));
+        assert_eq!(
+            act(&mut server, true),
+            Some(1),
+            "a second bomb while the first is still on the field"
+        );
+        assert!(server.take_outgoing().is_empty(), "suspects are not kicked");
+
+        server.set_kick_suspected_cheaters(true);
+        server.clients.get_mut(&host).unwrap().suspicions = SUSPICIONS_TO_KICK - 1;
+        assert_eq!(act(&mut server, false), Some(SUSPICIONS_TO_KICK - 1));
+        assert_eq!(act(&mut server, true), None, "kicked");
+        assert_eq!(
+            server.take_outgoing(),
+            vec![(host_address, ServerMessage::Bye(DisconnectReason::Kicked))]
+        );
+    }
+
+    #[test]
+    fn test_game_that_fell_behind_skips_ticks() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let (game_id, _, _) = start_two_player_game(&mut server);
+
+        server.periodic_update(Instant::now() + Duration::from_secs(10));
+        let Game::Started(game) = &server.games[&game_id] else {
+            panic!("game started");
+        };
+        assert_eq!(game.game_state.time.ticks_from_start(), MAX_CATCH_UP_TICKS);
+    }
+
+    /// Start a game of a host on port 1000 and a guest on port 1001
+    fn start_two_player_game(server: &mut Server) -> (GameId, SocketAddr, SocketAddr) {
+        start_two_player_game_with(server, Settings::default())
+    }
+
+    /// Like `start_two_player_game`, the host sets `settings` first
+    fn start_two_player_game_with(
+        server: &mut Server,
+        settings: Settings,
+    ) -> (GameId, SocketAddr, SocketAddr) {
+        let host_address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let host = hello(server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        server.handle_client_message(
+            ClientMessage::SetSettings(host, Box::new(settings)),
+            host_address,
+        );
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+        let guest_address: SocketAddr = "[::1]:1001".parse().unwrap();
+        let guest = hello(server, guest_address);
+        server.handle_client_message(
+            ClientMessage::JoinLobby(ClientJoinLobby {
+                client_id: guest,
+                lobby,
+            }),
+            guest_address,
+        );
+        server.handle_client_message(ClientMessage::SetReady(host, true), host_address);
+        // the other player's lobby updates of the join and the host's ready
+        assert_eq!(server.take_outgoing().len(), 2);
+        server.handle_client_message(ClientMessage::SetReady(guest, true), guest_address);
+        assert!(matches!(server.games[&lobby], Game::Started(_)));
+        assert!(matches!(
+            server.take_outgoing()[..],
+            [(_, ServerMessage::GameStart(_))]
+        ));
+        (lobby, host_address, guest_address)
+    }
+
+    /// Let the game run out of time
+    fn end_game(server: &mut Server, game_id: GameId) -> Results {
+        let Game::Started(game) = server.games.get_mut(&game_id).unwrap() else {
+            panic!("game started");
+        };
+        game.game_state.time =
+            TimeStamp::default() + bomberhans_lib::utils::Duration::from_ticks(MAX_GAME_TICKS);
+        game.game_state.results()
+    }
+
+    /// The winner scores a kill, then the game runs out of time and returns to the lobby
+    ///
+    /// Returns the tournament status the players got and what they got on the return.
+    fn win_round(
+        server: &mut Server,
+        game_id: GameId,
+        winner: PlayerId,
+    ) -> (TournamentStatus, Vec<(SocketAddr, ServerMessage)>) {
+        let Game::Started(game) = server.games.get_mut(&game_id).unwrap() else {
+            panic!("next round started");
+        };
+        game.game_state.player_states.get_mut(winner).unwrap().kills = 1;
+        assert_eq!(end_game(server, game_id).winner, Some(winner));
+        let finished = Instant::now();
+        server.periodic_update(finished);
+        let outgoing = server.take_outgoing();
+        let statuses: Vec<&TournamentStatus> = outgoing
+            .iter()
+            .filter_map(|(_, msg)| match msg {
+                ServerMessage::TournamentStatus(status) => Some(status),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(statuses.len(), 2, "both players get the status");
+        let status = statuses[0].clone();
+
+        let after = Duration::from_secs(Settings::RETURN_TO_LOBBY_AFTER_DEFAULT.into());
+        server.periodic_update(finished + after);
+        (status, server.take_outgoing())
+    }
+
+    #[test]
+    fn test_game_everyone_left_is_closed() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let (game_id, host_address, guest_address) = start_two_player_game(&mut server);
+        let client_id = |server: &Server, address| {
+            server
+                .clients
+                .values()
+                .find(|c| c.address == address)
+                .unwrap()
+                .id
+        };
+
+        let host = client_id(&server, host_address);
+        server.handle_client_message(ClientMessage::Bye(host), host_address);
+        assert!(server.games.contains_key(&game_id), "the guest still plays");
+
+        let guest = client_id(&server, guest_address);
+        server.handle_client_message(ClientMessage::Bye(guest), guest_address);
+        assert!(!server.games.contains_key(&game_id));
+        assert!(server.periodic_update(Instant::now()).is_empty());
+        assert!(server.take_outgoing().is_empty(), "nobody to tell");
+    }
+
+    #[test]
+    fn test_players_get_the_outcome_once_the_game_is_over() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let (lobby, host_address, guest_address) = start_two_player_game(&mut server);
+
+        server.periodic_update(Instant::now());
+        assert!(server.take_outgoing().is_empty());
+
+        let outcome = end_game(&mut server, lobby);
+        let now = Instant::now();
+        server.periodic_update(now);
+        let mut outgoing = server.take_outgoing();
+        outgoing.sort_by_key(|(address, _)| *address);
+        assert_eq!(
+            outgoing,
+            vec![
+                (host_address, ServerMessage::GameOver(outcome.clone())),
+                (guest_address, ServerMessage::GameOver(outcome.clone())),
+            ]
+        );
+
+        server.periodic_update(now + TIME_PER_TICK);
+        assert!(
+            server.take_outgoing().is_empty(),
+            "not sent again right away"
+        );
+
+        let host = server
+            .clients
+            .values()
+            .find(|c| c.address == host_address)
+            .unwrap()
+            .id;
+        server.handle_client_message(ClientMessage::GameOverAck(host), host_address);
+        server.periodic_update(now + GAME_OVER_RETRANSMIT_INTERVAL);
+        assert_eq!(
+            server.take_outgoing(),
+            vec![(guest_address, ServerMessage::GameOver(outcome))],
+            "sent again until acknowledged"
+        );
+    }
+
+    #[test]
+    fn test_last_player_standing_ends_the_game() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let (game_id, host_address, guest_address) = start_two_player_game(&mut server);
+        let player = |server: &Server, address| {
+            let client = server.clients.values().find(|c| c.address == address);
+            client.unwrap().game.as_ref().unwrap().player_id
+        };
+        let (host, guest) = (
+            player(&server, host_address),
+            player(&server, guest_address),
+        );
+        let Game::Started(game) = server.games.get_mut(&game_id).unwrap() else {
+            panic!("game started");
+        };
+        let mut game_static = (*game.game_static).clone();
+        game_static.settings.lives = 1;
+        game.game_static = Rc::new(game_static);
+        game.game_state.game = Rc::clone(&game.game_static);
+        let start = game.next_tick;
+        server.periodic_update(start);
+        assert!(server.take_outgoing().is_empty());
+
+        let Game::Started(game) = server.games.get_mut(&game_id).unwrap() else {
+            panic!("game started");
+        };
+        game.game_state.player_states.get_mut(guest).unwrap().deaths = 1;
+        server.periodic_update(start + TIME_PER_TICK);
+        let outgoing = server.take_outgoing();
+        assert_eq!(outgoing.len(), 2);
+        for (_, msg) in outgoing {
+            let ServerMessage::GameOver(outcome) = msg else {
+                panic!("game over: {msg:?}");
+            };
+            assert_eq!(outcome.winner, Some(host));
+        }
+
+        let time = |server: &Server| {
+            let Game::Started(game) = &server.games[&game_id] else {
+                panic!("game started");
+            };
+            game.game_state.time
+        };
+        let decided_at = time(&server);
+        server.periodic_update(start + TIME_PER_TICK * 10);
+        assert_eq!(time(&server), decided_at, "a decided game stands still");
+    }
+
+    #[test]
+    fn test_finished_game_returns_to_lobby_after_the_timeout() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let (lobby, host_address, guest_address) = start_two_player_game(&mut server);
+        end_game(&mut server, lobby);
+
+        let finished = Instant::now();
+        server.periodic_update(finished);
+        for (address, _) in server.take_outgoing() {
+            let client = server.clients.values().find(|c| c.address == address);
+            let client_id = client.unwrap().id;
+            server.handle_client_message(ClientMessage::GameOverAck(client_id), address);
+        }
+
+        let after = Duration::from_secs(Settings::RETURN_TO_LOBBY_AFTER_DEFAULT.into());
+        server.periodic_update(finished + after.saturating_sub(TIME_PER_TICK));
+        assert!(matches!(server.games[&lobby], Game::Started(_)));
+        assert!(server.take_outgoing().is_empty());
+
+        let updates = server.periodic_update(finished + after);
+        assert!(updates.is_empty(), "no more game updates");
+        let Game::Lobby(l) = &server.games[&lobby] else {
+            panic!("the game is a lobby again");
+        };
+        assert_eq!(l.game_static.players.len(), 2);
+        assert!(
+            l.players_ready.is_empty(),
+            "everyone has to get ready again"
+        );
+        let mut outgoing = server.take_outgoing();
+        outgoing.sort_by_key(|(address, _)| *address);
+        let [(a, ServerMessage::LobbyUpdate(_)), (b, ServerMessage::LobbyUpdate(_))] =
+            &outgoing[..]
+        else {
+            panic!("both players get the lobby: {outgoing:?}");
+        };
+        assert_eq!((*a, *b), (host_address, guest_address));
+    }
+
+    #[test]
+    fn test_host_that_leaves_hands_over_to_the_lowest_player_id() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let host_address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let host = hello(&mut server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+        let mut guests = Vec::new();
+        for port in [1001, 1002] {
+            let address: SocketAddr = format!("[::1]:{port}").parse().unwrap();
+            let guest = hello(&mut server, address);
+            server.handle_client_message(
+                ClientMessage::JoinLobby(ClientJoinLobby {
+                    client_id: guest,
+                    lobby,
+                }),
+                address,
+            );
+            guests.push((guest, address));
+        }
+        let [(first, first_address), (second, second_address)] = guests[..] else {
+            unreachable!();
+        };
+
+        server.handle_client_message(ClientMessage::Bye(host), host_address);
+        let Game::Lobby(l) = &server.games[&lobby] else {
+            panic!("the lobby stays open");
+        };
+        assert_eq!(l.host, first, "the guest with player id 1 hosts now");
+        assert!(
+            server
+                .handle_client_message(ClientMessage::ForceStart(second), second_address)
+                .is_empty(),
+            "only the new host can start"
+        );
+        server.handle_client_message(ClientMessage::SetReady(second, true), second_address);
+        let [ServerMessage::GameStart(_)] =
+            &server.handle_client_message(ClientMessage::ForceStart(first), first_address)[..]
+        else {
+            panic!("the new host starts the game");
+        };
+
+        server.handle_client_message(ClientMessage::Bye(first), first_address);
+        let Game::Started(game) = &server.games[&lobby] else {
+            panic!("the game goes on");
+        };
+        assert_eq!(game.host, second);
+        end_game(&mut server, lobby);
+        let finished = Instant::now();
+        server.periodic_update(finished);
+        let after = Duration::from_secs(Settings::RETURN_TO_LOBBY_AFTER_DEFAULT.into());
+        server.periodic_update(finished + after);
+        let Game::Lobby(l) = &server.games[&lobby] else {
+            panic!("the game is a lobby again");
+        };
+        assert_eq!(l.host, second, "the last one left hosts the lobby");
+    }
+
+    #[test]
+    fn test_best_of_3_ends_after_two_wins() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let settings = Settings {
+            best_of: 3,
+            ..Settings::default()
+        };
+        let (game_id, host_address, guest_address) =
+            start_two_player_game_with(&mut server, settings);
+        let player = |server: &Server, address| {
+            let client = server.clients.values().find(|c| c.address == address);
+            client.unwrap().game.as_ref().unwrap().player_id
+        };
+        let (host, guest) = (
+            player(&server, host_address),
+            player(&server, guest_address),
+        );
+
+        let (status, outgoing) = win_round(&mut server, game_id, host);
+        assert_eq!(status.wins, BTreeMap::from([(host, 1)]));
+        assert_eq!(status.winner, None);
+        assert!(matches!(server.games[&game_id], Game::Started(_)));
+        for (address, msg) in outgoing {
+            let ServerMessage::GameStart(start) = msg else {
+                panic!("the next round starts: {msg:?}");
+            };
+            assert_eq!(start.game.local_player, player(&server, address));
+        }
+
+        let (status, _) = win_round(&mut server, game_id, guest);
+        assert_eq!(status.wins, BTreeMap::from([(host, 1), (guest, 1)]));
+        assert_eq!(status.winner, None);
+
+        let (status, outgoing) = win_round(&mut server, game_id, host);
+        assert_eq!(status.wins, BTreeMap::from([(host, 2), (guest, 1)]));
+        assert_eq!(status.games_played, 3);
+        assert_eq!(status.winner, Some(host));
+        assert!(
+            matches!(server.games[&game_id], Game::Lobby(_)),
+            "the tournament is over"
+        );
+        assert!(outgoing
+            .iter()
+            .all(|(_, msg)| matches!(msg, ServerMessage::LobbyUpdate(_))));
+        assert!(server.tournaments.is_empty());
+    }
+
+    #[test]
+    fn test_tournament_rounds_cycle_through_the_playlist() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let round = |map_seed| RoundSettings {
+            map_seed: Some(map_seed),
+            custom_map: None,
+            ratios: Ratios::default(),
+        };
+        let settings = Settings {
+            best_of: 9,
+            playlist: vec![round(11), round(12), round(13)],
+            ..Settings::default()
+        };
+        let (game_id, host_address, guest_address) =
+            start_two_player_game_with(&mut server, settings.clone());
+        let round_settings = |server: &Server| {
+            let Game::Started(game) = &server.games[&game_id] else {
+                panic!("the round started");
+            };
+            for player in game.game_static.players.values() {
+                assert_eq!(
+                    game.game_state.field[player.start_position.as_cell_pos()],
+                    Cell::StartPoint,
+                    "{player:?} starts on the round's field"
+                );
+            }
+            (
+                game.game_static.settings.clone(),
+                game.lobby_settings.clone(),
+            )
+        };
+        let player = |server: &Server, address| {
+            let client = server.clients.values().find(|c| c.address == address);
+            client.unwrap().game.as_ref().unwrap().player_id
+        };
+        let (host, guest) = (
+            player(&server, host_address),
+            player(&server, guest_address),
+        );
+
+        let mut seeds = Vec::new();
+        for winner in [host, guest, host, guest] {
+            let (round, lobby) = round_settings(&server);
+            assert_eq!(lobby, settings, "the lobby keeps its settings");
+            seeds.push(round.map_seed);
+            win_round(&mut server, game_id, winner);
+        }
+        seeds.push(round_settings(&server).0.map_seed);
+        assert_eq!(seeds, [11, 12, 13, 11, 12].map(Some));
+    }
+
+    #[test]
+    fn test_game_starts_only_with_enough_ready_players() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let host_address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let host = hello(&mut server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+
+        let response =
+            server.handle_client_message(ClientMessage::SetReady(host, true), host_address);
+        let [ServerMessage::LobbyUpdate(update)] = &response[..] else {
+            panic!("a single player does not start the game: {response:?}");
+        };
+        assert!(update.players_ready.contains(&PlayerId(0)));
+        assert!(matches!(server.games[&lobby], Game::Lobby(_)));
+
+        let guest_address: SocketAddr = "[::1]:1001".parse().unwrap();
+        let guest = hello(&mut server, guest_address);
+        server.handle_client_message(
+            ClientMessage::JoinLobby(ClientJoinLobby {
+                client_id: guest,
+                lobby,
+            }),
+            guest_address,
+        );
+        let outgoing = server.take_outgoing();
+        let [(address, ServerMessage::LobbyUpdate(update))] = &outgoing[..] else {
+            panic!("the host hears of the guest: {outgoing:?}");
+        };
+        assert_eq!(*address, host_address);
+        assert_eq!(update.client_player_id, PlayerId(0));
+        assert_eq!(update.game.players.len(), 2);
+        assert_eq!(update.players_ready, BTreeSet::from([PlayerId(0)]));
+
+        let response =
+            server.handle_client_message(ClientMessage::SetReady(guest, true), guest_address);
+        let [ServerMessage::GameStart(start)] = &response[..] else {
+            panic!("two ready players start the game: {response:?}");
+        };
+        assert_eq!(start.game.local_player, PlayerId(1));
+        assert!(matches!(server.games[&lobby], Game::Started(_)));
+
+        let outgoing = server.take_outgoing();
+        let [(address, ServerMessage::GameStart(host_start))] = &outgoing[..] else {
+            panic!("the host hears of the start: {outgoing:?}");
+        };
+        assert_eq!(*address, host_address);
+        assert_eq!(host_start.game.local_player, PlayerId(0));
+        assert_eq!(host_start.game.players, start.game.players);
+    }
+
+    #[test]
+    fn test_lobby_members_hear_of_ready_changes_and_leaves() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let host_address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let host = hello(&mut server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+        let guest_address: SocketAddr = "[::1]:1001".parse().unwrap();
+        let guest = hello(&mut server, guest_address);
+        server.handle_client_message(
+            ClientMessage::JoinLobby(ClientJoinLobby {
+                client_id: guest,
+                lobby,
+            }),
+            guest_address,
+        );
+        server.take_outgoing();
+
+        server.handle_client_message(ClientMessage::SetReady(guest, true), guest_address);
+        let outgoing = server.take_outgoing();
+        let [(address, ServerMessage::LobbyUpdate(update))] = &outgoing[..] else {
+            panic!("the host hears the guest is ready: {outgoing:?}");
+        };
+        assert_eq!(*address, host_address);
+        assert_eq!(update.players_ready, BTreeSet::from([PlayerId(1)]));
+
+        server.handle_client_message(ClientMessage::Bye(guest), guest_address);
+        let outgoing = server.take_outgoing();
+        let [(address, ServerMessage::LobbyUpdate(update))] = &outgoing[..] else {
+            panic!("the host hears the guest left: {outgoing:?}");
+        };
+        assert_eq!(*address, host_address);
+        assert_eq!(
+            update.game.players.keys().copied().collect::<Vec<_>>(),
+            [PlayerId(0)]
+        );
+        assert!(update.players_ready.is_empty());
+    }
+
+    /// Ready players in the host's lobby after the host changed the settings to a bigger field
+    fn ready_after_settings_change(unready_on_settings_change: bool) -> BTreeSet<PlayerId> {
+        let mut server = Server::new("Test".to_owned(), None);
+        let host_address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let host = hello(&mut server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+        let guest_address: SocketAddr = "[::1]:1001".parse().unwrap();
+        let guest = hello(&mut server, guest_address);
+        server.handle_client_message(
+            ClientMessage::JoinLobby(ClientJoinLobby {
+                client_id: guest,
+                lobby,
+            }),
+            guest_address,
+        );
+        server.take_outgoing();
+        let Game::Lobby(game) = server.games.get_mut(&lobby).unwrap() else {
+            panic!("the guest joined a lobby");
+        };
+        game.players_ready.extend([PlayerId(0), PlayerId(1)]);
+
+        let settings = Settings {
+            width: 25,
+            unready_on_settings_change,
+            ..Settings::default()
+        };
+        assert!(
+            server
+                .handle_client_message(
+                    ClientMessage::SetSettings(guest, Box::new(settings.clone())),
+                    guest_address
+                )
+                .is_empty(),
+            "only the host can change the settings"
+        );
+        let response = server.handle_client_message(
+            ClientMessage::SetSettings(host, Box::new(settings.clone())),
+            host_address,
+        );
+        let [ServerMessage::LobbyUpdate(update)] = &response[..] else {
+            panic!("the host gets the new lobby: {response:?}");
+        };
+        assert_eq!(update.game.settings, settings);
+        let start_positions = Field::new_from_rules(&settings).start_positions();
+        for player in update.game.players.values() {
+            let cell = player.start_position.as_cell_pos();
+            assert!(
+                start_positions.contains(&cell),
+                "{player:?} starts on the new field"
+            );
+        }
+
+        let [(address, ServerMessage::LobbyUpdate(guest_update))] = &server.take_outgoing()[..]
+        else {
+            panic!("the guest is told about the change");
+        };
+        assert_eq!(*address, guest_address);
+        assert_eq!(guest_update.players_ready, update.players_ready);
+        update.players_ready.clone()
+    }
+
+    #[test]
+    fn test_settings_change_unreadies_guests_if_enabled() {
+        assert_eq!(
+            ready_after_settings_change(true),
+            BTreeSet::from([PlayerId(0)])
+        );
+        assert_eq!(
+            ready_after_settings_change(false),
+            BTreeSet::from([PlayerId(0), PlayerId(1)])
+        );
+    }
+
+    #[test]
+    fn test_invalid_settings_are_refused() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let host_address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let host = hello(&mut server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+
+        let settings = Settings {
+            ratios: Ratios::NONE,
+            ..Settings::default()
+        };
+        assert_eq!(
+            server.handle_client_message(
+                ClientMessage::SetSettings(host, Box::new(settings)),
+                host_address
+            ),
+            vec![ServerMessage::Bye(DisconnectReason::InvalidSettings)]
+        );
+        let Game::Lobby(game) = &server.games[&lobby] else {
+            panic!("still a lobby");
+        };
+        assert_eq!(game.game_static.settings, Settings::default());
+        assert!(server.clients[&host].game.is_some(), "the host stays");
+        assert!(server.take_outgoing().is_empty());
+        assert!(!DisconnectReason::InvalidSettings.ends_game());
+    }
+
+    #[test]
+    fn test_settings_are_locked_once_the_game_started() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let (game_id, host_address, _) = start_two_player_game(&mut server);
+        let host = server
+            .clients
+            .values()
+            .find(|c| c.address == host_address)
+            .unwrap()
+            .id;
+        server.take_outgoing();
+
+        let settings = Settings {
+            width: 21,
+            ..Settings::default()
+        };
+        assert_eq!(
+            server.handle_client_message(
+                ClientMessage::SetSettings(host, Box::new(settings)),
+                host_address
+            ),
+            vec![ServerMessage::Bye(DisconnectReason::SettingsLocked)]
+        );
+        let Game::Started(game) = &server.games[&game_id] else {
+            panic!("still running");
+        };
+        assert_eq!(
+            game.game_state.game.settings.width,
+            Settings::default().width
+        );
+        assert!(
+            server.clients[&host].game.is_some(),
+            "the host stays in the game"
+        );
+        assert!(server.take_outgoing().is_empty(), "nobody else hears of it");
+        assert!(!DisconnectReason::SettingsLocked.ends_game());
+    }
+
+    #[test]
+    fn test_game_without_enough_start_points_does_not_start() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let host_address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let host = hello(&mut server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+        let guest_address: SocketAddr = "[::1]:1001".parse().unwrap();
+        let guest = hello(&mut server, guest_address);
+        server.handle_client_message(
+            ClientMessage::JoinLobby(ClientJoinLobby {
+                client_id: guest,
+                lobby,
+            }),
+            guest_address,
+        );
+        // fields have at most 8 start points, the settings can not allow more players
+        let Game::Lobby(game) = server.games.get_mut(&lobby).unwrap() else {
+            panic!("the guest joined a lobby");
+        };
+        game.game_static.settings.players = 9;
+
+        assert_eq!(
+            server.handle_client_message(ClientMessage::ForceStart(host), host_address),
+            vec![ServerMessage::Bye(DisconnectReason::NotEnoughStartPoints)]
+        );
+        assert!(matches!(server.games[&lobby], Game::Lobby(_)));
+        assert!(server.clients[&host].game.is_some(), "the host can fix it");
+
+        server.handle_client_message(ClientMessage::SetReady(host, true), host_address);
+        server.take_outgoing();
+        assert_eq!(
+            server.handle_client_message(ClientMessage::SetReady(guest, true), guest_address),
+            vec![ServerMessage::Bye(DisconnectReason::NotEnoughStartPoints)],
+            "the player whose ready would start the game is told"
+        );
+        assert!(server.take_outgoing().is_empty(), "nobody else is told");
+        assert!(matches!(
+            server.handle_client_message(ClientMessage::SetReady(host, false), host_address)[..],
+            [ServerMessage::LobbyUpdate(_)]
+        ));
+        assert!(matches!(server.games[&lobby], Game::Lobby(_)));
+        assert!(DisconnectReason::NotEnoughStartPoints
+            .to_string()
+            .contains("start points"));
+    }
+
+    #[test]
+    fn test_bye_is_acknowledged() {
+        let mut server = Server::new("Test".to_owned(), None);
+        let address: SocketAddr = "[::1]:1001".parse().unwrap();
+        let client_id = hello(&mut server, address);
+
+        let other_address: SocketAddr = "[::1]:1002".parse().unwrap();
+        assert!(server
+            .handle_client_message(ClientMessage::Bye(client_id), other_address)
+            .is_empty());
+        assert!(server.clients.contains_key(&client_id));
+
+        assert_eq!(
+            server.handle_client_message(ClientMessage::Bye(client_id), address),
+            vec![ServerMessage::ByeAck]
+        );
+        assert!(!server.clients.contains_key(&client_id));
+
+        // the ByeAck was lost, client retransmits
+        assert_eq!(
+            server.handle_client_message(ClientMessage::Bye(client_id), address),
+            vec![ServerMessage::ByeAck]
+        );
+    }
+
+    #[test]
+    fn test_admin_requests_need_the_token() {
+        let token = AdminToken("hunter2".to_owned());
+        let mut server = Server::new("Test".to_owned(), Some(token.clone()));
+        let host_address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let host = hello(&mut server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+
+        let admin_address: SocketAddr = "[::1]:2000".parse().unwrap();
+        assert_eq!(
+            server
+                .handle_client_message(ClientMessage::AdminListGames(token.clone()), admin_address),
+            vec![ServerMessage::AdminGames(vec![(
+                lobby,
+                Settings::default().game_name
+            )])]
+        );
+        let response = server.handle_client_message(
+            ClientMessage::AdminGameInfo(token.clone(), lobby),
+            admin_address,
+        );
+        let [ServerMessage::AdminGameInfo(info)] = &response[..] else {
+            panic!("admin gets game info: {response:?}");
+        };
+        assert_eq!(info.game_id, lobby);
+        assert!(!info.started);
+        assert_eq!(info.players[&PlayerId(0)].name, "Hans");
+        assert_eq!(info.time, TimeStamp::default());
+        assert_eq!(info.settings, Settings::default());
+        assert_eq!(
+            server.handle_client_message(
+                ClientMessage::AdminGameInfo(token, GameId::new(0)),
+                admin_address
+            ),
+            vec![ServerMessage::Bye(DisconnectReason::UnknownGame)]
+        );
+
+        let wrong = AdminToken("hunter3".to_owned());
+        assert_eq!(
+            server
+                .handle_client_message(ClientMessage::AdminListGames(wrong.clone()), admin_address),
+            vec![ServerMessage::AdminRejected]
+        );
+        assert_eq!(
+            server.handle_client_message(ClientMessage::AdminGameInfo(wrong, lobby), admin_address),
+            vec![ServerMessage::AdminRejected]
+        );
+
+        let mut without_token = Server::new("Test".to_owned(), None);
+        assert_eq!(
+            without_token.handle_client_message(
+                ClientMessage::AdminListGames(AdminToken(String::new())),
+                admin_address
+            ),
+            vec![ServerMessage::AdminRejected],
+            "a server without token has no admins"
+        );
+    }
+
+    #[test]
+    fn test_admin_closes_game_and_notifies_its_clients() {
+        let token = AdminToken("hunter2".to_owned());
+        let mut server = Server::new("Test".to_owned(), Some(token.clone()));
+        let host_address: SocketAddr = "[::1]:1000".parse().unwrap();
+        let host = hello(&mut server, host_address);
+        server.handle_client_message(ClientMessage::OpenNewLobby(host), host_address);
+        let lobby = server.clients[&host].game.as_ref().unwrap().game_id;
+        let guest_address: SocketAddr = "[::1]:1001".parse().unwrap();
+        let guest = hello(&mut server, guest_address);
+        server.handle_client_message(
+            ClientMessage::JoinLobby(ClientJoinLobby {
+                client_id: guest,
+                lobby,
+            }),
+            guest_address,
+        );
+        server.take_outgoing();
+        let bystander_address: SocketAddr = "[::1]:1002".parse().unwrap();
+        let bystander = hello(&mut server, bystander_address);
+
+        let admin_address: SocketAddr = "[::1]:2000".parse().unwrap();
+        assert_eq!(
+            server.handle_client_message(
+                ClientMessage::AdminCloseGame(AdminToken("wrong".to_owned()), lobby),
+                admin_address
+            ),
+            vec![ServerMessage::AdminRejected]
+        );
+        assert!(server.games.contains_key(&lobby));
+
+        assert_eq!(
+            server.handle_client_message(
+                ClientMessage::AdminCloseGame(token.clone(), lobby),
+                admin_address
+            ),
+            vec![ServerMessage::AdminGames(Vec::new())]
+        );
+        assert!(server.games.is_empty());
+        assert!(server.clients[&host].game.is_none());
+        assert!(server.clients[&guest].game.is_none());
+        assert!(server.clients.contains_key(&bystander));
+
+        let mut outgoing = server.take_outgoing();
+        outgoing.sort_by_key(|(address, _)| *address);
+        assert_eq!(
+            outgoing,
+            vec![
+                (
+                    host_address,
+                    ServerMessage::Bye(DisconnectReason::GameClosed)
+                ),
+                (
+                    guest_address,
+                    ServerMessage::Bye(DisconnectReason::GameClosed)
+                ),
+            ]
+        );
+        assert!(server.take_outgoing().is_empty());
+
+        assert_eq!(
+            server
+                .handle_client_message(ClientMessage::AdminCloseGame(token, lobby), admin_address),
+            vec![ServerMessage::Bye(DisconnectReason::UnknownGame)]
+        );
     }
 }